@@ -0,0 +1,33 @@
+use closed_unitigs::{CloseOptions, FastaSink, Graph};
+use closed_unitigs::convert::{self, Format};
+use closed_unitigs::graph::Sort;
+use closed_unitigs::synthetic;
+use criterion::{BatchSize, Criterion, black_box, criterion_group, criterion_main};
+use std::io;
+
+/// Closes a graph, dropping the output
+fn close(graph: &Graph, options: &CloseOptions) {
+  graph.close(&mut FastaSink::new(io::sink(), io::sink()), options).unwrap();
+}
+
+fn closing(c: &mut Criterion) {
+  let genome = synthetic::generate_random_genome(31, 50_000, 10., 0.005, 1);
+  c.bench_function("close random genome", |b| b.iter(|| close(&genome, &CloseOptions::default())));
+  c.bench_function("close random genome unsorted", |b| b.iter(|| close(&genome, &CloseOptions{sort: Sort::None, ..Default::default()})));
+  // A single long closure, extended one k-mer at a time
+  let linear = synthetic::generate_linear(31, 100_000, |_| 5).graph;
+  c.bench_function("close long unitig", |b| b.iter(|| close(&linear, &CloseOptions::default())));
+  let branching = synthetic::generate_branching(15, 8, 2).graph;
+  c.bench_function("close branching", |b| b.iter(|| close(&branching, &CloseOptions::default())));
+}
+
+fn parsing(c: &mut Criterion) {
+  let mut fasta = Vec::new();
+  convert::write_to(&synthetic::generate_random_genome(31, 50_000, 10., 0.005, 1), Format::Bcalm, &mut fasta).unwrap();
+  c.bench_function("parse BCALM", |b| b.iter_batched(|| fasta.as_slice(), |fasta| {
+    black_box(Graph::parse_indexed(fasta, &Default::default(), None).unwrap())
+  }, BatchSize::SmallInput));
+}
+
+criterion_group!(benches, closing, parsing);
+criterion_main!(benches);
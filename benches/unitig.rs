@@ -0,0 +1,51 @@
+use closed_unitigs::Unitig;
+use criterion::{BenchmarkId, Criterion, criterion_group, criterion_main};
+use rand::{SeedableRng, seq::SliceRandom};
+use rand_pcg::Pcg64;
+use std::convert::TryFrom;
+
+/// Length of the k-mers joined
+const K: usize = 31;
+
+/// The k-mers of a random sequence, in order
+fn kmers(n: usize) -> Vec<Unitig> {
+  let mut rng = Pcg64::seed_from_u64(0);
+  let seq: String = (0..n + K - 1).map(|_| *['A', 'C', 'G', 'T'].choose(&mut rng).unwrap()).collect();
+  (0..n).map(|i| Unitig::try_from(&seq[i..i+K]).unwrap()).collect()
+}
+
+/// Joining the k-mers of a closure one at a time, in place or by concatenating into a new unitig at each step
+fn joining(c: &mut Criterion) {
+  let mut group = c.benchmark_group("join k-mers");
+  for n in [1_000, 5_000] {
+    let kmers = kmers(n);
+    let in_place = || {
+      let mut m = kmers[0].clone();
+      for kmer in &kmers[1..] {
+        m.push_overlap(kmer, K - 1);
+      }
+      m
+    };
+    let concatenated = || kmers[1..].iter().fold(kmers[0].clone(), |m, kmer| &m + kmer);
+    // Both give the same sequence
+    assert!(in_place().is_identical(&concatenated()));
+    group.bench_with_input(BenchmarkId::new("push_overlap", n), &n, |b, _| b.iter(in_place));
+    group.bench_with_input(BenchmarkId::new("concatenation", n), &n, |b, _| b.iter(concatenated));
+
+    let prepended = || {
+      let mut m = kmers[n-1].clone();
+      for kmer in kmers[..n-1].iter().rev() {
+        m.prepend_overlap(kmer, K - 1);
+      }
+      m
+    };
+    let concatenated_before = || kmers[..n-1].iter().rev().fold(kmers[n-1].clone(), |m, kmer| kmer + &m);
+    assert!(prepended().is_identical(&in_place()) && concatenated_before().is_identical(&in_place()));
+    group.bench_with_input(BenchmarkId::new("prepend_overlap", n), &n, |b, _| b.iter(prepended));
+    group.bench_with_input(BenchmarkId::new("concatenation before", n), &n, |b, _| b.iter(concatenated_before));
+  }
+  group.finish();
+}
+
+criterion_group!(benches, joining);
+criterion_main!(benches);
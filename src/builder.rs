@@ -0,0 +1,293 @@
+//! Programmatic construction of a de Bruijn graph
+
+use rayon::prelude::*;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::convert::TryInto;
+use std::str::FromStr;
+use super::{Graph, GraphError, Edge, KmerIndex, Orientation, SortedIndex, Unitig};
+
+/// Identifies a record (an input unitig) of a graph builder.
+/// Records are numbered from zero in insertion order, as BCALM does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RecordId(pub usize);
+
+/// Where a k-mer comes from: an input record and the position of the k-mer in it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Origin {
+  pub record: usize,
+  pub offset: usize
+}
+
+/// How the counts of a record and of its reverse complement are combined when collapsing them
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CollapseCounts {
+  /// Each strand carries the full count
+  #[default]
+  Max,
+  /// Each strand carries part of the count
+  Sum
+}
+
+impl FromStr for CollapseCounts {
+  type Err = String;
+
+  /// Parses a collapse mode from its command line name
+  fn from_str(s: &str) -> Result<CollapseCounts, Self::Err> {
+    match s {
+      "max" => Ok(CollapseCounts::Max),
+      "sum" => Ok(CollapseCounts::Sum),
+      _ => Err(format!("Unknown collapse mode \"{}\"", s))
+    }
+  }
+}
+
+/// Builds a de Bruijn graph from unitigs and links between them.
+///
+/// # Example
+/// ```
+/// use closed_unitigs::{GraphBuilder, CloseOptions, FastaSink, Orientation::*};
+///
+/// let mut builder = GraphBuilder::new();
+/// let a = builder.add_unitig("AACCGT", &[5, 5]);
+/// let b = builder.add_unitig("CCGTTA", &[5, 4]);
+/// let c = builder.add_unitig("CCGTGA", &[2, 2]);
+/// // Links are given in both directions, as BCALM does
+/// builder.add_link(a, Forward, b, Forward);
+/// builder.add_link(a, Forward, c, Forward);
+/// builder.add_link(b, Reverse, a, Reverse);
+/// builder.add_link(c, Reverse, a, Reverse);
+/// let graph = builder.build(5).unwrap();
+///
+/// let (mut fasta, mut counts) = (Vec::new(), Vec::new());
+/// graph.close(&mut FastaSink::new(&mut fasta, &mut counts), &CloseOptions::default()).unwrap();
+/// assert!(String::from_utf8(fasta).unwrap().lines().any(|l| l == "AACCGTT"));
+/// ```
+#[derive(Debug, Default)]
+pub struct GraphBuilder {
+  /// Sequence, k-mer counts and origin of each record
+  records: Vec<(String, Vec<u32>, Origin)>,
+  /// Links between records, as (record, orientation) pairs
+  links: Vec<((RecordId, Orientation), (RecordId, Orientation))>,
+  /// Whether k-mers found in several records are merged in a single node
+  merge_duplicates: bool,
+  /// How records which are the reverse complement of an earlier one are collapsed on it, if they are
+  collapse_rc: Option<CollapseCounts>,
+  /// Whether links between k-mers which do not overlap are dropped instead of making the build fail
+  lenient: bool,
+  /// Index of the k-mers to be built, loaded from a file
+  index: Option<SortedIndex>,
+  /// Whether the k-mers of the records are built on rayon's worker threads
+  parallel: bool
+}
+
+/// Number of records whose k-mers are built together
+const BATCH: usize = 4096;
+
+/// Builds the k-mers of a record, whose counts must match its length, finding their normal form for the lookups
+fn kmers(seq: &str, counts: usize, k: usize) -> Result<Vec<Unitig>, GraphError> {
+  if counts == 0 || seq.len() != counts + k - 1 {
+    return Err(GraphError::WrongCounts{len: seq.len(), counts, k});
+  }
+  let kmers = (0..counts).map(|i| seq[i..i+k].try_into()).collect::<Result<Vec<Unitig>, _>>()?;
+  kmers.iter().for_each(Unitig::find_norm);
+  Ok(kmers)
+}
+
+/// Pushes an edge, or increases its weight if it is already present
+fn push_edge(edges: &mut Vec<Edge>, edge: Edge) {
+  match edges.iter_mut().find(|e| (e.to, e.dir, e.to_dir) == (edge.to, edge.dir, edge.to_dir)) {
+    Some(e) => e.weight += edge.weight,
+    None => edges.push(edge)
+  }
+}
+
+impl GraphBuilder {
+  /// Creates an empty builder
+  pub fn new() -> GraphBuilder {
+    GraphBuilder::default()
+  }
+
+  /// Adds an unitig with the counts of each of its k-mers
+  pub fn add_unitig(&mut self, seq: &str, counts: &[u32]) -> RecordId {
+    self.add_segment(seq, counts, Origin{record: self.records.len(), offset: 0})
+  }
+
+  /// Adds an unitig which is part of an input record, starting at the given origin
+  pub fn add_segment(&mut self, seq: &str, counts: &[u32], origin: Origin) -> RecordId {
+    self.records.push((String::from(seq), counts.to_vec(), origin));
+    RecordId(self.records.len()-1)
+  }
+
+  /// Adds a link between two records, as a BCALM `L:` field does: `Forward` leaves `from` by its end and enters `to`
+  /// by its start, `Reverse` leaves `from` by the end of its reverse complement (its start) and enters `to` by
+  /// the start of its reverse complement (its end). The link is added in this direction only.
+  pub fn add_link(&mut self, from: RecordId, from_dir: Orientation, to: RecordId, to_dir: Orientation) {
+    self.links.push(((from, from_dir), (to, to_dir)));
+  }
+
+  /// Sets whether a k-mer found in several records is merged in a single node, summing its counts
+  /// and joining its edges, instead of making the build fail
+  pub fn merge_duplicates(&mut self, merge: bool) {
+    self.merge_duplicates = merge;
+  }
+
+  /// Sets whether a record which is the reverse complement of an earlier one is collapsed on it,
+  /// combining their counts as given, instead of being treated as a duplicate
+  pub fn collapse_rc(&mut self, counts: Option<CollapseCounts>) {
+    self.collapse_rc = counts;
+  }
+
+  /// Sets whether links between k-mers which do not overlap by k-1 bases are dropped, with a warning,
+  /// instead of making the build fail
+  pub fn lenient(&mut self, lenient: bool) {
+    self.lenient = lenient;
+  }
+
+  /// Sets whether the k-mers of the records are built in batches on rayon's worker threads, then added to the graph in order
+  pub fn parallel(&mut self, parallel: bool) {
+    self.parallel = parallel;
+  }
+
+  /// Sets an index of the k-mers, loaded from a file, used to look them up instead of building a map.
+  /// If the k-mers turn out not to match it, a map is built anyway.
+  pub fn sorted_index(&mut self, index: SortedIndex) {
+    self.index = Some(index);
+  }
+
+  /// Builds the graph, with one node per k-mer
+  pub fn build(self, k: usize) -> Result<Graph, GraphError> {
+    self.build_located(k).map_err(|(_, e)| e)
+  }
+
+  /// Builds the graph, giving on failure also the input record at fault, if there is one
+  pub fn build_located(self, k: usize) -> Result<Graph, (Option<usize>, GraphError)> {
+    let origins: Vec<_> = self.records.iter().map(|(_, _, origin)| origin.record).collect();
+    let mut graph = Graph::new(k);
+    if let Some(index) = self.index {
+      let k_matches = index.k == k;
+      graph.index = KmerIndex::Sorted(index);
+      if !k_matches {
+        graph.drop_sorted_index();
+      }
+    }
+    let mut last_kmers = HashMap::new(); // record ending with each node
+
+    // The k-mers of a batch of records are built before the records are added in order
+    let (parallel, mut records) = (self.parallel, self.records.into_iter().enumerate());
+    let batches = std::iter::from_fn(|| {
+      let batch: Vec<_> = records.by_ref().take(BATCH).collect();
+      let kmers: Vec<_> = match parallel {
+        true => batch.par_iter().map(|(_, (seq, counts, _))| kmers(seq, counts.len(), k)).collect(),
+        false => batch.iter().map(|(_, (seq, counts, _))| kmers(seq, counts.len(), k)).collect()
+      };
+      (!batch.is_empty()).then(|| batch.into_iter().zip(kmers))
+    }).flatten();
+
+    for ((id, (_, counts, origin)), kmers) in batches {
+      let at_fault = |e: GraphError| (Some(origin.record), e);
+      let kmers = kmers.map_err(at_fault)?;
+
+      // A reverse complement record starts with the reverse complement of the last k-mer of the original one
+      let original = match graph.find(&kmers[0]) {
+        Some((n, _)) => last_kmers.get(&n).copied().filter(|&r: &usize| {
+          let other = &graph.records[r];
+          other.len() == kmers.len() && kmers.iter().zip(other.iter().rev()).all(|(kmer, &(n, dir))| graph.find(kmer) == Some((n, !dir)))
+        }),
+        _ => None
+      };
+      if let Some(r) = original {
+        log::warn!("record {} is the reverse complement of record {}", id, r);
+        if let Some(mode) = self.collapse_rc {
+          let path: Vec<_> = graph.records[r].iter().rev().map(|&(n, dir)| (n, !dir)).collect();
+          for (&(n, _), &c) in path.iter().zip(&counts) {
+            let count = &mut graph.nodes[n].count;
+            *count = match mode {
+              CollapseCounts::Max => (*count).max(c),
+              CollapseCounts::Sum => *count + c
+            };
+          }
+          graph.records.push(path); // links to this record reach the original one on the other strand
+          continue;
+        }
+      }
+
+      // Append a node for each new k-mer
+      let mut path = Vec::with_capacity(counts.len());
+      for (i, (kmer, &c)) in kmers.into_iter().zip(&counts).enumerate() {
+        match graph.find(&kmer) {
+          Some((n, dir)) if self.merge_duplicates => {
+            graph.nodes[n].count += c;
+            path.push((n, dir));
+          },
+          Some((n, _)) => {
+            let first = graph.records.iter().position(|p| p.iter().any(|&(m, _)| m == n)).unwrap_or(id);
+            return Err(at_fault(GraphError::DuplicateKmer{kmer: kmer.to_string(), first, second: id}));
+          },
+          None => {
+            graph.append(kmer, c, Origin{offset: origin.offset + i, ..origin});
+            path.push((graph.nodes.len()-1, Orientation::Forward));
+          }
+        }
+      }
+
+      // Edges between k-mers of the unitig
+      for w in path.windows(2) {
+        let ((a, da), (b, db)) = (w[0], w[1]);
+        if a == b {continue} // avoid self loops
+        push_edge(&mut graph.nodes[a].out, Edge{to: b, dir: da, to_dir: db, weight: 1});
+        push_edge(&mut graph.nodes[a].into, Edge{to: b, dir: !da, to_dir: !db, weight: 1}); // Reverse complement
+      }
+      // Reverse direction
+      for w in path.windows(2) {
+        let ((a, da), (b, db)) = (w[0], w[1]);
+        if a == b {continue}
+        push_edge(&mut graph.nodes[b].out, Edge{to: a, dir: !db, to_dir: !da, weight: 1}); // Reverse complement
+        push_edge(&mut graph.nodes[b].into, Edge{to: a, dir: db, to_dir: da, weight: 1});
+      }
+
+      last_kmers.entry(path[path.len()-1].0).or_insert(id);
+      graph.records.push(path);
+    }
+
+    // The index must not list further k-mers
+    if matches!(&graph.index, KmerIndex::Sorted(index) if index.len() != graph.nodes.len()) {
+      graph.drop_sorted_index();
+    }
+
+    // Store edges
+    for ((RecordId(from_id), from_dir), (RecordId(to_id), to_dir)) in self.links {
+      let record = |id: usize| graph.records.get(id).ok_or((origins.get(from_id).copied(), GraphError::UnknownRecord{id}));
+      let (from, to) = (record(from_id)?, record(to_id)?);
+
+      // Leave by the last k-mer or enter by the first one of the strand of the link
+      let flip = |(n, dir): (usize, Orientation)| (n, !dir);
+      let (from, out_dir) = match from_dir {
+        Orientation::Forward => from[from.len()-1],
+        Orientation::Reverse => flip(from[0])
+      };
+      let (to, in_dir) = match to_dir {
+        Orientation::Forward => to[0],
+        Orientation::Reverse => flip(to[to.len()-1])
+      };
+
+      if from == to {continue} // avoid self loops
+
+      // The linked k-mers must overlap by k-1 bases
+      if !graph.nodes[from].kmer(out_dir).slice(1..k).is_identical(&graph.nodes[to].kmer(in_dir).slice(0..k-1)) {
+        let (sign, sign_to) = (from_dir.sign(), to_dir.sign());
+        if !self.lenient {
+          return Err((Some(origins[from_id]), GraphError::InconsistentLink{from: from_id, to: to_id, link: format!("L:{}:{}:{}", sign, to_id, sign_to)}));
+        }
+        log::warn!("dropped link L:{}:{}:{} of record {}, whose k-mers do not overlap", sign, to_id, sign_to, from_id);
+        continue;
+      }
+
+      push_edge(&mut graph.nodes[from].out, Edge{to, dir: out_dir, to_dir: in_dir, weight: 1});
+      push_edge(&mut graph.nodes[to].into, Edge{to: from, dir: in_dir, to_dir: out_dir, weight: 1}); // Reverse direction
+    }
+
+    Ok(graph)
+  }
+}
@@ -0,0 +1,344 @@
+//! Command line interface
+
+use crate::convert::Format;
+use crate::graph::{CollapseCounts, DegreePolicy, Extension, MinCount, SeedOrder, Sort, Support};
+use crate::io::Compress;
+use crate::sink::GfaVersion;
+use clap::{ArgAction, Args, Parser, Subcommand};
+use log::LevelFilter;
+use std::ffi::OsString;
+use std::path::PathBuf;
+
+/// Generates the closed unitigs of a de Bruijn graph written by BCALM
+#[derive(Debug, Parser)]
+#[command(version, arg_required_else_help = true)]
+pub struct Cli {
+  /// Writes more messages: -v also the time taken by each phase, -vv everything
+  #[arg(short, long, action = ArgAction::Count, global = true)]
+  pub verbose: u8,
+  /// Writes fewer messages: -q only the warnings, -qq none
+  #[arg(short, long, action = ArgAction::Count, global = true, conflicts_with = "verbose")]
+  pub quiet: u8,
+  #[command(subcommand)]
+  pub command: Command
+}
+
+impl Cli {
+  /// Most detailed messages written, informational ones by default
+  pub fn log_level(&self) -> LevelFilter {
+    // Errors end the run, and are written anyway
+    const LEVELS: [LevelFilter; 5] = [LevelFilter::Off, LevelFilter::Warn, LevelFilter::Info, LevelFilter::Debug, LevelFilter::Trace];
+    LEVELS[(2 + self.verbose as usize).saturating_sub(self.quiet as usize).min(LEVELS.len() - 1)]
+  }
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+  /// Closes the unitigs of a graph (the default subcommand)
+  Close(Box<CloseArgs>),
+  /// Checks that the records of a previous run are closed unitigs of a graph
+  Verify {
+    graph: PathBuf,
+    fasta: PathBuf,
+    /// Counts of the records, read from their km:f: header tags if not given
+    counts: Option<PathBuf>
+  },
+  /// Checks a BCALM file without building the graph
+  Validate {
+    /// Checks the records on a single thread
+    #[arg(long)]
+    serial: bool,
+    /// Number of threads (default one per core)
+    #[arg(long, value_name = "N")]
+    threads: Option<usize>,
+    /// Skips the k-mers containing this character
+    #[arg(long, value_name = "C")]
+    break_char: Option<char>,
+    input: PathBuf
+  },
+  /// Prints statistics of a graph: sizes, counts and degrees
+  Stats {
+    /// Also prints the number of k-mers with a higher degree
+    #[arg(long, value_name = "D")]
+    max_degree: Option<usize>,
+    input: PathBuf
+  },
+  /// Prints the connected components of a graph: their number and sizes
+  Components {
+    /// Writes the number of k-mers and input bases of each component to this file
+    #[arg(long, value_name = "FILE")]
+    tsv: Option<PathBuf>,
+    input: PathBuf
+  },
+  /// Prints the cyclic regions of a graph, with their k-mers and input records
+  Cycles {
+    input: PathBuf
+  },
+  /// Prints the closed unitig of each k-mer given, as the first seed of a closing
+  Query {
+    graph: PathBuf,
+    #[arg(required = true)]
+    kmers: Vec<String>
+  },
+  /// Converts a graph between file formats
+  Convert {
+    /// Format of the input: bcalm, gfa, binary, fastq, kmers, also named tsv, or kmc (by default as its extension says)
+    #[arg(long)]
+    from: Option<Format>,
+    /// Format of the output: bcalm, gfa or binary (by default as its extension says)
+    #[arg(long)]
+    to: Option<Format>,
+    /// Size of the k-mers counted in FASTQ reads
+    #[arg(long, value_name = "K")]
+    k: Option<usize>,
+    input: PathBuf,
+    output: PathBuf
+  },
+  /// Exports a graph for other tools
+  Export {
+    #[command(subcommand)]
+    format: Export
+  },
+  /// Compares the closed unitigs of two runs, exiting with 5 if they differ
+  Diff {
+    /// Writes the closed unitigs which differ to this file
+    #[arg(long, value_name = "FILE")]
+    tsv: Option<PathBuf>,
+    a: PathBuf,
+    b: PathBuf
+  }
+}
+
+/// Formats a graph can be exported to
+#[derive(Debug, Subcommand)]
+pub enum Export {
+  /// Writes the k-mers and their edges, or a part of them, as a Graphviz DOT digraph
+  Dot(ExportArgs),
+  /// Writes the k-mers and their edges, or a part of them, as GraphML, for Gephi or Cytoscape
+  Graphml(ExportArgs)
+}
+
+/// Part of a graph to export, and where
+#[derive(Debug, Args)]
+pub struct ExportArgs {
+  /// Writes only the connected component with this number
+  #[arg(long, value_name = "C", conflicts_with = "around")]
+  pub component: Option<usize>,
+  /// Writes only the k-mers near this one
+  #[arg(long, value_name = "KMER")]
+  pub around: Option<String>,
+  /// Greatest number of edges from the k-mer given by --around (default 3)
+  #[arg(long, value_name = "R", requires = "around")]
+  pub radius: Option<usize>,
+  pub input: PathBuf,
+  /// Output file (by default the standard output)
+  pub output: Option<PathBuf>
+}
+
+/// Options of the closing; see the README for their details
+#[derive(Debug, Args)]
+pub struct CloseArgs {
+  /// Format of the input: bcalm, gfa, binary, fastq, kmers, also named tsv, or kmc (by default as its extension says)
+  #[arg(long)]
+  pub format: Option<Format>,
+  /// Reads only the first N records
+  #[arg(long, value_name = "N")]
+  pub head: Option<usize>,
+  /// Merges the k-mers appearing in several records
+  #[arg(long)]
+  pub merge_duplicates: bool,
+  /// Collapses the records which are the reverse complement of an earlier one
+  #[arg(long)]
+  pub collapse_rc: bool,
+  /// Combines the counts of collapsed records: max or sum (implies --collapse-rc)
+  #[arg(long, value_name = "MODE")]
+  pub collapse_counts: Option<CollapseCounts>,
+  /// Drops the links joining k-mers which do not overlap
+  #[arg(long)]
+  pub lenient: bool,
+  /// Splits the records at the runs of this character
+  #[arg(long, value_name = "C")]
+  pub break_char: Option<char>,
+  /// Size of the k-mers, checked on every record instead of told from the first one
+  #[arg(long, value_name = "K")]
+  pub k: Option<usize>,
+  /// Number of threads parsing the input (default one per core; 1 parses it on the reading thread)
+  #[arg(long, value_name = "N")]
+  pub threads: Option<usize>,
+  /// Output order: count, count-desc, length (also length-desc), lexicographic (also seq) or none
+  #[arg(long)]
+  pub sort: Option<Sort>,
+  /// Seed order: input or shuffle
+  #[arg(long)]
+  pub seed_order: Option<SeedOrder>,
+  /// Seed of the random shuffles
+  #[arg(long, value_name = "N")]
+  pub rng_seed: Option<u64>,
+  /// Trims terminal k-mers while their count is at least the support plus D
+  #[arg(long, value_name = "D")]
+  pub shrink_delta: Option<u32>,
+  /// Trims at most T bases from each end
+  #[arg(long, value_name = "T")]
+  pub max_trim: Option<usize>,
+  /// Extends only by k-mers whose count is at most R times the support
+  #[arg(long, value_name = "R")]
+  pub max_count_ratio: Option<f64>,
+  /// Choice among the extensions: first, max-edge-weight, highest-count, lowest-count or lexicographic
+  #[arg(long, alias = "tie-break")]
+  pub extension: Option<Extension>,
+  /// Support of a closure from the counts of its k-mers: min, mean, median or pN (a percentile)
+  #[arg(long, conflicts_with = "allow_gaps")]
+  pub support: Option<Support>,
+  /// Does not extend closures with support at most F
+  #[arg(long, value_name = "F")]
+  pub support_floor: Option<u32>,
+  /// Does not write closures at or below the support floor
+  #[arg(long)]
+  pub drop_below_floor: bool,
+  /// Approximate memory ceiling while closing, in gigabytes
+  #[arg(long, value_name = "GB")]
+  pub max_memory: Option<f64>,
+  /// K-mers with more neighbours follow the degree policy
+  #[arg(long, value_name = "D")]
+  pub max_degree: Option<usize>,
+  /// Treatment of high degree k-mers: skip-seeds, boundary or both
+  #[arg(long, value_name = "POLICY")]
+  pub degree_policy: Option<DegreePolicy>,
+  /// Jumps over up to G k-mers below the support (experimental)
+  #[arg(long, value_name = "G")]
+  pub allow_gaps: Option<usize>,
+  /// Writes how shrinking changed each closed unitig
+  #[arg(long)]
+  pub shrink_metadata: bool,
+  /// Stops extending a closure after this many seconds
+  #[arg(long, value_name = "SECS")]
+  pub closure_timeout: Option<f64>,
+  /// Stops extending a closure once it is at least L nucleotides long
+  #[arg(long, value_name = "L")]
+  pub max_length: Option<usize>,
+  /// Stops extending a closure after N extensions
+  #[arg(long, value_name = "N")]
+  pub max_steps: Option<usize>,
+  /// Saves the state of the closing every SECS seconds, to continue it with --resume if interrupted
+  #[arg(long, value_name = "SECS")]
+  pub checkpoint_every: Option<f64>,
+  /// Continues an interrupted or aborted closing from its last saved state
+  #[arg(long)]
+  pub resume: bool,
+  /// Drops the k-mers with a lower count: a number or auto
+  #[arg(long, value_name = "N")]
+  pub min_count: Option<MinCount>,
+  /// Removes the dead-end tips shorter than 2k bases with counts below N
+  #[arg(long, value_name = "N")]
+  pub clip_tips: Option<u32>,
+  /// Removes the weaker branch of the simple bubbles
+  #[arg(long)]
+  pub pop_bubbles: bool,
+  /// Largest fraction of the count mass removed by the suggested minimum count
+  #[arg(long, value_name = "F")]
+  pub max_removed_mass: Option<f64>,
+  /// Keeps a random fraction of the connected components
+  #[arg(long, value_name = "F")]
+  pub sample_components: Option<f64>,
+  /// Keeps only the connected components with these numbers, separated by commas
+  #[arg(long, value_name = "IDS", value_delimiter = ',', conflicts_with = "sample_components")]
+  pub components: Vec<usize>,
+  /// Splits the output round-robin into N files
+  #[arg(long, value_name = "N")]
+  pub shards: Option<usize>,
+  /// Writes the closed unitigs of each support to DIR
+  #[arg(long, value_name = "DIR")]
+  pub split_by_support: Option<PathBuf>,
+  /// Writes the supports from N on together
+  #[arg(long, value_name = "N")]
+  pub support_cap: Option<u32>,
+  /// Writes the closed unitigs of each connected component to DIR
+  #[arg(long, value_name = "DIR")]
+  pub split_by_component: Option<PathBuf>,
+  /// Pools the components with fewer k-mers
+  #[arg(long, value_name = "N")]
+  pub min_component: Option<usize>,
+  /// Names the outputs PREFIX.clo.fa, PREFIX.clo.counts and so on, instead of after the input
+  #[arg(short, long, value_name = "PREFIX")]
+  pub output_prefix: Option<PathBuf>,
+  /// Compresses the closed unitigs and their counts: gz or zst (by default as the extension of --fasta-out and --counts-out says)
+  #[arg(long, value_name = "FORMAT")]
+  pub compress: Option<Compress>,
+  /// Wraps the sequences of the closed unitigs every N bases (by default, or with 0, each one is on a single line)
+  #[arg(long, value_name = "N")]
+  pub wrap: Option<usize>,
+  /// File of the closed unitigs
+  #[arg(long, value_name = "FILE")]
+  pub fasta_out: Option<PathBuf>,
+  /// File of the counts of the closed unitigs
+  #[arg(long, value_name = "FILE")]
+  pub counts_out: Option<PathBuf>,
+  /// Writes the counts as km:f: and ab:Z: header tags instead of a counts file
+  #[arg(long, conflicts_with_all = ["counts_out", "shards", "split_by_support", "split_by_component"])]
+  pub abundance_tags: bool,
+  /// Exits with an error if the input has no k-mers
+  #[arg(long)]
+  pub strict_empty: bool,
+  /// Writes the k-mer counts of the closed unitigs with the ids in this file, or of all of them
+  #[arg(long, value_name = "IDS")]
+  pub profiles: Option<PathBuf>,
+  /// File of the profiles
+  #[arg(long, value_name = "FILE")]
+  pub profiles_out: Option<PathBuf>,
+  /// Writes the input records of the k-mers of each closed unitig to this TSV file
+  #[arg(long, value_name = "FILE")]
+  pub provenance: Option<PathBuf>,
+  /// Writes every k-mer with the closed unitigs containing it to this TSV file
+  #[arg(long, value_name = "FILE")]
+  pub membership_out: Option<PathBuf>,
+  /// Also writes the closed unitigs and the links between them as a GFA file
+  #[arg(long, value_name = "FILE")]
+  pub gfa_out: Option<PathBuf>,
+  /// Version of the GFA file: 1, with links, or 2, with edges giving the overlap coordinates
+  #[arg(long, value_name = "V", default_value = "1", requires = "gfa_out")]
+  pub gfa_version: GfaVersion,
+  /// Writes the summary and the manifest as JSON
+  #[arg(long, value_name = "FILE")]
+  pub json_report: Option<PathBuf>,
+  /// Does not write the manifest as comment lines
+  #[arg(long)]
+  pub no_manifest: bool,
+  /// Writes a sorted index of the k-mers next to the input
+  #[arg(long)]
+  pub write_index: bool,
+  /// Writes progress as JSON events to stderr
+  #[arg(long, conflicts_with = "progress_file")]
+  pub progress_json: bool,
+  /// Does not show the progress bars
+  #[arg(long, conflicts_with_all = ["progress_json", "progress_file"])]
+  pub no_progress: bool,
+  /// Writes progress as JSON events to this file
+  #[arg(long, value_name = "FILE")]
+  pub progress_file: Option<PathBuf>,
+  /// Writes the k-mers not represented in the output, with the reason
+  #[arg(long, value_name = "FILE")]
+  pub unrepresented: Option<PathBuf>,
+  /// Writes the k-mer count spectrum
+  #[arg(long, value_name = "FILE")]
+  pub spectrum: Option<PathBuf>,
+  /// Writes the statistics of the closed unitigs by support
+  #[arg(long, value_name = "FILE")]
+  pub support_summary: Option<PathBuf>,
+  pub input: PathBuf
+}
+
+/// Command line arguments, with `close` inserted if no subcommand is given, as in the first versions.
+/// The subcommand may follow the verbosity flags, which are global.
+pub fn args(args: impl Iterator<Item = OsString>) -> Vec<OsString> {
+  let mut args: Vec<_> = args.collect();
+  let verbosity = |a: &str| {
+    a == "--verbose" || a == "--quiet" || a.strip_prefix('-').is_some_and(|a| !a.is_empty() && (a.chars().all(|c| c == 'v') || a.chars().all(|c| c == 'q')))
+  };
+  let explicit = args.iter().skip(1).find(|a| a.to_str().map_or(true, |a| !verbosity(a))).map(|a| a.to_str().is_some_and(|a| {
+    ["close", "verify", "validate", "stats", "components", "cycles", "query", "convert", "export", "diff", "help", "-h", "--help", "-V", "--version"].contains(&a)
+  }));
+  if explicit != Some(true) && args.len() > 1 {
+    args.insert(1, OsString::from("close"));
+  }
+  args
+}
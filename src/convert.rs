@@ -0,0 +1,142 @@
+//! Conversion of graphs between file formats
+
+use crate::graph::{Graph, GraphError, ParseError, ParseOptions, ReadError, SortedIndex};
+use crate::io::{self as seqio, FastaOptions, FastaWriter};
+use serde::Serialize;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// Supported graph file formats
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+  /// BCALM FASTA, with per k-mer counts (`ab:Z:`) and links (`L:`)
+  #[default]
+  Bcalm,
+  /// GFA1, with segments (`S`) and links (`L`); the counts come from the `ab:Z:`, `KC:i:` or `dp:f:` tags of the segments
+  Gfa,
+  /// Graph saved in binary, loaded without parsing
+  Binary,
+  /// FASTQ reads, whose k-mers are counted; graphs cannot be written as reads
+  Fastq,
+  /// K-mer count table, as dumped by KMC (`kmc_tools transform DB dump`) or Jellyfish; only read
+  Kmers,
+  /// KMC database, made of a `.kmc_pre` and a `.kmc_suf` file; only read
+  Kmc
+}
+
+impl Format {
+  /// Every format graphs can be written in
+  pub const ALL: [Format; 3] = [Format::Bcalm, Format::Gfa, Format::Binary];
+
+  /// Format of a file given by its extension, ignoring the compression one: `.gfa` files are GFA, `.cug` files
+  /// binary, `.fq` and `.fastq` files FASTQ, `.kmers` and `.tsv` files k-mer count tables, `.kmc_pre` and `.kmc_suf` files
+  /// KMC databases, the others BCALM
+  pub fn of(path: &Path) -> Format {
+    let path = if seqio::is_compressed(path) {path.with_extension("")} else {path.to_path_buf()};
+    match path.extension() {
+      Some(ext) if ext == "gfa" => Format::Gfa,
+      Some(ext) if ext == "cug" => Format::Binary,
+      Some(ext) if ext == "fq" || ext == "fastq" => Format::Fastq,
+      Some(ext) if ext == "kmers" || ext == "tsv" => Format::Kmers,
+      Some(ext) if ext == "kmc_pre" || ext == "kmc_suf" => Format::Kmc,
+      _ => Format::Bcalm
+    }
+  }
+}
+
+impl FromStr for Format {
+  type Err = String;
+
+  /// Parses a format from its command line name
+  fn from_str(s: &str) -> Result<Format, Self::Err> {
+    match s {
+      "bcalm" => Ok(Format::Bcalm),
+      "gfa" => Ok(Format::Gfa),
+      "binary" => Ok(Format::Binary),
+      "fastq" => Ok(Format::Fastq),
+      "kmers" | "tsv" => Ok(Format::Kmers),
+      "kmc" => Ok(Format::Kmc),
+      _ => Err(format!("Unknown format \"{}\"", s))
+    }
+  }
+}
+
+/// Writes a graph in the given format; the file appears only once complete
+pub fn write(graph: &Graph, format: Format, output: &Path) -> io::Result<()> {
+  match format {
+    Format::Bcalm => {
+      let mut fasta = FastaWriter::create(output, FastaOptions{atomic: true, ..Default::default()})?;
+      graph.write_bcalm(&mut fasta)?;
+      fasta.finish()
+    },
+    Format::Gfa | Format::Binary => {
+      let written = seqio::with_suffix(output, ".tmp");
+      let mut out = BufWriter::new(File::create(&written)?);
+      write_to(graph, format, &mut out)?;
+      out.flush()?;
+      fs::rename(written, output)
+    },
+    Format::Fastq | Format::Kmers | Format::Kmc => write_to(graph, format, &mut io::sink())
+  }
+}
+
+/// Reads a graph in the given format from a buffer
+pub fn read_from<T: BufRead>(format: Format, buf: T) -> Result<Graph, ParseError> {
+  read_indexed(format, buf, &ParseOptions::default(), None)
+}
+
+/// Reads a graph in the given format from a buffer with the given options, looking k-mers up in the given index
+/// of the same file, if it matches it. A binary graph is loaded as it was saved, with the options then given.
+/// A KMC database is made of two files, so it is read by [`read_kmc`] instead.
+pub fn read_indexed<T: BufRead>(format: Format, mut buf: T, options: &ParseOptions, index: Option<SortedIndex>) -> Result<Graph, ParseError> {
+  match format {
+    Format::Bcalm => Graph::parse_indexed(buf, options, index),
+    Format::Gfa => Graph::parse_gfa(buf, options, index),
+    Format::Fastq => Graph::parse_fastq(buf, options, index),
+    Format::Kmers => Graph::parse_kmers(buf, options, index),
+    Format::Kmc => Err(ReadError::Kmc{message: String::from("a database is read from its prefix and suffix files, not from a single input")}.into()),
+    Format::Binary => {
+      let mut data = Vec::new();
+      buf.read_to_end(&mut data).map_err(|source| ReadError::Io{source})?;
+      Graph::load(&data).map_err(|source| ParseError{record: None, line: None, source: GraphError::Load{source}})
+    }
+  }
+}
+
+/// Writes a graph in the given format to a writer
+pub fn write_to<T: Write>(graph: &Graph, format: Format, out: &mut T) -> io::Result<()> {
+  match format {
+    Format::Bcalm => {
+      let mut fasta = FastaWriter::new(out, FastaOptions::default())?;
+      graph.write_bcalm(&mut fasta)?;
+      fasta.finish()
+    },
+    Format::Gfa => graph.write_gfa(out),
+    Format::Binary => graph.save(out),
+    Format::Fastq => Err(io::Error::new(io::ErrorKind::InvalidInput, "graphs cannot be written as FASTQ")),
+    Format::Kmers => Err(io::Error::new(io::ErrorKind::InvalidInput, "graphs cannot be written as k-mer tables")),
+    Format::Kmc => Err(io::Error::new(io::ErrorKind::InvalidInput, "graphs cannot be written as KMC databases"))
+  }
+}
+
+/// Prefix and suffix files of the KMC database given by either of them, or by the path they share without extension
+pub fn kmc_files(path: &Path) -> (PathBuf, PathBuf) {
+  match path.extension() {
+    Some(ext) if ext == "kmc_pre" || ext == "kmc_suf" => (path.with_extension("kmc_pre"), path.with_extension("kmc_suf")),
+    _ => (seqio::with_suffix(path, ".kmc_pre"), seqio::with_suffix(path, ".kmc_suf"))
+  }
+}
+
+/// Reads the KMC database given by either of its files with the given options
+pub fn read_kmc(path: &Path, options: &ParseOptions) -> Result<Graph, ParseError> {
+  let (pre, suf) = kmc_files(path);
+  // The error names the other file, the given one being named with it
+  let open = |other: &Path| File::open(other).map_err(|source| ReadError::Io{source: match other == path {
+    true => source,
+    false => io::Error::new(source.kind(), format!("{}: {}", other.display(), source))
+  }});
+  Graph::parse_kmc(open(&pre)?, open(&suf)?, options)
+}
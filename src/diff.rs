@@ -0,0 +1,199 @@
+//! Comparison of the closed unitigs written by two runs
+
+use crate::graph::{SupportStats, Unitig};
+use crate::io::{read_counts, FastaReader};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::convert::TryFrom;
+use std::fmt;
+use std::io::{self, BufRead, Write};
+use xxhash_rust::xxh3::xxh3_64;
+
+/// Statistics of the closed unitigs of an output
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Stats {
+  /// Closed unitigs by support
+  pub per_support: BTreeMap<u32, SupportStats>,
+  /// Length of each closed unitig
+  lengths: Vec<usize>
+}
+
+impl Stats {
+  /// Accounts for a closed unitig
+  fn add(&mut self, len: usize, support: u32) {
+    self.per_support.entry(support).or_default().add(len);
+    self.lengths.push(len);
+  }
+
+  /// Number of closed unitigs
+  pub fn unitigs(&self) -> usize {
+    self.lengths.len()
+  }
+
+  /// Total length in bases
+  pub fn bases(&self) -> u64 {
+    self.lengths.iter().map(|&len| len as u64).sum()
+  }
+
+  /// Largest length such that the closed unitigs at least as long hold at least half of the bases
+  pub fn n50(&self) -> usize {
+    let mut lengths = self.lengths.clone();
+    lengths.sort_unstable_by(|a, b| b.cmp(a));
+    let (total, mut seen) = (self.bases(), 0);
+    lengths.into_iter().find(|&len| {
+      seen += len as u64;
+      2 * seen >= total
+    }).unwrap_or(0)
+  }
+}
+
+/// Closed unitigs found in one output only
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Unique {
+  pub unitigs: usize,
+  pub bases: u64
+}
+
+impl Unique {
+  /// Accounts for a closed unitig of the given length
+  fn add(&mut self, len: usize) {
+    self.unitigs += 1;
+    self.bases += len as u64;
+  }
+}
+
+/// Differences between the closed unitigs of two outputs, A and B
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Diff {
+  pub a: Stats,
+  pub b: Stats,
+  pub only_a: Unique,
+  pub only_b: Unique,
+  /// Number of closed unitigs in both outputs
+  pub shared: usize,
+  /// Number of closed unitigs in both outputs, with different supports
+  pub support_changed: usize
+}
+
+impl Diff {
+  /// Whether the outputs have the same closed unitigs, with the same supports
+  pub fn is_same(&self) -> bool {
+    self.only_a.unitigs == 0 && self.only_b.unitigs == 0 && self.support_changed == 0
+  }
+}
+
+impl fmt::Display for Diff {
+  /// Displays a human readable summary
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let delta = |a: u64, b: u64| b as i64 - a as i64;
+    let (a, b) = (&self.a, &self.b);
+    writeln!(f, "Closed unitigs: {} in A, {} in B ({:+})", a.unitigs(), b.unitigs(), delta(a.unitigs() as u64, b.unitigs() as u64))?;
+    writeln!(f, "Total length: {} bp in A, {} bp in B ({:+} bp)", a.bases(), b.bases(), delta(a.bases(), b.bases()))?;
+    writeln!(f, "N50: {} in A, {} in B ({:+})", a.n50(), b.n50(), delta(a.n50() as u64, b.n50() as u64))?;
+    writeln!(f, "Only in A: {} closed unitigs ({} bp)", self.only_a.unitigs, self.only_a.bases)?;
+    writeln!(f, "Only in B: {} closed unitigs ({} bp)", self.only_b.unitigs, self.only_b.bases)?;
+    write!(f, "In both: {} closed unitigs, {} with a different support", self.shared, self.support_changed)?;
+    let supports: BTreeSet<_> = a.per_support.keys().chain(b.per_support.keys()).collect();
+    if !supports.is_empty() {
+      write!(f, "\nClosed unitigs by support:")?;
+    }
+    for support in supports {
+      let unitigs = |stats: &Stats| stats.per_support.get(support).map_or(0, |s| s.unitigs as u64);
+      write!(f, "\n  {}: {} in A, {} in B ({:+})", support, unitigs(a), unitigs(b), delta(unitigs(a), unitigs(b)))?;
+    }
+    Ok(())
+  }
+}
+
+/// Reads the closed unitigs of an output, from its FASTA and counts files, with their supports
+fn read<R: BufRead>((fasta, counts): (R, R), mut f: impl FnMut(String, u32) -> io::Result<()>) -> io::Result<()> {
+  let invalid = |msg| io::Error::new(io::ErrorKind::InvalidData, msg);
+  let mut counts = read_counts(counts);
+  for record in FastaReader::new(fasta) {
+    let (_, sequence) = record?;
+    f(sequence, counts.next().ok_or_else(|| invalid("fewer counts than sequences"))??)?;
+  }
+  match counts.next().transpose()? {
+    Some(_) => Err(invalid("more counts than sequences")),
+    None => Ok(())
+  }
+}
+
+/// Canonical form of a sequence, the smaller of its strands
+fn canonical(sequence: &str) -> io::Result<String> {
+  let u = Unitig::try_from(sequence).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+  Ok(String::from(u.norm()))
+}
+
+/// Compares two outputs, each given as its FASTA and counts files, matching closed unitigs on either strand.
+/// Only the sequences of A are kept, indexed by the hash of their canonical form and compared on a hash match.
+/// The closed unitigs which differ are written, if asked, as TSV with their canonical sequence, their supports
+/// (`-` in the output without them) and the difference: `only_a`, `only_b` or `support`.
+pub fn compare<R: BufRead>(a: (R, R), b: (R, R), mut tsv: Option<&mut dyn Write>) -> io::Result<Diff> {
+  let mut diff = Diff::default();
+  if let Some(tsv) = &mut tsv {
+    writeln!(tsv, "sequence\tsupport_a\tsupport_b\tdifference")?;
+  }
+
+  // Canonical sequences of A with their supports and whether they were found in B
+  let mut first = Vec::new();
+  let mut index = HashMap::<u64, Vec<usize>>::new();
+  read(a, |sequence, support| {
+    diff.a.add(sequence.len(), support);
+    let sequence = canonical(&sequence)?;
+    index.entry(xxh3_64(sequence.as_bytes())).or_default().push(first.len());
+    first.push((sequence, support, false));
+    Ok(())
+  })?;
+
+  read(b, |sequence, support| {
+    diff.b.add(sequence.len(), support);
+    let sequence = canonical(&sequence)?;
+    let found = index.get(&xxh3_64(sequence.as_bytes()))
+      .and_then(|ids| ids.iter().copied().find(|&i| !first[i].2 && first[i].0 == sequence));
+    let Some(i) = found else {
+      diff.only_b.add(sequence.len());
+      return tsv.as_mut().map_or(Ok(()), |tsv| writeln!(tsv, "{}\t-\t{}\tonly_b", sequence, support));
+    };
+    first[i].2 = true;
+    diff.shared += 1;
+    if first[i].1 != support {
+      diff.support_changed += 1;
+      if let Some(tsv) = &mut tsv {
+        writeln!(tsv, "{}\t{}\t{}\tsupport", sequence, first[i].1, support)?;
+      }
+    }
+    Ok(())
+  })?;
+
+  for (sequence, support, _) in first.into_iter().filter(|&(_, _, found)| !found) {
+    diff.only_a.add(sequence.len());
+    if let Some(tsv) = &mut tsv {
+      writeln!(tsv, "{}\t{}\t-\tonly_a", sequence, support)?;
+    }
+  }
+  Ok(diff)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn outputs_are_matched_on_either_strand() {
+    let a = (">nk=1\nAACCGTT\n>nk=2\nCCGTGA\n>nk=1\nGGGATCA\n".as_bytes(), "; comment\n5\n2\n1\n".as_bytes());
+    // The first one on the other strand, the second one with another support
+    let b = (">0 nk=1\nAACGGTT\n>1 nk=2\nCCGTGA\n>2 nk=3\nACGTTAGC\n".as_bytes(), "5\n3\n4\n".as_bytes());
+    let mut tsv = Vec::new();
+    let diff = compare(a, b, Some(&mut tsv)).unwrap();
+    assert_eq!((diff.shared, diff.support_changed), (2, 1));
+    assert_eq!((diff.only_a, diff.only_b), (Unique{unitigs: 1, bases: 7}, Unique{unitigs: 1, bases: 8}));
+    assert_eq!((diff.a.bases(), diff.b.bases(), diff.a.n50(), diff.b.n50()), (20, 21, 7, 7));
+    assert!(!diff.is_same());
+    assert_eq!(String::from_utf8(tsv).unwrap(),
+      "sequence\tsupport_a\tsupport_b\tdifference\nCCGTGA\t2\t3\tsupport\nACGTTAGC\t-\t4\tonly_b\nGGGATCA\t1\t-\tonly_a\n");
+
+    let same = compare(a, a, None).unwrap();
+    assert!(same.is_same() && same.shared == 3);
+    assert!(compare(a, (b.0, "5\n3\n".as_bytes()), None).is_err());
+  }
+}
@@ -0,0 +1,79 @@
+//! Failures of the command line, each one with its exit code
+
+use crate::graph::{GraphError, ParseError, ReadError};
+use snafu::Snafu;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Exit code of a wrong command line
+pub const USAGE: i32 = 1;
+/// Exit code of an input which cannot be read as expected, or fails a check
+pub const INPUT: i32 = 2;
+/// Exit code of a run stopped by a resource limit
+pub const LIMIT: i32 = 3;
+/// Exit code of a file which cannot be opened, read or written
+pub const IO: i32 = 4;
+/// Exit code of `diff` when the runs compared differ, which is not a failure
+pub const DIFFERENT: i32 = 5;
+/// Exit code of a bug: a panic, whose message is printed by the panic hook
+pub const INTERNAL: i32 = 101;
+
+#[derive(Debug, Snafu)]
+/// Describes why a run failed
+pub enum Error {
+  #[snafu(display("{}", message))]
+  Usage{message: String},
+  #[snafu(display("{}: {}{}", path.display(), source, hint(&source.source)))]
+  Parse{path: PathBuf, source: ParseError},
+  #[snafu(display("{}", message))]
+  Invalid{message: String},
+  #[snafu(display("{}", message))]
+  Limit{message: String},
+  #[snafu(display("{}: {}", context, source))]
+  Io{context: String, source: io::Error}
+}
+
+/// Suggestion about a malformed graph, after its error
+fn hint(error: &GraphError) -> &'static str {
+  match error {
+    GraphError::WrongCounts{counts: 0, ..} => " (was the graph written by BCALM with -all-abundance-counts? With mean counts only, give k with --k)",
+    GraphError::DuplicateKmer{..} => " (--merge-duplicates merges repeated k-mers)",
+    GraphError::InconsistentLink{..} => " (--lenient drops such links)",
+    GraphError::Read{source: ReadError::MissingK} => ", given with --k",
+    _ => ""
+  }
+}
+
+impl Error {
+  /// Usage error with the given message
+  pub fn usage(message: impl Into<String>) -> Error {
+    Error::Usage{message: message.into()}
+  }
+
+  /// Maps an IO error on a file; malformed content, reported as invalid data, is an input error
+  pub fn io(path: &Path) -> impl FnOnce(io::Error) -> Error + '_ {
+    move |source| Error::Io{context: path.display().to_string(), source}
+  }
+
+  /// Class of the failure, which prefixes its message as `error[CLASS]:`
+  pub fn class(&self) -> &'static str {
+    match self.code() {
+      USAGE => "usage",
+      INPUT => "parse",
+      LIMIT => "limit",
+      _ => "io"
+    }
+  }
+
+  /// Exit code of the failure
+  pub fn code(&self) -> i32 {
+    match self {
+      Error::Usage{..} => USAGE,
+      Error::Parse{source: ParseError{source: GraphError::Read{source: ReadError::Io{source}}, ..}, ..} if source.kind() != io::ErrorKind::InvalidData => IO,
+      Error::Parse{..} | Error::Invalid{..} => INPUT,
+      Error::Limit{..} => LIMIT,
+      Error::Io{source, ..} if source.kind() == io::ErrorKind::InvalidData => INPUT,
+      Error::Io{..} => IO
+    }
+  }
+}
@@ -0,0 +1,102 @@
+//! Counting of the canonical k-mers of reads in FASTQ files, giving a record for each k-mer
+
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::io::BufRead;
+use super::kmers;
+use super::reader::{ReadError, Record, RecordSource};
+use super::unitig::Unitig;
+
+/// Reads the reads of a FASTQ file, counting their canonical k-mers: each distinct k-mer is a record, with its count,
+/// linked to the k-mers overlapping it by k-1 bases. The k-mers containing other characters than nucleotides
+/// are skipped. The whole file is read at once, since the links are only known once all k-mers are.
+///
+/// # Example
+/// ```
+/// use closed_unitigs::FastqReader;
+///
+/// let input = "@r1\nAACCGT\n+\nIIIIII\n@r2\nACGGT\n+\nIIIII\n";
+/// let records: Vec<_> = FastqReader::new(input.as_bytes(), 5).unwrap().map(Result::unwrap).collect();
+/// // ACCGT is read twice, the second time as its reverse complement ACGGT
+/// assert_eq!(records.iter().map(|r| (r.sequence.as_str(), r.counts[0])).collect::<Vec<_>>(), [("AACCG", 1), ("ACCGT", 2)]);
+/// ```
+pub struct FastqReader {
+  records: std::vec::IntoIter<Record>,
+  bytes: u64
+}
+
+impl FastqReader {
+  /// Reads the whole input, counting its k-mers of the given size
+  pub fn new<R: BufRead>(input: R, k: usize) -> Result<FastqReader, ReadError> {
+    let (mut ids, mut kmers, mut bytes) = (HashMap::new(), Vec::new(), 0);
+    let mut lines = input.lines().enumerate();
+    while let Some((index, header)) = lines.next() {
+      let header = header.map_err(|source| ReadError::Io{source})?;
+      let mut next = || lines.next().map(|(_, l)| l.map_err(|source| ReadError::Io{source})).transpose();
+      let (sequence, separator, quality) = (next()?, next()?, next()?);
+      bytes += [Some(&header), sequence.as_ref(), separator.as_ref(), quality.as_ref()].iter().flatten().map(|l| l.len() as u64 + 1).sum::<u64>();
+      let (Some(sequence), Some(separator), Some(_)) = (sequence, separator, quality) else {
+        return Err(ReadError::Syntax{line: index + 1, content: header});
+      };
+      if !header.starts_with('@') || !separator.starts_with('+') {
+        return Err(ReadError::Syntax{line: index + 1, content: header});
+      }
+      // Runs of nucleotides, in which the k-mers are
+      for run in sequence.trim_end().split(|c| !"ACGTacgt".contains(c)).filter(|run| run.len() >= k) {
+        let run = run.to_ascii_uppercase();
+        for i in 0..=run.len() - k {
+          let kmer = Unitig::try_from(&run[i..i+k]).unwrap().norm();
+          let id = *ids.entry(kmer.clone()).or_insert_with(|| {
+            kmers.push((kmer, index + 1, 0));
+            kmers.len() - 1
+          });
+          kmers[id].2 += 1;
+        }
+      }
+    }
+
+    Ok(FastqReader{records: kmers::records(k, &ids, kmers).into_iter(), bytes})
+  }
+}
+
+impl Iterator for FastqReader {
+  type Item = Result<Record, ReadError>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    self.records.next().map(Ok)
+  }
+}
+
+impl RecordSource for FastqReader {
+  fn bytes(&self) -> u64 {
+    self.bytes
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use super::super::Orientation;
+  use super::super::reader::Link;
+
+  #[test]
+  fn kmers_of_reads_are_counted_and_linked() {
+    let input = "@r1\nAACCGTT\n+\nIIIIIII\n@r2\nacggtNAACCG\n+\nIIIIIIIIIII\n";
+    let records: Vec<_> = FastqReader::new(input.as_bytes(), 5).unwrap().map(Result::unwrap).collect();
+    let summary: Vec<_> = records.iter().map(|r| (r.sequence.as_str(), r.counts.as_slice(), r.line)).collect();
+    assert_eq!(summary, [("AACCG", &[2][..], 1), ("ACCGT", &[2][..], 1), ("AACGG", &[1][..], 1)]);
+    // AACCG is followed by ACCGT, which is followed by CCGTT, the reverse complement of AACGG
+    assert_eq!(records[0].links, [Link{from_dir: Orientation::Forward, to: 1, to_dir: Orientation::Forward}]);
+    assert_eq!(records[1].links, [
+      Link{from_dir: Orientation::Forward, to: 2, to_dir: Orientation::Reverse},
+      Link{from_dir: Orientation::Reverse, to: 0, to_dir: Orientation::Reverse}
+    ]);
+  }
+
+  #[test]
+  fn malformed_files_are_reported() {
+    let error = |input: &str| FastqReader::new(input.as_bytes(), 5).err().unwrap().to_string();
+    assert_eq!(error("@r1\nAACCG\n+\n"), "Syntax error at line 1: \"@r1\"");
+    assert_eq!(error("@r1\nAACCG\n+\nIIIII\n>r2\nAACCG\n+\nIIIII\n"), "Syntax error at line 5: \">r2\"");
+  }
+}
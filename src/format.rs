@@ -0,0 +1,68 @@
+//! Formatting of the numbers of machine-readable outputs: counts, FASTA headers, TSV and JSON files.
+//!
+//! Integers are written through `Display`, which gives plain decimal digits, without separators
+//! or exponents, on every platform. Ratios must go through this module.
+
+use serde::{Serialize, Serializer, ser::Error};
+use serde_json::value::RawValue;
+use std::time::Duration;
+
+/// Decimal digits of ratios
+pub const RATIO_DECIMALS: usize = 6;
+
+/// Formats a ratio with a fixed number of decimals, or returns `None` if it is NaN or infinite
+pub fn ratio(x: f64) -> Option<String> {
+  x.is_finite().then(|| format!("{:.*}", RATIO_DECIMALS, x))
+}
+
+/// A ratio serialized as a JSON number with fixed decimals, or as null if it is NaN or infinite
+#[derive(Debug, Clone, Copy)]
+pub struct Ratio(pub f64);
+
+impl Serialize for Ratio {
+  fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+    match ratio(self.0) {
+      Some(r) => RawValue::from_string(r).map_err(S::Error::custom)?.serialize(s),
+      None => s.serialize_none()
+    }
+  }
+}
+
+/// Serializes an optional ratio as a JSON number with fixed decimals, or as null if it is missing, NaN or infinite
+pub fn serialize_ratio<S: Serializer>(x: &Option<f64>, s: S) -> Result<S::Ok, S::Error> {
+  match x {
+    Some(x) => Ratio(*x).serialize(s),
+    None => s.serialize_none()
+  }
+}
+
+/// Serializes an optional duration as a JSON number of seconds with fixed decimals, or as null if it is missing
+pub fn serialize_secs<S: Serializer>(d: &Option<Duration>, s: S) -> Result<S::Ok, S::Error> {
+  serialize_ratio(&d.map(|d| d.as_secs_f64()), s)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[derive(Serialize)]
+  struct Ratio(#[serde(serialize_with = "serialize_ratio")] Option<f64>);
+
+  #[test]
+  fn ratios_round_trip() {
+    for x in [0., -0., 0.5, 1. / 3., 1e-7, 1e20, 12345678.9] {
+      let s = ratio(x).unwrap();
+      assert_eq!(s.split('.').nth(1).map(str::len), Some(RATIO_DECIMALS), "{}", s);
+      assert!(s.bytes().all(|b| b.is_ascii_digit() || b == b'.' || b == b'-'), "{}", s);
+      assert!((s.parse::<f64>().unwrap() - x).abs() <= 5e-7 * x.abs().max(1.));
+      let json = serde_json::to_string(&Ratio(Some(x))).unwrap();
+      assert_eq!(json, s);
+      assert_eq!(serde_json::from_str::<f64>(&json).unwrap(), s.parse::<f64>().unwrap());
+    }
+    for x in [f64::NAN, f64::INFINITY, f64::NEG_INFINITY] {
+      assert_eq!(ratio(x), None);
+      assert_eq!(serde_json::to_string(&Ratio(Some(x))).unwrap(), "null");
+    }
+    assert_eq!(serde_json::to_string(&Ratio(None)).unwrap(), "null");
+  }
+}
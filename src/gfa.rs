@@ -0,0 +1,197 @@
+//! Record level reading of GFA1 files, as written by Cuttlefish or Bifrost
+
+use std::collections::{BTreeSet, HashMap};
+use std::io::BufRead;
+use super::Orientation;
+use super::reader::{Link, ReadError, Record, RecordSource};
+
+/// Abundance of a segment, as given by its tags
+#[derive(Debug, Clone, Copy)]
+enum Abundance {
+  /// Counts of its k-mers summed (`KC:i:`)
+  Total(f64),
+  /// Mean count of its k-mers (`dp:f:`)
+  Mean(f64)
+}
+
+/// A segment as read, before its links and counts are resolved
+struct Segment {
+  line: usize,
+  name: String,
+  sequence: String,
+  /// Counts of its k-mers (`ab:Z:` tag), if given
+  counts: Option<Vec<u32>>,
+  /// Abundance given otherwise, if any; a total count is preferred
+  abundance: Option<Abundance>,
+  tags: Vec<String>
+}
+
+/// Parses a number, reporting the whole tag if it is not one
+fn number<T: std::str::FromStr>(line: usize, tag: &str, value: &str) -> Result<T, ReadError> {
+  value.parse().map_err(|_| ReadError::WrongCount{line, count: String::from(tag)})
+}
+
+/// Parses a link sign
+fn sign(sign: &str) -> Option<Orientation> {
+  matches!(sign, "+" | "-").then(|| Orientation::from_sign(sign))
+}
+
+/// Reads the records of a GFA1 file: its `S` lines are the records and its `L` lines their links, each one
+/// given in both directions. The counts of the k-mers of a segment are taken from its `ab:Z:` tag or, failing that,
+/// are all the mean count given by its `KC:i:` (total count) or `dp:f:` (mean count) tag, or 1 without any of them.
+/// k is the overlap of the links plus one, or is found from the `ab:Z:` counts.
+/// The whole file is read at once, since links may come before the segments they join.
+///
+/// # Example
+/// ```
+/// use closed_unitigs::GfaReader;
+///
+/// let input = "H\tVN:Z:1.0\nS\ta\tAACCGT\tKC:i:10\nS\tb\tCCGTTA\tdp:f:4.6\nL\ta\t+\tb\t+\t4M\n";
+/// let records: Vec<_> = GfaReader::new(input.as_bytes()).unwrap().map(Result::unwrap).collect();
+/// assert_eq!((records[0].counts.as_slice(), records[1].counts.as_slice()), (&[5, 5][..], &[5, 5][..]));
+/// ```
+pub struct GfaReader {
+  records: std::vec::IntoIter<Record>,
+  bytes: u64
+}
+
+impl GfaReader {
+  /// Reads the whole input
+  pub fn new<R: BufRead>(input: R) -> Result<GfaReader, ReadError> {
+    let (mut segments, mut links, mut bytes) = (Vec::new(), Vec::new(), 0);
+    let mut overlap = None;
+    for (index, line) in input.lines().enumerate() {
+      let line = line.map_err(|source| ReadError::Io{source})?;
+      bytes += line.len() as u64 + 1;
+      let line_number = index + 1;
+      let fields: Vec<_> = line.trim_end().split('\t').collect();
+      let syntax = || ReadError::Syntax{line: line_number, content: line.clone()};
+      match fields[0] {
+        "S" => {
+          let (name, sequence) = match fields[..] {
+            [_, name, sequence, ..] => (name, if sequence == "*" {""} else {sequence}),
+            _ => return Err(syntax())
+          };
+          let (mut counts, mut abundance, mut tags) = (None, None, Vec::new());
+          for &tag in &fields[3..] {
+            if let Some(field) = tag.strip_prefix("ab:Z:") {
+              counts = Some(field.split(' ').map(|c| number(line_number, tag, c)).collect::<Result<_, _>>()?);
+            } else if let Some(total) = tag.strip_prefix("KC:i:") {
+              abundance = Some(Abundance::Total(number::<u64>(line_number, tag, total)? as f64));
+            } else if let Some(depth) = tag.strip_prefix("dp:f:") {
+              abundance = abundance.or(Some(Abundance::Mean(number(line_number, tag, depth)?)));
+            } else {
+              tags.push(String::from(tag));
+            }
+          }
+          segments.push(Segment{line: line_number, name: String::from(name), sequence: String::from(sequence), counts, abundance, tags});
+        },
+        "L" => {
+          let link = match fields[..] {
+            [_, from, from_dir, to, to_dir, overlap, ..] => sign(from_dir).zip(sign(to_dir)).map(|dirs| (from, to, dirs, overlap)),
+            _ => return Err(syntax())
+          };
+          let (from, to, (from_dir, to_dir), cigar) = link.ok_or_else(|| ReadError::WrongLink{line: line_number, link: line.clone()})?;
+          if cigar != "*" {
+            let bases = cigar.strip_suffix('M').and_then(|n| n.parse::<usize>().ok());
+            match (bases, overlap) {
+              (Some(bases), None) => overlap = Some(bases),
+              (Some(bases), Some(other)) if bases == other => (),
+              _ => return Err(ReadError::WrongLink{line: line_number, link: line.clone()})
+            }
+          }
+          links.push((line_number, String::from(from), from_dir, String::from(to), to_dir));
+        },
+        // Header, comments, paths and the other line types do not change the graph
+        _ => ()
+      }
+    }
+
+    let k = match overlap {
+      Some(overlap) => overlap + 1,
+      None => match segments.iter().find_map(|s| s.counts.as_ref().map(|c| (s.sequence.len() + 1).saturating_sub(c.len()))) {
+        Some(k) => k,
+        None if segments.iter().all(|s| s.sequence.is_empty()) => 0,
+        None => return Err(ReadError::UnknownK)
+      }
+    };
+
+    let ids: HashMap<_, _> = segments.iter().enumerate().map(|(id, s)| (s.name.clone(), id)).collect();
+    let id = |line: usize, name: &str| ids.get(name).copied().ok_or_else(|| ReadError::UnknownSegment{line, name: String::from(name)});
+    // Links given in both directions are kept once
+    let mut directed = BTreeSet::new();
+    for (line, from, from_dir, to, to_dir) in links {
+      let (from, to) = (id(line, &from)?, id(line, &to)?);
+      directed.insert((from, Link{from_dir, to, to_dir}));
+      directed.insert((to, Link{from_dir: !to_dir, to: from, to_dir: !from_dir}));
+    }
+    let mut links = vec![Vec::new(); segments.len()];
+    for (from, link) in directed {
+      links[from].push(link);
+    }
+
+    let mut uncounted = 0;
+    let records = segments.into_iter().zip(links).enumerate().map(|(id, (segment, links))| {
+      let kmers = (segment.sequence.len() + 1).saturating_sub(k);
+      let abundance = segment.abundance;
+      let counts = segment.counts.unwrap_or_else(|| {
+        // The number of k-mers is known once k is
+        let mean = match abundance {
+          Some(Abundance::Total(total)) => total / kmers.max(1) as f64,
+          Some(Abundance::Mean(mean)) => mean,
+          None => {
+            uncounted += 1;
+            1.
+          }
+        };
+        vec![(mean.round() as u32).max(1); kmers]
+      });
+      Record{id, line: segment.line, sequence: segment.sequence, counts, links, tags: segment.tags}
+    }).collect::<Vec<_>>();
+    if uncounted > 0 {
+      log::warn!("{} segments without ab:Z:, KC:i: or dp:f: tag, their k-mers are counted once", uncounted);
+    }
+    Ok(GfaReader{records: records.into_iter(), bytes})
+  }
+}
+
+impl Iterator for GfaReader {
+  type Item = Result<Record, ReadError>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    self.records.next().map(Ok)
+  }
+}
+
+impl RecordSource for GfaReader {
+  fn bytes(&self) -> u64 {
+    self.bytes
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn segments_and_links_are_read() {
+    let input = "H\tVN:Z:1.0\nS\t1\tAACCGT\tKC:i:11\tLN:i:6\nS\t2\tCCGTTA\tab:Z:4 3\nL\t1\t+\t2\t+\t4M\nL\t2\t-\t1\t-\t4M\nS\t3\tGTTAC\n";
+    let records: Vec<_> = GfaReader::new(input.as_bytes()).unwrap().map(Result::unwrap).collect();
+    let summary: Vec<_> = records.iter().map(|r| (r.id, r.line, r.sequence.as_str(), r.counts.clone(), r.links.len())).collect();
+    // The same link given in both directions is kept once
+    assert_eq!(summary, [(0, 2, "AACCGT", vec![6, 6], 1), (1, 3, "CCGTTA", vec![4, 3], 1), (2, 6, "GTTAC", vec![1], 0)]);
+    assert_eq!(records[0].links, [Link{from_dir: Orientation::Forward, to: 1, to_dir: Orientation::Forward}]);
+    assert_eq!(records[1].links, [Link{from_dir: Orientation::Reverse, to: 0, to_dir: Orientation::Reverse}]);
+    assert_eq!(records[0].tags, ["LN:i:6"]);
+  }
+
+  #[test]
+  fn malformed_files_are_reported() {
+    let error = |input: &str| GfaReader::new(input.as_bytes()).err().unwrap().to_string();
+    assert_eq!(error("S\t1\tACGT\nS\t2\tCGTA\tdp:f:x\n"), "Wrong count \"dp:f:x\" at line 2");
+    assert_eq!(error("S\t1\tACGT\nL\t1\t+\t2\t+\t3M\n"), "Unknown segment \"2\" at line 2");
+    assert_eq!(error("S\t1\tACGT\nL\t1\t+\t1\t-\t3M\nL\t1\t-\t1\t+\t2M\n"), "Wrong link \"L\t1\t-\t1\t+\t2M\" at line 3");
+    assert_eq!(error("S\t1\tACGT\n"), "Cannot tell k: the links give no overlap and the segments no ab:Z: counts");
+    assert_eq!(error("S\t1\n"), "Syntax error at line 1: \"S\t1\"");
+  }
+}
@@ -3,20 +3,123 @@
 #[path="./unitig.rs"]
 mod unitig;
 
+use serde::{Deserialize, Serialize};
 use snafu::Snafu;
-use std::io::{BufRead, Write};
-use regex::Regex;
-use std::collections::HashMap;
-use std::convert::{TryFrom, TryInto};
-use unitig::*;
+use std::borrow::Cow;
+use std::io::{self, BufRead, Write};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+use std::collections::hash_map::{DefaultHasher, Entry};
+use std::convert::TryFrom;
+use std::hash::{Hash, Hasher};
+use std::mem::size_of;
+use std::str::FromStr;
+use std::fmt;
+use std::ops::Not;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+use rand::{SeedableRng, seq::SliceRandom};
+use rustc_hash::{FxHashMap, FxHashSet};
+use rand_pcg::Pcg64;
+use xxhash_rust::xxh3::Xxh3;
+use memory::{Degradation, Memory, map_entry};
+use resume::Journal;
+use crate::io::{FastaRecord, FastaWriter};
+use crate::sink::{Metadata, Sink};
+use crate::term;
+
+pub use unitig::{Unitig, UnitigError};
+
+#[path="./builder.rs"]
+mod builder;
+#[path="./reader.rs"]
+mod reader;
+#[path="./gfa.rs"]
+mod gfa;
+#[path="./fastq.rs"]
+mod fastq;
+#[path="./kmers.rs"]
+mod kmers;
+#[path="./kmc.rs"]
+mod kmc;
+#[path="./memory.rs"]
+mod memory;
+#[path="./index.rs"]
+mod index;
+#[path="./spectrum.rs"]
+mod spectrum;
+#[path="./saved.rs"]
+mod saved;
+#[path="./support.rs"]
+mod support;
+#[path="./resume.rs"]
+mod resume;
+
+pub use builder::{CollapseCounts, GraphBuilder, Origin, RecordId};
+pub use resume::{Checkpointing, CloseState};
+pub use index::{IndexError, IndexKey, SortedIndex};
+pub use gfa::GfaReader;
+pub use fastq::FastqReader;
+pub use kmc::KmcReader;
+pub use kmers::KmerTableReader;
+pub use saved::LoadError;
+pub use reader::{BcalmReader, Link, ParallelBcalmReader, ReadError, Record, RecordSource, Segment};
+pub use spectrum::{MinCount, Spectrum, MAX_REMOVED_MASS};
+pub use support::{MeanSupport, MinSupport, QuantileSupport, Support, SupportMeasure};
 
 #[derive(Debug, Snafu)]
 /// Describes and error on graph generation
-enum GraphError {
+pub enum GraphError {
   #[snafu(display("Unknown '{}' nucleotide", nucleo))]
   WrongNucleotide{nucleo: char},
-  #[snafu(display("Unknown nucleotide into sequence \"{}\"", seq))]
-  WrongNucleotideInto{seq: String}
+  #[snafu(display("Unitig of length {} has {} counts, but {} are expected with k = {}", len, counts, (len+1).saturating_sub(*k), k))]
+  WrongCounts{len: usize, counts: usize, k: usize},
+  #[snafu(display("Link to unknown record {}", id))]
+  UnknownRecord{id: usize},
+  #[snafu(display("k-mer {} appears in records {} and {}", kmer, first, second))]
+  DuplicateKmer{kmer: String, first: usize, second: usize},
+  #[snafu(display("Link {} of record {} joins k-mers which do not overlap", link, from))]
+  InconsistentLink{from: usize, to: usize, link: String},
+  #[snafu(display("k-mer {} is not in the graph", kmer))]
+  UnknownKmer{kmer: String},
+  #[snafu(display("{}", source))]
+  Read{source: ReadError},
+  #[snafu(display("{}", source))]
+  Load{source: LoadError}
+}
+
+/// Describes an error on reading a graph, with where it was found
+#[derive(Debug)]
+pub struct ParseError {
+  /// Id of the input record at fault, if the error concerns one
+  pub record: Option<usize>,
+  /// Line of the error, or of the header of the record at fault, if known
+  pub line: Option<usize>,
+  pub source: GraphError
+}
+
+impl fmt::Display for ParseError {
+  /// Displays the error after the record at fault; read errors already tell their line
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match (self.record, self.line) {
+      (Some(record), Some(line)) => write!(f, "record {} at line {}: {}", record, line, self.source),
+      (Some(record), None) => write!(f, "record {}: {}", record, self.source),
+      (None, _) => write!(f, "{}", self.source)
+    }
+  }
+}
+
+impl std::error::Error for ParseError {
+  fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+    Some(&self.source)
+  }
+}
+
+impl From<ReadError> for ParseError {
+  /// Convert ReadError in ParseError, at its line
+  fn from(source: ReadError) -> ParseError {
+    ParseError{record: None, line: source.line(), source: GraphError::Read{source}}
+  }
 }
 
 impl From<UnitigError> for GraphError {
@@ -28,23 +131,745 @@ impl From<UnitigError> for GraphError {
   }
 }
 
-/// Represents a graph node
-#[derive(Debug)]
+/// Order in which closed unitigs are written. Ties are broken by canonical sequence, so that the output is the same at every run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Sort {
+  /// Ascending count, to reduce count differences
+  #[default]
+  Count,
+  /// Descending count, the most supported closed unitigs first
+  CountDesc,
+  /// Descending length, the longest closed unitigs first, then ascending count
+  Length,
+  /// Sequence as written, in closure orientation
+  Lexicographic,
+  /// Production order: each closed unitig is written as soon as it is found
+  None
+}
+
+impl FromStr for Sort {
+  type Err = String;
+
+  /// Parses a sort mode from its command line name; `length-desc` and `seq` are other names of `length` and `lexicographic`
+  fn from_str(s: &str) -> Result<Sort, Self::Err> {
+    match s {
+      "count" => Ok(Sort::Count),
+      "count-desc" => Ok(Sort::CountDesc),
+      "length" | "length-desc" => Ok(Sort::Length),
+      "lexicographic" | "seq" => Ok(Sort::Lexicographic),
+      "none" => Ok(Sort::None),
+      _ => Err(format!("Unknown sort mode \"{}\"", s))
+    }
+  }
+}
+
+/// Options driving the parsing of a FASTA file
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ParseOptions {
+  /// Read only the first records
+  pub head: Option<usize>,
+  /// Merge k-mers appearing in several records instead of failing
+  pub merge_duplicates: bool,
+  /// Collapse records which are the reverse complement of an earlier one, combining their counts as given
+  pub collapse_rc: Option<CollapseCounts>,
+  /// Drop links between k-mers which do not overlap instead of failing
+  pub lenient: bool,
+  /// Split records at this character, dropping the k-mers containing it
+  pub break_char: Option<char>,
+  /// Size of the k-mers, which every record must agree with, instead of telling it from the first record
+  pub k: Option<usize>,
+  /// Parse the headers of BCALM records and build the k-mers of the records on rayon's worker threads,
+  /// reading the input on the calling thread. The graph is the same as a serial parsing gives.
+  #[serde(skip)]
+  pub parallel: bool
+}
+
+/// Order in which k-mers are used as closure seeds
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SeedOrder {
+  /// Input file order
+  #[default]
+  Input,
+  /// Random permutation drawn from the given RNG seed
+  Shuffle
+}
+
+impl FromStr for SeedOrder {
+  type Err = String;
+
+  /// Parses a seed order from its command line name
+  fn from_str(s: &str) -> Result<SeedOrder, Self::Err> {
+    match s {
+      "input" => Ok(SeedOrder::Input),
+      "shuffle" => Ok(SeedOrder::Shuffle),
+      _ => Err(format!("Unknown seed order \"{}\"", s))
+    }
+  }
+}
+
+impl fmt::Display for SeedOrder {
+  /// Displays the command line name of a seed order
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      SeedOrder::Input => write!(f, "input"),
+      SeedOrder::Shuffle => write!(f, "shuffle")
+    }
+  }
+}
+
+/// How closed unitigs are shrunk, trimming their terminal k-mers with count higher than the support
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct ShrinkPolicy {
+  /// Terminal k-mers are trimmed while their count is at least the support plus this delta
+  pub threshold_delta: u32,
+  /// Maximum number of bases trimmed from each end
+  pub max_trim: Option<usize>
+}
+
+impl Default for ShrinkPolicy {
+  /// Trims all the terminal k-mers with count strictly greater than the support
+  fn default() -> ShrinkPolicy {
+    ShrinkPolicy{threshold_delta: 1, max_trim: None}
+  }
+}
+
+impl ShrinkPolicy {
+  /// Finds the range of the k-mers kept from an unitig with the given k-mer counts and support.
+  /// At least one k-mer is always kept.
+  fn trim(&self, counts: &[u32], support: u32) -> (usize, usize) {
+    let threshold = support.saturating_add(self.threshold_delta);
+    let max = self.max_trim.unwrap_or(usize::MAX);
+    let (mut a, mut b) = (0, counts.len());
+    // Try shrink on left
+    while a+1 < b && a < max && counts[a] >= threshold { a += 1 }
+    // Try shrink on right
+    while a+1 < b && counts.len()-b < max && counts[b-1] >= threshold { b -= 1 }
+    (a, b)
+  }
+}
+
+/// Choice of the k-mer extending a closure among the eligible ones, which changes the closed unitigs found.
+/// Ties of the other policies are broken by input order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Extension {
+  /// The first one in input order
+  #[default]
+  First,
+  /// The one joined by the heaviest edge
+  MaxEdgeWeight,
+  /// The one with the highest count
+  HighestCount,
+  /// The one with the lowest count, keeping the support as low as possible
+  LowestCount,
+  /// The lowest one as read extending the closure, that is with the lowest new nucleotide, whatever the input order
+  Lexicographic
+}
+
+impl FromStr for Extension {
+  type Err = String;
+
+  /// Parses an extension policy from its command line name
+  fn from_str(s: &str) -> Result<Extension, Self::Err> {
+    match s {
+      "first" => Ok(Extension::First),
+      "max-edge-weight" => Ok(Extension::MaxEdgeWeight),
+      "highest-count" => Ok(Extension::HighestCount),
+      "lowest-count" => Ok(Extension::LowestCount),
+      "lexicographic" => Ok(Extension::Lexicographic),
+      _ => Err(format!("Unknown extension policy \"{}\"", s))
+    }
+  }
+}
+
+/// How k-mers with more neighbours than `--max-degree` are treated
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DegreePolicy {
+  /// They are not used as seeds
+  SkipSeeds,
+  /// Closures do not extend through them
+  Boundary,
+  /// Both
+  #[default]
+  Both
+}
+
+impl DegreePolicy {
+  /// Whether high degree k-mers are not used as seeds
+  fn skips_seeds(self) -> bool {
+    self != DegreePolicy::Boundary
+  }
+
+  /// Whether closures do not extend through high degree k-mers
+  fn is_boundary(self) -> bool {
+    self != DegreePolicy::SkipSeeds
+  }
+}
+
+impl FromStr for DegreePolicy {
+  type Err = String;
+
+  /// Parses a degree policy from its command line name
+  fn from_str(s: &str) -> Result<DegreePolicy, Self::Err> {
+    match s {
+      "skip-seeds" => Ok(DegreePolicy::SkipSeeds),
+      "boundary" => Ok(DegreePolicy::Boundary),
+      "both" => Ok(DegreePolicy::Both),
+      _ => Err(format!("Unknown degree policy \"{}\"", s))
+    }
+  }
+}
+
+/// A k-mer adjacent to another one
+#[derive(Debug, Clone, PartialEq)]
+pub struct Neighbor {
+  /// The k-mer, oriented as it extends the other one
+  pub kmer: Unitig,
+  /// Its count
+  pub count: u32,
+  /// Number of times the link joining them was given
+  pub weight: u32
+}
+
+/// Options driving the closure of a graph
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CloseOptions {
+  /// Output order
+  pub sort: Sort,
+  /// Seeds order
+  pub seed_order: SeedOrder,
+  /// Seed of the RNG used to shuffle the seeds
+  pub rng_seed: u64,
+  /// Shrinking of the closed unitigs
+  pub shrink: ShrinkPolicy,
+  /// Extensions are taken only by k-mers whose count is at most this ratio times the support (unlimited if None)
+  #[serde(serialize_with = "crate::format::serialize_ratio")]
+  pub max_count_ratio: Option<f64>,
+  /// Choice among the eligible extensions
+  pub extension: Extension,
+  /// Definition of the support of a closure from the counts of its k-mers; gaps are only defined for the minimum
+  pub support: Support,
+  /// Closures with support at most this floor are not extended
+  pub support_floor: Option<u32>,
+  /// Closures at or below the support floor are not written
+  pub drop_below_floor: bool,
+  /// Approximate ceiling, in bytes, on the memory used while closing (unlimited if None)
+  pub max_memory: Option<usize>,
+  /// K-mers with more neighbours are treated according to the degree policy (unlimited if None)
+  pub max_degree: Option<usize>,
+  pub degree_policy: DegreePolicy,
+  /// Whether the k-mers not represented in the output are listed in the summary, with the reason
+  pub list_unrepresented: bool,
+  /// Closures with no extension may jump over up to this many consecutive k-mers below their support,
+  /// which are kept in the closed unitig as gaps (never if None)
+  pub allow_gaps: Option<usize>,
+  /// Whether the closed unitigs are emitted with how shrinking changed them, and trimming statistics are gathered
+  pub shrink_metadata: bool,
+  /// Whether the closed unitigs are emitted with the counts of their k-mers
+  pub kmer_counts: bool,
+  /// Time budget of each closure, after which it stops extending (unlimited if None)
+  #[serde(serialize_with = "crate::format::serialize_secs")]
+  pub closure_timeout: Option<Duration>,
+  /// Closures at least this long, in nucleotides, are not extended (unlimited if None)
+  pub max_length: Option<usize>,
+  /// Closures are extended at most this many times (unlimited if None)
+  pub max_steps: Option<usize>,
+  /// Saving of the state of the closing, to resume it if interrupted or aborted for lack of memory (never if None)
+  #[serde(skip)]
+  pub checkpoint: Option<Checkpointing>,
+  /// Token aborting the closing once cancelled
+  #[serde(skip)]
+  pub cancel: CancelToken
+}
+
+/// Extension steps of a closure between two checks of its timeout and of the cancellation
+const CHECK_STEPS: usize = 64;
+
+/// Cancels a closing cooperatively, from any thread holding a clone of it.
+/// The closure being computed is dropped and the ones already found are still emitted.
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+  /// Asks the closing using this token to stop
+  pub fn cancel(&self) {
+    self.0.store(true, Ordering::Relaxed);
+  }
+
+  /// Whether the closing was asked to stop
+  pub fn is_cancelled(&self) -> bool {
+    self.0.load(Ordering::Relaxed)
+  }
+}
+
+/// Why a k-mer is not represented in the output
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Reason {
+  /// It was not used as seed for its degree
+  Filtered,
+  /// It was in a closure at or below the support floor, which was dropped
+  Floored,
+  /// It was in a closure, but trimmed by shrinking
+  Trimmed,
+  /// No closure reached it and it was not used as seed, since closing was aborted
+  NeverSeeded
+}
+
+impl fmt::Display for Reason {
+  /// Writes the reason code
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Reason::Filtered => write!(f, "filtered"),
+      Reason::Floored => write!(f, "floored"),
+      Reason::Trimmed => write!(f, "trimmed"),
+      Reason::NeverSeeded => write!(f, "never-seeded")
+    }
+  }
+}
+
+/// A k-mer not represented in the output
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Unrepresented {
+  /// Canonical k-mer
+  pub kmer: Unitig,
+  pub count: u32,
+  pub reason: Reason
+}
+
+/// Closed unitigs written with a given support
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SupportStats {
+  /// Number of closed unitigs
+  pub unitigs: usize,
+  /// Total length in bases
+  pub bases: u64,
+  /// Length of the longest one
+  pub max_len: usize
+}
+
+impl SupportStats {
+  /// Accounts for a closed unitig of the given length
+  pub fn add(&mut self, len: usize) {
+    self.unitigs += 1;
+    self.bases += len as u64;
+    self.max_len = self.max_len.max(len);
+  }
+
+  /// Mean length
+  pub fn mean_len(&self) -> f64 {
+    self.bases as f64 / self.unitigs as f64
+  }
+}
+
+impl Serialize for SupportStats {
+  fn serialize<S: serde::Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+    use serde::ser::SerializeStruct;
+    let mut stats = s.serialize_struct("SupportStats", 4)?;
+    stats.serialize_field("n_unitigs", &self.unitigs)?;
+    stats.serialize_field("total_bp", &self.bases)?;
+    stats.serialize_field("mean_len", &crate::format::Ratio(self.mean_len()))?;
+    stats.serialize_field("max_len", &self.max_len)?;
+    stats.end()
+  }
+}
+
+/// Lengths and supports of the closed unitigs written, giving assembly statistics
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AssemblyStats {
+  /// Number of closed unitigs of each length
+  pub lengths: BTreeMap<usize, usize>,
+  /// Sum of their supports
+  pub support: u64
+}
+
+impl AssemblyStats {
+  /// Accounts for a closed unitig of the given length and support
+  pub fn add(&mut self, len: usize, support: u32) {
+    *self.lengths.entry(len).or_default() += 1;
+    self.support += support as u64;
+  }
+
+  /// Number of closed unitigs
+  pub fn unitigs(&self) -> usize {
+    self.lengths.values().sum()
+  }
+
+  /// Total length in bases
+  pub fn bases(&self) -> u64 {
+    self.lengths.iter().map(|(&len, &n)| (len * n) as u64).sum()
+  }
+
+  /// Length of the longest one, 0 if there is none
+  pub fn longest(&self) -> usize {
+    self.lengths.keys().next_back().copied().unwrap_or(0)
+  }
+
+  /// Nx and Lx: the length of the shortest of the longest closed unitigs covering this fraction of the bases (from 0 to 1),
+  /// and their number. Both are 0 without closed unitigs.
+  pub fn nx(&self, fraction: f64) -> (usize, usize) {
+    let target = fraction * self.bases() as f64;
+    let (mut covered, mut unitigs) = (0, 0);
+    for (&len, &n) in self.lengths.iter().rev() {
+      if (covered + len * n) as f64 >= target {
+        let needed = ((target - covered as f64) / len as f64).ceil().max(1.) as usize;
+        return (len, unitigs + needed);
+      }
+      covered += len * n;
+      unitigs += n;
+    }
+    (0, 0)
+  }
+
+  /// Mean support
+  pub fn mean_support(&self) -> f64 {
+    self.support as f64 / self.unitigs() as f64
+  }
+}
+
+impl Serialize for AssemblyStats {
+  fn serialize<S: serde::Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+    use serde::ser::SerializeStruct;
+    let (n50, l50) = self.nx(0.5);
+    let (n90, l90) = self.nx(0.9);
+    let mut stats = s.serialize_struct("AssemblyStats", 8)?;
+    stats.serialize_field("n_unitigs", &self.unitigs())?;
+    stats.serialize_field("total_bp", &self.bases())?;
+    stats.serialize_field("longest", &self.longest())?;
+    stats.serialize_field("n50", &n50)?;
+    stats.serialize_field("l50", &l50)?;
+    stats.serialize_field("n90", &n90)?;
+    stats.serialize_field("l90", &l90)?;
+    stats.serialize_field("mean_support", &crate::format::Ratio(self.mean_support()))?;
+    stats.end()
+  }
+}
+
+/// Connected components of a graph, numbered in the order of their first k-mer in the input
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Components {
+  /// Component of each node
+  pub of_node: Vec<usize>,
+  /// Number of k-mers of each component
+  pub kmers: Vec<usize>,
+  /// Bases of the input records (or segments) of each component
+  pub bases: Vec<u64>
+}
+
+impl Components {
+  /// Number of components
+  pub fn len(&self) -> usize {
+    self.kmers.len()
+  }
+
+  /// Whether the graph has no components
+  pub fn is_empty(&self) -> bool {
+    self.kmers.is_empty()
+  }
+
+  /// Numbers of k-mers of the components, from the largest
+  pub fn sizes(&self) -> Vec<usize> {
+    let mut sizes = self.kmers.clone();
+    sizes.sort_unstable_by(|a, b| b.cmp(a));
+    sizes
+  }
+}
+
+/// Cyclic region of a graph: k-mers each reachable from the others following the edges, on either strand
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cycle {
+  /// Nodes of its k-mers, in increasing order
+  pub nodes: Vec<usize>,
+  /// Input records of its k-mers, in increasing order
+  pub records: Vec<usize>
+}
+
+/// Connected components kept by sampling a graph
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Sample {
+  /// Fraction of the components asked for
+  pub fraction: f64,
+  /// Seed of the random generator choosing them
+  pub seed: u64,
+  /// Number of components of the whole graph
+  pub components: usize,
+  /// Number of components kept
+  pub kept: usize
+}
+
+/// The closed unitig of a single seed
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ClosedUnitig {
+  /// Sequence, on the strand of the seed as given
+  pub sequence: String,
+  pub support: u32,
+  /// Length in bases
+  pub len: usize,
+  /// Nodes of its k-mers, in order
+  pub nodes: Vec<usize>
+}
+
+/// Statistics about the closure of a graph
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CloseSummary {
+  /// Number of k-mers of the graph
+  pub kmers: usize,
+  /// Total count of the k-mers of the graph
+  pub count: u64,
+  /// Number of k-mers represented by at least one closed unitig in the output
+  pub covered_kmers: usize,
+  /// Total count of the represented k-mers
+  pub covered_count: u64,
+  /// Number of closed unitigs written
+  pub outputs: usize,
+  /// Number of closed unitigs by number of k-mers closed by them
+  pub closed_per_output: BTreeMap<usize, usize>,
+  /// Closed unitigs written by support
+  pub per_support: BTreeMap<u32, SupportStats>,
+  /// Lengths of the closed unitigs written
+  pub assembly: AssemblyStats,
+  /// Number of closures stopped by the support floor
+  pub floored: usize,
+  /// Number of seeds skipped for their degree
+  pub skipped_seeds: usize,
+  /// Number of closures stopped before a k-mer with too high degree
+  pub truncated: usize,
+  /// Number of closures stopped by the timeout
+  pub timed_out: usize,
+  /// Number of closures stopped by the maximum length or number of steps
+  pub capped: usize,
+  /// Number of seeds processed before closing was cancelled, if it was
+  #[serde(skip)]
+  pub cancelled: Option<usize>,
+  /// Number of seeds processed before closing was aborted for lack of memory, if it was
+  #[serde(skip)]
+  pub aborted: Option<usize>,
+  /// K-mers not represented in the output, in node order, if they are listed
+  #[serde(skip)]
+  pub unrepresented: Vec<Unrepresented>,
+  /// Components kept, if the graph was sampled before closing
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub sample: Option<Sample>,
+  /// Trimming of the closed unitigs written, if it is gathered
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub trims: Option<TrimStats>
+}
+
+impl fmt::Display for CloseSummary {
+  /// Displays a human readable summary
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let percent = |a, b| if b == 0 {0.} else {a as f64 / b as f64 * 100.};
+    writeln!(f, "Represented k-mers: {} of {} ({:.2}%)", self.covered_kmers, self.kmers, percent(self.covered_kmers as u64, self.kmers as u64))?;
+    writeln!(f, "Represented count mass: {} of {} ({:.2}%)", self.covered_count, self.count, percent(self.covered_count, self.count))?;
+    let closed: usize = self.closed_per_output.iter().map(|(nk, n)| nk * n).sum();
+    let median = self.closed_per_output.iter().scan(0, |seen, (&nk, &n)| {*seen += n; Some((*seen, nk))})
+      .find(|&(seen, _)| 2 * seen >= self.outputs).map_or(0, |(_, nk)| nk);
+    let max = self.closed_per_output.keys().next_back().copied().unwrap_or(0);
+    writeln!(f, "Closed k-mers per output: mean {:.2}, median {}, max {}", if self.outputs == 0 {0.} else {closed as f64 / self.outputs as f64}, median, max)?;
+    let assembly = &self.assembly;
+    let ((n50, l50), (n90, l90)) = (assembly.nx(0.5), assembly.nx(0.9));
+    write!(f, "Closed unitigs: {} of {} bp in total, longest {}, N50 {} (L50 {}), N90 {} (L90 {})", assembly.unitigs(), assembly.bases(), assembly.longest(), n50, l50, n90, l90)?;
+    if assembly.unitigs() > 0 {
+      write!(f, ", mean support {:.2}", assembly.mean_support())?;
+    }
+    if self.floored > 0 {
+      write!(f, "\nClosures at or below the support floor: {}", self.floored)?;
+    }
+    if self.skipped_seeds > 0 || self.truncated > 0 {
+      write!(f, "\nHigh degree k-mers: {} skipped as seeds, {} closures truncated at them", self.skipped_seeds, self.truncated)?;
+    }
+    if self.timed_out > 0 {
+      write!(f, "\nClosures stopped by the timeout: {}", self.timed_out)?;
+    }
+    if self.capped > 0 {
+      write!(f, "\nClosures stopped by the maximum length or steps: {}", self.capped)?;
+    }
+    if let Some(seeds_done) = self.aborted {
+      write!(f, "\nAborted after {} seeds: memory ceiling reached", seeds_done)?;
+    }
+    if let Some(seeds_done) = self.cancelled {
+      write!(f, "\nCancelled after {} seeds", seeds_done)?;
+    }
+    if let Some(TrimStats{trimmed, left_bases, right_bases, support_raised}) = &self.trims {
+      write!(f, "\nShrinking: {} closed unitigs trimmed, by {} bases on the left and {} on the right, {} with a raised support", trimmed, left_bases, right_bases, support_raised)?;
+    }
+    if let Some(Sample{fraction, seed, components, kept}) = &self.sample {
+      write!(f, "\nSampled {} of {} connected components (fraction {}, RNG seed {}): the output covers only them", kept, components, fraction, seed)?;
+    }
+    Ok(())
+  }
+}
+
+/// How shrinking changed a closure
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Trim {
+  /// Length of the closure before shrinking
+  pub closure_len: usize,
+  /// Bases trimmed from the left
+  pub left: usize,
+  /// Bases trimmed from the right
+  pub right: usize,
+  /// Support of the closure before shrinking, lower than the one after if the minimum was trimmed
+  pub support: u32
+}
+
+/// Trimming of the closed unitigs written
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct TrimStats {
+  /// Number of closed unitigs shrunk
+  pub trimmed: usize,
+  pub left_bases: u64,
+  pub right_bases: u64,
+  /// Number of closed unitigs whose support was raised by shrinking
+  pub support_raised: usize
+}
+
+impl TrimStats {
+  /// Accounts for a closed unitig with its support
+  fn add(&mut self, trim: &Trim, support: u32) {
+    if trim.left + trim.right > 0 {
+      self.trimmed += 1;
+    }
+    self.left_bases += trim.left as u64;
+    self.right_bases += trim.right as u64;
+    if support != trim.support {
+      self.support_raised += 1;
+    }
+  }
+}
+
+/// Result of the closure of a seed
+struct Closure {
+  /// Closed unitig
+  unitig: Unitig,
+  /// Its support
+  support: u32,
+  /// Nodes of its k-mers, in order
+  path: VecDeque<usize>,
+  /// Number of k-mers closed by it: the seed and the ones with count equal to the support joined while extending
+  closed: usize,
+  /// Whether its support is at most the support floor, so that it was not extended
+  floored: bool,
+  /// Whether it stopped before a k-mer with too high degree
+  truncated: bool,
+  /// Whether it stopped for the timeout
+  timed_out: bool,
+  /// Whether it stopped at the maximum length or number of steps
+  capped: bool
+}
+
+/// A closed unitig waiting to be sorted
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+struct Pending {
+  /// Position of its seed in the seed order
+  rank: usize,
+  support: u32,
+  /// Number of k-mers closed by it
+  closed: usize,
+  /// How shrinking changed it
+  trim: Trim,
+  /// Whether its closure stopped for the timeout
+  timed_out: bool,
+  /// Whether its closure stopped at the maximum length or number of steps
+  capped: bool
+}
+
+/// Set of already written unitigs remembering only two 64 bit hashes of each one.
+/// A collision on the first hash is detected by the second one and resolved storing the full key;
+/// two different unitigs sharing both hashes (probability about n²/2¹²⁹) would be wrongly deduplicated.
+#[derive(Default)]
+struct Seen {
+  hashes: HashMap<u64, u64>,
+  collisions: HashSet<Unitig>
+}
+
+impl Seen {
+  /// Hashes the normal form of an unitig with the given salt
+  fn hash(salt: u8, u: &Unitig) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    salt.hash(&mut hasher);
+    u.hash(&mut hasher);
+    hasher.finish()
+  }
+
+  /// Inserts an unitig, returning false if it was already present
+  fn insert(&mut self, u: &Unitig) -> bool {
+    let (h1, h2) = (Self::hash(0, u), Self::hash(1, u));
+    match self.hashes.get(&h1) {
+      None => {
+        self.hashes.insert(h1, h2);
+        true
+      },
+      Some(&h) if h == h2 => false,
+      Some(_) => self.collisions.insert(u.clone()) // Collision on first hash
+    }
+  }
+}
+
+/// Strand on which a k-mer is read: as stored in its node, or as its reverse complement.
+/// BCALM writes it as the `+` or `-` sign of the ends of a link, reading a whole record.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Orientation {
+  Forward,
+  Reverse
+}
+
+impl Orientation {
+  /// Orientation of a BCALM link sign
+  pub fn from_sign(sign: &str) -> Orientation {
+    match sign {
+      "+" => Orientation::Forward,
+      _ => Orientation::Reverse
+    }
+  }
+
+  /// BCALM link sign
+  pub fn sign(self) -> char {
+    match self {
+      Orientation::Forward => '+',
+      Orientation::Reverse => '-'
+    }
+  }
+}
+
+impl Not for Orientation {
+  type Output = Orientation;
+
+  /// The other strand
+  fn not(self) -> Orientation {
+    match self {
+      Orientation::Forward => Orientation::Reverse,
+      Orientation::Reverse => Orientation::Forward
+    }
+  }
+}
+
+/// Represents a graph edge between the node owning it, read as `dir`, and node `to`, read as `to_dir`.
+/// In the `out` edges of a node the k-mer of `to` follows the one of the node, overlapping it by k-1 bases;
+/// in its `into` edges it precedes it. Each edge is stored at both ends, in the `out` edges of the first node and in
+/// the `into` edges of the second one. Its twin on the other strand, reading both nodes reverse complemented in the
+/// opposite order, is added for edges inside a record, while for links it comes from the reverse link, which BCALM also writes.
+#[derive(Debug, PartialEq)]
 struct Edge{
   to: usize,
-  start: bool,
-  end: bool
+  dir: Orientation,
+  to_dir: Orientation,
+  /// Number of times the link was given
+  weight: u32
 }
 
 /// Represents a graph node
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 struct Node{
-  /// Sequence of nucleotides
+  /// Sequence of nucleotides; its reverse complement is built when needed, to keep a single k-mer per node
   kmer: Unitig,
-  /// Sequence reverse complement
-  complement: Unitig,
   /// kmer count
   count: u32,
+  /// Where the k-mer was first found
+  origin: Origin,
   /// paths to other nodes
   out: Vec<Edge>,
   /// paths from other nodes
@@ -53,21 +878,110 @@ struct Node{
 
 impl Node{
   /// Creates a new node containing the given sequence and vector of counts.
-  fn new(kmer: Unitig, count: u32) -> Node {
+  fn new(kmer: Unitig, count: u32, origin: Origin) -> Node {
     Node{
-      complement: kmer.rev_compl(),
       kmer,
       count,
+      origin,
       out: vec![],
       into: vec![]
     }
   }
+
+  /// K-mer of the node read in the given orientation
+  fn kmer(&self, dir: Orientation) -> Cow<'_, Unitig> {
+    match dir {
+      Orientation::Forward => Cow::Borrowed(&self.kmer),
+      Orientation::Reverse => Cow::Owned(self.kmer.rev_compl())
+    }
+  }
+}
+
+#[derive(Debug, Snafu)]
+/// Describes why a sequence is not a closed unitig of a graph
+pub enum Violation {
+  #[snafu(display("Sequence shorter than k = {}", k))]
+  TooShort{k: usize},
+  #[snafu(display("k-mer {} is not in the graph", kmer))]
+  MissingKmer{kmer: Unitig},
+  #[snafu(display("k-mers {} and {} are not adjacent in the graph", from, to))]
+  NotAdjacent{from: Unitig, to: Unitig},
+  #[snafu(display("Support is {} but {} was reported", support, reported))]
+  WrongSupport{support: u32, reported: u32},
+  #[snafu(display("Extending on the {} with {} does not decrease the support {}", side, kmer, support))]
+  NotClosed{side: &'static str, kmer: Unitig, support: u32},
+  #[snafu(display("Gap k-mer {} has count {}, not below the support {}", kmer, count, support))]
+  NotGap{kmer: Unitig, count: u32, support: u32}
+}
+
+/// Index of the nodes by canonical k-mer
+#[derive(Debug, PartialEq)]
+enum KmerIndex {
+  /// Built while appending the nodes
+  Map(FxHashMap<Unitig, usize>),
+  /// Loaded from an index file, listing in advance the node of each k-mer appended
+  Sorted(SortedIndex)
+}
+
+/// Map keyed by canonical k-mer: by their 2-bit codes packed in an integer if k is at most 32, by their sequence otherwise.
+/// K-mers are hashed with FxHash, much faster than SipHash on packed words, since the keys are not chosen by an attacker.
+enum KmerMap<V> {
+  Code(FxHashMap<u64, V>),
+  Unitig(FxHashMap<Unitig, V>)
+}
+
+impl<V> KmerMap<V> {
+  /// Builds an empty map of k-mers of the given size
+  fn new(k: usize) -> KmerMap<V> {
+    match k <= 32 {
+      true => KmerMap::Code(FxHashMap::default()),
+      false => KmerMap::Unitig(FxHashMap::default())
+    }
+  }
+
+  /// Approximate bytes taken by an entry of a map of k-mers of the given size
+  fn entry_size(k: usize) -> usize {
+    match k <= 32 {
+      true => map_entry::<u64, V>(0),
+      false => map_entry::<Unitig, V>(Unitig::heap_size(k))
+    }
+  }
+
+  /// Value of a k-mer, given in either orientation
+  fn get(&self, kmer: &Unitig) -> Option<&V> {
+    match self {
+      KmerMap::Code(map) => map.get(&kmer.norm_code().unwrap()),
+      KmerMap::Unitig(map) => map.get(kmer)
+    }
+  }
+
+  /// Sets the value of a k-mer, given in either orientation, returning the previous one
+  fn insert(&mut self, kmer: &Unitig, value: V) -> Option<V> {
+    match self {
+      KmerMap::Code(map) => map.insert(kmer.norm_code().unwrap(), value),
+      KmerMap::Unitig(map) => map.insert(kmer.clone(), value)
+    }
+  }
+}
+
+impl<V> std::ops::Index<&Unitig> for KmerMap<V> {
+  type Output = V;
+
+  /// Value of a k-mer in the map
+  fn index(&self, kmer: &Unitig) -> &V {
+    self.get(kmer).expect("k-mer not in the map")
+  }
 }
 
 /// Represents a de Bruijn graph
+#[derive(Debug, PartialEq)]
 pub struct Graph {
   /// List of nodes of the graph
   nodes: Vec<Node>,
+  /// Nodes of the k-mers of each input record, with the orientation in which the record reads them
+  records: Vec<Vec<(usize, Orientation)>>,
+  /// Index of the nodes by k-mer (in either orientation)
+  index: KmerIndex,
   /// size of the k-mers
   k: usize
 }
@@ -77,219 +991,2138 @@ impl Graph {
   fn new(k: usize) -> Graph {
     Graph{
       nodes: Vec::new(),
+      records: Vec::new(),
+      index: KmerIndex::Map(FxHashMap::default()),
       k
     }
   }
 
   /// Appends a new node to the graph
-  fn append(&mut self, seq: String, count: u32) -> Result<(), GraphError>{
-    self.nodes.push(Node::new(seq.try_into()?, count));
-    Ok(())
+  fn append(&mut self, kmer: Unitig, count: u32, origin: Origin) {
+    let node = Node::new(kmer, count, origin);
+    if let KmerIndex::Sorted(index) = &self.index {
+      if index.get(&node.kmer) != Some(self.nodes.len()) {
+        self.drop_sorted_index();
+      }
+    }
+    if let KmerIndex::Map(index) = &mut self.index {
+      index.insert(node.kmer.clone(), self.nodes.len());
+    }
+    self.nodes.push(node);
   }
 
-  /// Finds support of u, with memorization
-  fn supp(u: &Unitig, k: usize, supp: &mut HashMap<Unitig, u32>) -> u32 {
-    if let Some(&s) = supp.get(u) {
-      // Use memorization
-      return s;
-    }
-    // Compite support taking the minimum of k-mer counts
-    let s = *(0..u.len()-k+1).into_iter()
-      .map(|i| Unitig::try_from(&u[i..i+k]).unwrap()) // Safe because coming from an unitig
-      .map(|u| supp.get(&u).unwrap_or(&0)) // k-mers counts must be already memorized; if the k-mer is not present its support is zero
-      .min().unwrap_or(&0);
-    supp.insert(u.clone(), s); // Memorize
-    s
+  /// Replaces an index file which does not match the graph with an index built from the nodes
+  fn drop_sorted_index(&mut self) {
+    log::warn!("the k-mer index does not match the input, rebuilding it");
+    self.index = KmerIndex::Map(self.nodes.iter().enumerate().map(|(i, n)| (n.kmer.clone(), i)).collect());
   }
 
-  /// Finds closure of m
-  fn closure<'a>(&'a self, m: &Unitig, first: (&'a Node, bool), last: (&'a Node, bool), k: usize, supp: &mut HashMap<Unitig, u32>, (is_closed, n_closed): (&mut HashMap<Unitig, bool>, &mut u32)) -> Unitig {
-    let (mut m, mut first, mut last) = (m.clone(), first, last); // Make those mutable
+  /// Whether k-mers are looked up in an index file
+  pub fn has_sorted_index(&self) -> bool {
+    matches!(self.index, KmerIndex::Sorted(_))
+  }
 
-    // Explore the graph trying to extend this unitig until support decreases
-    'clo: loop {
-      // dbg!(&m);
-      let my_supp = Self::supp(&m, k, supp);
+  /// Builds the sorted index of the k-mers of the graph, to be written to a file
+  pub fn sorted_index(&self) -> Result<SortedIndex, IndexError> {
+    SortedIndex::new(self.k, self.nodes.iter().map(|n| &n.kmer))
+  }
 
-      // Try to extend to the right
-      for Edge{to, start, end} in &last.0.out {
-        if *start != last.1 {continue} // direction do not match
-        let node = &self.nodes[*to]; // target node
-        if m.contains(&node.kmer) || m.contains(&node.complement) {continue} // avoid loops
-        let kmer = if *end {&node.kmer} else {&node.complement};
-        // dbg!("out", node, to, start, end);
-        let c = node.count;
-        if c >= my_supp {
-          if c == my_supp {
-            // The closed unitig we are building is valid also for this k-mer
-            is_closed.insert(kmer.clone(), true);
-            *n_closed += 1;
-          }
-          m = &m + kmer; // Join
-          last = (node, *end); // Extend
-          continue 'clo
-        } // elsewhere the support decreases and so we cannot extend
-      }
-      // Try to extend to the left
-      for Edge{to, start, end} in &first.0.into {
-        if *start != first.1 {continue} // direction do not match
-        let node = &self.nodes[*to]; // target node
-        if m.contains(&node.kmer) || m.contains(&node.complement) {continue} // avoid loops
-        let kmer = if *end {&node.kmer} else {&node.complement};
-        // dbg!("into", node, to, start, end);
-        let c = node.count;
-        if c >= my_supp {
-          if c == my_supp {
-            // The closed unitig we are building is valid also for this k-mer
-            is_closed.insert(kmer.clone(), true);
-            *n_closed += 1;
-          }
-          m = kmer + &m; // Join
-          first = (node, *end); // Extend
-          continue 'clo
-        } // elsewhere the support decreases and so we cannot extend
-      }
-      break
-    };
-    is_closed.insert(m.clone(), true);
-    *n_closed += 1;
-    m //clo
+  /// Size of the k-mers
+  pub fn k(&self) -> usize {
+    self.k
   }
 
-  /// Shrinks a closed unitig removing head and tail with higher support
-  fn shrink(u: Unitig, k: usize, supp: &HashMap<Unitig, u32>) -> (Unitig, u32) {
-    let (mut a, mut b) = (0, u.len()); // extremities
-    let my_supp = supp.get(&u).unwrap();
-    // Try shrink on left
-    while a+k < b && supp.get(&u[a..a+k].try_into().unwrap()).unwrap() > my_supp { a += 1 }
-    // Try shrink on right
-    while b >= k && supp.get(&u[b-k..b].try_into().unwrap()).unwrap() > my_supp { b -= 1 }
-    // Return shrunk closed unitig
-    (u[a..b].try_into().unwrap(), *my_supp)
+  /// Whether the graph has no k-mers
+  pub fn is_empty(&self) -> bool {
+    self.nodes.is_empty()
   }
 
-  /// Finds closed unitigs
-  pub fn close<T: Write, U: Write>(&self, fasta: &mut T, counts: &mut U) {
-    let k = self.k;
-    let mut closed = HashMap::<Unitig, u32>::new(); // using a map instead of a vector avoids duplicates
+  /// Finds the node of a k-mer, with the orientation in which the k-mer reads it
+  fn find(&self, kmer: &Unitig) -> Option<(usize, Orientation)> {
+    let i = match &self.index {
+      KmerIndex::Map(index) => index.get(kmer).copied(),
+      // The index lists also the nodes still to be appended
+      KmerIndex::Sorted(index) => index.get(kmer).filter(|&i| i < self.nodes.len() && self.nodes[i].kmer == *kmer)
+    };
+    i.map(|i| (i, if self.nodes[i].kmer.is_identical(kmer) {Orientation::Forward} else {Orientation::Reverse}))
+  }
 
-    {
-      let mut supp = HashMap::<Unitig, u32>::new();
-      let mut is_closed = HashMap::<Unitig, bool>::new();
-      // Compute k-mers supports (equal to their counts)
-      for node in &self.nodes {
-        supp.insert(node.kmer.clone(), node.count);
-        is_closed.insert(node.kmer.clone(), false);
-      }
+  /// Returns where a k-mer was first found: its input record and the position of its forward strand in it.
+  /// Returns None if the k-mer is not in the graph.
+  pub fn origin(&self, kmer: &Unitig) -> Option<Origin> {
+    self.find(kmer).map(|(n, _)| self.nodes[n].origin)
+  }
 
-      let mut n_closed = 0;
-      // Close and shrink all k-mers
-      for node in &self.nodes {
-        if is_closed[&node.kmer] {continue}
-        print!("Closing {:?} ({:.2}%)\r", node.kmer, (1. + n_closed as f64)/self.nodes.len() as f64*100.);
-        let close = self.closure(&node.kmer, (&node, true), (&node, true), k, &mut supp, (&mut is_closed, &mut n_closed));
-        let (u, c) = Self::shrink(close, k, &supp);
-        closed.insert(u, c);
-      }
-    }
+  /// Whether two graphs have the same k, the same k-mers with the same counts and the same edges,
+  /// whatever the order of their nodes and the strand from which each k-mer and edge is given
+  pub fn equivalent(&self, other: &Graph) -> bool {
+    self.k == other.k && self.kmer_counts() == other.kmer_counts() && self.edge_set() == other.edge_set()
+  }
 
-    let mut closed: Vec<_> = closed.iter().collect();
-    closed.sort_by_key(|(_, &c)| c); // Sort by count to reduce count differences
-    for (u, c) in closed {
-      writeln!(fasta, ">\n{}", u).unwrap();
-      writeln!(counts, "{}", c).unwrap();
-    }
+  /// Canonical k-mers with their counts, sorted
+  fn kmer_counts(&self) -> Vec<(String, u32)> {
+    let mut counts: Vec<_> = self.nodes.iter().map(|n| (String::from(n.kmer.norm()), n.count)).collect();
+    counts.sort_unstable();
+    counts
   }
 
-}
+  /// Edges as pairs of oriented k-mers, each given by the strand on which the pair is smaller
+  fn edge_set(&self) -> BTreeSet<(String, String)> {
+    let kmer = |n: usize, dir: Orientation| self.nodes[n].kmer(dir).to_string();
+    let flip = |n: usize, dir: Orientation| kmer(n, !dir);
+    self.nodes.iter().enumerate().flat_map(|(from, node)| node.out.iter().map(move |e| (from, e)))
+      .map(|(from, e)| (kmer(from, e.dir), kmer(e.to, e.to_dir)).min((flip(e.to, e.to_dir), flip(from, e.dir))))
+      .collect()
+  }
 
-impl<T: BufRead> std::convert::From<T> for Graph {
-  /// Build a de Bruijn graph from FASTA file
-  fn from(buf: T) -> Graph {
-    let mut graph = Graph::new(0); // temporary k = 0
-    let mut nodes = Vec::<(usize, usize)>::new(); // left, right
-    let mut edges = Vec::<((usize, bool), (usize, bool))>::new(); // from, to which are (node, dir)
+  /// Number of distinct k-mers adjacent to each node, on either side
+  pub fn degrees(&self) -> Vec<usize> {
+    self.nodes.iter().map(|n| {
+      let mut neighbors: Vec<_> = n.out.iter().chain(&n.into).map(|e| e.to).collect();
+      neighbors.sort_unstable();
+      neighbors.dedup();
+      neighbors.len()
+    }).collect()
+  }
 
-    let count_re = Regex::new(r"ab:Z:(\d+(?: \d+)*)").unwrap();
-    let link_re = Regex::new(r"L:([+-]):(\d+):([+-])").unwrap();
+  /// K-mers of the nodes, in node order, with their counts
+  pub fn kmers(&self) -> impl ExactSizeIterator<Item = (&Unitig, u32)> + '_ {
+    self.nodes.iter().map(|n| (&n.kmer, n.count))
+  }
 
-    let mut opt = String::new();
+  /// Node of a k-mer, given on either strand
+  pub fn node(&self, kmer: &Unitig) -> Option<usize> {
+    self.find(kmer).map(|(n, _)| n)
+  }
 
-    for (index, line) in buf.lines().enumerate() {
-      let line = line.unwrap();
+  /// Number of edges between k-mers, counting once an edge and its twin on the other strand
+  pub fn edges(&self) -> usize {
+    self.nodes.iter().enumerate().map(|(n, node)| node.out.iter().filter(|e| (n, e.dir) <= (e.to, !e.to_dir)).count()).sum()
+  }
+
+  /// Number of records (or of their segments, when split) and their total length in bases
+  pub fn records(&self) -> (usize, u64) {
+    (self.records.len(), self.records.iter().map(|path| (path.len() + self.k - 1) as u64).sum())
+  }
+
+  /// Labels the connected components of the graph, following edges in either direction
+  pub fn components(&self) -> Components {
+    let mut components = Components{of_node: vec![usize::MAX; self.nodes.len()], ..Default::default()};
+    let mut stack = Vec::new();
+    for start in 0..self.nodes.len() {
+      if components.of_node[start] != usize::MAX {continue}
+      let id = components.len();
+      components.of_node[start] = id;
+      components.kmers.push(0);
+      stack.push(start);
+      while let Some(n) = stack.pop() {
+        components.kmers[id] += 1;
+        for e in self.nodes[n].out.iter().chain(&self.nodes[n].into) {
+          if components.of_node[e.to] == usize::MAX {
+            components.of_node[e.to] = id;
+            stack.push(e.to);
+          }
+        }
+      }
+    }
+    components.bases = vec![0; components.len()];
+    for path in &self.records {
+      components.bases[components.of_node[path[0].0]] += (path.len() + self.k - 1) as u64;
+    }
+    components
+  }
+
+  /// Removes the nodes not kept, with their edges, keeping the order of the others. Records are split into
+  /// their runs of kept nodes, as records are at a break character. Node and record ids change accordingly.
+  pub fn retain_nodes(&mut self, keep: &[bool]) {
+    let mut ids = vec![usize::MAX; self.nodes.len()];
+    for (id, n) in (0..self.nodes.len()).filter(|&n| keep[n]).enumerate() {
+      ids[n] = id;
+    }
+    let nodes = std::mem::take(&mut self.nodes);
+    self.nodes = nodes.into_iter().zip(keep).filter(|(_, &keep)| keep).map(|(mut node, _)| {
+      for edges in [&mut node.out, &mut node.into] {
+        edges.retain(|e| keep[e.to]);
+        for e in edges {
+          e.to = ids[e.to];
+        }
+      }
+      node
+    }).collect();
+    let records = std::mem::take(&mut self.records);
+    for path in records {
+      for run in path.split(|&(n, _)| !keep[n]).filter(|run| !run.is_empty()) {
+        self.records.push(run.iter().map(|&(n, dir)| (ids[n], dir)).collect());
+      }
+    }
+    self.index = KmerIndex::Map(self.nodes.iter().enumerate().map(|(i, n)| (n.kmer.clone(), i)).collect());
+  }
+
+  /// Keeps a random fraction of the connected components, drawn with the given seed, removing the others.
+  /// Closures never leave a component, so those of the kept ones are the same as in the whole graph.
+  pub fn sample_components(&mut self, fraction: f64, seed: u64) -> Sample {
+    assert!((0. ..=1.).contains(&fraction), "The fraction of components to keep must be between 0 and 1");
+    let components = self.components();
+    let mut order: Vec<_> = (0..components.len()).collect();
+    order.shuffle(&mut Pcg64::seed_from_u64(seed));
+    let kept = (fraction * components.len() as f64).round() as usize;
+    let mut keep = vec![false; components.len()];
+    for &c in &order[..kept] {
+      keep[c] = true;
+    }
+    self.retain_nodes(&components.of_node.iter().map(|&c| keep[c]).collect::<Vec<_>>());
+    Sample{fraction, seed, components: components.len(), kept}
+  }
+
+  /// Keeps the connected components with the given numbers, removing the others, and returns the number of components
+  /// of the whole graph; numbers above it are ignored. As with sampling, the closures of the kept components do not change.
+  pub fn retain_components(&mut self, selected: &[usize]) -> usize {
+    let components = self.components();
+    let mut keep = vec![false; components.len()];
+    for &c in selected.iter().filter(|&&c| c < components.len()) {
+      keep[c] = true;
+    }
+    self.retain_nodes(&components.of_node.iter().map(|&c| keep[c]).collect::<Vec<_>>());
+    components.len()
+  }
+
+  /// Finds the cyclic regions of the graph: the strongly connected components of the k-mers read on either strand,
+  /// with more than one of them or with an edge to itself, which closures cannot go around entirely.
+  /// A region is reported once with its reverse complement, in the order of its first k-mer.
+  pub fn cycles(&self) -> Vec<Cycle> {
+    // K-mer n read as dir is vertex 2n, or 2n + 1 reverse complemented
+    let vertex = |n: usize, dir: Orientation| 2 * n + (dir == Orientation::Reverse) as usize;
+    let next = |v: usize| {
+      let dir = if v & 1 == 0 {Orientation::Forward} else {Orientation::Reverse};
+      self.nodes[v / 2].out.iter().filter(move |e| e.dir == dir).map(move |e| vertex(e.to, e.to_dir))
+    };
+    // Tarjan's algorithm, with an explicit stack of the vertices visited and of the edges they followed
+    let vertices = 2 * self.nodes.len();
+    let (mut index, mut low, mut on_stack) = (vec![usize::MAX; vertices], vec![0; vertices], vec![false; vertices]);
+    let (mut stack, mut visited, mut cycles) = (Vec::new(), 0, Vec::new());
+    for root in 0..vertices {
+      if index[root] != usize::MAX {continue}
+      let mut calls = vec![(root, 0)];
+      while let Some(&mut (v, ref mut followed)) = calls.last_mut() {
+        if *followed == 0 && index[v] == usize::MAX {
+          index[v] = visited;
+          low[v] = visited;
+          visited += 1;
+          stack.push(v);
+          on_stack[v] = true;
+        }
+        if let Some(w) = next(v).nth(*followed) {
+          *followed += 1;
+          if index[w] == usize::MAX {
+            calls.push((w, 0));
+          } else if on_stack[w] {
+            low[v] = low[v].min(index[w]);
+          }
+          continue
+        }
+        calls.pop();
+        if let Some(&(u, _)) = calls.last() {
+          low[u] = low[u].min(low[v]);
+        }
+        if low[v] != index[v] {continue}
+        let mut component = Vec::new();
+        loop {
+          let w = stack.pop().unwrap();
+          on_stack[w] = false;
+          component.push(w);
+          if w == v {break}
+        }
+        let cyclic = component.len() > 1 || next(v).any(|w| w == v);
+        // Of a component and its reverse complement, the one with the lowest vertex is kept
+        let canonical = component.iter().min() <= component.iter().map(|&w| w ^ 1).min().as_ref();
+        if cyclic && canonical {
+          let mut nodes: Vec<_> = component.iter().map(|&w| w / 2).collect();
+          nodes.sort_unstable();
+          nodes.dedup();
+          let mut records: Vec<_> = nodes.iter().map(|&n| self.nodes[n].origin.record).collect();
+          records.sort_unstable();
+          records.dedup();
+          cycles.push(Cycle{nodes, records});
+        }
+      }
+    }
+    cycles.sort_unstable_by_key(|c| c.nodes[0]);
+    cycles
+  }
+
+  /// Number of k-mers with each count
+  pub fn spectrum(&self) -> Spectrum {
+    Spectrum::new(self.nodes.iter().map(|n| n.count))
+  }
+
+  /// Removes the k-mers with count below the given minimum, returning how many were removed
+  pub fn drop_below(&mut self, min_count: u32) -> usize {
+    let keep: Vec<_> = self.nodes.iter().map(|n| n.count >= min_count).collect();
+    self.retain_nodes(&keep);
+    keep.len() - self.nodes.len()
+  }
+
+  /// Removes the tips: the chains of k-mers leaving a dead end, shorter than 2k bases and with all their counts below
+  /// `max_count`, which join a branching k-mer having another neighbor on their side with count at least `max_count`.
+  /// These are mostly the branches of sequencing errors near the ends of reads. The tips left by the removal are kept.
+  /// Returns the number of tips and of k-mers removed.
+  pub fn clip_tips(&mut self, max_count: u32) -> (usize, usize) {
+    let next = Graph::adjacent;
+    let mut keep = vec![true; self.nodes.len()];
+    let mut tips = 0;
+    for start in (0..self.nodes.len()).flat_map(|n| [(n, Orientation::Forward), (n, Orientation::Reverse)]) {
+      if !next(self, start, false).is_empty() {continue}
+      // Follows the chain rightwards from the dead end, up to k k-mers (2k-1 bases)
+      let (mut tip, mut last) = (vec![start.0], start);
+      let junction = loop {
+        let &[to] = &next(self, last, true)[..] else {break None};
+        let preceding = next(self, to, false);
+        if preceding.len() > 1 {break Some(preceding)}
+        if tip.len() == self.k || tip.contains(&to.0) {break None}
+        tip.push(to.0);
+        last = to;
+      };
+      let Some(preceding) = junction else {continue};
+      let other = preceding.iter().any(|&(n, _)| n != last.0 && self.nodes[n].count >= max_count);
+      if other && tip.iter().all(|&n| self.nodes[n].count < max_count) {
+        tips += 1;
+        for n in tip {
+          keep[n] = false;
+        }
+      }
+    }
+    let kmers = self.nodes.len();
+    self.retain_nodes(&keep);
+    (tips, kmers - self.nodes.len())
+  }
+
+  /// Pops the simple bubbles: two chains of at most 2k k-mers leaving the same k-mer and joining the same k-mer, whose inner
+  /// k-mers have no other neighbor. The chain with the lower mean count, mostly from a sequencing error or a rare variant,
+  /// is removed, the second one on a tie. Returns the number of bubbles popped and of k-mers removed.
+  pub fn pop_bubbles(&mut self) -> (usize, usize) {
+    let mut keep = vec![true; self.nodes.len()];
+    let mut bubbles = 0;
+    for start in (0..self.nodes.len()).flat_map(|n| [(n, Orientation::Forward), (n, Orientation::Reverse)]) {
+      let branches = self.adjacent(start, true);
+      if branches.len() != 2 || !keep[start.0] {continue}
+      // Inner k-mers of each branch and the k-mer it joins
+      let chains: Vec<_> = branches.into_iter().map(|mut kmer| {
+        let mut chain = Vec::new();
+        loop {
+          if self.adjacent(kmer, false).len() > 1 {break Some((chain, kmer))}
+          if chain.len() == 2 * self.k || kmer.0 == start.0 || chain.contains(&kmer.0) {break None}
+          chain.push(kmer.0);
+          let &[to] = &self.adjacent(kmer, true)[..] else {break None};
+          kmer = to;
+        }
+      }).collect();
+      let [Some((a, a_end)), Some((b, b_end))] = &chains[..] else {continue};
+      if a_end != b_end || a.is_empty() || b.is_empty() || a.iter().chain(b).any(|&n| !keep[n] || n == a_end.0) {continue}
+      // Compares the mean counts without rounding
+      let sum = |chain: &[usize]| chain.iter().map(|&n| self.nodes[n].count as u64).sum::<u64>();
+      let weaker = if sum(a) * (b.len() as u64) < sum(b) * (a.len() as u64) {a} else {b};
+      bubbles += 1;
+      for &n in weaker {
+        keep[n] = false;
+      }
+    }
+    let kmers = self.nodes.len();
+    self.retain_nodes(&keep);
+    (bubbles, kmers - self.nodes.len())
+  }
+
+  /// Distinct k-mers following `(n, dir)`, or preceding it if not `right`, read as they follow (or precede) it
+  fn adjacent(&self, (n, dir): (usize, Orientation), right: bool) -> Vec<(usize, Orientation)> {
+    let edges = if right {&self.nodes[n].out} else {&self.nodes[n].into};
+    let mut adjacent: Vec<_> = edges.iter().filter(|e| e.dir == dir).map(|e| (e.to, e.to_dir)).collect();
+    adjacent.sort_unstable();
+    adjacent.dedup();
+    adjacent
+  }
+
+  /// Computes the support of an unitig (the minimum count of its k-mers).
+  /// Returns None if the unitig is shorter than k or some of its k-mers are not in the graph.
+  pub fn support(&self, u: &Unitig) -> Option<u32> {
+    if u.len() < self.k {
+      return None;
+    }
+    (0..u.len()-self.k+1)
+      .map(|i| self.find(&u.slice(i..i+self.k)).map(|(n, _)| self.nodes[n].count))
+      .min().unwrap() // a missing k-mer (None) is lower than any count
+  }
+
+  /// Computes the count of each k-mer of an unitig, in order.
+  /// Returns None if some of its k-mers are not in the graph.
+  pub fn support_profile(&self, u: &Unitig) -> Option<Vec<u32>> {
+    (0..(u.len()+1).saturating_sub(self.k))
+      .map(|i| self.find(&u.slice(i..i+self.k)).map(|(n, _)| self.nodes[n].count))
+      .collect()
+  }
+
+  /// Where each k-mer of an unitig was first found, with the strand of its record it is read on.
+  /// Returns None if the unitig is shorter than k or some of its k-mers are not in the graph.
+  pub fn provenance(&self, u: &Unitig) -> Option<Vec<(Origin, Orientation)>> {
+    (0..(u.len()+1).saturating_sub(self.k))
+      .map(|i| self.find(&u.slice(i..i+self.k)).map(|(n, dir)| (self.nodes[n].origin, dir)))
+      .collect()
+  }
+
+  /// Finds the k-mers extending the given one on the right (if `right`) or on the left.
+  /// Returns None if the k-mer is not in the graph.
+  pub fn neighbors(&self, kmer: &Unitig, right: bool) -> Option<Vec<Neighbor>> {
+    let (node, dir) = self.find(kmer)?;
+    // Left neighbors of the k-mer are right neighbors of its reverse complement, read backwards
+    let edges = if right {&self.nodes[node].out} else {&self.nodes[node].into};
+    Some(edges.iter().filter(|e| e.dir == dir).map(|Edge{to, to_dir, weight, ..}| {
+      let next = &self.nodes[*to];
+      Neighbor{kmer: next.kmer(*to_dir).into_owned(), count: next.count, weight: *weight}
+    }).collect())
+  }
+
+  /// Checks that an unitig is a closed unitig of this graph with the reported support:
+  /// its k-mers must form a path in the graph, and every extension by a neighbouring k-mer must decrease its support.
+  /// Extensions by k-mers with higher count than the support are accepted, since shrinking removes them.
+  /// The k-mers at the given gap positions do not count toward the support, but must be below it.
+  pub fn check_closed(&self, u: &Unitig, reported: u32, gaps: &[usize]) -> Vec<Violation> {
+    let k = self.k;
+    if u.len() < k {
+      return vec![Violation::TooShort{k}];
+    }
+    let kmers: Vec<Unitig> = (0..u.len()-k+1).map(|i| u.slice(i..i+k)).collect();
+    let mut path = Vec::with_capacity(kmers.len());
+    let mut violations = Vec::new();
+    for kmer in &kmers {
+      match self.find(kmer) {
+        Some(n) => path.push(n),
+        None => violations.push(Violation::MissingKmer{kmer: kmer.clone()})
+      }
+    }
+    if !violations.is_empty() {
+      return violations;
+    }
+
+    // Path
+    for (i, (&(from, dir), &(to, to_dir))) in path.iter().zip(&path[1..]).enumerate() {
+      if !self.nodes[from].out.iter().any(|e| (e.to, e.dir, e.to_dir) == (to, dir, to_dir)) {
+        violations.push(Violation::NotAdjacent{from: kmers[i].clone(), to: kmers[i+1].clone()});
+      }
+    }
+
+    // Support
+    let counts: Vec<_> = path.iter().map(|&(n, _)| self.nodes[n].count).collect();
+    let support = counts.iter().enumerate().filter(|(i, _)| !gaps.contains(i)).map(|(_, &c)| c).min().unwrap_or(0);
+    if support != reported {
+      violations.push(Violation::WrongSupport{support, reported});
+    }
+    for &i in gaps.iter().filter(|&&i| i < counts.len() && counts[i] >= support) {
+      violations.push(Violation::NotGap{kmer: kmers[i].clone(), count: counts[i], support});
+    }
+
+    // Extensions
+    for (side, right, kmer) in [("right", true, &kmers[kmers.len()-1]), ("left", false, &kmers[0])] {
+      for Neighbor{kmer, count, ..} in self.neighbors(kmer, right).unwrap() {
+        if u.contains(&kmer) || u.contains(&kmer.rev_compl()) {continue} // loops are not extensions
+        if count == support {
+          violations.push(Violation::NotClosed{side, kmer, support});
+        }
+      }
+    }
+    violations
+  }
+
+  /// The records of the graph, with the counts of their k-mers and their links, as a reader would give them
+  fn to_records(&self) -> Vec<Record> {
+    // Records entered by each oriented k-mer: by the first one on the forward strand, by the last one on the reverse strand
+    let mut entries = HashMap::new();
+    for (id, path) in self.records.iter().enumerate() {
+      let (first, (last, dir)) = (path[0], path[path.len()-1]);
+      entries.entry(first).or_insert((id, Orientation::Forward));
+      entries.entry((last, !dir)).or_insert((id, Orientation::Reverse));
+    }
+
+    self.records.iter().enumerate().map(|(id, path)| {
+      let counts = path.iter().map(|&(n, _)| self.nodes[n].count).collect();
+      let mut links = Vec::new();
+      // Links leave the record by its last k-mer on the forward strand and by its first one on the reverse strand
+      let (first, (last, dir)) = (path[0], path[path.len()-1]);
+      for (from_dir, (node, dir)) in [(Orientation::Forward, (last, dir)), (Orientation::Reverse, (first.0, !first.1))] {
+        for Edge{to, dir: edge_dir, to_dir, weight} in &self.nodes[node].out {
+          if *edge_dir != dir {continue} // edges inside the record or in the other direction
+          if let Some(&(to, to_dir)) = entries.get(&(*to, *to_dir)) {
+            links.extend((0..*weight).map(|_| Link{from_dir, to, to_dir}));
+          }
+        }
+      }
+      let sequence = String::from(self.spell(path));
+      Record{id, line: 0, sequence, counts, links, tags: Vec::new()}
+    }).collect()
+  }
+
+  /// Writes the graph as a BCALM FASTA file, with per k-mer counts and links
+  pub fn write_bcalm<T: Write>(&self, out: &mut FastaWriter<T>) -> std::io::Result<()> {
+    for Record{id, sequence, counts, links, ..} in self.to_records() {
+      let counts: Vec<_> = counts.iter().map(ToString::to_string).collect();
+      let mut tags = vec![format!("LN:i:{}", sequence.len()), format!("ab:Z:{}", counts.join(" "))];
+      tags.extend(links.iter().map(|Link{from_dir, to, to_dir}| format!("L:{}:{}:{}", from_dir.sign(), to, to_dir.sign())));
+      out.write(&FastaRecord{name: Some(id.to_string()), tags, sequence: &sequence})?;
+    }
+    Ok(())
+  }
+
+  /// Writes the graph as a GFA1 file, with a segment for each record, holding the counts of its k-mers and their sum,
+  /// and each link once, in the direction given by the smaller record
+  pub fn write_gfa<T: Write>(&self, out: &mut T) -> std::io::Result<()> {
+    let records = self.to_records();
+    writeln!(out, "H\tVN:Z:1.0")?;
+    for Record{id, sequence, counts, ..} in &records {
+      let total: u64 = counts.iter().map(|&c| c as u64).sum();
+      let counts: Vec<_> = counts.iter().map(ToString::to_string).collect();
+      writeln!(out, "S\t{}\t{}\tLN:i:{}\tKC:i:{}\tab:Z:{}", id, sequence, sequence.len(), total, counts.join(" "))?;
+    }
+    for Record{id, links, ..} in &records {
+      for &Link{from_dir, to, to_dir} in links {
+        // The same link read from the other record
+        if (*id, from_dir) <= (to, !to_dir) {
+          writeln!(out, "L\t{}\t{}\t{}\t{}\t{}M", id, from_dir.sign(), to, to_dir.sign(), self.k - 1)?;
+        }
+      }
+    }
+    Ok(())
+  }
+
+  /// Writes the k-mers as a Graphviz DOT digraph, each one labelled with its sequence and count, and each edge once,
+  /// labelled with the strands its ends are read on. If `keep` is given, only its nodes are written, with the edges between them.
+  pub fn write_dot<T: Write + ?Sized>(&self, out: &mut T, keep: Option<&[bool]>) -> std::io::Result<()> {
+    let kept = |n: usize| keep.map_or(true, |keep| keep[n]);
+    writeln!(out, "digraph {{")?;
+    writeln!(out, "  node [shape=box, fontname=monospace];")?;
+    for (n, node) in self.nodes.iter().enumerate().filter(|&(n, _)| kept(n)) {
+      writeln!(out, "  {} [label=\"{}\\n{}\"];", n, node.kmer, node.count)?;
+    }
+    for (n, node) in self.nodes.iter().enumerate().filter(|&(n, _)| kept(n)) {
+      // The same edge read from the other strand
+      for e in node.out.iter().filter(|e| kept(e.to) && (n, e.dir) <= (e.to, !e.to_dir)) {
+        writeln!(out, "  {} -> {} [label=\"{}{}\"];", n, e.to, e.dir.sign(), e.to_dir.sign())?;
+      }
+    }
+    writeln!(out, "}}")
+  }
+
+  /// Writes the k-mers as a directed GraphML graph, each node having its `sequence`, `count` and `length` as attributes,
+  /// and each edge once, with the strands its ends are read on (`from_strand`, `to_strand`, `+` or `-`) and its `weight`.
+  /// If `keep` is given, only its nodes are written, with the edges between them.
+  pub fn write_graphml<T: Write + ?Sized>(&self, out: &mut T, keep: Option<&[bool]>) -> std::io::Result<()> {
+    let kept = |n: usize| keep.map_or(true, |keep| keep[n]);
+    writeln!(out, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>")?;
+    writeln!(out, "<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">")?;
+    for (name, domain, kind) in [("sequence", "node", "string"), ("count", "node", "long"), ("length", "node", "int"),
+      ("from_strand", "edge", "string"), ("to_strand", "edge", "string"), ("weight", "edge", "long")] {
+      writeln!(out, "  <key id=\"{}\" for=\"{}\" attr.name=\"{}\" attr.type=\"{}\"/>", name, domain, name, kind)?;
+    }
+    writeln!(out, "  <graph id=\"G\" edgedefault=\"directed\">")?;
+    for (n, node) in self.nodes.iter().enumerate().filter(|&(n, _)| kept(n)) {
+      writeln!(out, "    <node id=\"n{}\"><data key=\"sequence\">{}</data><data key=\"count\">{}</data><data key=\"length\">{}</data></node>",
+        n, node.kmer, node.count, self.k)?;
+    }
+    for (n, node) in self.nodes.iter().enumerate().filter(|&(n, _)| kept(n)) {
+      // The same edge read from the other strand
+      for e in node.out.iter().filter(|e| kept(e.to) && (n, e.dir) <= (e.to, !e.to_dir)) {
+        writeln!(out, "    <edge source=\"n{}\" target=\"n{}\"><data key=\"from_strand\">{}</data><data key=\"to_strand\">{}</data><data key=\"weight\">{}</data></edge>",
+          n, e.to, e.dir.sign(), e.to_dir.sign(), e.weight)?;
+      }
+    }
+    writeln!(out, "  </graph>")?;
+    writeln!(out, "</graphml>")
+  }
+
+  /// Nodes at most `radius` edges away from the node of a k-mer, following the edges either way.
+  /// Returns None if the k-mer is not in the graph.
+  pub fn neighborhood(&self, kmer: &Unitig, radius: usize) -> Option<Vec<bool>> {
+    let start = self.node(kmer)?;
+    let mut near = vec![false; self.nodes.len()];
+    near[start] = true;
+    let mut frontier = vec![start];
+    for _ in 0..radius {
+      let mut next = Vec::new();
+      for n in frontier {
+        for e in self.nodes[n].out.iter().chain(&self.nodes[n].into) {
+          if !near[e.to] {
+            near[e.to] = true;
+            next.push(e.to);
+          }
+        }
+      }
+      frontier = next;
+    }
+    Some(near)
+  }
+
+  /// Finds support of u, given the k-mers counts
+  fn supp(u: &Unitig, k: usize, supp: &KmerMap<u32>) -> u32 {
+    // Compute support taking the minimum of k-mer counts
+    *(0..u.len()-k+1)
+      .map(|i| u.slice(i..i+k))
+      .map(|u| supp.get(&u).unwrap_or(&0)) // if the k-mer is not present its support is zero
+      .min().unwrap_or(&0)
+  }
+
+  /// Chooses the edge extending an unitig with support `my_supp`, made of the nodes `in_path`, among the edges leaving
+  /// its terminal k-mer, read as `dir`, whose target is not in the unitig and does not decrease its support.
+  /// Targets with too high degree, if given, are excluded, setting `truncated` if there is no other extension.
+  fn extension<'a>(&self, edges: &'a [Edge], (dir, in_path, my_supp): (Orientation, &FxHashSet<usize>, u32), options: &CloseOptions, (degrees, truncated): (Option<&[usize]>, &mut bool)) -> Option<&'a Edge> {
+    let mut blocked = false;
+    let mut candidates = edges.iter()
+      .filter(|e| e.dir == dir) // orientation must match
+      .filter(|e| {
+        // elsewhere the support decreases and so we cannot extend
+        let c = self.nodes[e.to].count;
+        c >= my_supp && options.max_count_ratio.map_or(true, |r| c as f64 <= my_supp as f64 * r)
+      })
+      .filter(|e| !in_path.contains(&e.to)) // avoid loops
+      .filter(|e| {
+        let high = degrees.is_some_and(|d| d[e.to] > options.max_degree.unwrap_or(usize::MAX));
+        blocked |= high;
+        !high
+      });
+    let edge = match options.extension {
+      Extension::First => candidates.next(),
+      // The last maximum backwards is the first one
+      Extension::MaxEdgeWeight => candidates.rev().max_by_key(|e| e.weight),
+      Extension::HighestCount => candidates.rev().max_by_key(|e| self.nodes[e.to].count),
+      Extension::LowestCount => candidates.min_by_key(|e| self.nodes[e.to].count),
+      // The candidates overlap the closure alike, so they differ only by the new nucleotide
+      Extension::Lexicographic => candidates.min_by(|e, f| self.nodes[e.to].kmer(e.to_dir).codes().cmp(self.nodes[f.to].kmer(f.to_dir).codes()))
+    };
+    *truncated |= edge.is_none() && blocked;
+    edge
+  }
+
+  /// Looks for a path leaving the terminal k-mer `(node, dir)` of an unitig with support `my_supp`, made of the nodes `in_path`
+  /// (by the edges leaving its end if `right`, its start otherwise) through at most `max_gap` k-mers with count below the support,
+  /// followed by a k-mer which does not decrease it. Returns the k-mers of the path, oriented as they extend the unitig.
+  fn gap(&self, (node, dir): (usize, Orientation), right: bool, (in_path, my_supp): (&FxHashSet<usize>, u32), max_gap: usize, options: &CloseOptions, degrees: Option<&[usize]>) -> Option<Vec<(usize, Orientation)>> {
+    let edges = |n: usize| if right {&self.nodes[n].out} else {&self.nodes[n].into};
+    let eligible = |e: &Edge, steps: &[(usize, Orientation)]| {
+      !in_path.contains(&e.to) && steps.iter().all(|&(n, _)| n != e.to)
+        && degrees.map_or(true, |d| d[e.to] <= options.max_degree.unwrap_or(usize::MAX))
+    };
+    // Depth first, trying the edges in order as the first extension does
+    let mut stack = vec![(vec![], edges(node).iter().filter(|e| e.dir == dir).collect::<Vec<_>>())];
+    while let Some((steps, mut pending)) = stack.pop() {
+      if pending.is_empty() {continue}
+      let e = pending.remove(0);
+      stack.push((steps.clone(), pending));
+      if !eligible(e, &steps) {continue}
+      let c = self.nodes[e.to].count;
+      let mut steps = steps;
+      steps.push((e.to, e.to_dir));
+      if c >= my_supp {
+        // A path made only of the resuming k-mer would be an extension
+        if steps.len() > 1 && options.max_count_ratio.map_or(true, |r| c as f64 <= my_supp as f64 * r) {
+          return Some(steps);
+        }
+      } else if steps.len() <= max_gap {
+        let next = edges(e.to).iter().filter(|f| f.dir == e.to_dir).collect();
+        stack.push((steps, next));
+      }
+    }
+    None
+  }
+
+  /// Finds closure of the k-mer of a seed node
+  /// Closures do not extend through k-mers with too high degree, if degrees are given.
+  fn closure(&self, seed: usize, k: usize, supp: Option<&KmerMap<u32>>, (is_closed, n_closed): (&mut KmerMap<bool>, &mut u32), options: &CloseOptions, degrees: Option<&[usize]>) -> Closure {
+    // The oriented k-mers joined, spelling the closed unitig only once it is complete, and their nodes to avoid loops
+    let mut path = VecDeque::from(vec![(seed, Orientation::Forward)]);
+    let mut in_path: FxHashSet<_> = std::iter::once(seed).collect();
+    let (mut first, mut last) = ((seed, Orientation::Forward), (seed, Orientation::Forward));
+    // The support is computed only for the seed, then measured incrementally: joining a k-mer with count c
+    // makes it min(support, c) for the minimum
+    let mut measure = options.support.measure();
+    measure.push(supp.map_or(self.nodes[seed].count, |supp| Self::supp(&self.nodes[seed].kmer, k, supp)));
+    let mut my_supp = measure.support();
+    let mut closed = 1; // the seed
+    // The support never decreases, so a closure is either floored from the seed or never
+    let floored = options.support_floor.is_some_and(|floor| my_supp <= floor);
+    let mut truncated = false;
+    let mut gapped = false;
+    let deadline = options.closure_timeout.map(|timeout| Instant::now() + timeout);
+    let mut timed_out = false;
+    let mut capped = false;
+
+    // Explore the graph trying to extend this unitig until support decreases
+    'clo: for steps in 1.. {
+      if floored {break}
+      // A closure at its bound stops, as capped, only if it could still be extended
+      let at_bound = options.max_steps.is_some_and(|max| steps > max) || options.max_length.is_some_and(|max| path.len() + k > max);
+      // Reading the clock at every step would slow down the extension
+      if steps % CHECK_STEPS == 0 {
+        if options.cancel.is_cancelled() {break}
+        if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+          timed_out = true;
+          break;
+        }
+      }
+
+      // Try to extend to the right
+      if let Some(&Edge{to, to_dir, ..}) = self.extension(&self.nodes[last.0].out, (last.1, &in_path, my_supp), options, (degrees, &mut truncated)) {
+        if at_bound {
+          capped = true;
+          break
+        }
+        let node = &self.nodes[to]; // target node
+        let kmer = node.kmer(to_dir);
+        let c = node.count;
+        if c == my_supp {
+          // The closed unitig we are building is valid also for this k-mer
+          if is_closed.insert(&kmer, true) == Some(false) {
+            closed += 1;
+          }
+          *n_closed += 1;
+        }
+        measure.push(c);
+        my_supp = measure.support();
+        path.push_back((to, to_dir)); // Join
+        in_path.insert(to);
+        last = (to, to_dir); // Extend
+        continue 'clo
+      }
+      // Try to extend to the left
+      if let Some(&Edge{to, to_dir, ..}) = self.extension(&self.nodes[first.0].into, (first.1, &in_path, my_supp), options, (degrees, &mut truncated)) {
+        if at_bound {
+          capped = true;
+          break
+        }
+        let node = &self.nodes[to]; // target node
+        let kmer = node.kmer(to_dir);
+        let c = node.count;
+        if c == my_supp {
+          // The closed unitig we are building is valid also for this k-mer
+          if is_closed.insert(&kmer, true) == Some(false) {
+            closed += 1;
+          }
+          *n_closed += 1;
+        }
+        measure.push(c);
+        my_supp = measure.support();
+        path.push_front((to, to_dir)); // Join
+        in_path.insert(to);
+        first = (to, to_dir); // Extend
+        continue 'clo
+      }
+      // Jump over k-mers below the support, on the right then on the left
+      if let Some(max_gap) = options.allow_gaps {
+        for right in [true, false] {
+          let terminal = if right {last} else {first};
+          if let Some(steps) = self.gap(terminal, right, (&in_path, my_supp), max_gap, options, degrees) {
+            if at_bound {
+              capped = true;
+              break 'clo
+            }
+            for (to, to_dir) in steps {
+              let node = &self.nodes[to];
+              let kmer = node.kmer(to_dir);
+              // Gap k-mers do not count toward the support, and the resuming one does not decrease it
+              if node.count >= my_supp {
+                measure.push(node.count);
+              }
+              if node.count == my_supp {
+                if is_closed.insert(&kmer, true) == Some(false) {
+                  closed += 1;
+                }
+                *n_closed += 1;
+              }
+              in_path.insert(to);
+              if right {
+                path.push_back((to, to_dir));
+                last = (to, to_dir);
+              } else {
+                path.push_front((to, to_dir));
+                first = (to, to_dir);
+              }
+            }
+            my_supp = measure.support();
+            gapped = true;
+            continue 'clo
+          }
+        }
+      }
+      break
+    }
+    // A closure which did not extend closes its seed
+    if path.len() == 1 {
+      is_closed.insert(&self.nodes[seed].kmer, true);
+    }
+    *n_closed += 1;
+    let unitig = self.spell(&path);
+    // Only the joined k-mers can lower the support, so it is never computed again but checked once in debug builds
+    if let Some(supp) = supp.filter(|_| cfg!(debug_assertions) && !gapped && options.support == Support::Min) {
+      debug_assert_eq!(my_supp, Self::supp(&unitig, k, supp), "Wrong incremental support of {:?}", unitig);
+    }
+    Closure{unitig, support: my_supp, path: path.into_iter().map(|(n, _)| n).collect(), closed, floored, truncated, timed_out, capped} //clo
+  }
+
+  /// Spells the unitig read along the given oriented nodes, each one overlapping the previous one by k-1 nucleotides
+  fn spell<'a>(&self, path: impl IntoIterator<Item = &'a (usize, Orientation)>) -> Unitig {
+    let mut path = path.into_iter();
+    let &(n, dir) = path.next().expect("empty path");
+    let mut unitig = self.nodes[n].kmer(dir).into_owned();
+    for &(n, dir) in path {
+      unitig.push_overlap(&self.nodes[n].kmer(dir), self.k - 1);
+    }
+    unitig
+  }
+
+  /// Shrinks a closed unitig removing head and tail with higher support, returning it with how it was changed
+  fn shrink(&self, Closure{unitig: u, support: my_supp, mut path, closed, floored, truncated, timed_out, capped}: Closure, (policy, measure): (&ShrinkPolicy, Support)) -> (Closure, Trim) {
+    let counts: Vec<_> = path.iter().map(|&n| self.nodes[n].count).collect();
+    let (a, b) = policy.trim(&counts, my_supp);
+    // Drop the trimmed k-mers from the path
+    path.truncate(b);
+    path.drain(..a);
+    // Trimming k-mers with count equal to the support may raise it; gaps, below the minimum, do not count
+    let support = match measure {
+      Support::Min => *counts[a..b].iter().filter(|&&c| c >= my_supp).min().unwrap(),
+      _ => measure.of(counts[a..b].iter().copied())
+    };
+    let trim = Trim{closure_len: u.len(), left: a, right: counts.len() - b, support: my_supp};
+    // Return shrunk closed unitig
+    (Closure{unitig: u.slice(a..b+self.k-1), support, path, closed, floored, truncated, timed_out, capped}, trim)
+  }
+
+  /// Shrinks an unitig of this graph with the given policy, returning it with its support.
+  /// The result is never shorter than k.
+  ///
+  /// # Panics
+  /// If the unitig is shorter than k or some of its k-mers are not in the graph.
+  pub fn shrink_unitig(&self, u: &Unitig, policy: ShrinkPolicy) -> (Unitig, u32) {
+    assert!(u.len() >= self.k, "Unitig shorter than k = {}", self.k);
+    let counts = self.support_profile(u).expect("Unitig not in the graph");
+    let (a, b) = policy.trim(&counts, *counts.iter().min().unwrap());
+    (u.slice(a..b+self.k-1), *counts[a..b].iter().min().unwrap())
+  }
+
+  /// Computes the closed unitig of a single k-mer, given on either strand, with the default options
+  pub fn close_seed(&self, kmer: &str) -> Result<ClosedUnitig, GraphError> {
+    self.close_seed_with(kmer, &CloseOptions::default())
+  }
+
+  /// Computes the closed unitig of a single k-mer, given on either strand, as closing the whole graph
+  /// would if it were the first seed. The graph is not changed, so that seeds can be closed concurrently.
+  pub fn close_seed_with(&self, kmer: &str, options: &CloseOptions) -> Result<ClosedUnitig, GraphError> {
+    let unitig = Unitig::try_from(kmer)?;
+    let (seed, dir) = Some(&unitig).filter(|u| u.len() == self.k).and_then(|u| self.find(u))
+      .ok_or_else(|| GraphError::UnknownKmer{kmer: String::from(kmer)})?;
+    let degrees = options.max_degree.filter(|_| options.degree_policy.is_boundary()).map(|_| self.degrees());
+    let close = self.closure(seed, self.k, None, (&mut KmerMap::new(self.k), &mut 0), options, degrees.as_deref());
+    let (Closure{unitig, support, path, ..}, _) = self.shrink(close, (&options.shrink, options.support));
+    // The closure reads the seed on its forward strand
+    let (unitig, nodes) = match dir {
+      Orientation::Forward => (unitig, path.into()),
+      Orientation::Reverse => (unitig.rev_compl(), path.into_iter().rev().collect())
+    };
+    Ok(ClosedUnitig{len: unitig.len(), sequence: String::from(unitig), support, nodes})
+  }
+
+  /// Emits a closed unitig with its count, number of closed k-mers, trimming and timeout, assigning it the next id.
+  /// The counts of its k-mers are looked up only if asked or if closures may jump over gap k-mers, below the support.
+  fn emit<S: Sink + ?Sized>(&self, sink: &mut S, summary: &mut CloseSummary, u: &Unitig, &Pending{support: c, closed, trim, timed_out, capped, ..}: &Pending, options: &CloseOptions) -> io::Result<()> {
+    let counts = (options.kmer_counts || options.allow_gaps.is_some()).then(|| self.support_profile(u).unwrap());
+    let gaps: Vec<_> = match (options.allow_gaps, &counts) {
+      (Some(_), Some(counts)) => counts.iter().enumerate().filter(|(_, &count)| count < c).map(|(i, _)| i).collect(),
+      _ => Vec::new()
+    };
+    let counts = counts.as_deref().filter(|_| options.kmer_counts);
+    sink.emit(summary.outputs, u, c, &Metadata{closed, gaps: &gaps, trim: options.shrink_metadata.then_some(&trim), timed_out, capped, counts})?;
+    if let Some(trims) = &mut summary.trims {
+      trims.add(&trim, c);
+    }
+    summary.outputs += 1;
+    *summary.closed_per_output.entry(closed).or_insert(0) += 1;
+    summary.per_support.entry(c).or_default().add(u.len());
+    summary.assembly.add(u.len(), c);
+    Ok(())
+  }
+
+  /// Emits closed unitigs sorted as asked, by count by default.
+  /// Ties are broken by sequence, so that ids do not depend on the map order.
+  fn emit_sorted<S: Sink + ?Sized>(&self, sink: &mut S, summary: &mut CloseSummary, closed: FxHashMap<Unitig, Pending>, options: &CloseOptions) -> io::Result<()> {
+    let mut closed: Vec<_> = closed.iter().collect();
+    // Equal canonical sequences are a single entry, so the order is total
+    closed.sort_unstable_by(|(u, p), (v, q)| match options.sort {
+      Sort::Length => v.len().cmp(&u.len()).then(p.support.cmp(&q.support)),
+      Sort::CountDesc => q.support.cmp(&p.support),
+      // By the sequence as written, in closure orientation, rather than by canonical form
+      Sort::Lexicographic => u.codes().cmp(v.codes()),
+      // Sort by count to reduce count differences; closed unitigs left when streaming as well
+      Sort::Count | Sort::None => p.support.cmp(&q.support)
+    }.then_with(|| u.cmp(v)));
+    for (u, p) in closed {
+      self.emit(sink, summary, u, p, options)?;
+    }
+    Ok(())
+  }
+
+  /// Keeps a closed unitig waiting to be sorted, returning whether its sequence is new.
+  /// Among equal (canonical) sequences the closure of the earliest seed in seed order is kept, with its support
+  /// and closed k-mers, whatever the insertion order: this is the one written when streaming.
+  fn keep(closed: &mut FxHashMap<Unitig, Pending>, u: Unitig, pending: Pending) -> bool {
+    match closed.entry(u) {
+      Entry::Vacant(entry) => {
+        entry.insert(pending);
+        true
+      },
+      Entry::Occupied(mut entry) => {
+        if pending.rank < entry.get().rank {
+          entry.insert(pending);
+        }
+        false
+      }
+    }
+  }
+
+  /// Approximate bytes taken by the graph
+  fn memory(&self) -> usize {
+    let nodes: usize = self.nodes.iter().map(|n| Unitig::heap_size(self.k) + (n.out.capacity() + n.into.capacity()) * size_of::<Edge>()).sum();
+    let records: usize = self.records.iter().map(|r| size_of::<Vec<(usize, Orientation)>>() + r.capacity() * size_of::<(usize, Orientation)>()).sum();
+    self.nodes.capacity() * size_of::<Node>() + nodes + records + match &self.index {
+      KmerIndex::Map(index) => index.len() * map_entry::<Unitig, usize>(Unitig::heap_size(self.k)),
+      KmerIndex::Sorted(index) => index.len() * size_of::<(u128, usize)>()
+    }
+  }
+
+  /// Fingerprint of the graph and of the options driving its closing, which a saved state must match to be resumed.
+  /// The memory ceiling is left out, so that a closing aborted for lack of memory can be resumed with a higher one.
+  pub fn fingerprint(&self, options: &CloseOptions) -> u64 {
+    let mut hasher = Xxh3::new();
+    hasher.update(&(self.k as u64).to_le_bytes());
+    for node in &self.nodes {
+      for code in node.kmer.codes() {
+        hasher.update(&[code]);
+      }
+      hasher.update(&node.count.to_le_bytes());
+      for e in &node.out {
+        hasher.update(&(e.to as u64).to_le_bytes());
+        hasher.update(&[e.dir.sign() as u8, e.to_dir.sign() as u8]);
+      }
+    }
+    let mut options = serde_json::to_value(options).unwrap();
+    options.as_object_mut().unwrap().remove("max_memory");
+    hasher.update(options.to_string().as_bytes());
+    hasher.digest()
+  }
+
+  /// Finds closed unitigs, emitting them to a sink.
+  /// Under a memory ceiling, steps trading the output order for memory are taken when it gets near,
+  /// and closing is aborted if it is exceeded anyway, saving its state if checkpointing.
+  /// Fails only if the sink or the journal of the closed unitigs found does.
+  pub fn close<S: Sink + ?Sized>(&self, sink: &mut S, options: &CloseOptions) -> io::Result<CloseSummary> {
+    self.close_from(sink, options, None)
+  }
+
+  /// Resumes a closing from the state saved at one of its checkpoints, emitting again the closed unitigs found before it,
+  /// read from the journal of `options.checkpoint`, so that the sink gets the same output as without the interruption.
+  ///
+  /// # Panics
+  /// If the state was saved for another graph or other options, as told by [`Graph::fingerprint`],
+  /// or if `options.checkpoint` is None.
+  pub fn resume<S: Sink + ?Sized>(&self, sink: &mut S, options: &CloseOptions, state: CloseState) -> io::Result<CloseSummary> {
+    assert!(options.checkpoint.is_some(), "No journal to read the closed unitigs found from");
+    self.close_from(sink, options, Some(state))
+  }
+
+  /// Finds closed unitigs from the given state, or from the start
+  fn close_from<S: Sink + ?Sized>(&self, sink: &mut S, options: &CloseOptions, state: Option<CloseState>) -> io::Result<CloseSummary> {
+    term::phase_start(term::Phase::Close);
+    let k = self.k;
+    let mut summary = CloseSummary{trims: options.shrink_metadata.then(TrimStats::default), ..Default::default()};
+    let mut covered = vec![false; self.nodes.len()]; // k-mers represented in the output
+    let mut reasons = vec![None; self.nodes.len()]; // why they may not be
+    let mut closed = FxHashMap::<Unitig, Pending>::default(); // using a map instead of a vector avoids duplicates
+    let mut seen = Seen::default(); // lighter deduplication when streaming
+    let mut streaming = options.sort == Sort::None;
+
+    {
+      let mut supp = KmerMap::new(k);
+      let mut is_closed = KmerMap::new(k);
+      // Compute k-mers supports (equal to their counts)
+      for node in &self.nodes {
+        supp.insert(&node.kmer, node.count);
+        is_closed.insert(&node.kmer, false);
+      }
+      let mut supp = Some(supp);
+      let mut memory = options.max_memory.map(|ceiling| {
+        let mut memory = Memory::new(ceiling);
+        memory.graph = self.memory();
+        memory.supp = self.nodes.len() * KmerMap::<u32>::entry_size(k);
+        memory.is_closed = self.nodes.len() * KmerMap::<bool>::entry_size(k);
+        memory
+      });
+
+      // Seeds order
+      let mut order: Vec<usize> = (0..self.nodes.len()).collect();
+      if options.seed_order == SeedOrder::Shuffle {
+        // A fixed algorithm RNG makes the permutation reproducible across platforms
+        order.shuffle(&mut Pcg64::seed_from_u64(options.rng_seed));
+      }
+
+      let degrees = options.max_degree.map(|_| self.degrees());
+      let max_degree = options.max_degree.unwrap_or(usize::MAX);
+      let boundary = degrees.as_deref().filter(|_| options.degree_policy.is_boundary());
+
+      let mut n_closed = 0;
+      let fingerprint = options.checkpoint.as_ref().map(|_| self.fingerprint(options));
+      if let Some(state) = &state {
+        assert_eq!(Some(state.fingerprint), fingerprint, "State saved for another graph or other options");
+      }
+      let mut last_checkpoint = Instant::now();
+      let start = state.as_ref().map_or(0, |state| state.seeds_done);
+      let mut journal = match (&options.checkpoint, state) {
+        (None, _) => None,
+        (Some(checkpoint), None) => Some(Journal::create(&checkpoint.journal())?),
+        (Some(checkpoint), Some(state)) => {
+          for (n, node) in self.nodes.iter().enumerate() {
+            if resume::flag(&state.closed, n) {
+              is_closed.insert(&node.kmer, true);
+              n_closed += 1;
+            }
+            covered[n] = resume::flag(&state.covered, n);
+          }
+          for (n, reason) in state.reasons {
+            reasons[n] = Some(reason);
+          }
+          summary.skipped_seeds = state.skipped_seeds;
+          summary.truncated = state.truncated;
+          summary.timed_out = state.timed_out;
+          summary.capped = state.capped;
+          summary.floored = state.floored;
+          // The journal holds each closed unitig once, as written when streaming
+          Some(Journal::resume(&checkpoint.journal(), state.journal_len, |u, pending| {
+            let len = u.len();
+            if streaming {
+              seen.insert(&u);
+              self.emit(sink, &mut summary, &u, &pending, options)?;
+            } else {
+              Self::keep(&mut closed, u, pending);
+            }
+            if let Some(memory) = &mut memory {
+              match streaming {
+                true => memory.seen += map_entry::<u64, u64>(0),
+                false => memory.pending += map_entry::<Unitig, Pending>(Unitig::heap_size(len))
+              }
+            }
+            Ok(())
+          })?)
+        }
+      };
+      // Saves the state before processing the given number of seeds
+      let save = |checkpoint: &Checkpointing, journal: &mut Journal, seeds_done, is_closed: &KmerMap<bool>, covered: &[bool], reasons: &[Option<Reason>], summary: &CloseSummary| -> io::Result<()> {
+        CloseState{
+          fingerprint: fingerprint.unwrap(),
+          seeds_done,
+          closed: resume::pack(self.nodes.iter().map(|node| is_closed[&node.kmer])),
+          covered: resume::pack(covered.iter().copied()),
+          reasons: reasons.iter().enumerate().filter_map(|(n, reason)| reason.map(|reason| (n, reason))).collect(),
+          skipped_seeds: summary.skipped_seeds,
+          truncated: summary.truncated,
+          timed_out: summary.timed_out,
+          capped: summary.capped,
+          floored: summary.floored,
+          journal_len: journal.sync()?
+        }.write(&checkpoint.path)
+      };
+
+      // Close and shrink all k-mers
+      for (done, &seed) in order.iter().enumerate().skip(start) {
+        if let (Some(checkpoint), Some(journal)) = (&options.checkpoint, &mut journal) {
+          if checkpoint.every.is_some_and(|every| last_checkpoint.elapsed() >= every) {
+            // A failed checkpoint leaves the previous one, closing goes on
+            if let Err(e) = save(checkpoint, journal, done, &is_closed, &covered, &reasons, &summary) {
+              log::warn!("cannot write the checkpoint {}: {}", checkpoint.path.display(), e);
+            }
+            last_checkpoint = Instant::now();
+          }
+        }
+        let node = &self.nodes[seed];
+        if is_closed[&node.kmer] {continue}
+        if options.degree_policy.skips_seeds() && degrees.as_ref().is_some_and(|d| d[seed] > max_degree) {
+          summary.skipped_seeds += 1;
+          reasons[seed] = Some(Reason::Filtered);
+          continue;
+        }
 
-      print!("Reading fasta file (line {})\r", index+1);
+        if let Some(memory) = &mut memory {
+          if let Some(step) = memory.check() {
+            log::warn!("using about {} of {} bytes allowed: {}", memory.used(), memory.ceiling, step);
+            match step {
+              Degradation::DropSupportCache => {
+                supp = None;
+                memory.supp = 0;
+              },
+              Degradation::Stream => {
+                // Closed unitigs waiting to be sorted are still deduplicated
+                for u in closed.keys().filter(|_| !streaming) {
+                  seen.insert(u);
+                  memory.seen += map_entry::<u64, u64>(0);
+                }
+                streaming = true;
+              },
+              Degradation::FlushPending => {
+                self.emit_sorted(sink, &mut summary, std::mem::take(&mut closed), options)?;
+                memory.pending = 0;
+              },
+              Degradation::Abort => {
+                if let (Some(checkpoint), Some(journal)) = (&options.checkpoint, &mut journal) {
+                  save(checkpoint, journal, done, &is_closed, &covered, &reasons, &summary)?;
+                }
+                summary.aborted = Some(done);
+                break;
+              }
+            }
+          }
+        }
 
-      // If line is even get options
-      if index%2 == 0{
-        if !opt.is_empty() || !line.starts_with('>') {
-          panic!("Syntax error at line {}: \"{}\"", index+1, line);
+        term::progress(term::Progress::Close{seeds_done: done, seeds_total: order.len(), closed: n_closed as usize, kmers: self.nodes.len()});
+        let close = self.closure(seed, k, supp.as_ref(), (&mut is_closed, &mut n_closed), options, boundary);
+        // A closure interrupted by the cancellation is incomplete
+        if options.cancel.is_cancelled() {
+          summary.cancelled = Some(done);
+          break;
+        }
+        // The k-mers of the closure are represented unless shrinking trims them or it is dropped
+        for &i in &close.path {
+          reasons[i] = Some(if close.floored && options.drop_below_floor {Reason::Floored} else {Reason::Trimmed});
+        }
+        let (Closure{unitig: u, support: c, path, closed: nk, floored, truncated, timed_out, capped}, trim) = self.shrink(close, (&options.shrink, options.support));
+        if truncated {
+          summary.truncated += 1;
+        }
+        if timed_out {
+          summary.timed_out += 1;
+        }
+        if capped {
+          summary.capped += 1;
         }
-        opt = line;
+        if floored {
+          summary.floored += 1;
+          if options.drop_below_floor {continue} // its k-mers are still marked as closed
+        }
+        for i in path {
+          covered[i] = true;
+        }
+        // Only the first closure of duplicates is kept, with the k-mers it closed
+        let pending = Pending{rank: done, support: c, closed: nk, trim, timed_out, capped};
+        if streaming {
+          if seen.insert(&u) {
+            self.emit(sink, &mut summary, &u, &pending, options)?;
+            if let Some(memory) = &mut memory {
+              memory.seen += map_entry::<u64, u64>(0);
+            }
+            if let Some(journal) = &mut journal {
+              journal.push(&u, &pending)?;
+            }
+          }
+        } else {
+          if let Some(journal) = &mut journal {
+            journal.push(&u, &pending)?;
+          }
+          let len = u.len();
+          if Self::keep(&mut closed, u, pending) {
+            if let Some(memory) = &mut memory {
+              memory.pending += map_entry::<Unitig, Pending>(Unitig::heap_size(len));
+            }
+          }
+        }
+      }
+      term::clear_progress();
+    }
+
+    self.emit_sorted(sink, &mut summary, closed, options)?;
+    sink.finish()?;
+    term::phase_end(term::Phase::Close);
+    // A complete closing is not resumed
+    if let Some(checkpoint) = options.checkpoint.as_ref().filter(|_| summary.cancelled.is_none() && summary.aborted.is_none()) {
+      for path in [checkpoint.path.clone(), checkpoint.journal()] {
+        match std::fs::remove_file(path) {
+          Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+          _ => ()
+        }
+      }
+    }
+
+    if options.list_unrepresented {
+      summary.unrepresented = covered.iter().zip(reasons).enumerate().filter(|(_, (&c, _))| !c).map(|(i, (_, reason))| {
+        let node = &self.nodes[i];
+        Unrepresented{kmer: node.kmer.norm(), count: node.count, reason: reason.unwrap_or(Reason::NeverSeeded)}
+      }).collect();
+    }
+    let covered = self.nodes.iter().zip(covered).filter(|(_, c)| *c).map(|(n, _)| n);
+    summary.kmers = self.nodes.len();
+    summary.count = self.nodes.iter().map(|n| n.count as u64).sum();
+    summary.covered_kmers = covered.clone().count();
+    summary.covered_count = covered.map(|n| n.count as u64).sum();
+    Ok(summary)
+  }
+
+}
+
+impl<T: BufRead> std::convert::From<T> for Graph {
+  /// Build a de Bruijn graph from FASTA file
+  ///
+  /// # Panics
+  /// If the file cannot be read or is not a valid graph: use [`Graph::try_from_reader`] to get the error.
+  fn from(buf: T) -> Graph {
+    Graph::parse(buf, &ParseOptions::default())
+  }
+}
+
+impl Graph {
+  /// Build a de Bruijn graph from FASTA file with the given options
+  ///
+  /// # Panics
+  /// If the file cannot be read or is not a valid graph.
+  pub fn parse<T: BufRead>(buf: T, options: &ParseOptions) -> Graph {
+    Graph::try_from_reader(buf, options).unwrap_or_else(|e| panic!("{}", e))
+  }
+
+  /// Build a de Bruijn graph from FASTA file with the given options, reporting where the file is malformed
+  ///
+  /// # Example
+  /// ```
+  /// use closed_unitigs::{Graph, GraphError};
+  ///
+  /// let input = ">0 ab:Z:5 5\nAACCGT\n>1 ab:Z:5 4 3\nCCGTTA\n";
+  /// let error = Graph::try_from_reader(input.as_bytes(), &Default::default()).unwrap_err();
+  /// assert_eq!((error.record, error.line), (Some(1), Some(3)));
+  /// assert!(matches!(error.source, GraphError::WrongCounts{len: 6, counts: 3, k: 5}));
+  /// ```
+  pub fn try_from_reader<T: BufRead>(buf: T, options: &ParseOptions) -> Result<Graph, ParseError> {
+    Graph::parse_indexed(buf, options, None)
+  }
+
+  /// Build a de Bruijn graph from FASTA file with the given options, looking k-mers up in the given index
+  /// of the same file, if it matches it
+  pub fn parse_indexed<T: BufRead>(buf: T, options: &ParseOptions, index: Option<SortedIndex>) -> Result<Graph, ParseError> {
+    match options.parallel {
+      true => Graph::from_records(ParallelBcalmReader::new(buf), options, index),
+      false => Graph::from_records(BcalmReader::new(buf), options, index)
+    }
+  }
+
+  /// Build a de Bruijn graph from GFA1 file with the given options, looking k-mers up in the given index
+  /// of the same file, if it matches it
+  pub fn parse_gfa<T: BufRead>(buf: T, options: &ParseOptions, index: Option<SortedIndex>) -> Result<Graph, ParseError> {
+    Graph::from_records(GfaReader::new(buf)?, options, index)
+  }
+
+  /// Build a de Bruijn graph from the k-mers counted in the reads of a FASTQ file, whose size must be given in the options,
+  /// looking k-mers up in the given index of the same file, if it matches it
+  pub fn parse_fastq<T: BufRead>(buf: T, options: &ParseOptions, index: Option<SortedIndex>) -> Result<Graph, ParseError> {
+    Graph::from_records(FastqReader::new(buf, options.k.ok_or(ReadError::MissingK)?)?, options, index)
+  }
+
+  /// Build a de Bruijn graph from a k-mer count table, as dumped by KMC or Jellyfish, with the given options,
+  /// looking k-mers up in the given index of the same file, if it matches it
+  pub fn parse_kmers<T: BufRead>(buf: T, options: &ParseOptions, index: Option<SortedIndex>) -> Result<Graph, ParseError> {
+    Graph::from_records(KmerTableReader::new(buf)?, options, index)
+  }
+
+  /// Build a de Bruijn graph from a KMC database, given by its prefix and suffix files, with the given options
+  pub fn parse_kmc<P: io::Read, S: io::Read>(pre: P, suf: S, options: &ParseOptions) -> Result<Graph, ParseError> {
+    Graph::from_records(KmcReader::new(pre, suf)?, options, None)
+  }
+
+  /// Build a de Bruijn graph from the records of a file with the given options
+  fn from_records<S: RecordSource>(mut reader: S, options: &ParseOptions, index: Option<SortedIndex>) -> Result<Graph, ParseError> {
+    let mut builder = GraphBuilder::new();
+    if let Some(index) = index {
+      builder.sorted_index(index);
+    }
+    builder.merge_duplicates(options.merge_duplicates);
+    builder.collapse_rc(options.collapse_rc);
+    builder.lenient(options.lenient);
+    builder.parallel(options.parallel);
+    let mut k = options.k.unwrap_or(0);
+    let mut segments = Vec::<Option<(RecordId, RecordId)>>::new(); // first and last segment of each record, if it has any
+    let mut links = Vec::new();
+    let mut lines = Vec::new(); // header line of each record
+
+    term::phase_start(term::Phase::Parse);
+    for _ in 0..options.head.unwrap_or(usize::MAX) {
+      let Some(record) = reader.next() else {break};
+      let mut record = record?;
+      let id = record.id;
+      lines.push(record.line);
+
+      term::progress(term::Progress::Parse{records: id+1, bytes: reader.bytes()});
+
+      if record.sequence.is_empty() {
+        log::warn!("skipped record {} without sequence", id);
+        segments.push(None);
         continue;
       }
 
-      // Get counts
-      let count: Vec<_> = match count_re.captures(&opt) {
-          Some(r) => r.get(1).unwrap().as_str(),
-          None => ""
-        }.split(' ').map(|s| s.parse().unwrap()).collect();
+      // With k given, a record with only its mean count (`km:f:`) has it for all its k-mers
+      if options.k.is_some() && record.counts.is_empty() {
+        if let Some(mean) = record.mean_count()? {
+          record.counts = vec![mean; (record.sequence.len() + 1).saturating_sub(k)];
+        }
+      }
 
       // Get k
-      if graph.k == 0 {
-        graph.k = line.len() - count.len() + 1; // line.len = count.len + k - 1
-        println!("\x1B[2K\rk = {}", graph.k);
+      if k == 0 {
+        k = (record.sequence.len() + 1).saturating_sub(record.counts.len()); // sequence.len = counts.len + k - 1
+        log::info!("k = {}", k);
       }
 
-      // Append this node
-      let first = graph.nodes.len();
-      for (i, &c) in count.iter().enumerate() {
-        assert!(i + graph.k <= line.len());
-        if let Err(e) = graph.append(String::from(&line[i..i+graph.k]), c) {
-          panic!("{}; on line {}", e, index+1);
-        }
+      // Append the segments of this unitig
+      let ids: Vec<_> = record.segments(k, options.break_char).into_iter()
+        .map(|Segment{offset, sequence, counts}| builder.add_segment(sequence, counts, Origin{record: id, offset}))
+        .collect();
+      segments.push(ids.first().copied().zip(ids.last().copied()));
+      links.extend(record.links.into_iter().map(|link| (id, link)));
+    }
+
+    term::phase_end(term::Phase::Parse);
+    log::debug!("{} records read, with {} links", lines.len(), links.len());
+
+    // Get edges between unitigs: links leave a record by its last segment on the forward strand and by its first one
+    // on the reverse strand, and enter it by its first segment on the forward strand and by its last one on the reverse strand
+    let mut dangling = 0;
+    for (from, Link{from_dir, to, to_dir}) in links {
+      match (segments[from], segments.get(to)) {
+        (Some((first, last)), Some(&Some((to_first, to_last)))) => {
+          let from = match from_dir {
+            Orientation::Forward => last,
+            Orientation::Reverse => first
+          };
+          let to = match to_dir {
+            Orientation::Forward => to_first,
+            Orientation::Reverse => to_last
+          };
+          builder.add_link(from, from_dir, to, to_dir);
+        },
+        // Links to records after the last read one cannot be resolved
+        (_, None) if options.head.is_some() => dangling += 1,
+        (_, None) => return Err(ParseError{record: Some(from), line: Some(lines[from]), source: GraphError::UnknownRecord{id: to}}),
+        _ => () // records without k-mers
       }
-      let last = graph.nodes.len()-1;
-      nodes.push((first, last));
+    }
+    if let Some(head) = options.head.filter(|_| dangling > 0) {
+      log::warn!("dropped {} links to records beyond the first {}", dangling, head);
+    }
+
+    builder.build_located(k).map_err(|(record, source)| ParseError{record, line: record.and_then(|r| lines.get(r).copied()), source})
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::io::read_counts;
+  use crate::sink::FastaSink;
+  use crate::synthetic;
+  use Orientation::{Forward, Reverse};
+
+  const SMALL: &str = include_str!("../tests/data/small.fa");
+
+  /// Closes a graph returning the written FASTA and the supports of the counts file, one per line
+  fn close_graph(graph: &Graph, options: &CloseOptions) -> (String, String) {
+    let (mut fasta, mut counts) = (Vec::new(), Vec::new());
+    graph.close(&mut FastaSink::new(&mut fasta, &mut counts), options).unwrap();
+    (String::from_utf8(fasta).unwrap(), read_counts(&counts[..]).map(|c| c.unwrap().to_string() + "\n").collect())
+  }
+
+  /// Closes the small test graph returning the written FASTA and counts
+  fn close(options: &CloseOptions) -> (String, String) {
+    close_graph(&Graph::from(SMALL.as_bytes()), options)
+  }
+
+  /// Pairs each written sequence with its count, in lexicographic order
+  fn pairs((fasta, counts): (String, String)) -> Vec<(String, String)> {
+    let mut pairs: Vec<_> = fasta.lines().skip(1).step_by(2).map(String::from).zip(counts.lines().map(String::from)).collect();
+    pairs.sort();
+    pairs
+  }
+
+  #[test]
+  fn streamed_output_matches_sorted() {
+    assert_eq!(pairs(close(&CloseOptions::default())), pairs(close(&CloseOptions{sort: Sort::None, ..Default::default()})));
+  }
+
+  #[test]
+  fn sort_modes_order_the_output() {
+    let written = |sort| {
+      let (fasta, counts) = close(&CloseOptions{sort, ..Default::default()});
+      fasta.lines().skip(1).step_by(2).map(|u| Unitig::try_from(u).unwrap()).zip(counts.lines().map(|c| c.parse::<u32>().unwrap())).collect::<Vec<_>>()
+    };
+    let by_count = written(Sort::Count);
+    assert!(by_count.windows(2).all(|w| (w[0].1, &w[0].0) < (w[1].1, &w[1].0)));
+    let by_length = written(Sort::Length);
+    assert!(by_length.windows(2).all(|w| (std::cmp::Reverse(w[0].0.len()), w[0].1, &w[0].0) < (std::cmp::Reverse(w[1].0.len()), w[1].1, &w[1].0)));
+    let by_count_desc = written(Sort::CountDesc);
+    assert!(by_count_desc.windows(2).all(|w| (std::cmp::Reverse(w[0].1), &w[0].0) < (std::cmp::Reverse(w[1].1), &w[1].0)));
+    // The written sequences are in order, whatever their canonical forms
+    let (fasta, _) = close(&CloseOptions{sort: Sort::Lexicographic, ..Default::default()});
+    let lexicographic: Vec<_> = fasta.lines().skip(1).step_by(2).collect();
+    assert!(lexicographic.windows(2).all(|w| w[0] < w[1]), "{:?}", lexicographic);
+    assert_eq!(["length-desc", "count-desc", "seq"].map(|s| s.parse::<Sort>().unwrap()), [Sort::Length, Sort::CountDesc, Sort::Lexicographic]);
+    assert_eq!(pairs(close(&CloseOptions{sort: Sort::Length, ..Default::default()})), pairs(close(&CloseOptions::default())));
+  }
+
+  #[test]
+  fn shuffled_seeds_are_reproducible() {
+    let options = CloseOptions{seed_order: SeedOrder::Shuffle, rng_seed: 42, ..Default::default()};
+    assert_eq!(pairs(close(&options)), pairs(close(&options)));
+  }
+
+  #[test]
+  fn support_stats_match_output() {
+    let options = CloseOptions{support_floor: Some(2), drop_below_floor: true, ..Default::default()};
+    let (mut fasta, mut counts) = (Vec::new(), Vec::new());
+    let summary = Graph::from(SMALL.as_bytes()).close(&mut FastaSink::new(&mut fasta, &mut counts), &options).unwrap();
+    let mut expected = BTreeMap::<u32, SupportStats>::new();
+    for (u, c) in String::from_utf8(fasta).unwrap().lines().skip(1).step_by(2).zip(read_counts(&counts[..])) {
+      expected.entry(c.unwrap()).or_default().add(u.len());
+    }
+    assert!(!expected.contains_key(&2));
+    assert_eq!(summary.per_support, expected);
+    assert_eq!(summary.per_support.values().map(|s| s.unitigs).sum::<usize>(), summary.outputs);
+    assert_eq!(summary.assembly.unitigs(), summary.outputs);
+    assert_eq!(summary.assembly.bases(), summary.per_support.values().map(|s| s.bases).sum::<u64>());
+  }
 
-      // Edges between k-mers of the unitig
-      for i in first..last {
-        graph.nodes[i].out.push(Edge{to: i+1, start: true, end: true});
-        graph.nodes[i].into.push(Edge{to: i+1, start: false, end: false}); // Reverse complement
+  #[test]
+  fn assembly_stats_follow_the_lengths() {
+    let mut stats = AssemblyStats::default();
+    assert_eq!((stats.nx(0.5), stats.longest()), ((0, 0), 0));
+    for (len, support) in [(80, 2), (10, 4), (50, 3), (30, 5), (30, 5)] {
+      stats.add(len, support);
+    }
+    // 200 bases: 80 + 50 reach half of them, 80 + 50 + 30 + 30 reach 90%
+    assert_eq!((stats.unitigs(), stats.bases(), stats.longest()), (5, 200, 80));
+    assert_eq!((stats.nx(0.5), stats.nx(0.9), stats.nx(1.)), ((50, 2), (30, 4), (10, 5)));
+    assert_eq!(stats.nx(0.4), (80, 1));
+    assert_eq!(stats.mean_support(), 19. / 5.);
+  }
+
+  #[test]
+  fn high_degree_kmers_are_skipped_and_bound_closures() {
+    // The last k-mer of the root is joined to its predecessor and to the 3 children
+    let graph = synthetic::generate_branching(7, 1, 3).graph;
+    assert_eq!(graph.degrees().iter().filter(|&&d| d > 3).count(), 1);
+    let close = |degree_policy| {
+      let options = CloseOptions{max_degree: Some(3), degree_policy, ..Default::default()};
+      let (mut fasta, mut counts) = (Vec::new(), Vec::new());
+      let summary = graph.close(&mut FastaSink::new(&mut fasta, &mut counts), &options).unwrap();
+      (summary, String::from_utf8(fasta).unwrap())
+    };
+
+    // The root without its last k-mer, and the children, stop before the hub, which is never closed
+    let (summary, fasta) = close(DegreePolicy::Both);
+    assert_eq!((summary.skipped_seeds, summary.truncated, summary.outputs), (1, 4, 4));
+    assert!(fasta.lines().skip(1).step_by(2).all(|l| l.len() == 12 || l.len() == 13));
+    // The first seed closes the hub through it
+    let (summary, _) = close(DegreePolicy::SkipSeeds);
+    assert_eq!((summary.skipped_seeds, summary.truncated), (0, 0));
+    // The hub is a seed of its own, closed with one of the children
+    let (summary, _) = close(DegreePolicy::Boundary);
+    assert_eq!((summary.skipped_seeds, summary.truncated, summary.outputs), (0, 3, 4));
+  }
+
+  #[test]
+  fn single_seeds_close_as_the_whole_graph() {
+    // On a linear graph each k-mer closes to the closed unitig of the seed which closed it
+    let synthetic::Synthetic{graph, expected} = synthetic::generate_linear(7, 40, |i| [3, 5, 5, 2, 4, 4, 4][i % 7]);
+    for n in &graph.nodes {
+      let u = graph.close_seed(&n.kmer.to_string()).unwrap();
+      assert!(expected.contains(&(u.sequence.clone(), u.support)), "{:?} is not expected", u);
+      assert_eq!(u.len, u.sequence.len());
+      // Seeds given on the reverse strand give the reverse complement, along the reversed path
+      let mut rc = graph.close_seed(&n.kmer.rev_compl().to_string()).unwrap();
+      rc.nodes.reverse();
+      assert_eq!((String::from(Unitig::try_from(rc.sequence).unwrap().rev_compl()), rc.nodes), (u.sequence, u.nodes));
+    }
+    assert!(matches!(graph.close_seed("AAAA"), Err(GraphError::UnknownKmer{..})));
+    assert!(matches!(graph.close_seed("AAAANAA"), Err(GraphError::WrongNucleotide{nucleo: 'N'})));
+  }
+
+  #[test]
+  fn unrepresented_kmers_have_a_reason() {
+    let unrepresented = |graph: &Graph, options: CloseOptions| {
+      let (mut fasta, mut counts) = (Vec::new(), Vec::new());
+      let summary = graph.close(&mut FastaSink::new(&mut fasta, &mut counts), &CloseOptions{list_unrepresented: true, ..options}).unwrap();
+      summary.unrepresented.iter().map(|u| (u.count, u.reason)).collect::<Vec<_>>()
+    };
+    let linear = |profile: &'static [u32]| synthetic::generate_linear(5, profile.len(), |i| profile[i]).graph;
+
+    let floor = CloseOptions{support_floor: Some(1), drop_below_floor: true, ..Default::default()};
+    assert_eq!(unrepresented(&linear(&[1, 1, 4, 4]), floor), vec![(1, Reason::Floored), (1, Reason::Floored)]);
+    // K-mers with count equal to the support are closed, so they are not seeds even when trimmed
+    let shrink = CloseOptions{shrink: ShrinkPolicy{threshold_delta: 0, max_trim: None}, ..Default::default()};
+    assert_eq!(unrepresented(&linear(&[3, 3, 3]), shrink), vec![(3, Reason::Trimmed), (3, Reason::Trimmed)]);
+    let hub = synthetic::generate_branching(7, 1, 3).graph;
+    assert_eq!(unrepresented(&hub, CloseOptions{max_degree: Some(3), ..Default::default()}), vec![(1, Reason::Filtered)]);
+    assert_eq!(unrepresented(&hub, CloseOptions::default()), vec![]);
+  }
+
+  #[test]
+  fn record_edges_have_twins_on_the_other_strand() {
+    let graph = Graph::from(">0 ab:Z:5 5\nAACCGT\n".as_bytes());
+    // AACCG -> ACCGT, and ACGGT -> CGGTT on the other strand
+    assert_eq!(graph.nodes[0].out, vec![Edge{to: 1, dir: Forward, to_dir: Forward, weight: 1}]);
+    assert_eq!(graph.nodes[1].into, vec![Edge{to: 0, dir: Forward, to_dir: Forward, weight: 1}]);
+    assert_eq!(graph.nodes[1].out, vec![Edge{to: 0, dir: Reverse, to_dir: Reverse, weight: 1}]);
+    assert_eq!(graph.nodes[0].into, vec![Edge{to: 1, dir: Reverse, to_dir: Reverse, weight: 1}]);
+  }
+
+  #[test]
+  fn links_join_oriented_record_ends() {
+    let kmer = |s: &str| Unitig::try_from(s).unwrap();
+    let right = |graph: &Graph, s: &str| graph.neighbors(&kmer(s), true).unwrap().into_iter().map(|n| n.kmer.to_string()).collect::<Vec<_>>();
+    let left = |graph: &Graph, s: &str| graph.neighbors(&kmer(s), false).unwrap().into_iter().map(|n| n.kmer.to_string()).collect::<Vec<_>>();
+    // Record 0 is AACCGT, with k-mers AACCG (node 0) and ACCGT (node 1); record 1 has nodes 2 and 3.
+    // For each link: the second record, the node left read as `dir`, the node entered read as `to_dir`,
+    // and the k-mers they join, in traversal order
+    let cases = [
+      ("+:1:+", "CCGTTA", (1, Forward), (2, Forward), ("ACCGT", "CCGTT")),
+      ("+:1:-", "TAACGG", (1, Forward), (3, Reverse), ("ACCGT", "CCGTT")),
+      ("-:1:+", "GGTTAC", (0, Reverse), (2, Forward), ("CGGTT", "GGTTA")),
+      ("-:1:-", "GTAACC", (0, Reverse), (3, Reverse), ("CGGTT", "GGTTA"))
+    ];
+    for (link, second, (from, dir), (to, to_dir), (left_kmer, right_kmer)) in cases {
+      let graph = Graph::from(format!(">0 ab:Z:5 5 L:{}\nAACCGT\n>1 ab:Z:5 5\n{}\n", link, second).as_bytes());
+      let out: Vec<_> = graph.nodes[from].out.iter().filter(|e| e.to >= 2).map(|e| (e.to, e.dir, e.to_dir)).collect();
+      let into: Vec<_> = graph.nodes[to].into.iter().filter(|e| e.to < 2).map(|e| (e.to, e.dir, e.to_dir)).collect();
+      assert_eq!((out, into), (vec![(to, dir, to_dir)], vec![(from, to_dir, dir)]), "L:{}", link);
+      assert_eq!((right(&graph, left_kmer), left(&graph, right_kmer)), (vec![String::from(right_kmer)], vec![String::from(left_kmer)]), "L:{}", link);
+
+      // The other strand is joined only by the reverse link
+      let (rc_left, rc_right) = (kmer(right_kmer).rev_compl().to_string(), kmer(left_kmer).rev_compl().to_string());
+      assert!(right(&graph, &rc_left).iter().all(|n| *n != *rc_right), "L:{}", link);
+      // BCALM writes L:+:1:- of record 0 also as L:+:0:- of record 1, L:+:1:+ as L:-:0:-, and so on
+      let reverse = format!("{}:0:{}", (!Orientation::from_sign(&link[4..])).sign(), (!Orientation::from_sign(&link[..1])).sign());
+      let graph = Graph::from(format!(">0 ab:Z:5 5 L:{}\nAACCGT\n>1 ab:Z:5 5 L:{}\n{}\n", link, reverse, second).as_bytes());
+      assert_eq!((right(&graph, &rc_left), left(&graph, &rc_right)), (vec![rc_right.to_string()], vec![rc_left.to_string()]), "L:{}", link);
+    }
+  }
+
+  /// Forwards closed unitigs to another sink, cancelling the closing once it got a number of them
+  struct Cancelling<S: Sink> {
+    inner: S,
+    left: usize,
+    cancel: CancelToken
+  }
+
+  impl<S: Sink> Sink for Cancelling<S> {
+    fn emit(&mut self, id: usize, u: &Unitig, support: u32, meta: &Metadata) -> io::Result<()> {
+      self.left -= 1;
+      if self.left == 0 {
+        self.cancel.cancel();
       }
-      // Reverse direction
-      for i in first+1..=last {
-        graph.nodes[i].out.push(Edge{to: i-1, start: false, end: false}); // Reverse complement
-        graph.nodes[i].into.push(Edge{to: i-1, start: true, end: true});
+      self.inner.emit(id, u, support, meta)
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+      self.inner.finish()
+    }
+  }
+
+  #[test]
+  fn interrupted_closings_are_resumed() {
+    let graph = Graph::from(SMALL.as_bytes());
+    let dir = tempfile::tempdir().unwrap();
+    let checkpoint = Some(Checkpointing{path: dir.path().join("state.json"), every: Some(Duration::ZERO)});
+    // Closed unitigs are written as found, so that the closing can be cancelled after some of them
+    let streaming = CloseOptions{sort: Sort::None, checkpoint: checkpoint.clone(), ..Default::default()};
+    let (mut fasta, mut counts) = (Vec::new(), Vec::new());
+    let mut sink = Cancelling{inner: FastaSink::new(&mut fasta, &mut counts), left: 5, cancel: streaming.cancel.clone()};
+    assert!(graph.close(&mut sink, &streaming).unwrap().cancelled.is_some());
+    let state = CloseState::read(&dir.path().join("state.json")).unwrap();
+    assert!(state.seeds_done() > 0 && state.fingerprint() == graph.fingerprint(&streaming));
+    let journal = std::fs::read(dir.path().join("state.unitigs")).unwrap();
+    assert_eq!(journal[..state.journal_len as usize].iter().filter(|&&b| b == b'\n').count(), 5);
+
+    // The closed unitigs found are the same whether they were sorted or not
+    for sort in [Sort::None, Sort::Count] {
+      let options = CloseOptions{sort, checkpoint: checkpoint.clone(), ..Default::default()};
+      let expected = close_graph(&graph, &options);
+      // Closing again removed the journal
+      std::fs::write(dir.path().join("state.unitigs"), &journal).unwrap();
+      let (mut fasta, mut counts) = (Vec::new(), Vec::new());
+      // Sorting does not change the state, only when the closed unitigs are written
+      let state = CloseState{fingerprint: graph.fingerprint(&options), ..state.clone()};
+      let summary = graph.resume(&mut FastaSink::new(&mut fasta, &mut counts), &options, state).unwrap();
+      let counts: String = read_counts(&counts[..]).map(|c| c.unwrap().to_string() + "\n").collect();
+      assert_eq!((String::from_utf8(fasta).unwrap(), counts), expected, "{:?}", sort);
+      assert_eq!(summary.to_string(), graph.close(&mut FastaSink::new(io::sink(), io::sink()), &options).unwrap().to_string());
+      // A complete closing leaves no state
+      assert!(!dir.path().join("state.json").exists() && !dir.path().join("state.unitigs").exists());
+    }
+  }
+
+  #[test]
+  fn sampled_components_close_as_in_the_whole_graph() {
+    let graph = || synthetic::generate_components(9, 20, 15);
+    let closed = |graph: &Graph| {
+      let (fasta, counts) = close_graph(graph, &CloseOptions::default());
+      fasta.lines().skip(1).step_by(2).zip(counts.lines()).map(|(u, c)| format!("{} {}", u, c)).collect::<HashSet<_>>()
+    };
+    let whole = graph();
+    assert_eq!(whole.components().len(), 20);
+
+    let mut sampled = graph();
+    let sample = sampled.sample_components(0.3, 5);
+    assert_eq!((sample.components, sample.kept), (20, 6));
+    assert_eq!(sampled.components().len(), sample.kept);
+    let (closed_whole, closed_sampled) = (closed(&whole), closed(&sampled));
+    assert!(!closed_sampled.is_empty() && closed_sampled.is_subset(&closed_whole));
+
+    let mut all = graph();
+    all.sample_components(1., 5);
+    assert_eq!(all, whole);
+  }
+
+  #[test]
+  fn dropped_kmers_split_records() {
+    let mut builder = GraphBuilder::new();
+    builder.add_unitig("AACCGTTA", &[5, 1, 5, 5]);
+    builder.add_unitig("GGGATCA", &[1, 1, 1]);
+    let mut graph = builder.build(5).unwrap();
+    assert_eq!(graph.spectrum().kmers, [(1, 4), (5, 3)].into());
+    assert_eq!(graph.drop_below(2), 4);
+
+    let mut builder = GraphBuilder::new();
+    builder.add_unitig("AACCG", &[5]);
+    builder.add_unitig("CCGTTA", &[5, 5]);
+    let split = builder.build(5).unwrap();
+    assert!(graph.equivalent(&split));
+    assert_eq!(graph.records.len(), 2);
+  }
+
+  #[test]
+  fn short_weak_tips_are_clipped() {
+    let build = || {
+      let mut builder = GraphBuilder::new();
+      let a = builder.add_unitig("GATTACA", &[10, 10, 10]);
+      let b = builder.add_unitig("TACAGGT", &[10, 10, 10]);
+      // A tip of 6 bases, and a longer one of 10
+      let tip = builder.add_unitig("CCTACA", &[1, 1]);
+      let long = builder.add_unitig("GGGGCATACA", &[1, 1, 1, 1, 1, 1]);
+      for from in [a, tip, long] {
+        builder.add_link(from, Forward, b, Forward);
+        builder.add_link(b, Reverse, from, Reverse);
       }
+      builder.build(5).unwrap()
+    };
+    let mut graph = build();
+    assert_eq!(graph.clip_tips(1), (0, 0));
+    assert_eq!(graph.clip_tips(3), (1, 2));
+    assert!(graph.node(&Unitig::try_from("CCTAC").unwrap()).is_none());
+    assert!(graph.node(&Unitig::try_from("GGGGC").unwrap()).is_some());
+    // The main branch is not clipped even when weaker than the threshold
+    assert_eq!(build().clip_tips(20), (0, 0));
+  }
 
-      // Get edges between unitigs
-      for group in link_re.captures_iter(&opt) {
-        edges.push((
-          (nodes.len()-1, group[1].starts_with('+')),
-          (group[2].parse().unwrap(), group[3].starts_with('+'))
-        ));
+  #[test]
+  fn bubbles_are_popped() {
+    // A SNP between GATTAC[A]GGTCCA and GATTAC[T]GGTCCA, whose 5 k-mers on each side are the branches of a bubble
+    let build = |counts: [u32; 2]| {
+      let mut builder = GraphBuilder::new();
+      let left = builder.add_unitig("GATTAC", &[10, 10]);
+      let right = builder.add_unitig("GGTCCA", &[10, 10]);
+      for (&branch, count) in ["TTACAGGTC", "TTACTGGTC"].iter().zip(counts) {
+        let branch = builder.add_unitig(branch, &[count; 5]);
+        builder.add_link(left, Forward, branch, Forward);
+        builder.add_link(branch, Reverse, left, Reverse);
+        builder.add_link(branch, Forward, right, Forward);
+        builder.add_link(right, Reverse, branch, Reverse);
       }
+      builder.build(5).unwrap()
+    };
+    let kmer = |s| Unitig::try_from(s).unwrap();
+    let mut graph = build([2, 10]);
+    assert_eq!(graph.pop_bubbles(), (1, 5));
+    assert!(graph.node(&kmer("TACAG")).is_none() && graph.node(&kmer("TACTG")).is_some());
+    assert_eq!(graph.pop_bubbles(), (0, 0));
+
+    let mut graph = build([10, 10]);
+    assert_eq!(graph.pop_bubbles(), (1, 5));
+    assert!(graph.node(&kmer("TACAG")).is_some());
+  }
 
-      opt = String::new();
+  #[test]
+  fn cycles_are_found_once() {
+    let mut builder = GraphBuilder::new();
+    builder.add_unitig("CCCCTT", &[4, 4]);
+    // The circular sequence GATTACAGC, whose last k-mer is followed by the first one
+    let circular = builder.add_unitig("GATTACAGCGATT", &[5; 9]);
+    builder.add_link(circular, Forward, circular, Forward);
+    builder.add_link(circular, Reverse, circular, Reverse);
+    let graph = builder.build(5).unwrap();
+    assert_eq!(graph.cycles(), [Cycle{nodes: (2..11).collect(), records: vec![1]}]);
+
+    let graph = Graph::from(include_str!("../tests/data/small.fa").as_bytes());
+    for cycle in graph.cycles() {
+      assert!(cycle.nodes.len() > 1 || graph.nodes[cycle.nodes[0]].out.iter().any(|e| e.to == cycle.nodes[0]));
     }
+  }
 
-    // Store edges
-    for ((from, start), (to, end)) in edges {
-      let (from, to) = (nodes[from], nodes[to]);
+  #[test]
+  fn graphs_are_written_as_dot() {
+    let mut builder = GraphBuilder::new();
+    builder.add_unitig("AACCGT", &[5, 4]);
+    builder.add_unitig("GGGATC", &[1, 2]);
+    let graph = builder.build(5).unwrap();
+    let dot = |keep: Option<&[bool]>| {
+      let mut out = Vec::new();
+      graph.write_dot(&mut out, keep).unwrap();
+      String::from_utf8(out).unwrap()
+    };
+    let all = dot(None);
+    assert!(all.starts_with("digraph {\n") && all.ends_with("}\n"));
+    assert!(all.contains("  0 [label=\"AACCG\\n5\"];\n") && all.contains("  3 [label=\"GGATC\\n2\"];\n"));
+    // Each edge once, not with its twin
+    assert_eq!(all.matches(" -> ").count(), 2);
+    assert!(all.contains("  0 -> 1 [label=\"++\"];\n"));
 
-      // check direction
-      let from = if start {from.1} else {from.0};
-      let to = if end {to.0} else {to.1};
+    let near = graph.neighborhood(&Unitig::try_from("ACGGT").unwrap(), 1).unwrap();
+    assert_eq!(near, [true, true, false, false]);
+    let part = dot(Some(&near));
+    assert!(part.contains("  0 -> 1 ") && !part.contains("GGGAT"));
+    assert!(graph.neighborhood(&Unitig::try_from("TTTTT").unwrap(), 1).is_none());
 
-      if from == to {continue} // avoid self loops
+    let mut out = Vec::new();
+    graph.write_graphml(&mut out, Some(&near)).unwrap();
+    let graphml = String::from_utf8(out).unwrap();
+    assert!(graphml.starts_with("<?xml ") && graphml.ends_with("</graphml>\n"));
+    assert_eq!((graphml.matches("<node ").count(), graphml.matches("<edge ").count()), (2, 1));
+    assert!(graphml.contains("<node id=\"n1\"><data key=\"sequence\">ACCGT</data><data key=\"count\">4</data><data key=\"length\">5</data></node>"));
+    assert!(graphml.contains("<edge source=\"n0\" target=\"n1\"><data key=\"from_strand\">+</data><data key=\"to_strand\">+</data>"));
+  }
+
+  #[test]
+  fn components_follow_edges_either_way() {
+    let mut builder = GraphBuilder::new();
+    let a = builder.add_unitig("AACCGT", &[5, 5]);
+    builder.add_unitig("GGGATCA", &[1, 1, 1]);
+    let c = builder.add_unitig("CCGTTA", &[5, 4]);
+    // Only given in one direction
+    builder.add_link(c, Reverse, a, Reverse);
+    let graph = builder.build(5).unwrap();
+
+    let components = graph.components();
+    assert_eq!(components.of_node, vec![0, 0, 1, 1, 1, 0, 0]);
+    assert_eq!((&components.kmers, &components.bases), (&vec![4, 3], &vec![12, 7]));
+    assert_eq!(graph.node(&Unitig::try_from("ACGGT").unwrap()).map(|n| components.of_node[n]), Some(0));
+  }
+
+  #[test]
+  fn earliest_seeds_win_ties() {
+    let mut closed = FxHashMap::default();
+    let u = |s| Unitig::try_from(s).unwrap();
+    assert!(Graph::keep(&mut closed, u("AACCGT"), Pending{rank: 5, support: 3, closed: 1, trim: Trim::default(), timed_out: false, capped: false}));
+    // The reverse complement is the same sequence, found from an earlier seed
+    assert!(!Graph::keep(&mut closed, u("ACGGTT"), Pending{rank: 2, support: 4, closed: 2, trim: Trim::default(), timed_out: false, capped: false}));
+    assert!(!Graph::keep(&mut closed, u("AACCGT"), Pending{rank: 7, support: 9, closed: 3, trim: Trim::default(), timed_out: false, capped: false}));
+    assert_eq!(closed[&u("AACCGT")], Pending{rank: 2, support: 4, closed: 2, trim: Trim::default(), timed_out: false, capped: false});
+  }
 
-      graph.nodes[from].out.push(Edge{to, start, end});
-      graph.nodes[to].into.push(Edge{to: from, start: end, end: start}); // Reverse direction
+  #[test]
+  fn sorted_output_is_deterministic() {
+    // Every closing uses maps with different hashers
+    let first = close(&CloseOptions::default());
+    for _ in 0..5 {
+      assert_eq!(close(&CloseOptions::default()), first);
     }
+  }
+
+  #[test]
+  fn seen_deduplicates_reverse_complements() {
+    let mut seen = Seen::default();
+    assert!(seen.insert(&Unitig::try_from("AACG").unwrap()));
+    assert!(!seen.insert(&Unitig::try_from("CGTT").unwrap()));
+    assert!(seen.insert(&Unitig::try_from("AACC").unwrap()));
+  }
+
+  #[test]
+  fn builder_rejects_unknown_records() {
+    let mut builder = GraphBuilder::new();
+    let a = builder.add_unitig("AACCGT", &[5, 5]);
+    builder.add_link(a, Forward, RecordId(1), Forward);
+    assert!(matches!(builder.build(5), Err(GraphError::UnknownRecord{id: 1})));
+  }
+
+  #[test]
+  fn builder_rejects_wrong_counts() {
+    let mut builder = GraphBuilder::new();
+    builder.add_unitig("AACCGT", &[5, 5, 5]);
+    assert!(matches!(builder.build(5), Err(GraphError::WrongCounts{len: 6, counts: 3, k: 5})));
+  }
+
+  #[test]
+  fn builder_rejects_duplicate_kmers() {
+    let mut builder = GraphBuilder::new();
+    builder.add_unitig("AACCGT", &[5, 5]);
+    builder.add_unitig("ACGGT", &[5]);
+    assert!(matches!(builder.build(5), Err(GraphError::DuplicateKmer{first: 0, second: 1, ..})));
+  }
+
+  #[test]
+  fn builder_checks_link_overlaps() {
+    let build = |lenient| {
+      let mut builder = GraphBuilder::new();
+      let a = builder.add_unitig("AACCGT", &[5, 5]);
+      let b = builder.add_unitig("CCGTTA", &[5, 4]);
+      let c = builder.add_unitig("GGGGGA", &[2, 2]);
+      builder.add_link(a, Forward, b, Forward);
+      builder.add_link(a, Forward, c, Forward);
+      builder.lenient(lenient);
+      builder.build(5)
+    };
+    assert!(matches!(build(false), Err(GraphError::InconsistentLink{from: 0, to: 2, ..})));
+    let graph = build(true).unwrap();
+    assert_eq!(graph.nodes[1].out.iter().map(|e| e.to).collect::<Vec<_>>(), vec![0, 2]);
+  }
+
+  #[test]
+  fn parallel_parsing_builds_the_same_graph() {
+    let mut bcalm = Vec::new();
+    let mut fasta = crate::io::FastaWriter::new(&mut bcalm, Default::default()).unwrap();
+    synthetic::generate_random_genome(9, 20_000, 3., 0.01, 7).write_bcalm(&mut fasta).unwrap();
+    fasta.finish().unwrap();
+    drop(fasta);
+    for input in [SMALL.as_bytes(), &bcalm, include_bytes!("../tests/data/duplicated.fa")] {
+      let options = ParseOptions{merge_duplicates: true, break_char: Some('N'), ..Default::default()};
+      let parallel = Graph::try_from_reader(input, &ParseOptions{parallel: true, ..options.clone()}).unwrap();
+      assert_eq!(parallel, Graph::try_from_reader(input, &options).unwrap());
+    }
+    // Errors are located as in a serial parse
+    let input = ">0 ab:Z:5 5\nAACCGT\n>1 ab:Z:5 4 3\nCCGTTA\n";
+    let error = Graph::try_from_reader(input.as_bytes(), &ParseOptions{parallel: true, ..Default::default()}).unwrap_err();
+    assert_eq!((error.record, error.line), (Some(1), Some(3)));
+  }
 
-    // dbg!(graph.nodes);
+  #[test]
+  fn merged_duplicates_match_deduplicated_input() {
+    let graph = Graph::parse(include_str!("../tests/data/duplicated.fa").as_bytes(), &ParseOptions{merge_duplicates: true, ..Default::default()});
+    assert_eq!(pairs(close_graph(&graph, &CloseOptions::default())), pairs(close(&CloseOptions::default())));
+  }
 
-    graph
+  #[test]
+  fn collapsed_strands_match_single_strand_input() {
+    let options = ParseOptions{collapse_rc: Some(CollapseCounts::Max), ..Default::default()};
+    let graph = Graph::parse(include_str!("../tests/data/stranded.fa").as_bytes(), &options);
+    assert_eq!(pairs(close_graph(&graph, &CloseOptions::default())), pairs(close(&CloseOptions::default())));
+  }
+
+  #[test]
+  fn empty_inputs_give_empty_graphs() {
+    for input in ["", ">0 LN:i:0\n\n>1 LN:i:0\n\n", ">0\n>1\n"] {
+      let graph = Graph::from(input.as_bytes());
+      assert!(graph.is_empty());
+      let (fasta, counts) = close_graph(&graph, &CloseOptions::default());
+      assert!(fasta.is_empty() && counts.is_empty());
+    }
+  }
+
+  #[test]
+  fn break_characters_split_records() {
+    let input = ">0 ab:Z:3 3 9 9 9 9 9 9 4 4 L:+:1:+\nAACCGTNNGATTCA\n>1 ab:Z:4 4 L:-:0:-\nTTCAGG\n";
+    let graph = Graph::parse(input.as_bytes(), &ParseOptions{break_char: Some('N'), ..Default::default()});
+    assert_eq!(graph.nodes.len(), 6);
+    let origin = |kmer| graph.origin(&Unitig::try_from(kmer).unwrap()).map(|Origin{record, offset}| (record, offset));
+    assert_eq!(origin("ATTCA"), Some((0, 9)));
+    assert_eq!(origin("ACGGT"), Some((0, 1))); // reverse complement of ACCGT
+    assert_eq!(origin("TCAGG"), Some((1, 1)));
+    assert_eq!(origin("CCGTA"), None);
+    // The link leaves the first record by its last segment
+    let expected = [("AACCGT", "3"), ("GATTCAGG", "4")].map(|(u, c)| (String::from(u), String::from(c)));
+    assert_eq!(pairs(close_graph(&graph, &CloseOptions::default())), expected);
+  }
+
+  #[test]
+  fn head_beyond_records_reads_everything() {
+    let graph = Graph::parse(SMALL.as_bytes(), &ParseOptions{head: Some(1000), ..Default::default()});
+    assert_eq!(pairs(close_graph(&graph, &CloseOptions::default())), pairs(close(&CloseOptions::default())));
+  }
+
+  #[test]
+  fn head_drops_dangling_links() {
+    let graph = Graph::parse(SMALL.as_bytes(), &ParseOptions{head: Some(2), ..Default::default()});
+    assert_eq!(graph.nodes.len(), 2 + 7);
+  }
+
+  #[test]
+  fn given_k_is_checked_on_every_record() {
+    let options = ParseOptions{k: Some(5), ..Default::default()};
+    // Mean counts are given to all the k-mers
+    let graph = Graph::try_from_reader(">0 km:f:2.6 L:+:1:+\nAACCGT\n>1 ab:Z:4 5\nCCGTTA\n".as_bytes(), &options).unwrap();
+    assert_eq!(graph.nodes.iter().map(|n| n.count).collect::<Vec<_>>(), [3, 3, 4, 5]);
+    let error = Graph::try_from_reader(">0 ab:Z:1 1 1\nAACCGT\n".as_bytes(), &options).unwrap_err();
+    assert!(matches!(error.source, GraphError::WrongCounts{len: 6, counts: 3, k: 5}));
+    let error = Graph::try_from_reader(">0 km:f:x\nAACCGT\n".as_bytes(), &options).unwrap_err();
+    assert_eq!(error.to_string(), "Wrong count \"km:f:x\" at line 1");
+  }
+
+  #[test]
+  fn bcalm_round_trip() {
+    let graph = Graph::from(SMALL.as_bytes());
+    let mut out = Vec::new();
+    crate::convert::write_to(&graph, crate::convert::Format::Bcalm, &mut out).unwrap();
+    assert_eq!(Graph::from(&out[..]), graph);
+  }
+
+  #[test]
+  fn every_kmer_is_represented() {
+    for graph in [Graph::from(SMALL.as_bytes()), synthetic::generate_random_genome(21, 800, 1., 0.05, 1)] {
+      let summary = graph.close(&mut FastaSink::new(Vec::new(), Vec::new()), &CloseOptions::default()).unwrap();
+      assert_eq!((summary.covered_kmers, summary.covered_count), (summary.kmers, summary.count));
+    }
+  }
+
+  #[test]
+  fn random_genomes_are_closed() {
+    let graph = synthetic::generate_random_genome(11, 300, 10., 0.01, 2);
+    for (u, c) in pairs(close_graph(&graph, &CloseOptions::default())) {
+      let violations = graph.check_closed(&Unitig::try_from(u.as_str()).unwrap(), c.parse().unwrap(), &[]);
+      assert!(violations.is_empty(), "{}: {:?}", u, violations);
+    }
+  }
+
+  #[test]
+  fn linear_graphs_match_oracle() {
+    let profiles: [&dyn Fn(usize) -> u32; 4] = [&|_| 5, &|i| i as u32 + 1, &|i| (i as u32).abs_diff(20) + 1, &|i| (i * 7919 % 13) as u32 + 1];
+    // K-mers longer than 32 bases are not packed in an integer
+    for (k, profile) in [9, 41].iter().flat_map(|&k| profiles.map(|profile| (k, profile))) {
+      let synthetic::Synthetic{graph, expected} = synthetic::generate_linear(k, 40, profile);
+      let expected: Vec<_> = expected.into_iter().map(|(u, c)| (u, c.to_string())).collect();
+      assert_eq!(pairs(close_graph(&graph, &CloseOptions::default())), expected);
+    }
+  }
+
+  #[test]
+  fn branching_graphs_give_paths_to_leaves() {
+    let synthetic::Synthetic{graph, expected} = synthetic::generate_branching(7, 3, 3);
+    assert_eq!(expected.len(), 27);
+    let expected: Vec<_> = expected.into_iter().map(|(u, c)| (u, c.to_string())).collect();
+    for seed_order in [SeedOrder::Input, SeedOrder::Shuffle] {
+      assert_eq!(pairs(close_graph(&graph, &CloseOptions{seed_order, ..Default::default()})), expected);
+    }
+  }
+
+  #[test]
+  fn closed_kmers_are_counted() {
+    let mut builder = GraphBuilder::new();
+    builder.add_unitig("AACCGTTA", &[3, 3000, 3, 3]);
+    let graph = builder.build(5).unwrap();
+    let (mut fasta, mut counts) = (Vec::new(), Vec::new());
+    let summary = graph.close(&mut FastaSink::new(&mut fasta, &mut counts), &CloseOptions{sort: Sort::None, ..Default::default()}).unwrap();
+    assert_eq!(String::from_utf8(fasta).unwrap(), ">clo_0 len=8 support=3 nk=3\nAACCGTTA\n>clo_1 len=5 support=3000 nk=1\nACCGT\n");
+    assert_eq!(String::from_utf8(counts).unwrap(), "clo_0\t8\t3\nclo_1\t5\t3000\n");
+    assert_eq!(summary.closed_per_output, BTreeMap::from([(1, 1), (3, 1)]));
+    assert!(summary.to_string().contains("\nClosed k-mers per output: mean 2.00, median 1, max 3\n"));
+  }
+
+  #[test]
+  fn gaps_are_jumped_and_declared() {
+    let close = |profile: &[u32], allow_gaps| {
+      let profile = profile.to_vec();
+      let graph = synthetic::generate_linear(5, profile.len(), |i| profile[i]).graph;
+      let (fasta, counts) = close_graph(&graph, &CloseOptions{allow_gaps, ..Default::default()});
+      let records: Vec<_> = fasta.lines().step_by(2).zip(fasta.lines().skip(1).step_by(2)).zip(counts.lines())
+        .map(|((h, u), c)| (String::from(h), String::from(u), c.parse::<u32>().unwrap())).collect();
+      (graph, records)
+    };
+
+    // A single dip splits the closure, unless it is jumped over
+    assert_eq!(close(&[4, 4, 4, 1, 4, 4, 4], None).1.len(), 3);
+    let (graph, records) = close(&[4, 4, 4, 1, 4, 4, 4], Some(1));
+    // The dip as seed is shrunk to itself
+    assert_eq!(records.iter().map(|(h, u, c)| (h.as_str(), u.len(), *c)).collect::<Vec<_>>(), vec![(">clo_0 len=5 support=1 nk=1", 5, 1), (">clo_1 len=11 support=4 nk=6 gaps=3", 11, 4)]);
+    let (_, u, c) = &records[1];
+    let u = Unitig::try_from(u.as_str()).unwrap();
+    assert!(graph.check_closed(&u, *c, &[3]).is_empty());
+    assert!(matches!(graph.check_closed(&u, *c, &[]).as_slice(), [Violation::WrongSupport{support: 1, reported: 4}]));
+    assert!(matches!(graph.check_closed(&u, *c, &[2, 3]).as_slice(), [Violation::NotGap{count: 4, ..}]));
+    // Gaps longer than allowed stop the closure
+    assert!(close(&[4, 4, 1, 1, 4, 4], Some(1)).1.iter().all(|(h, _, _)| !h.contains("gaps")));
+    assert!(close(&[4, 4, 1, 1, 4, 4], Some(2)).1.iter().any(|(h, _, _)| h.ends_with(" nk=4 gaps=2,3")));
+  }
+
+  #[test]
+  fn shrink_trims_path() {
+    let mut builder = GraphBuilder::new();
+    builder.add_unitig("AACCGTT", &[9, 5, 9]);
+    let graph = builder.build(5).unwrap();
+    let (closure, trim) = graph.shrink(Closure{unitig: Unitig::try_from("AACCGTT").unwrap(), support: 5, path: VecDeque::from(vec![0, 1, 2]), closed: 1, floored: false, truncated: false, timed_out: false, capped: false}, (&ShrinkPolicy::default(), Support::Min));
+    assert_eq!((String::from(closure.unitig), closure.path), (String::from("ACCGT"), VecDeque::from(vec![1])));
+    assert_eq!(trim, Trim{closure_len: 7, left: 1, right: 1, support: 5});
+  }
+
+  #[test]
+  fn timed_out_closures_are_flagged() {
+    // Root to leaf paths of 70 k-mers, longer than the steps between two checks of the timeout
+    let graph = synthetic::generate_branching(9, 9, 2).graph;
+    let close = |closure_timeout| {
+      let (mut fasta, mut counts) = (Vec::new(), Vec::new());
+      let options = CloseOptions{closure_timeout, ..Default::default()};
+      let summary = graph.close(&mut FastaSink::new(&mut fasta, &mut counts), &options).unwrap();
+      (summary, String::from_utf8(fasta).unwrap())
+    };
+    let (summary, fasta) = close(None);
+    assert_eq!((summary.outputs, summary.timed_out), (512, 0));
+    assert!(!fasta.contains("timeout"));
+
+    // Every closure stops at the first check, and closing goes on with the next seeds
+    let (summary, fasta) = close(Some(Duration::ZERO));
+    assert_eq!(summary.timed_out, fasta.matches(" timeout=1\n").count());
+    assert!(summary.timed_out > 0 && summary.covered_kmers == summary.kmers);
+    assert!(fasta.lines().skip(1).step_by(2).all(|l| l.len() < 9 + CHECK_STEPS));
+  }
+
+  #[test]
+  fn capped_closures_are_flagged() {
+    let graph = synthetic::generate_branching(9, 9, 2).graph;
+    let close = |max_length, max_steps| {
+      let (mut fasta, mut counts) = (Vec::new(), Vec::new());
+      let options = CloseOptions{max_length, max_steps, ..Default::default()};
+      let summary = graph.close(&mut FastaSink::new(&mut fasta, &mut counts), &options).unwrap();
+      (summary, String::from_utf8(fasta).unwrap())
+    };
+    let (summary, fasta) = close(None, None);
+    assert_eq!((summary.outputs, summary.capped), (512, 0));
+    assert!(!fasta.contains("capped"));
+
+    let (summary, fasta) = close(Some(20), None);
+    assert!(summary.capped > 0 && summary.capped == fasta.matches(" capped=1\n").count());
+    assert!(summary.covered_kmers == summary.kmers);
+    assert!(fasta.lines().skip(1).step_by(2).all(|l| l.len() <= 20));
+
+    // A closure extended at most 3 times has at most 4 k-mers
+    let (summary, fasta) = close(None, Some(3));
+    assert!(summary.capped > 0);
+    assert!(fasta.lines().skip(1).step_by(2).all(|l| l.len() <= 9 + 3));
+  }
+
+  #[test]
+  fn cancelled_closing_stops_at_the_next_seed() {
+    /// Cancels the closing once it gets the first closed unitig
+    struct Cancelling(CancelToken, usize);
+    impl Sink for Cancelling {
+      fn emit(&mut self, _id: usize, _u: &Unitig, _support: u32, _meta: &Metadata) -> std::io::Result<()> {
+        self.0.cancel();
+        self.1 += 1;
+        Ok(())
+      }
+    }
+
+    let graph = Graph::from(SMALL.as_bytes());
+    let options = CloseOptions{sort: Sort::None, ..Default::default()};
+    let mut sink = Cancelling(options.cancel.clone(), 0);
+    let summary = graph.close(&mut sink, &options).unwrap();
+    assert_eq!((sink.1, summary.outputs), (1, 1));
+    assert!(summary.cancelled.is_some_and(|seeds_done| seeds_done < summary.kmers));
+    // Nothing is closed once cancelled
+    let summary = graph.close(&mut sink, &options).unwrap();
+    assert_eq!((summary.outputs, summary.cancelled), (0, Some(0)));
+  }
+
+  #[test]
+  fn max_count_ratio_stops_at_repeats() {
+    let mut builder = GraphBuilder::new();
+    builder.add_unitig("AACCGTTA", &[3, 3000, 3, 3]);
+    let graph = builder.build(5).unwrap();
+    let pairs = |max_count_ratio| pairs(close_graph(&graph, &CloseOptions{max_count_ratio, ..Default::default()}));
+    let expected = |p: &[(&str, &str)]| p.iter().map(|&(u, c)| (String::from(u), String::from(c))).collect::<Vec<_>>();
+    assert_eq!(pairs(None), expected(&[("AACCGTTA", "3"), ("ACCGT", "3000")]));
+    assert_eq!(pairs(Some(1000.)), expected(&[("AACCGTTA", "3"), ("ACCGT", "3000")]));
+    // The k-mer with count equal to the support is still marked as closed, and it is not used as a seed
+    assert_eq!(pairs(Some(10.)), expected(&[("AACCG", "3"), ("ACCGT", "3000"), ("CCGTTA", "3")]));
+  }
+
+  #[test]
+  fn heaviest_edges_are_preferred() {
+    let mut builder = GraphBuilder::new();
+    let a = builder.add_unitig("AACCGT", &[5, 5]);
+    let b = builder.add_unitig("CCGTTA", &[5, 5]);
+    let c = builder.add_unitig("CCGTGA", &[5, 5]);
+    builder.add_link(a, Forward, b, Forward);
+    builder.add_link(a, Forward, c, Forward);
+    builder.add_link(a, Forward, c, Forward);
+    let graph = builder.build(5).unwrap();
+    let weights: Vec<_> = graph.neighbors(&Unitig::try_from("ACCGT").unwrap(), true).unwrap().into_iter().map(|n| (String::from(n.kmer), n.weight)).collect();
+    assert_eq!(weights, vec![(String::from("CCGTT"), 1), (String::from("CCGTG"), 2)]);
+    // The closure of the first seed is written first
+    let first = |extension| close_graph(&graph, &CloseOptions{sort: Sort::None, extension, ..Default::default()}).0.lines().nth(1).map(String::from);
+    assert_eq!(first(Extension::First), Some(String::from("AACCGTTA")));
+    assert_eq!(first(Extension::MaxEdgeWeight), Some(String::from("AACCGTGA")));
+  }
+
+  #[test]
+  fn closures_follow_the_support_measure() {
+    let mut builder = GraphBuilder::new();
+    builder.add_unitig("AACCGTTA", &[3, 5, 7, 9]);
+    let graph = builder.build(5).unwrap();
+    let first = |support| {
+      let (fasta, counts) = close_graph(&graph, &CloseOptions{sort: Sort::None, support, ..Default::default()});
+      (fasta.lines().nth(1).map(String::from).unwrap(), counts.lines().next().map(String::from).unwrap())
+    };
+    // The whole unitig has minimum 3, shrunk to the first k-mer
+    assert_eq!(first(Support::Min), (String::from("AACCG"), String::from("3")));
+    // ... and mean 6, shrunk to the k-mers below 7, whose mean is 4
+    assert_eq!(first(Support::Mean), (String::from("AACCGT"), String::from("4")));
+    assert_eq!(first(Support::Median), (String::from("AACCGT"), String::from("3")));
+  }
+
+  #[test]
+  fn ties_are_broken_as_asked() {
+    let mut builder = GraphBuilder::new();
+    let a = builder.add_unitig("AACCGT", &[5, 5]);
+    let b = builder.add_unitig("CCGTTA", &[9, 9]);
+    let c = builder.add_unitig("CCGTGA", &[6, 6]);
+    builder.add_link(a, Forward, b, Forward);
+    builder.add_link(a, Forward, c, Forward);
+    let graph = builder.build(5).unwrap();
+    // Without trimming the k-mers with higher counts than the support
+    let shrink = ShrinkPolicy{max_trim: Some(0), ..Default::default()};
+    let first = |extension| close_graph(&graph, &CloseOptions{sort: Sort::None, extension, shrink, ..Default::default()}).0.lines().nth(1).map(String::from);
+    assert_eq!(first(Extension::First), Some(String::from("AACCGTTA")));
+    assert_eq!(first(Extension::HighestCount), Some(String::from("AACCGTTA")));
+    assert_eq!(first(Extension::LowestCount), Some(String::from("AACCGTGA")));
+    // CCGTG is lower than CCGTT
+    assert_eq!(first(Extension::Lexicographic), Some(String::from("AACCGTGA")));
+  }
+
+  #[test]
+  fn support_floor_stops_closures() {
+    let mut builder = GraphBuilder::new();
+    builder.add_unitig("AACCGTTA", &[1, 3, 3, 1]);
+    let graph = builder.build(5).unwrap();
+    let close = |drop_below_floor| {
+      let options = CloseOptions{sort: Sort::None, support_floor: Some(1), drop_below_floor, ..Default::default()};
+      let (mut fasta, mut counts) = (Vec::new(), Vec::new());
+      let summary = graph.close(&mut FastaSink::new(&mut fasta, &mut counts), &options).unwrap();
+      (String::from_utf8(fasta).unwrap().lines().skip(1).step_by(2).map(String::from).collect::<Vec<_>>(), summary.floored)
+    };
+    assert_eq!(close(false), (vec![String::from("AACCG"), String::from("ACCGTT"), String::from("CGTTA")], 2));
+    assert_eq!(close(true), (vec![String::from("ACCGTT")], 2));
+  }
+
+  #[test]
+  fn shrink_policies() {
+    let mut builder = GraphBuilder::new();
+    builder.add_unitig("AACCGTTAC", &[9, 6, 5, 5, 7]);
+    let graph = builder.build(5).unwrap();
+    let u = Unitig::try_from("AACCGTTAC").unwrap();
+    let shrink = |threshold_delta, max_trim| {
+      let (u, c) = graph.shrink_unitig(&u, ShrinkPolicy{threshold_delta, max_trim});
+      (String::from(u), c)
+    };
+    assert_eq!(shrink(1, None), (String::from("CCGTTA"), 5));
+    assert_eq!(shrink(2, None), (String::from("ACCGTTA"), 5));
+    assert_eq!(shrink(1, Some(1)), (String::from("ACCGTTA"), 5));
+    // Trimming k-mers equal to the support raises it, but at least one k-mer is kept
+    assert_eq!(shrink(0, None), (String::from("GTTAC"), 7));
   }
 }
@@ -0,0 +1,172 @@
+//! Sorted index of the canonical k-mers of a graph, stored next to the input and searched by binary search
+
+use snafu::Snafu;
+use std::convert::TryInto;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use xxhash_rust::xxh3::xxh3_64;
+use super::Unitig;
+
+/// First bytes of an index file
+const MAGIC: &[u8; 8] = b"CUIDX\0\0\0";
+/// Version of the format, increased at each incompatible change
+const VERSION: u32 = 1;
+/// Bytes of the header: magic, version, k, input hash, options hash, number of entries and checksum of the entries
+const HEADER: usize = 8 + 4 + 4 + 8 + 8 + 8 + 8;
+/// Bytes of an entry: packed k-mer and node id
+const ENTRY: usize = 16 + 8;
+/// Longest k-mers which can be packed
+pub const MAX_K: usize = 64;
+
+#[derive(Debug, Snafu)]
+/// Describes why an index cannot be used
+pub enum IndexError {
+  #[snafu(display("Cannot access the index: {}", source))]
+  Io{source: io::Error},
+  #[snafu(display("Not an index file"))]
+  NotIndex,
+  #[snafu(display("Index version {} is not supported (expected {})", version, VERSION))]
+  Version{version: u32},
+  #[snafu(display("Index of another input or of other options"))]
+  Stale,
+  #[snafu(display("Corrupted index"))]
+  Corrupted,
+  #[snafu(display("k = {} is too long for an index (at most {})", k, MAX_K))]
+  TooLong{k: usize}
+}
+
+impl From<io::Error> for IndexError {
+  fn from(source: io::Error) -> IndexError {
+    IndexError::Io{source}
+  }
+}
+
+/// Identifies what an index was built from: the input content and the options changing the nodes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IndexKey {
+  pub input: u64,
+  pub options: u64
+}
+
+/// Packs a k-mer in two bits per base, the first base in the most significant bits, so that packed k-mers
+/// of the same length sort as the k-mers
+fn pack(kmer: &Unitig) -> u128 {
+  kmer.codes().fold(0, |packed, code| packed << 2 | code as u128)
+}
+
+/// Canonical k-mers with their node ids, sorted by k-mer
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SortedIndex {
+  pub k: usize,
+  entries: Vec<(u128, usize)>
+}
+
+impl SortedIndex {
+  /// Indexes the given k-mers, the i-th being the k-mer of node i
+  pub fn new<'a>(k: usize, kmers: impl Iterator<Item = &'a Unitig>) -> Result<SortedIndex, IndexError> {
+    if k > MAX_K {
+      return Err(IndexError::TooLong{k});
+    }
+    let mut entries: Vec<_> = kmers.enumerate().map(|(i, kmer)| (pack(&kmer.norm()), i)).collect();
+    entries.sort_unstable();
+    Ok(SortedIndex{k, entries})
+  }
+
+  /// Number of indexed k-mers
+  pub fn len(&self) -> usize {
+    self.entries.len()
+  }
+
+  /// Whether no k-mer is indexed
+  pub fn is_empty(&self) -> bool {
+    self.entries.is_empty()
+  }
+
+  /// Finds the node of a k-mer, given on either strand
+  pub fn get(&self, kmer: &Unitig) -> Option<usize> {
+    let packed = pack(&kmer.norm());
+    self.entries.binary_search_by_key(&packed, |&(p, _)| p).ok().map(|i| self.entries[i].1)
+  }
+
+  /// Writes the index to a file
+  pub fn write(&self, path: &Path, key: IndexKey) -> Result<(), IndexError> {
+    let mut body = Vec::with_capacity(self.entries.len() * ENTRY);
+    for &(packed, id) in &self.entries {
+      body.extend_from_slice(&packed.to_le_bytes());
+      body.extend_from_slice(&(id as u64).to_le_bytes());
+    }
+    let mut out = BufWriter::new(File::create(path)?);
+    out.write_all(MAGIC)?;
+    out.write_all(&VERSION.to_le_bytes())?;
+    out.write_all(&(self.k as u32).to_le_bytes())?;
+    for field in [key.input, key.options, self.entries.len() as u64, xxh3_64(&body)] {
+      out.write_all(&field.to_le_bytes())?;
+    }
+    out.write_all(&body)?;
+    out.flush()?;
+    Ok(())
+  }
+
+  /// Reads an index from a file, checking that it was built with the given key and that it is intact
+  pub fn load(path: &Path, key: IndexKey) -> Result<SortedIndex, IndexError> {
+    let data = fs::read(path)?;
+    if data.len() < HEADER || &data[..8] != MAGIC {
+      return Err(IndexError::NotIndex);
+    }
+    let u32_at = |i: usize| u32::from_le_bytes(data[i..i+4].try_into().unwrap());
+    let u64_at = |i: usize| u64::from_le_bytes(data[i..i+8].try_into().unwrap());
+    let version = u32_at(8);
+    if version != VERSION {
+      return Err(IndexError::Version{version});
+    }
+    if (u64_at(16), u64_at(24)) != (key.input, key.options) {
+      return Err(IndexError::Stale);
+    }
+    let (k, n, checksum) = (u32_at(12) as usize, u64_at(32) as usize, u64_at(40));
+    let body = &data[HEADER..];
+    if k > MAX_K || Some(body.len()) != n.checked_mul(ENTRY) || xxh3_64(body) != checksum {
+      return Err(IndexError::Corrupted);
+    }
+    let entries: Vec<_> = body.chunks_exact(ENTRY)
+      .map(|e| (u128::from_le_bytes(e[..16].try_into().unwrap()), u64::from_le_bytes(e[16..].try_into().unwrap()) as usize))
+      .collect();
+    if entries.windows(2).any(|w| w[0].0 >= w[1].0) || entries.iter().any(|&(_, id)| id >= n) {
+      return Err(IndexError::Corrupted);
+    }
+    Ok(SortedIndex{k, entries})
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::convert::TryFrom;
+
+  #[test]
+  fn indexes_are_checked_when_loaded() {
+    let kmers: Vec<_> = ["AACCG", "ACGGT", "CGTTA", "TTTTT"].iter().map(|&k| Unitig::try_from(k).unwrap()).collect();
+    let index = SortedIndex::new(5, kmers.iter()).unwrap();
+    let get = |kmer| index.get(&Unitig::try_from(kmer).unwrap());
+    // K-mers are found on either strand
+    assert_eq!((get("AACCG"), get("CGGTT"), get("ACCGT"), get("AAAAA")), (Some(0), Some(0), Some(1), Some(3)));
+    assert_eq!(get("CCCCC"), None);
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("small.fa.cuidx");
+    let key = IndexKey{input: 1, options: 2};
+    index.write(&path, key).unwrap();
+    assert_eq!(SortedIndex::load(&path, key).unwrap(), index);
+    assert!(matches!(SortedIndex::load(&path, IndexKey{input: 3, ..key}), Err(IndexError::Stale)));
+
+    let mut data = fs::read(&path).unwrap();
+    data[HEADER] ^= 1;
+    fs::write(&path, &data).unwrap();
+    assert!(matches!(SortedIndex::load(&path, key), Err(IndexError::Corrupted)));
+    data[8] = 9;
+    fs::write(&path, &data).unwrap();
+    assert!(matches!(SortedIndex::load(&path, key), Err(IndexError::Version{version: 9})));
+    fs::write(&path, b">0 ab:Z:1\nACGTA\n").unwrap();
+    assert!(matches!(SortedIndex::load(&path, key), Err(IndexError::NotIndex)));
+  }
+}
@@ -0,0 +1,438 @@
+//! Reading and writing of sequence files
+
+use flate2::{Compression, bufread::MultiGzDecoder, write::GzEncoder};
+use serde::Serialize;
+use std::fmt::Write as _;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, BufWriter, Lines, Read, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// A FASTA record, whose header is made of its name, if any, and of its tags, separated by spaces
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FastaRecord<'a> {
+  pub name: Option<String>,
+  pub tags: Vec<String>,
+  pub sequence: &'a str
+}
+
+/// Compression of an output file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Compress {
+  Gz,
+  Zst
+}
+
+impl Compress {
+  /// Compression given by the extension of a file name, if any
+  pub fn of(path: &Path) -> Option<Compress> {
+    path.extension().and_then(|ext| ext.to_str()).and_then(|ext| ext.parse().ok())
+  }
+
+  /// Extension of the compressed files
+  pub fn extension(compress: Option<Compress>) -> &'static str {
+    match compress {
+      Some(Compress::Gz) => ".gz",
+      Some(Compress::Zst) => ".zst",
+      None => ""
+    }
+  }
+}
+
+impl FromStr for Compress {
+  type Err = String;
+
+  /// Parses a compression from its command line name, which is also its extension
+  fn from_str(s: &str) -> Result<Compress, Self::Err> {
+    match s {
+      "gz" => Ok(Compress::Gz),
+      "zst" => Ok(Compress::Zst),
+      _ => Err(format!("Unknown compression \"{}\"", s))
+    }
+  }
+}
+
+/// How FASTA files are written
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct FastaOptions {
+  /// Bases per sequence line, or None to write each sequence on a single line
+  pub width: Option<usize>,
+  /// Compression of the file, if any; the counts files written alongside are compressed the same way
+  pub compress: Option<Compress>,
+  /// Whether a samtools `.fai` index is written next to the file, which needs it uncompressed
+  pub fai: bool,
+  /// Whether the file is written under a temporary name and renamed once complete, so that it is never seen partial
+  pub atomic: bool
+}
+
+impl FastaOptions {
+  /// Extension of the FASTA files
+  pub fn extension(&self) -> String {
+    String::from(".fa") + Compress::extension(self.compress)
+  }
+
+  /// Extension of the counts files written alongside
+  pub fn counts_extension(&self) -> String {
+    String::from(".counts") + Compress::extension(self.compress)
+  }
+}
+
+/// Destination of a writer, compressed or not
+pub enum Encoder<W: Write> {
+  Plain(W),
+  Gzip(GzEncoder<W>),
+  Zstd(zstd::Encoder<'static, W>)
+}
+
+impl<W: Write> Encoder<W> {
+  /// Compresses a writer as asked
+  pub fn new(out: W, compress: Option<Compress>) -> io::Result<Encoder<W>> {
+    Ok(match compress {
+      Some(Compress::Gz) => Encoder::Gzip(GzEncoder::new(out, Compression::default())),
+      Some(Compress::Zst) => Encoder::Zstd(zstd::Encoder::new(out, 0)?),
+      None => Encoder::Plain(out)
+    })
+  }
+
+  /// Ends the compressed stream, if any, and flushes the writer; nothing can be written afterwards
+  pub fn finish(&mut self) -> io::Result<()> {
+    match self {
+      Encoder::Plain(out) => out.flush(),
+      Encoder::Gzip(out) => {
+        out.try_finish()?;
+        out.get_mut().flush()
+      },
+      Encoder::Zstd(out) => {
+        out.do_finish()?;
+        out.get_mut().flush()
+      }
+    }
+  }
+}
+
+impl<W: Write> Write for Encoder<W> {
+  fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    match self {
+      Encoder::Plain(out) => out.write(buf),
+      Encoder::Gzip(out) => out.write(buf),
+      Encoder::Zstd(out) => out.write(buf)
+    }
+  }
+
+  fn flush(&mut self) -> io::Result<()> {
+    match self {
+      Encoder::Plain(out) => out.flush(),
+      Encoder::Gzip(out) => out.flush(),
+      Encoder::Zstd(out) => out.flush()
+    }
+  }
+}
+
+/// Source of a reader, decompressed if needed
+pub enum Decoder<R: BufRead> {
+  Plain(R),
+  Gzip(BufReader<MultiGzDecoder<R>>),
+  Zstd(BufReader<zstd::Decoder<'static, R>>),
+  Bzip2(BufReader<bzip2::bufread::MultiBzDecoder<R>>)
+}
+
+impl<R: BufRead> Decoder<R> {
+  /// Recognizes gzip, zstd and bzip2 streams by their magic bytes; anything else is read as it is
+  pub fn new(mut buf: R) -> io::Result<Decoder<R>> {
+    let magic = buf.fill_buf()?;
+    Ok(if magic.starts_with(&[0x1f, 0x8b]) {
+      Decoder::Gzip(BufReader::new(MultiGzDecoder::new(buf)))
+    } else if magic.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+      Decoder::Zstd(BufReader::new(zstd::Decoder::with_buffer(buf)?))
+    } else if magic.starts_with(b"BZh") {
+      Decoder::Bzip2(BufReader::new(bzip2::bufread::MultiBzDecoder::new(buf)))
+    } else {
+      Decoder::Plain(buf)
+    })
+  }
+
+  /// The underlying reader
+  pub fn into_inner(self) -> R {
+    match self {
+      Decoder::Plain(buf) => buf,
+      Decoder::Gzip(buf) => buf.into_inner().into_inner(),
+      Decoder::Zstd(buf) => buf.into_inner().finish(),
+      Decoder::Bzip2(buf) => buf.into_inner().into_inner()
+    }
+  }
+}
+
+impl<R: BufRead> Read for Decoder<R> {
+  fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+    match self {
+      Decoder::Plain(inner) => inner.read(buf),
+      Decoder::Gzip(inner) => inner.read(buf),
+      Decoder::Zstd(inner) => inner.read(buf),
+      Decoder::Bzip2(inner) => inner.read(buf)
+    }
+  }
+}
+
+impl<R: BufRead> BufRead for Decoder<R> {
+  fn fill_buf(&mut self) -> io::Result<&[u8]> {
+    match self {
+      Decoder::Plain(inner) => inner.fill_buf(),
+      Decoder::Gzip(inner) => inner.fill_buf(),
+      Decoder::Zstd(inner) => inner.fill_buf(),
+      Decoder::Bzip2(inner) => inner.fill_buf()
+    }
+  }
+
+  fn consume(&mut self, amt: usize) {
+    match self {
+      Decoder::Plain(inner) => inner.consume(amt),
+      Decoder::Gzip(inner) => inner.consume(amt),
+      Decoder::Zstd(inner) => inner.consume(amt),
+      Decoder::Bzip2(inner) => inner.consume(amt)
+    }
+  }
+}
+
+/// Opens a file for buffered reading, decompressing it if needed
+pub fn open(path: &Path) -> io::Result<Decoder<BufReader<File>>> {
+  Decoder::new(BufReader::new(File::open(path)?))
+}
+
+/// Whether a path has the extension of a compressed file
+pub fn is_compressed(path: &Path) -> bool {
+  path.extension().is_some_and(|ext| ext == "gz" || ext == "zst" || ext == "bz2")
+}
+
+/// Appends a suffix to a path
+pub fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+  let mut path = path.as_os_str().to_os_string();
+  path.push(suffix);
+  PathBuf::from(path)
+}
+
+/// Writes FASTA records
+pub struct FastaWriter<W: Write> {
+  out: Encoder<W>,
+  width: Option<usize>,
+  /// Uncompressed bytes written so far
+  offset: u64,
+  /// Lines of the `.fai` index, if it is built
+  fai: Option<String>,
+  /// Path of the file while it is written and once complete, if written to a file
+  paths: Option<(PathBuf, PathBuf)>
+}
+
+impl<W: Write> FastaWriter<W> {
+  /// Creates a writer with the given options; the index, if built, is not written anywhere
+  /// and writing atomically is possible only for files
+  ///
+  /// # Panics
+  /// If both compression and the index are asked for.
+  pub fn new(out: W, options: FastaOptions) -> io::Result<FastaWriter<W>> {
+    assert!(!(options.compress.is_some() && options.fai), "A .fai index needs an uncompressed FASTA file");
+    let out = Encoder::new(out, options.compress)?;
+    Ok(FastaWriter{out, width: options.width, offset: 0, fai: options.fai.then(String::new), paths: None})
+  }
+
+  /// Writes a record, wrapping its sequence as asked
+  pub fn write(&mut self, record: &FastaRecord) -> io::Result<()> {
+    let mut header = String::from(">");
+    let mut fields = record.name.iter().chain(&record.tags);
+    if let Some(first) = fields.next() {
+      header += first;
+      for field in fields {
+        header.push(' ');
+        header += field;
+      }
+    }
+    writeln!(self.out, "{}", header)?;
+    self.offset += header.len() as u64 + 1;
+
+    let sequence = record.sequence.as_bytes();
+    let width = self.width.unwrap_or(sequence.len()).max(1);
+    if let Some(fai) = &mut self.fai {
+      let name = header[1..].split(' ').next().unwrap();
+      writeln!(fai, "{}\t{}\t{}\t{}\t{}", name, sequence.len(), self.offset, width, width + 1).unwrap();
+    }
+    if sequence.is_empty() {
+      writeln!(self.out)?;
+      self.offset += 1;
+    }
+    for line in sequence.chunks(width) {
+      self.out.write_all(line)?;
+      writeln!(self.out)?;
+      self.offset += line.len() as u64 + 1;
+    }
+    Ok(())
+  }
+
+  /// Completes the file: ends the compression, writes the index and moves the file to its final path, if needed
+  pub fn finish(&mut self) -> io::Result<()> {
+    self.out.finish()?;
+    if let Some((written, path)) = self.paths.take() {
+      if let Some(fai) = &self.fai {
+        fs::write(with_suffix(path.as_ref(), ".fai"), fai)?;
+      }
+      if written != path {
+        fs::rename(written, path)?;
+      }
+    }
+    Ok(())
+  }
+}
+
+impl FastaWriter<BufWriter<File>> {
+  /// Creates a FASTA file with the given options, writing its index next to it as `PATH.fai` if asked
+  pub fn create(path: &Path, options: FastaOptions) -> io::Result<FastaWriter<BufWriter<File>>> {
+    let written = if options.atomic {with_suffix(path, ".tmp")} else {path.to_path_buf()};
+    let mut writer = FastaWriter::new(BufWriter::new(File::create(&written)?), options)?;
+    writer.paths = Some((written, path.to_path_buf()));
+    Ok(writer)
+  }
+}
+
+/// Reads the records of a FASTA file as their header, without `>`, and their sequence, joining its lines.
+/// Lines before the first header are skipped.
+pub struct FastaReader<T: BufRead> {
+  lines: Lines<T>,
+  /// Header of the next record, once read
+  header: Option<String>
+}
+
+impl<T: BufRead> FastaReader<T> {
+  /// Creates a reader of the given buffer
+  pub fn new(buf: T) -> FastaReader<T> {
+    FastaReader{lines: buf.lines(), header: None}
+  }
+
+  /// Reads the next record, if there is one
+  fn read(&mut self) -> io::Result<Option<(String, String)>> {
+    let header = match self.header.take() {
+      Some(header) => header,
+      None => loop {
+        match self.lines.next().transpose()? {
+          Some(line) => if let Some(header) = line.strip_prefix('>') {break String::from(header)},
+          None => return Ok(None)
+        }
+      }
+    };
+    let mut sequence = String::new();
+    while let Some(line) = self.lines.next().transpose()? {
+      if let Some(next) = line.strip_prefix('>') {
+        self.header = Some(String::from(next));
+        break;
+      }
+      sequence.push_str(line.trim_end());
+    }
+    Ok(Some((header, sequence)))
+  }
+}
+
+impl<T: BufRead> Iterator for FastaReader<T> {
+  type Item = io::Result<(String, String)>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    self.read().transpose()
+  }
+}
+
+/// Reads the supports of a counts file, with a closed unitig per line given by its name, length and support
+/// separated by tabs, or by its support alone as in older outputs, skipping blank lines and `;` comment lines.
+/// A line which is neither gives an invalid data error.
+pub fn read_counts<T: BufRead>(buf: T) -> impl Iterator<Item = io::Result<u32>> {
+  buf.lines().filter(|l| l.as_ref().map_or(true, |l| !l.trim().is_empty() && !l.starts_with(';'))).map(|l| {
+    let l = l?;
+    let support = match l.trim().split('\t').collect::<Vec<_>>()[..] {
+      [support] | [_, _, support] => support.parse().ok(),
+      _ => None
+    };
+    support.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, format!("wrong count \"{}\"", l)))
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn records_are_formatted_as_asked() {
+    let records = [
+      FastaRecord{name: Some(String::from("3")), tags: vec![String::from("nk=2"), String::from("gaps=1")], sequence: "AACCGTTA"},
+      FastaRecord{name: None, tags: vec![String::from("nk=1")], sequence: "ACG"}
+    ];
+    let write = |options| {
+      let mut writer = FastaWriter::new(Vec::new(), options).unwrap();
+      for record in &records {
+        writer.write(record).unwrap();
+      }
+      writer.finish().unwrap();
+      let fai = writer.fai.clone();
+      (writer, fai)
+    };
+    let text = |writer: FastaWriter<Vec<u8>>| {
+      let out = match &writer.out {
+        Encoder::Plain(out) => out,
+        Encoder::Gzip(out) => out.get_ref(),
+        Encoder::Zstd(out) => out.get_ref()
+      };
+      let mut text = String::new();
+      Decoder::new(&out[..]).unwrap().read_to_string(&mut text).unwrap();
+      text
+    };
+
+    let (writer, _) = write(FastaOptions::default());
+    assert_eq!(text(writer), ">3 nk=2 gaps=1\nAACCGTTA\n>nk=1\nACG\n");
+    let (writer, fai) = write(FastaOptions{width: Some(3), fai: true, ..Default::default()});
+    assert_eq!(text(writer), ">3 nk=2 gaps=1\nAAC\nCGT\nTA\n>nk=1\nACG\n");
+    assert_eq!(fai.unwrap(), "3\t8\t15\t3\t4\nnk=1\t3\t32\t3\t4\n");
+    for compress in [Compress::Gz, Compress::Zst] {
+      let (writer, _) = write(FastaOptions{compress: Some(compress), ..Default::default()});
+      assert!(!matches!(writer.out, Encoder::Plain(_)));
+      assert_eq!(text(writer), ">3 nk=2 gaps=1\nAACCGTTA\n>nk=1\nACG\n");
+    }
+  }
+
+  #[test]
+  fn records_are_read_back() {
+    let fasta = "skipped\n>3 nk=2\nAAC\nCGT\nTA\n>nk=1\n\n>nk=1\nACG\n";
+    let records: Vec<_> = FastaReader::new(fasta.as_bytes()).map(Result::unwrap).collect();
+    let record = |header: &str, sequence: &str| (String::from(header), String::from(sequence));
+    assert_eq!(records, [record("3 nk=2", "AACCGTTA"), record("nk=1", ""), record("nk=1", "ACG")]);
+    assert_eq!(read_counts("; comment\n3\n\n 4\nclo_2\t8\t5\n".as_bytes()).collect::<io::Result<Vec<_>>>().unwrap(), [3, 4, 5]);
+    assert_eq!(read_counts("3\nx\n".as_bytes()).nth(1).unwrap().unwrap_err().kind(), io::ErrorKind::InvalidData);
+    assert_eq!(read_counts("clo_0\t5\n".as_bytes()).next().unwrap().unwrap_err().kind(), io::ErrorKind::InvalidData);
+  }
+
+  #[test]
+  fn compressed_inputs_are_recognized() {
+    let text = ">0\nACGT\n".repeat(100);
+    let read = |data: Vec<u8>| {
+      let mut decoder = Decoder::new(&data[..]).unwrap();
+      let mut read = String::new();
+      decoder.read_to_string(&mut read).unwrap();
+      (read, matches!(decoder, Decoder::Plain(_)))
+    };
+    let mut gzip = GzEncoder::new(Vec::new(), Compression::default());
+    gzip.write_all(text.as_bytes()).unwrap();
+    let mut bzip2 = bzip2::write::BzEncoder::new(Vec::new(), bzip2::Compression::default());
+    bzip2.write_all(text.as_bytes()).unwrap();
+    for compressed in [gzip.finish().unwrap(), zstd::encode_all(text.as_bytes(), 0).unwrap(), bzip2.finish().unwrap()] {
+      assert_eq!(read(compressed), (text.clone(), false));
+    }
+    assert_eq!(read(text.clone().into_bytes()), (text, true));
+  }
+
+  #[test]
+  fn atomic_files_appear_once_complete() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("out.fa");
+    let mut writer = FastaWriter::create(&path, FastaOptions{atomic: true, fai: true, ..Default::default()}).unwrap();
+    writer.write(&FastaRecord{name: Some(String::from("0")), tags: vec![], sequence: "ACGT"}).unwrap();
+    assert!(!path.exists());
+    writer.finish().unwrap();
+    assert_eq!(fs::read_to_string(&path).unwrap(), ">0\nACGT\n");
+    assert_eq!(fs::read_to_string(dir.path().join("out.fa.fai")).unwrap(), "0\t4\t3\t4\t5\n");
+    assert!(!dir.path().join("out.fa.tmp").exists());
+  }
+}
@@ -0,0 +1,224 @@
+//! Graphs of the k-mers counted by KMC, read from its database files
+
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::io::{self, Read};
+use super::kmers;
+use super::reader::{ReadError, Record, RecordSource};
+use super::unitig::Unitig;
+
+/// Marker at both ends of the prefix file
+const PREFIX_MARKER: &[u8; 4] = b"KMCP";
+/// Marker at both ends of the suffix file
+const SUFFIX_MARKER: &[u8; 4] = b"KMCS";
+/// Version of the databases of KMC 2 and 3
+const VERSION: u32 = 0x200;
+
+/// Reads a KMC database, made of a prefix file (`.kmc_pre`) and a suffix file (`.kmc_suf`), as written by KMC 2 and 3.
+/// Each k-mer is a record with its count, linked to the k-mers overlapping it, as for k-mer count tables:
+/// a k-mer counted on both strands, in a database written with `-b`, has the sum of its counts.
+/// The line of a record is the position of its k-mer in the database, from 1.
+/// The whole database is read at once, since the links are only known once all k-mers are.
+pub struct KmcReader {
+  records: std::vec::IntoIter<Record>,
+  bytes: u64
+}
+
+/// Parameters of a database, from the header at the end of its prefix file
+struct Header {
+  k: usize,
+  /// Bytes of the count of each k-mer
+  counter_size: usize,
+  /// Bases of each k-mer given by its position in the prefix file, the others are in the suffix file
+  lut_prefix_length: usize,
+  /// Bases of the signatures binning the k-mers
+  signature_len: usize,
+  total_kmers: u64
+}
+
+/// Little endian number of the given bytes
+fn number(bytes: &[u8]) -> u64 {
+  bytes.iter().rev().fold(0, |n, &b| n << 8 | b as u64)
+}
+
+/// Error of a malformed database
+fn malformed(message: impl Into<String>) -> ReadError {
+  ReadError::Kmc{message: message.into()}
+}
+
+impl Header {
+  /// Reads the header of a prefix file, found from its offset stored before the final marker
+  fn read(pre: &[u8]) -> Result<Header, ReadError> {
+    if pre.len() < 16 || &pre[..4] != PREFIX_MARKER || &pre[pre.len()-4..] != PREFIX_MARKER {
+      return Err(malformed("the prefix file has no KMCP markers"));
+    }
+    let version = number(&pre[pre.len()-12..pre.len()-8]) as u32;
+    if version != VERSION {
+      return Err(malformed(format!("version {:#x} is not supported, only the databases of KMC 2 and 3 are", version)));
+    }
+    let offset = number(&pre[pre.len()-8..pre.len()-4]) as usize;
+    let start = pre.len().checked_sub(offset + 8).filter(|&start| start >= 4 && offset >= 36).ok_or_else(|| malformed("wrong header offset"))?;
+    let field = |i: usize| number(&pre[start+4*i..start+4*i+4]) as usize;
+    if field(1) != 0 {
+      return Err(malformed("quality-aware counters are not supported"));
+    }
+    let header = Header{k: field(0), counter_size: field(2), lut_prefix_length: field(3), signature_len: field(4), total_kmers: number(&pre[start+28..start+36])};
+    if header.k == 0 || header.lut_prefix_length > header.k || (header.k - header.lut_prefix_length) % 4 != 0 || header.counter_size > 4 || header.signature_len > 16 {
+      return Err(malformed("wrong parameters in the header"));
+    }
+    Ok(header)
+  }
+
+  /// Bytes of the suffix of each k-mer
+  fn suffix_size(&self) -> usize {
+    (self.k - self.lut_prefix_length) / 4
+  }
+}
+
+impl KmcReader {
+  /// Reads the whole database from its prefix file and its suffix file
+  pub fn new<P: Read, S: Read>(mut pre: P, mut suf: S) -> Result<KmcReader, ReadError> {
+    let mut data = Vec::new();
+    pre.read_to_end(&mut data).map_err(|source| ReadError::Io{source})?;
+    let header = Header::read(&data)?;
+    let (k, offset) = (header.k, number(&data[data.len()-8..data.len()-4]) as usize);
+
+    // Each bin has a table of the index of the first k-mer of each prefix, then comes the total number of k-mers
+    let signatures = ((1usize << (2 * header.signature_len)) + 1) * 4;
+    let lut = data.len().checked_sub(4 + signatures + offset + 8).filter(|lut| lut % 8 == 0 && *lut > 0).ok_or_else(|| malformed("wrong size of the prefix file"))?;
+    let starts: Vec<u64> = data[4..4+lut].chunks(8).map(number).collect();
+    let prefixes = 1usize << (2 * header.lut_prefix_length);
+    if (starts.len() - 1) % prefixes != 0 || starts.windows(2).any(|w| w[0] > w[1]) || starts[starts.len()-1] != header.total_kmers {
+      return Err(malformed("wrong prefix tables"));
+    }
+
+    let mut marker = [0; 4];
+    suf.read_exact(&mut marker).map_err(|source| ReadError::Io{source})?;
+    if &marker != SUFFIX_MARKER {
+      return Err(malformed("the suffix file has no KMCS marker"));
+    }
+    let mut suf = io::BufReader::new(suf);
+    let mut record = vec![0; header.suffix_size() + header.counter_size];
+    let (mut ids, mut kmers) = (HashMap::new(), Vec::new());
+    let mut index = 0;
+    for (i, w) in starts.windows(2).enumerate() {
+      let prefix = i % prefixes;
+      for _ in w[0]..w[1] {
+        suf.read_exact(&mut record).map_err(|source| match source.kind() {
+          io::ErrorKind::UnexpectedEof => malformed("the suffix file has fewer k-mers than the prefix file"),
+          _ => ReadError::Io{source}
+        })?;
+        index += 1;
+        // Two bits per base, the first one in the highest bits
+        let prefix = (0..header.lut_prefix_length).rev().map(|j| prefix >> (2 * j) & 3);
+        let suffix = record[..header.suffix_size()].iter().flat_map(|&b| [b >> 6, b >> 4 & 3, b >> 2 & 3, b & 3].map(usize::from));
+        let kmer: String = prefix.chain(suffix).map(|code| ['A', 'C', 'G', 'T'][code]).collect();
+        let kmer = Unitig::try_from(kmer).unwrap().norm();
+        let count = match header.counter_size {
+          0 => 1,
+          _ => u32::try_from(number(&record[header.suffix_size()..])).unwrap()
+        };
+        match ids.get(&kmer) {
+          Some(&id) => {
+            let entry: &mut (Unitig, usize, u32) = &mut kmers[id];
+            entry.2 = entry.2.saturating_add(count);
+          },
+          None => {
+            ids.insert(kmer.clone(), kmers.len());
+            kmers.push((kmer, index, count));
+          }
+        }
+      }
+    }
+    let mut rest = Vec::new();
+    suf.read_to_end(&mut rest).map_err(|source| ReadError::Io{source})?;
+    if rest != SUFFIX_MARKER {
+      return Err(malformed("the suffix file has more k-mers than the prefix file, or no final KMCS marker"));
+    }
+
+    let bytes = (data.len() + 8 + record.len() * index) as u64;
+    let records = match index {
+      0 => Vec::new(),
+      _ => kmers::records(k, &ids, kmers)
+    };
+    Ok(KmcReader{records: records.into_iter(), bytes})
+  }
+}
+
+impl Iterator for KmcReader {
+  type Item = Result<Record, ReadError>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    self.records.next().map(Ok)
+  }
+}
+
+impl RecordSource for KmcReader {
+  fn bytes(&self) -> u64 {
+    self.bytes
+  }
+}
+
+/// Writes a database of the given k-mers and counts as KMC does, in a single bin
+#[cfg(test)]
+pub(super) fn write(k: usize, lut_prefix_length: usize, kmers: &[(&str, u32)]) -> (Vec<u8>, Vec<u8>) {
+  let code = |b: u8| b"ACGT".iter().position(|&c| c == b).unwrap();
+  let mut sorted: Vec<_> = kmers.to_vec();
+  sorted.sort();
+  let (mut pre, mut suf) = (PREFIX_MARKER.to_vec(), SUFFIX_MARKER.to_vec());
+  let prefixes = 1 << (2 * lut_prefix_length);
+  let mut start = 0u64;
+  for prefix in 0..prefixes {
+    pre.extend(start.to_le_bytes());
+    start += sorted.iter().filter(|(kmer, _)| kmer.bytes().take(lut_prefix_length).fold(0, |p, b| p << 2 | code(b)) == prefix).count() as u64;
+  }
+  pre.extend(start.to_le_bytes());
+  for (kmer, count) in &sorted {
+    for bases in kmer.as_bytes()[lut_prefix_length..].chunks(4) {
+      suf.push(bases.iter().fold(0, |byte, &b| byte << 2 | code(b) as u8));
+    }
+    suf.extend(&count.to_le_bytes()[..1]);
+  }
+  suf.extend(SUFFIX_MARKER);
+  // A signature map of length 1 for each base, all in bin 0
+  pre.extend([0; 5 * 4]);
+  let mut header = Vec::new();
+  for field in [k, 0, 1, lut_prefix_length, 1, 1, 255] {
+    header.extend((field as u32).to_le_bytes());
+  }
+  header.extend((kmers.len() as u64).to_le_bytes());
+  header.push(0);
+  header.resize(64, 0);
+  header.extend(VERSION.to_le_bytes());
+  pre.extend(&header);
+  pre.extend((header.len() as u32).to_le_bytes());
+  pre.extend(PREFIX_MARKER);
+  (pre, suf)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn databases_are_read() {
+    let (pre, suf) = write(5, 1, &[("AACCG", 3), ("ACCGT", 2), ("CGGTT", 1)]);
+    let records: Vec<_> = KmcReader::new(pre.as_slice(), suf.as_slice()).unwrap().map(Result::unwrap).collect();
+    // CGGTT is the reverse complement of AACCG
+    let summary: Vec<_> = records.iter().map(|r| (r.sequence.as_str(), r.counts[0], r.line, r.links.len())).collect();
+    assert_eq!(summary, [("AACCG", 4, 1, 1), ("ACCGT", 2, 2, 1)]);
+  }
+
+  #[test]
+  fn malformed_databases_are_refused() {
+    let (pre, suf) = write(5, 1, &[("AACCG", 3), ("ACCGT", 2)]);
+    let error = |pre: &[u8], suf: &[u8]| KmcReader::new(pre, suf).err().unwrap().to_string();
+    assert!(error(&pre[..pre.len()-1], &suf).contains("no KMCP markers"));
+    assert!(error(&pre, &suf[..suf.len()-5]).contains("fewer k-mers"));
+    assert!(error(&pre, &[&suf[..suf.len()-4], &[0], SUFFIX_MARKER].concat()).contains("more k-mers"));
+    let mut old = pre.clone();
+    let version = old.len() - 12;
+    old[version..version+4].copy_from_slice(&0u32.to_le_bytes());
+    assert!(error(&old, &suf).contains("version 0x0"));
+  }
+}
@@ -0,0 +1,145 @@
+//! Graphs of counted k-mers, each one a record: from reads, or from the k-mer count tables written by counters such as KMC
+
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::io::BufRead;
+use super::Orientation;
+use super::reader::{Link, ReadError, Record, RecordSource};
+use super::unitig::Unitig;
+
+/// Records of the given canonical k-mers, each one with the line where it was found and its count,
+/// linked to the k-mers overlapping it by k-1 bases. `ids` gives the position of each k-mer in the list.
+pub fn records(k: usize, ids: &HashMap<Unitig, usize>, kmers: Vec<(Unitig, usize, u32)>) -> Vec<Record> {
+  kmers.iter().enumerate().map(|(id, (kmer, line, count))| {
+    let mut links = Vec::new();
+    for from_dir in [Orientation::Forward, Orientation::Reverse] {
+      let kmer = match from_dir {
+        Orientation::Forward => kmer.clone(),
+        Orientation::Reverse => kmer.rev_compl()
+      };
+      let overlap = kmer.slice(1..k).to_string();
+      for base in ['A', 'C', 'G', 'T'] {
+        let next = Unitig::try_from(format!("{}{}", overlap, base)).unwrap();
+        if let Some(&to) = ids.get(&next) {
+          let to_dir = if next.is_identical(&kmers[to].0) {Orientation::Forward} else {Orientation::Reverse};
+          links.push(Link{from_dir, to, to_dir});
+        }
+      }
+    }
+    Record{id, line: *line, sequence: kmer.to_string(), counts: vec![*count], links, tags: Vec::new()}
+  }).collect()
+}
+
+/// Reads a k-mer count table, with a k-mer and its count on each line separated by whitespace, as written by
+/// `kmc_tools transform DB dump` or `jellyfish dump -c`, or with each k-mer on the line after a `>count` header,
+/// as written by `jellyfish dump`. Each k-mer is a record, linked to the k-mers overlapping it;
+/// a k-mer given on both strands has the sum of its counts. A first row naming the columns, as in TSV files, is skipped. k is the length of the k-mers, which must all agree.
+/// The whole file is read at once, since the links are only known once all k-mers are.
+///
+/// # Example
+/// ```
+/// use closed_unitigs::KmerTableReader;
+///
+/// let input = "AACCG\t3\nACCGT\t2\n";
+/// let records: Vec<_> = KmerTableReader::new(input.as_bytes()).unwrap().map(Result::unwrap).collect();
+/// assert_eq!((records[0].counts.as_slice(), records[0].links.len()), (&[3][..], 1));
+/// ```
+pub struct KmerTableReader {
+  records: std::vec::IntoIter<Record>,
+  bytes: u64
+}
+
+impl KmerTableReader {
+  /// Reads the whole input
+  pub fn new<R: BufRead>(input: R) -> Result<KmerTableReader, ReadError> {
+    let (mut ids, mut kmers, mut bytes, mut k) = (HashMap::new(), Vec::new(), 0, None);
+    // Count given by the last FASTA header, for the k-mer on the next line
+    let mut header: Option<(usize, String)> = None;
+    for (index, line) in input.lines().enumerate() {
+      let line = line.map_err(|source| ReadError::Io{source})?;
+      bytes += line.len() as u64 + 1;
+      let syntax = || ReadError::Syntax{line: index + 1, content: line.clone()};
+      let fields: Vec<_> = line.split_whitespace().collect();
+      let (kmer, count) = match (&header, &fields[..]) {
+        (None, []) => continue,
+        (None, [header_count]) if header_count.starts_with('>') => {
+          header = Some((index + 1, line.clone()));
+          continue;
+        },
+        // A header row naming the columns, as written by scripts
+        (None, [kmer, count]) if index == 0 && count.parse::<u32>().is_err() && !kmer.chars().all(|c| "ACGTacgt".contains(c)) => continue,
+        (None, [kmer, count]) => (*kmer, *count),
+        (Some((_, header)), [kmer]) => (*kmer, &header[1..]),
+        _ => return Err(syntax())
+      };
+      if *k.get_or_insert(kmer.len()) != kmer.len() {
+        return Err(syntax());
+      }
+      let kmer = Unitig::try_from(kmer.to_ascii_uppercase()).map_err(|_| syntax())?.norm();
+      let count: u32 = count.parse().map_err(|_| ReadError::WrongCount{line: index + 1, count: String::from(count)})?;
+      match ids.get(&kmer) {
+        Some(&id) => {
+          let entry: &mut (Unitig, usize, u32) = &mut kmers[id];
+          entry.2 = entry.2.saturating_add(count);
+        },
+        None => {
+          ids.insert(kmer.clone(), kmers.len());
+          kmers.push((kmer, index + 1, count));
+        }
+      }
+      header = None;
+    }
+    if let Some((line, content)) = header {
+      return Err(ReadError::Syntax{line, content});
+    }
+    let records = match k {
+      Some(k) => records(k, &ids, kmers),
+      None => Vec::new()
+    };
+    Ok(KmerTableReader{records: records.into_iter(), bytes})
+  }
+}
+
+impl Iterator for KmerTableReader {
+  type Item = Result<Record, ReadError>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    self.records.next().map(Ok)
+  }
+}
+
+impl RecordSource for KmerTableReader {
+  fn bytes(&self) -> u64 {
+    self.bytes
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn kmer_tables_are_read() {
+    let input = "kmer\tcount\nAACCG 2\nacggt\t4\n\nCGGTT 1\n";
+    let records: Vec<_> = KmerTableReader::new(input.as_bytes()).unwrap().map(Result::unwrap).collect();
+    // CGGTT is the reverse complement of AACCG
+    let summary: Vec<_> = records.iter().map(|r| (r.sequence.as_str(), r.counts[0], r.line, r.links.len())).collect();
+    assert_eq!(summary, [("AACCG", 3, 2, 1), ("ACCGT", 4, 3, 1)]);
+
+    let error = |input: &str| KmerTableReader::new(input.as_bytes()).err().unwrap().to_string();
+    assert_eq!(error("AACCG 2\nAACC 1\n"), "Syntax error at line 2: \"AACC 1\"");
+    assert_eq!(error("AACCG x\n"), "Wrong count \"x\" at line 1");
+    assert_eq!(error("AACCG 2\nkmer count\n"), "Syntax error at line 2: \"kmer count\"");
+    assert_eq!(error("AACCG\n"), "Syntax error at line 1: \"AACCG\"");
+    assert_eq!(error(">2\nAACCG\n>1\n"), "Syntax error at line 3: \">1\"");
+    assert_eq!(error(">2\n>1\nAACCG\n"), "Syntax error at line 2: \">1\"");
+  }
+
+  #[test]
+  fn jellyfish_fasta_dumps_are_read() {
+    let input = ">3\nAACCG\n>4\nACCGT\n";
+    let records: Vec<_> = KmerTableReader::new(input.as_bytes()).unwrap().map(Result::unwrap).collect();
+    let summary: Vec<_> = records.iter().map(|r| (r.sequence.as_str(), r.counts[0], r.line, r.links.len())).collect();
+    assert_eq!(summary, [("AACCG", 3, 2, 1), ("ACCGT", 4, 4, 1)]);
+  }
+}
@@ -0,0 +1,32 @@
+//! Generation of the closed unitigs of a de Bruijn graph written by BCALM.
+//!
+//! A [`Graph`] is read from a BCALM or GFA1 file, or built with a [`GraphBuilder`], and [`Graph::close`] writes its
+//! closed unitigs to a [`Sink`], such as a [`FastaSink`].
+//!
+//! # Example
+//! ```
+//! use closed_unitigs::{CloseOptions, FastaSink, Graph};
+//!
+//! let input = ">0 LN:i:6 ab:Z:5 5 L:+:1:+\nAACCGT\n>1 LN:i:6 ab:Z:5 4 L:-:0:-\nCCGTTA\n";
+//! let graph = Graph::parse_indexed(input.as_bytes(), &Default::default(), None).unwrap();
+//! let (mut fasta, mut counts) = (Vec::new(), Vec::new());
+//! graph.close(&mut FastaSink::new(&mut fasta, &mut counts), &CloseOptions::default()).unwrap();
+//! assert!(String::from_utf8(fasta).unwrap().lines().any(|l| l == "AACCGTT"));
+//! ```
+
+pub mod convert;
+pub mod format;
+pub mod graph;
+pub mod io;
+pub mod sink;
+#[cfg(any(test, feature = "testing"))]
+pub mod synthetic;
+pub mod term;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+
+pub use graph::{
+  BcalmReader, CloseOptions, CloseSummary, FastqReader, GfaReader, Graph, GraphBuilder, GraphError, IndexError, KmcReader, KmerTableReader, LoadError, Orientation,
+  ParallelBcalmReader, ParseError, ParseOptions, ReadError, Unitig, UnitigError
+};
+pub use sink::{FastaSink, Sink};
@@ -1,19 +1,625 @@
-mod graph;
+mod cli;
+mod diff;
+mod error;
+mod manifest;
+mod validate;
+mod verify;
 
+use closed_unitigs::{convert, graph, io, sink, term};
+
+use std::convert::TryFrom;
 use std::env;
 use std::fs::File;
-use std::io::{BufReader, BufWriter};
+use std::io::{BufRead, BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+use std::panic;
+use std::process;
+use std::time::Duration;
+use clap::Parser;
+use cli::Command;
+use error::Error;
+use serde::Serialize;
+use xxhash_rust::xxh3::xxh3_64;
+
+/// Options about the output files
+#[derive(Debug, Default, Serialize)]
+struct OutputOptions {
+  shards: Option<usize>,
+  split_by_support: Option<PathBuf>,
+  support_cap: Option<u32>,
+  split_by_component: Option<PathBuf>,
+  min_component: Option<usize>,
+  output_prefix: Option<PathBuf>,
+  compress: Option<io::Compress>,
+  wrap: Option<usize>,
+  fasta_out: Option<PathBuf>,
+  counts_out: Option<PathBuf>,
+  profiles: Option<PathBuf>,
+  profiles_out: Option<PathBuf>,
+  provenance: Option<PathBuf>,
+  membership_out: Option<PathBuf>,
+  gfa_out: Option<PathBuf>,
+  gfa_version: sink::GfaVersion,
+  strict_empty: bool,
+  json_report: Option<PathBuf>,
+  support_summary: Option<PathBuf>,
+  spectrum: Option<PathBuf>,
+  unrepresented: Option<PathBuf>,
+  no_manifest: bool
+}
+
+/// All the options of a run, as recorded in its manifest
+#[derive(Debug, Serialize)]
+struct RunOptions<'a> {
+  format: convert::Format,
+  parse: &'a graph::ParseOptions,
+  /// K-mers with lower counts are dropped
+  min_count: Option<graph::MinCount>,
+  /// Tips with lower counts are removed
+  clip_tips: Option<u32>,
+  /// The weaker branches of simple bubbles are removed
+  pop_bubbles: bool,
+  /// Largest fraction of the count mass removed by a suggested minimum count
+  max_removed_mass: f64,
+  /// Fraction of the connected components kept
+  sample_components: Option<f64>,
+  /// Connected components kept, all if empty
+  components: &'a [usize],
+  close: &'a graph::CloseOptions,
+  output: &'a OutputOptions
+}
+
+/// Builds the path of an output file replacing the extension of the input, and the compression one if any, with the given suffix
+fn output_path(input: &Path, suffix: &str) -> PathBuf {
+  let input = if io::is_compressed(input) {input.with_extension("")} else {input.to_path_buf()};
+  let mut name = input.file_stem().unwrap_or_default().to_os_string();
+  name.push(suffix);
+  input.with_file_name(name)
+}
+
+/// Path of the k-mer index of an input
+fn index_path(input: &Path) -> PathBuf {
+  let mut path = input.as_os_str().to_os_string();
+  path.push(".cuidx");
+  PathBuf::from(path)
+}
+
+/// Identifies the input content and the parse options which change the nodes of the graph
+fn index_key(input: &Path, options: &graph::ParseOptions) -> Result<graph::IndexKey, Error> {
+  let hash = manifest::Input::read(input).map_err(Error::io(input))?.hash;
+  let nodes = (options.head, options.merge_duplicates, options.collapse_rc, options.break_char, options.k);
+  Ok(graph::IndexKey{input: u64::from_str_radix(&hash, 16).unwrap(), options: xxh3_64(serde_json::to_string(&nodes).unwrap().as_bytes())})
+}
+
+/// Opens a file for buffered reading, decompressing it if needed
+fn open(path: &Path) -> Result<io::Decoder<BufReader<File>>, Error> {
+  io::open(path).map_err(Error::io(path))
+}
+
+/// Reads a graph, looking k-mers up in the index file next to the input if it matches the input and the options.
+/// The index is written if asked, or if it was found but could not be used. KMC databases are read without an index.
+fn read_graph(input: &Path, format: convert::Format, options: &graph::ParseOptions, write_index: bool) -> Result<graph::Graph, Error> {
+  if format == convert::Format::Kmc {
+    return convert::read_kmc(input, options).map_err(|source| Error::Parse{path: input.to_path_buf(), source});
+  }
+  let path = index_path(input);
+  let found = path.exists();
+  let key = (write_index || found).then(|| index_key(input, options)).transpose()?;
+  let index = key.filter(|_| found).and_then(|key| graph::SortedIndex::load(&path, key)
+    .map_err(|e| log::warn!("{}: {}, rebuilding it", path.display(), e)).ok());
+  let graph = convert::read_indexed(format, open(input)?, options, index).map_err(|source| Error::Parse{path: input.to_path_buf(), source})?;
+  if let Some(key) = key.filter(|_| !graph.has_sorted_index()) {
+    match graph.sorted_index().and_then(|index| index.write(&path, key)) {
+      Ok(()) => log::info!("K-mer index written to {}", path.display()),
+      Err(e) => log::warn!("cannot write {}: {}", path.display(), e)
+    }
+  }
+  Ok(graph)
+}
 
 fn main() {
+  // Panics are bugs: their message is kept on one line, prefixed as the other errors
+  panic::set_hook(Box::new(|info| eprintln!("error[internal]: {}", info.to_string().replace('\n', " "))));
+  match panic::catch_unwind(run) {
+    Ok(Ok(())) => (),
+    Ok(Err(e)) => {
+      term::clear_progress();
+      eprintln!("error[{}]: {}", e.class(), e);
+      process::exit(e.code());
+    },
+    Err(_) => process::exit(error::INTERNAL)
+  }
+}
+
+/// Runs the subcommand given on the command line
+fn run() -> Result<(), Error> {
+  let cli = match cli::Cli::try_parse_from(cli::args(env::args_os())) {
+    Ok(cli) => cli,
+    // Help and version are not failures
+    Err(e) if !e.use_stderr() => {
+      e.print().unwrap();
+      return Ok(());
+    },
+    Err(e) if e.kind() == clap::error::ErrorKind::DisplayHelpOnMissingArgumentOrSubcommand => {
+      e.print().unwrap();
+      return Err(Error::usage("missing arguments"));
+    },
+    // On one line, without the usage and the pointer to the help which follow
+    Err(e) => {
+      let message = e.to_string();
+      let message: Vec<_> = message.lines().take_while(|l| !l.is_empty()).map(str::trim).collect();
+      let message = message.join(" ");
+      return Err(Error::usage(message.strip_prefix("error: ").unwrap_or(&message)));
+    }
+  };
+  term::init_log(cli.log_level());
+
+  match cli.command {
+    Command::Close(args) => close(args),
+    Command::Verify{graph, fasta, counts} => {
+      let graph = read_graph(&graph, convert::Format::of(&graph), &graph::ParseOptions::default(), false)?;
+      match verify::verify(&graph, &fasta, counts.as_deref())? {
+        0 => Ok(()),
+        invalid => Err(Error::Invalid{message: format!("{} invalid records", invalid)})
+      }
+    },
+    Command::Validate{serial, threads, break_char, input} => {
+      if let Some(threads) = threads {
+        rayon::ThreadPoolBuilder::new().num_threads(threads).build_global()
+          .map_err(|e| Error::usage(format!("cannot start the threads: {}", e)))?;
+      }
+      let (records, issues) = validate::validate(open(&input)?, !serial, break_char);
+      for issue in &issues {
+        println!("{}", issue);
+      }
+      println!("{} records checked, {} issues", records, issues.len());
+      match issues.len() {
+        0 => Ok(()),
+        n => Err(Error::Invalid{message: format!("{}: {} issues", input.display(), n)})
+      }
+    },
+    Command::Stats{max_degree, input} => {
+      let graph = read_graph(&input, convert::Format::of(&input), &graph::ParseOptions::default(), false)?;
+      let degrees = graph.degrees();
+      let mut histogram = std::collections::BTreeMap::new();
+      for &d in &degrees {
+        *histogram.entry(d).or_insert(0usize) += 1;
+      }
+      let (records, bases) = graph.records();
+      println!("k\t{}", graph.k());
+      println!("records\t{}", records);
+      println!("bases\t{}", bases);
+      println!("k-mers\t{}", degrees.len());
+      println!("edges\t{}", graph.edges());
+      let spectrum = graph.spectrum();
+      println!("total_count\t{}", spectrum.kmers.iter().map(|(&c, &n)| c as u64 * n as u64).sum::<u64>());
+      for (name, fraction) in [("min", 0.), ("q25", 0.25), ("median", 0.5), ("q75", 0.75), ("q99", 0.99), ("max", 1.)] {
+        println!("count_{}\t{}", name, spectrum.quantile(fraction).unwrap_or(0));
+      }
+      println!("max_degree\t{}", degrees.iter().max().unwrap_or(&0));
+      if let Some(max) = max_degree {
+        println!("above_max_degree\t{}", degrees.iter().filter(|&&d| d > max).count());
+      }
+      println!("degree\tk-mers");
+      for (d, n) in histogram {
+        println!("{}\t{}", d, n);
+      }
+      Ok(())
+    },
+    Command::Components{tsv, input} => {
+      let graph = read_graph(&input, convert::Format::of(&input), &graph::ParseOptions::default(), false)?;
+      let components = graph.components();
+      let sizes = components.sizes();
+      println!("components\t{}", components.len());
+      println!("k-mers\t{}", sizes.iter().sum::<usize>());
+      println!("largest\t{}", sizes.first().unwrap_or(&0));
+      println!("median\t{}", sizes.get(sizes.len().saturating_sub(1) / 2).unwrap_or(&0));
+      println!("singletons\t{}", sizes.iter().filter(|&&n| n == 1).count());
+      if let Some(path) = &tsv {
+        sink::write_components(path, &components).map_err(Error::io(path))?;
+      }
+      Ok(())
+    },
+    Command::Cycles{input} => {
+      let graph = read_graph(&input, convert::Format::of(&input), &graph::ParseOptions::default(), false)?;
+      let cycles = graph.cycles();
+      println!("cycles\t{}", cycles.len());
+      println!("k-mers\t{}", cycles.iter().map(|c| c.nodes.len()).sum::<usize>());
+      println!("cycle\tkmers\trecords");
+      for (i, cycle) in cycles.iter().enumerate() {
+        let records: Vec<_> = cycle.records.iter().map(usize::to_string).collect();
+        println!("{}\t{}\t{}", i, cycle.nodes.len(), records.join(","));
+      }
+      Ok(())
+    },
+    Command::Query{graph, kmers} => {
+      let graph = read_graph(&graph, convert::Format::of(&graph), &graph::ParseOptions::default(), false)?;
+      let mut unknown = 0;
+      println!("kmer\tsequence\tsupport\tlen");
+      for kmer in kmers {
+        match graph.close_seed(&kmer) {
+          Ok(u) => println!("{}\t{}\t{}\t{}", kmer, u.sequence, u.support, u.len),
+          Err(e) => {
+            log::warn!("{}", e);
+            unknown += 1;
+          }
+        }
+      }
+      match unknown {
+        0 => Ok(()),
+        n => Err(Error::Invalid{message: format!("{} k-mers could not be closed", n)})
+      }
+    },
+    Command::Convert{from, to, k, input, output} => {
+      let (from, to) = (from.unwrap_or_else(|| convert::Format::of(&input)), to.unwrap_or_else(|| convert::Format::of(&output)));
+      match to {
+        convert::Format::Fastq => return Err(Error::usage("graphs cannot be written as FASTQ")),
+        convert::Format::Kmers => return Err(Error::usage("graphs cannot be written as k-mer tables")),
+        convert::Format::Kmc => return Err(Error::usage("graphs cannot be written as KMC databases")),
+        _ => ()
+      }
+      let options = graph::ParseOptions{k, ..Default::default()};
+      let graph = match from {
+        convert::Format::Kmc => convert::read_kmc(&input, &options),
+        _ => convert::read_indexed(from, open(&input)?, &options, None)
+      }.map_err(|source| Error::Parse{path: input, source})?;
+      convert::write(&graph, to, &output).map_err(Error::io(&output))
+    },
+    Command::Export{format} => {
+      let (cli::Export::Dot(args) | cli::Export::Graphml(args)) = &format;
+      let cli::ExportArgs{component, around, radius, input, output} = args;
+      let graph = read_graph(input, convert::Format::of(input), &graph::ParseOptions::default(), false)?;
+      let keep = match (*component, around) {
+        (Some(c), _) => {
+          let components = graph.components();
+          if c >= components.len() {
+            return Err(Error::usage(format!("the graph has {} connected components, there is no component {}", components.len(), c)));
+          }
+          Some(components.of_node.iter().map(|&of| of == c).collect())
+        },
+        (None, Some(kmer)) => {
+          let unitig = graph::Unitig::try_from(kmer.as_str()).map_err(|e| Error::usage(format!("wrong k-mer \"{}\": {}", kmer, e)))?;
+          Some(graph.neighborhood(&unitig, radius.unwrap_or(3)).ok_or_else(|| Error::Invalid{message: format!("k-mer {} is not in the graph", kmer)})?)
+        },
+        (None, None) => None
+      };
+      let write = |out: &mut dyn std::io::Write| match format {
+        cli::Export::Dot(_) => graph.write_dot(out, keep.as_deref()),
+        cli::Export::Graphml(_) => graph.write_graphml(out, keep.as_deref())
+      }.and_then(|()| out.flush());
+      match output.as_ref().filter(|path| path.as_os_str() != "-") {
+        Some(path) => write(&mut BufWriter::new(File::create(path).map_err(Error::io(path))?)).map_err(Error::io(path)),
+        None => write(&mut BufWriter::new(std::io::stdout().lock())).map_err(Error::io(Path::new("-")))
+      }
+    },
+    Command::Diff{tsv, a, b} => {
+      // The counts are next to the FASTA files, compressed the same way
+      let counts = |fasta: &Path| match io::Compress::of(fasta) {
+        Some(compress) => io::with_suffix(&fasta.with_extension("").with_extension("counts"), io::Compress::extension(Some(compress))),
+        None => fasta.with_extension("counts")
+      };
+      let output = |fasta: &Path| Ok::<_, Error>((open(fasta)?, open(&counts(fasta))?));
+      let mut out = tsv.as_ref().map(|path| File::create(path).map(BufWriter::new).map_err(Error::io(path))).transpose()?;
+      let context = format!("{} and {}", a.display(), b.display());
+      let diff = diff::compare(output(&a)?, output(&b)?, out.as_mut().map(|t| t as &mut dyn std::io::Write))
+        .map_err(|source| Error::Io{context, source})?;
+      if let (Some(mut out), Some(path)) = (out, &tsv) {
+        std::io::Write::flush(&mut out).map_err(Error::io(path))?;
+      }
+      println!("{}", diff);
+      // Different runs are not an error, but exit with a code of their own
+      if !diff.is_same() {
+        process::exit(error::DIFFERENT);
+      }
+      Ok(())
+    }
+  }
+}
+
+/// Closes the unitigs of the input, writing the outputs asked
+fn close(args: Box<cli::CloseArgs>) -> Result<(), Error> {
+  let parse_options = graph::ParseOptions{
+    head: args.head,
+    merge_duplicates: args.merge_duplicates,
+    collapse_rc: args.collapse_counts.or(args.collapse_rc.then(Default::default)),
+    lenient: args.lenient,
+    break_char: args.break_char,
+    k: args.k,
+    parallel: args.threads != Some(1)
+  };
+  if let Some(threads) = args.threads.filter(|&threads| threads > 1) {
+    rayon::ThreadPoolBuilder::new().num_threads(threads).build_global()
+      .map_err(|e| Error::usage(format!("cannot start the threads: {}", e)))?;
+  }
+  if parse_options.k == Some(0) {
+    return Err(Error::usage("--k must be at least 1"));
+  }
+  let defaults = graph::CloseOptions::default();
+  let closure_timeout = args.closure_timeout.map(Duration::try_from_secs_f64).transpose()
+    .map_err(|e| Error::usage(format!("invalid value for --closure-timeout: {}", e)))?;
+  let checkpoint_every = args.checkpoint_every.map(Duration::try_from_secs_f64).transpose()
+    .map_err(|e| Error::usage(format!("invalid value for --checkpoint-every: {}", e)))?;
+  let mut options = graph::CloseOptions{
+    sort: args.sort.unwrap_or(defaults.sort),
+    seed_order: args.seed_order.unwrap_or(defaults.seed_order),
+    rng_seed: args.rng_seed.unwrap_or(defaults.rng_seed),
+    shrink: graph::ShrinkPolicy{threshold_delta: args.shrink_delta.unwrap_or(defaults.shrink.threshold_delta), max_trim: args.max_trim},
+    max_count_ratio: args.max_count_ratio,
+    extension: args.extension.unwrap_or(defaults.extension),
+    support: args.support.unwrap_or(defaults.support),
+    support_floor: args.support_floor,
+    drop_below_floor: args.drop_below_floor,
+    max_memory: args.max_memory.map(|gb| (gb * 1e9) as usize),
+    max_degree: args.max_degree,
+    degree_policy: args.degree_policy.unwrap_or(defaults.degree_policy),
+    list_unrepresented: args.unrepresented.is_some(),
+    allow_gaps: args.allow_gaps,
+    shrink_metadata: args.shrink_metadata,
+    kmer_counts: args.abundance_tags,
+    closure_timeout,
+    max_length: args.max_length,
+    max_steps: args.max_steps,
+    checkpoint: None,
+    cancel: defaults.cancel
+  };
+  let output = OutputOptions{
+    shards: args.shards,
+    split_by_support: args.split_by_support,
+    support_cap: args.support_cap,
+    split_by_component: args.split_by_component,
+    min_component: args.min_component,
+    output_prefix: args.output_prefix,
+    compress: args.compress,
+    wrap: args.wrap.filter(|&width| width > 0),
+    fasta_out: args.fasta_out,
+    counts_out: args.counts_out,
+    profiles: args.profiles,
+    profiles_out: args.profiles_out,
+    provenance: args.provenance,
+    membership_out: args.membership_out,
+    gfa_out: args.gfa_out,
+    gfa_version: args.gfa_version,
+    strict_empty: args.strict_empty,
+    json_report: args.json_report,
+    support_summary: args.support_summary,
+    spectrum: args.spectrum,
+    unrepresented: args.unrepresented,
+    no_manifest: args.no_manifest
+  };
+  let (write_index, sample_components, min_count) = (args.write_index, args.sample_components, args.min_count);
+  let (clip_tips, pop_bubbles, components) = (args.clip_tips, args.pop_bubbles, args.components);
+  let resume = args.resume;
+  let max_removed_mass = args.max_removed_mass.unwrap_or(graph::MAX_REMOVED_MASS);
+  if args.no_progress {
+    term::disable_progress();
+  }
+  if args.progress_json {
+    term::json_progress(Box::new(std::io::stderr()));
+  }
+  if let Some(path) = &args.progress_file {
+    term::json_progress(Box::new(File::create(path).map_err(Error::io(path))?));
+  }
 
-  let input_file = &env::args().collect::<Vec<String>>()[1];
-  let output_fasta = input_file.clone() + ".clo.fa";
-  let output_counts = input_file.clone() + ".clo.counts";
+  let input_file = args.input;
+  let format = args.format.unwrap_or_else(|| convert::Format::of(&input_file));
+  // A saved graph is loaded as it was parsed
+  let parsing = [(parse_options.head.is_some(), "--head"), (parse_options.merge_duplicates, "--merge-duplicates"),
+    (parse_options.collapse_rc.is_some(), "--collapse-rc"), (parse_options.break_char.is_some(), "--break-char"),
+    (parse_options.lenient, "--lenient"), (parse_options.k.is_some(), "--k")];
+  if let Some((_, flag)) = parsing.iter().find(|(given, _)| *given).filter(|_| format == convert::Format::Binary) {
+    return Err(Error::usage(format!("{} cannot be used with a saved graph, which is loaded as parsed", flag)));
+  }
+  let stdin = input_file.as_os_str() == "-";
+  if stdin && write_index {
+    return Err(Error::usage("--write-index needs an input file"));
+  }
+  if format == convert::Format::Kmc && (stdin || write_index) {
+    return Err(Error::usage(match stdin {
+      true => "a KMC database is read from its files, not from the standard input",
+      false => "--write-index cannot be used with a KMC database, which is read without an index"
+    }));
+  }
+  if sample_components.is_some_and(|fraction| !(0. ..=1.).contains(&fraction)) {
+    return Err(Error::usage("the fraction of --sample-components must be between 0 and 1"));
+  }
+  // Partial results get a distinct name
+  let suffix = match parse_options.head {
+    Some(n) => format!(".head{}.clo", n),
+    None => String::from(".clo")
+  };
+  // Reading from a pipe, the closed unitigs go on by default and the other outputs are named after stdin
+  let prefix = output.output_prefix.clone().unwrap_or_else(|| match stdin {
+    true => PathBuf::from("stdin"),
+    false => output_path(&input_file, "")
+  });
+  let output_path = |suffix: &str| io::with_suffix(&prefix, suffix);
+  // The state saved at the checkpoints, or when aborting for lack of memory, is named after the outputs
+  let state_path = output_path(&(suffix.clone() + ".resume.json"));
+  options.checkpoint = (checkpoint_every.is_some() || options.max_memory.is_some() || resume)
+    .then(|| graph::Checkpointing{path: state_path.clone(), every: checkpoint_every});
+  // FASTA files appear only once complete
+  let fasta = io::FastaOptions{width: output.wrap, compress: output.compress, atomic: true, ..Default::default()};
+  let output_fasta = output.fasta_out.clone().unwrap_or_else(|| match stdin && output.output_prefix.is_none() {
+    true => PathBuf::from("-"),
+    false => output_path(&(suffix.clone() + &fasta.extension()))
+  });
+  let output_counts = output.counts_out.clone().unwrap_or_else(|| output_path(&(suffix.clone() + &fasta.counts_extension())));
+  let (fasta_stdout, counts_stdout) = (output_fasta.as_os_str() == "-", output_counts.as_os_str() == "-");
+  if fasta_stdout && counts_stdout {
+    return Err(Error::usage("only one of the closed unitigs and their counts can be written to the standard output"));
+  }
 
   // Read BCALM FASTA file and generate graph
-  let graph = graph::Graph::from(BufReader::new(File::open(input_file).unwrap()));
-  // Close unitigs and write output files
-  graph.close(&mut BufWriter::new(File::create(&output_fasta).unwrap()), &mut BufWriter::new(File::create(&output_counts).unwrap()));
+  let started = manifest::now();
+  let (mut graph, input) = match stdin {
+    true => {
+      let mut buf = io::Decoder::new(BufReader::new(manifest::Hashing::new(std::io::stdin()))).map_err(Error::io(&input_file))?;
+      let graph = convert::read_indexed(format, &mut buf, &parse_options, None).map_err(|source| Error::Parse{path: input_file.clone(), source})?;
+      (graph, Some(buf.into_inner().into_inner().finish(&input_file)))
+    },
+    false => (read_graph(&input_file, format, &parse_options, write_index)?, None)
+  };
+  let run_options = RunOptions{format, parse: &parse_options, min_count, clip_tips, pop_bubbles, max_removed_mass, sample_components, components: &components, close: &options, output: &output};
+  let mut manifest = match output.no_manifest {
+    true => None,
+    false => Some(manifest::Manifest::new(input.map_or_else(|| manifest::Input::read(&input_file), Ok).map_err(Error::io(&input_file))?, &run_options, started))
+  };
+  let mut comments = manifest.as_ref().map(|m| m.comments()).unwrap_or_default();
+  if output.spectrum.is_some() || min_count.is_some() {
+    let spectrum = graph.spectrum();
+    if let Some(path) = &output.spectrum {
+      sink::write_spectrum(path, &spectrum, &comments).map_err(Error::io(path))?;
+    }
+    let suggested = spectrum.suggest_min_count(max_removed_mass);
+    log::info!("suggested --min-count: {}", suggested);
+    let min_count = match min_count {
+      Some(graph::MinCount::Fixed(n)) => n,
+      Some(graph::MinCount::Auto) => suggested,
+      None => 0
+    };
+    if min_count > 1 {
+      let kmers: usize = spectrum.kmers.values().sum();
+      let dropped = graph.drop_below(min_count);
+      log::info!("Dropped {} of {} k-mers with count below {}", dropped, kmers, min_count);
+      comments += &format!("; min count: {} ({} of {} k-mers dropped)\n", min_count, dropped, kmers);
+    }
+  }
+  if let Some(max_count) = clip_tips {
+    let (tips, clipped) = graph.clip_tips(max_count);
+    log::info!("Clipped {} tips with count below {}, of {} k-mers", tips, max_count, clipped);
+    comments += &format!("; tips clipped: {} with count below {} ({} k-mers)\n", tips, max_count, clipped);
+  }
+  if pop_bubbles {
+    let (bubbles, removed) = graph.pop_bubbles();
+    log::info!("Popped {} bubbles, removing {} k-mers", bubbles, removed);
+    comments += &format!("; bubbles popped: {} ({} k-mers)\n", bubbles, removed);
+  }
+  let sample = sample_components.map(|fraction| graph.sample_components(fraction, options.rng_seed));
+  if let Some(graph::Sample{fraction, seed, components, kept}) = &sample {
+    comments += &format!("; sampled: {} of {} connected components (fraction {}, RNG seed {})\n", kept, components, fraction, seed);
+  }
+  if !components.is_empty() {
+    let total = graph.retain_components(&components);
+    if let Some(c) = components.iter().find(|&&c| c >= total) {
+      return Err(Error::usage(format!("the graph has {} connected components, there is no component {}", total, c)));
+    }
+    let list: Vec<_> = components.iter().map(usize::to_string).collect();
+    comments += &format!("; components: {} of {} connected components kept\n", list.join(","), total);
+  }
+  if graph.is_empty() {
+    log::warn!("no k-mers in {}", input_file.display());
+  }
+  if let Some(manifest) = &mut manifest {
+    manifest.graph = Some(manifest::GraphSize{k: graph.k(), kmers: graph.kmers().len(), edges: graph.edges(), records: graph.records().0});
+  }
+  // The state must match the graph and options to be resumed
+  let state = match resume {
+    true => {
+      let state = graph::CloseState::read(&state_path).map_err(Error::io(&state_path))?;
+      if state.fingerprint() != graph.fingerprint(&options) {
+        return Err(Error::usage(format!("{} was saved for another input or other options", state_path.display())));
+      }
+      log::info!("resuming after {} seeds", state.seeds_done());
+      Some(state)
+    },
+    false => None
+  };
+  // Close unitigs and write output files; without --compress, the files named explicitly are compressed as their extension says
+  let compress = |path| output.compress.or_else(|| io::Compress::of(path));
+  let mut sink: Box<dyn sink::Sink> = match (output.shards, &output.split_by_support, &output.split_by_component) {
+    (Some(_), Some(_), _) | (Some(_), _, Some(_)) | (_, Some(_), Some(_)) =>
+      return Err(Error::usage("only one of --shards, --split-by-support and --split-by-component can be used")),
+    (Some(0), None, None) => return Err(Error::usage("--shards must be at least 1")),
+    (Some(n), None, None) => {
+      let prefix = output_path(&suffix);
+      Box::new(sink::ShardedSink::create(&prefix, n, fasta, &comments).map_err(Error::io(&prefix))?)
+    },
+    (None, Some(dir), None) => Box::new(sink::SupportSink::create(dir, output.support_cap, fasta, &comments).map_err(Error::io(dir))?),
+    (None, None, Some(dir)) => Box::new(sink::ComponentSink::create(dir, &graph, output.min_component, fasta, &comments).map_err(Error::io(dir))?),
+    // The counts are in the FASTA headers
+    (None, None, None) if options.kmer_counts => {
+      let fasta = io::FastaOptions{compress: compress(&output_fasta), ..fasta};
+      match fasta_stdout {
+        true => Box::new(sink::FastaSink::without_counts(io::FastaWriter::new(BufWriter::new(std::io::stdout().lock()), fasta).map_err(Error::io(&output_fasta))?)),
+        false => Box::new(sink::FastaSink::without_counts(io::FastaWriter::create(&output_fasta, fasta).map_err(Error::io(&output_fasta))?))
+      }
+    },
+    (None, None, None) => {
+      let counts: Box<dyn std::io::Write> = match counts_stdout {
+        true => Box::new(std::io::stdout().lock()),
+        false => Box::new(File::create(&output_counts).map_err(Error::io(&output_counts))?)
+      };
+      let counts = sink::counts_with_comments(counts, compress(&output_counts), &comments).map_err(Error::io(&output_counts))?;
+      let fasta = io::FastaOptions{compress: compress(&output_fasta), ..fasta};
+      match fasta_stdout {
+        true => Box::new(sink::FastaSink::with_writer(io::FastaWriter::new(BufWriter::new(std::io::stdout().lock()), fasta).map_err(Error::io(&output_fasta))?, counts)),
+        false => Box::new(sink::FastaSink::with_writer(io::FastaWriter::create(&output_fasta, fasta).map_err(Error::io(&output_fasta))?, counts))
+      }
+    }
+  };
+  if let Some(profiles) = &output.profiles {
+    let ids = if profiles.as_os_str() == "all" {
+      log::warn!("profiling all closed unitigs writes a line for each k-mer of the output");
+      None
+    } else {
+      let invalid = |l: &str| std::io::Error::new(std::io::ErrorKind::InvalidData, format!("wrong id \"{}\"", l));
+      let ids = open(profiles)?.lines().filter(|l| l.as_ref().map_or(true, |l| !l.trim().is_empty()))
+        .map(|l| l.and_then(|l| l.trim().parse().map_err(|_| invalid(&l))));
+      Some(ids.collect::<std::io::Result<_>>().map_err(Error::io(profiles))?)
+    };
+    let profiles_out = output.profiles_out.clone().unwrap_or_else(|| output_path(&(suffix.clone() + ".profiles.tsv")));
+    sink = Box::new(sink::ProfileSink::create(sink, &graph, ids, &profiles_out, &comments).map_err(Error::io(&profiles_out))?);
+  }
+  if let Some(path) = &output.provenance {
+    sink = Box::new(sink::ProvenanceSink::create(sink, &graph, path, &comments).map_err(Error::io(path))?);
+  }
+  if let Some(path) = &output.membership_out {
+    sink = Box::new(sink::MembershipSink::create(sink, &graph, path, &comments).map_err(Error::io(path))?);
+  }
+  if let Some(path) = &output.gfa_out {
+    sink = Box::new(sink::GfaSink::create(sink, &graph, path, output.gfa_version).map_err(Error::io(path))?);
+  }
+  let summary = match state {
+    Some(state) => graph.resume(&mut *sink, &options, state),
+    None => graph.close(&mut *sink, &options)
+  };
+  let mut summary = summary.map_err(|source| Error::Io{context: String::from("writing the closed unitigs"), source})?;
+  summary.sample = sample;
 
-}
+  let mut report = format!("Seed order: {}", options.seed_order);
+  if options.seed_order == graph::SeedOrder::Shuffle {
+    report += &format!(" (RNG seed {})", options.rng_seed);
+  }
+  log::info!("{}\n{}", report, summary);
+
+  if let Some(path) = &output.support_summary {
+    let mut comments = comments.clone();
+    if options.drop_below_floor {
+      comments += "; only closed unitigs written: those at or below the support floor were dropped\n";
+    }
+    if summary.aborted.is_some() {
+      comments += "; only closed unitigs written before aborting\n";
+    }
+    sink::write_support_summary(path, &summary.per_support, &comments).map_err(Error::io(path))?;
+  }
+
+  if let Some(path) = &output.unrepresented {
+    sink::write_unrepresented(path, &summary.unrepresented, &comments).map_err(Error::io(path))?;
+  }
 
+  if let Some(report) = &output.json_report {
+    if let Some(manifest) = &mut manifest {
+      manifest.finish();
+    }
+    manifest::Report{manifest: manifest.as_ref(), summary: &summary}.write(report).map_err(Error::io(report))?;
+  }
+
+  if summary.aborted.is_some() {
+    return Err(Error::Limit{message: format!("memory ceiling reached, state written to {} to continue with --resume", state_path.display())});
+  }
+
+  if output.strict_empty && graph.is_empty() {
+    return Err(Error::Invalid{message: format!("no k-mers in {}", input_file.display())});
+  }
+  Ok(())
+}
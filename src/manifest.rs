@@ -0,0 +1,167 @@
+//! Provenance of the outputs of a run
+
+use closed_unitigs::format::Ratio;
+use crate::graph::CloseSummary;
+use crate::term::{self, Phase};
+use serde::Serialize;
+use std::collections::{BTreeMap, VecDeque};
+use std::fs::File;
+use std::io::{self, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use xxhash_rust::xxh3::Xxh3;
+
+/// Bytes hashed at each end of the input
+const HASHED: u64 = 1 << 20;
+
+/// Identifies an input file
+#[derive(Debug, Serialize)]
+pub struct Input {
+  pub path: PathBuf,
+  /// Size in bytes
+  pub size: u64,
+  /// XXH3 hash of the first and last MiB and of the size, in hexadecimal
+  pub hash: String
+}
+
+impl Input {
+  /// Reads the size and hashes the content of a file
+  pub fn read(path: &Path) -> io::Result<Input> {
+    let mut file = File::open(path)?;
+    let size = file.metadata()?.len();
+    let mut hasher = Xxh3::new();
+    let mut buf = Vec::with_capacity(HASHED as usize);
+    Read::by_ref(&mut file).take(HASHED).read_to_end(&mut buf)?;
+    hasher.update(&buf);
+    if size > HASHED {
+      buf.clear();
+      file.seek(SeekFrom::Start((size - HASHED).max(HASHED)))?;
+      file.take(HASHED).read_to_end(&mut buf)?;
+      hasher.update(&buf);
+    }
+    hasher.update(&size.to_le_bytes());
+    Ok(Input{path: path.to_path_buf(), size, hash: format!("{:016x}", hasher.digest())})
+  }
+}
+
+/// Reader of a stream which cannot be read again, such as the standard input, hashing it as [`Input::read`] does
+pub struct Hashing<R: Read> {
+  inner: R,
+  /// First bytes read, up to a MiB
+  head: Vec<u8>,
+  /// Last bytes read after the head, up to a MiB
+  tail: VecDeque<u8>,
+  size: u64
+}
+
+impl<R: Read> Hashing<R> {
+  /// Wraps a stream
+  pub fn new(inner: R) -> Hashing<R> {
+    Hashing{inner, head: Vec::new(), tail: VecDeque::new(), size: 0}
+  }
+
+  /// Identifies the stream read so far with the given path
+  pub fn finish(mut self, path: &Path) -> Input {
+    let mut hasher = Xxh3::new();
+    hasher.update(&self.head);
+    hasher.update(self.tail.make_contiguous());
+    hasher.update(&self.size.to_le_bytes());
+    Input{path: path.to_path_buf(), size: self.size, hash: format!("{:016x}", hasher.digest())}
+  }
+}
+
+impl<R: Read> Read for Hashing<R> {
+  fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+    let n = self.inner.read(buf)?;
+    let (head, tail) = buf[..n].split_at((HASHED as usize - self.head.len()).min(n));
+    self.head.extend_from_slice(head);
+    self.tail.extend(tail);
+    let excess = self.tail.len().saturating_sub(HASHED as usize);
+    self.tail.drain(..excess);
+    self.size += n as u64;
+    Ok(n)
+  }
+}
+
+/// Seconds since the Unix epoch
+pub fn now() -> u64 {
+  SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs()
+}
+
+/// Peak resident memory of the process in bytes, where the system tells it
+fn peak_memory() -> Option<u64> {
+  // Linux only: the high water mark of the resident set, in kB
+  let status = std::fs::read_to_string("/proc/self/status").ok()?;
+  let line = status.lines().find(|l| l.starts_with("VmHWM:"))?;
+  line.split_whitespace().nth(1)?.parse::<u64>().ok().map(|kb| kb * 1024)
+}
+
+/// Size of the graph closed, once filtered
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct GraphSize {
+  pub k: usize,
+  pub kmers: usize,
+  /// Edges between k-mers, an edge and its twin on the other strand counting once
+  pub edges: usize,
+  /// Records, or their segments when split
+  pub records: usize
+}
+
+/// Describes how the outputs of a run were produced
+#[derive(Debug, Serialize)]
+pub struct Manifest<O> {
+  /// Version of this program
+  pub version: &'static str,
+  pub input: Input,
+  /// Resolved options, defaults included
+  pub options: O,
+  /// Start time, in seconds since the Unix epoch
+  pub started: u64,
+  /// End time, in seconds since the Unix epoch
+  pub finished: Option<u64>,
+  /// Graph closed, once it is read
+  pub graph: Option<GraphSize>,
+  /// Seconds taken by each phase, once the run ends
+  pub phases: BTreeMap<Phase, Ratio>,
+  /// Peak resident memory in bytes, once the run ends, if the system tells it
+  pub peak_memory: Option<u64>
+}
+
+impl<O: Serialize> Manifest<O> {
+  /// Manifest of a run started at the given time
+  pub fn new(input: Input, options: O, started: u64) -> Manifest<O> {
+    Manifest{version: env!("CARGO_PKG_VERSION"), input, options, started, finished: None, graph: None, phases: BTreeMap::new(), peak_memory: None}
+  }
+
+  /// Records the end of the run, with the time taken by its phases and its peak memory
+  pub fn finish(&mut self) {
+    self.finished = Some(now());
+    self.phases = term::phase_times().into_iter().map(|(phase, time)| (phase, Ratio(time.as_secs_f64()))).collect();
+    self.peak_memory = peak_memory();
+  }
+
+  /// Comment lines, prefixed by `;`, to put at the top of text outputs.
+  /// The end time is not known yet when they are written.
+  pub fn comments(&self) -> String {
+    format!("; closed-unitigs {}\n; input: {} ({} bytes, hash {})\n; options: {}\n; started: {}\n",
+      self.version, self.input.path.display(), self.input.size, self.input.hash, serde_json::to_string(&self.options).unwrap(), self.started)
+  }
+}
+
+/// Report of a run, written as JSON
+#[derive(Debug, Serialize)]
+pub struct Report<'a, O> {
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub manifest: Option<&'a Manifest<O>>,
+  pub summary: &'a CloseSummary
+}
+
+impl<O: Serialize> Report<'_, O> {
+  /// Writes the report to a file
+  pub fn write(&self, path: &Path) -> io::Result<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+    serde_json::to_writer_pretty(&mut out, self)?;
+    writeln!(out)?;
+    out.flush()
+  }
+}
@@ -0,0 +1,86 @@
+//! Approximate accounting of the memory used while closing, with the steps taken to stay under a ceiling
+
+use std::fmt;
+use std::mem::size_of;
+
+/// Fraction of the ceiling above which a degradation step is taken
+const HIGH_WATER: f64 = 0.9;
+
+/// Approximate bytes taken by an entry of a hash map, given the heap bytes owned by the entry
+pub fn map_entry<K, V>(heap: usize) -> usize {
+  // Tables are kept at most 7/8 full, with a control byte per slot
+  (size_of::<(K, V)>() + 1) * 8 / 7 + heap
+}
+
+/// Steps taken, in this order, when the memory used gets near the ceiling
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Degradation {
+  /// Drops the support cache, reading the counts from the nodes
+  DropSupportCache,
+  /// Writes new closed unitigs as soon as they are found, instead of keeping them for sorting
+  Stream,
+  /// Writes the closed unitigs kept for sorting
+  FlushPending,
+  /// Stops closing
+  Abort
+}
+
+impl fmt::Display for Degradation {
+  /// Describes what the step does
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Degradation::DropSupportCache => write!(f, "dropped the support cache"),
+      Degradation::Stream => write!(f, "writing new closed unitigs unsorted"),
+      Degradation::FlushPending => write!(f, "wrote the closed unitigs waiting to be sorted"),
+      Degradation::Abort => write!(f, "aborting")
+    }
+  }
+}
+
+/// Approximate bytes taken by the major structures used while closing
+#[derive(Debug, Default)]
+pub struct Memory {
+  pub ceiling: usize,
+  /// Nodes, edges, records and index of the graph
+  pub graph: usize,
+  /// Support cache
+  pub supp: usize,
+  /// Closed marks of the k-mers
+  pub is_closed: usize,
+  /// Closed unitigs waiting to be sorted
+  pub pending: usize,
+  /// Hashes of the written closed unitigs
+  pub seen: usize,
+  /// Last step taken
+  step: Option<Degradation>
+}
+
+impl Memory {
+  /// Starts accounting under a ceiling
+  pub fn new(ceiling: usize) -> Memory {
+    Memory{ceiling, ..Default::default()}
+  }
+
+  /// Approximate bytes used
+  pub fn used(&self) -> usize {
+    self.graph + self.supp + self.is_closed + self.pending + self.seen
+  }
+
+  /// Returns the next step to take, if the memory used is near the ceiling.
+  /// Aborting is needed only once the ceiling is exceeded after all the other steps.
+  pub fn check(&mut self) -> Option<Degradation> {
+    let used = self.used();
+    if (used as f64) < self.ceiling as f64 * HIGH_WATER {
+      return None;
+    }
+    let next = match self.step {
+      None => Degradation::DropSupportCache,
+      Some(Degradation::DropSupportCache) => Degradation::Stream,
+      Some(Degradation::Stream) => Degradation::FlushPending,
+      Some(Degradation::FlushPending) if used > self.ceiling => Degradation::Abort,
+      _ => return None
+    };
+    self.step = Some(next);
+    Some(next)
+  }
+}
@@ -0,0 +1,515 @@
+//! Record level reading of BCALM FASTA files
+
+use rayon::prelude::*;
+use snafu::Snafu;
+use std::io::{self, BufRead, Lines};
+use std::iter::Enumerate;
+use std::mem;
+use super::Orientation;
+
+#[derive(Debug, Snafu)]
+/// Describes an error reading a BCALM FASTA or GFA file
+pub enum ReadError {
+  #[snafu(display("Cannot read input: {}", source))]
+  Io{source: io::Error},
+  #[snafu(display("Syntax error at line {}: \"{}\"", line, content))]
+  Syntax{line: usize, content: String},
+  #[snafu(display("Wrong count \"{}\" at line {}", count, line))]
+  WrongCount{line: usize, count: String},
+  #[snafu(display("Wrong link \"{}\" at line {}", link, line))]
+  WrongLink{line: usize, link: String},
+  #[snafu(display("Unknown segment \"{}\" at line {}", name, line))]
+  UnknownSegment{line: usize, name: String},
+  #[snafu(display("Cannot tell k: the links give no overlap and the segments no ab:Z: counts"))]
+  UnknownK,
+  #[snafu(display("Counting the k-mers of reads needs k"))]
+  MissingK,
+  #[snafu(display("Malformed KMC database: {}", message))]
+  Kmc{message: String}
+}
+
+impl ReadError {
+  /// Line of the error, if it concerns one
+  pub fn line(&self) -> Option<usize> {
+    match self {
+      ReadError::Syntax{line, ..} | ReadError::WrongCount{line, ..} | ReadError::WrongLink{line, ..} | ReadError::UnknownSegment{line, ..} => Some(*line),
+      ReadError::Io{..} | ReadError::UnknownK | ReadError::MissingK | ReadError::Kmc{..} => None
+    }
+  }
+}
+
+/// Reader of the records of a graph file
+pub trait RecordSource: Iterator<Item = Result<Record, ReadError>> {
+  /// Bytes read so far, counting one for each line ending
+  fn bytes(&self) -> u64;
+}
+
+/// A link leaving a record towards another one
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Link {
+  /// Strand on which the record is left: by its end on the forward strand, by its start on the reverse one
+  pub from_dir: Orientation,
+  /// Id of the target record
+  pub to: usize,
+  /// Strand on which the target record is entered: by its start on the forward strand, by its end on the reverse one
+  pub to_dir: Orientation
+}
+
+/// A record of a BCALM FASTA file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Record {
+  /// Position of the record in the file, counting from zero
+  pub id: usize,
+  /// Line of its header, counting from one
+  pub line: usize,
+  /// Sequence of the unitig, empty if missing
+  pub sequence: String,
+  /// Counts of its k-mers (`ab:Z:` field)
+  pub counts: Vec<u32>,
+  /// Links to other records (`L:` fields)
+  pub links: Vec<Link>,
+  /// Other fields of the header, such as `LN:i:13`
+  pub tags: Vec<String>
+}
+
+/// A part of a record between break characters, with the counts of its k-mers
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Segment<'a> {
+  /// Position of its first base in the record
+  pub offset: usize,
+  pub sequence: &'a str,
+  pub counts: &'a [u32]
+}
+
+impl Record {
+  /// Mean count of its k-mers, given by its `km:f:` field if it has one, rounded and at least 1
+  pub fn mean_count(&self) -> Result<Option<u32>, ReadError> {
+    self.tags.iter().find_map(|tag| tag.strip_prefix("km:f:")).map(|mean| {
+      mean.parse::<f64>().ok().filter(|m| m.is_finite() && *m >= 0.).map(|m| (m.round() as u32).max(1))
+        .ok_or_else(|| ReadError::WrongCount{line: self.line, count: format!("km:f:{}", mean)})
+    }).transpose()
+  }
+
+  /// Splits the sequence at each run of the break character, keeping the parts which hold at least a k-mer.
+  /// Without break character, or if the counts do not match the sequence, the whole record is a single segment.
+  pub fn segments(&self, k: usize, break_char: Option<char>) -> Vec<Segment<'_>> {
+    let whole = Segment{offset: 0, sequence: &self.sequence, counts: &self.counts};
+    let c = match break_char {
+      Some(c) if k > 0 && self.counts.len() + k - 1 == self.sequence.len() => c,
+      _ => return vec![whole]
+    };
+    let mut segments = Vec::new();
+    let mut offset = 0;
+    for part in self.sequence.split(c) {
+      if part.len() >= k {
+        segments.push(Segment{offset, sequence: part, counts: &self.counts[offset..offset+part.len()+1-k]});
+      }
+      offset += part.len() + c.len_utf8();
+    }
+    segments
+  }
+}
+
+/// Scans the space separated counts of an `ab:Z:` field without allocating,
+/// yielding each count or, if it does not fit in 32 bits, its text
+pub struct Counts<'a> {
+  field: &'a [u8],
+  pos: usize
+}
+
+impl<'a> Counts<'a> {
+  /// Scans a field made of digits and single spaces
+  pub fn new(field: &'a str) -> Counts<'a> {
+    Counts{field: field.as_bytes(), pos: 0}
+  }
+}
+
+impl<'a> Iterator for Counts<'a> {
+  type Item = Result<u32, &'a str>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    if self.pos >= self.field.len() {
+      return None;
+    }
+    let start = self.pos;
+    let mut count: Option<u32> = Some(0);
+    // The last count has no delimiter after it
+    while let Some(&b) = self.field.get(self.pos).filter(|&&b| b != b' ') {
+      count = count.and_then(|c| c.checked_mul(10)).and_then(|c| c.checked_add((b - b'0') as u32));
+      self.pos += 1;
+    }
+    let token = &self.field[start..self.pos];
+    self.pos += 1; // skip the delimiter
+    Some(count.ok_or_else(|| std::str::from_utf8(token).unwrap())) // only digits
+  }
+}
+
+/// Finds the first `ab:Z:` field of a header, returning the span of the field and the one of its counts:
+/// digits separated by single spaces
+fn count_field(header: &str) -> Option<(usize, usize, usize)> {
+  const TAG: &str = "ab:Z:";
+  let bytes = header.as_bytes();
+  let start = header.match_indices(TAG).map(|(i, _)| i).find(|&i| bytes.get(i + TAG.len()).is_some_and(u8::is_ascii_digit))?;
+  let counts = start + TAG.len();
+  let mut end = counts;
+  while end < bytes.len() && (bytes[end].is_ascii_digit() || (bytes[end] == b' ' && bytes.get(end + 1).is_some_and(u8::is_ascii_digit))) {
+    end += 1;
+  }
+  Some((start, counts, end))
+}
+
+/// Parses an `L:` field at the start of some text: `L:`, a sign, `:`, a record id, `:` and a sign.
+/// Returns its length and the link, or the field if the id does not fit in a `usize`.
+fn link_field(text: &str) -> Option<(usize, Result<Link, &str>)> {
+  let bytes = text.as_bytes();
+  let sign = |i: usize| text.get(i..i+1).filter(|&s| s == "+" || s == "-").map(Orientation::from_sign);
+  if !text.starts_with("L:") || bytes.get(3) != Some(&b':') {
+    return None;
+  }
+  let from_dir = sign(2)?;
+  let digits = bytes[4..].iter().take_while(|b| b.is_ascii_digit()).count();
+  let end = 4 + digits;
+  if digits == 0 || bytes.get(end) != Some(&b':') {
+    return None;
+  }
+  let to_dir = sign(end + 1)?;
+  // Bounded read of the id
+  let to = bytes[4..end].iter().try_fold(0usize, |to, &b| to.checked_mul(10)?.checked_add((b - b'0') as usize));
+  Some((end + 2, to.map(|to| Link{from_dir, to, to_dir}).ok_or(&text[..end + 2])))
+}
+
+/// Finds the `L:` fields of a header, wherever they start, returning them and the header without them
+fn link_fields(header: &str) -> (Vec<Result<Link, &str>>, String) {
+  let (mut links, mut rest) = (Vec::new(), String::new());
+  let (mut pos, mut kept) = (0, 0); // where to look for the next field, start of the text to keep
+  while let Some(i) = header[pos..].find("L:") {
+    let start = pos + i;
+    match link_field(&header[start..]) {
+      Some((len, link)) => {
+        links.push(link);
+        rest += &header[kept..start];
+        pos = start + len;
+        kept = pos;
+      },
+      None => pos = start + 1
+    }
+  }
+  rest += &header[kept..];
+  (links, rest)
+}
+
+/// Reads the records of a BCALM FASTA file, whose sequences may be wrapped on several lines.
+///
+/// # Example
+/// ```
+/// use closed_unitigs::BcalmReader;
+///
+/// let input = ">0 LN:i:6 ab:Z:5 5 L:+:1:+\nAACCGT\n>1 LN:i:6 ab:Z:5 4 L:-:0:-\nCCGTTA\n";
+/// for record in BcalmReader::new(input.as_bytes()) {
+///   let record = record.unwrap();
+///   assert_eq!(record.counts.len(), 2);
+/// }
+/// ```
+pub struct BcalmReader<R: BufRead> {
+  lines: Enumerate<Lines<R>>,
+  /// Pending header, with its line number
+  header: Option<(usize, String)>,
+  /// Sequence lines read after the pending header, joined
+  sequence: String,
+  /// Number of records read
+  records: usize,
+  /// Bytes read, counting one for each line ending
+  bytes: u64
+}
+
+impl<R: BufRead> BcalmReader<R> {
+  /// Creates a reader of the given input
+  pub fn new(input: R) -> BcalmReader<R> {
+    BcalmReader{lines: input.lines().enumerate(), header: None, sequence: String::new(), records: 0, bytes: 0}
+  }
+
+  /// Numbers a parsed record, counting only the records read without error
+  fn number(&mut self, record: Result<Record, ReadError>) -> Result<Record, ReadError> {
+    record.map(|record| {
+      self.records += 1;
+      Record{id: self.records - 1, ..record}
+    })
+  }
+
+  /// Reads the lines of the next record: its header, with its line number, and its sequence lines joined
+  fn raw(&mut self) -> Option<Result<Raw, ReadError>> {
+    loop {
+      match self.lines.next() {
+        // The last record ends with the input
+        None => return self.header.take().map(|header| Ok((header, mem::take(&mut self.sequence)))),
+        Some((_, Err(source))) => return Some(Err(ReadError::Io{source})),
+        Some((index, Ok(mut line))) => {
+          self.bytes += line.len() as u64 + 1;
+          if line.starts_with('>') {
+            // A header closes the record before it, if any
+            if let Some(header) = self.header.replace((index+1, line)) {
+              return Some(Ok((header, mem::take(&mut self.sequence))));
+            }
+            continue;
+          }
+          if self.header.is_none() {
+            return Some(Err(ReadError::Syntax{line: index+1, content: line}));
+          }
+          line.truncate(line.trim_end().len());
+          // Unwrapped sequences are not copied
+          if self.sequence.is_empty() {
+            self.sequence = line;
+          } else {
+            self.sequence.push_str(&line);
+          }
+        }
+      }
+    }
+  }
+}
+
+/// Lines of a record as read: its header, with its line number, and its sequence
+type Raw = ((usize, String), String);
+
+/// Parses a record from its lines, leaving its id to be numbered in file order
+fn parse(((line, header), sequence): Raw) -> Result<Record, ReadError> {
+  // Count lists can be huge: they are scanned in place, and left out of the copies of the header
+  let (counts, rest) = match count_field(&header) {
+    Some((start, counts, end)) => {
+      let counts = Counts::new(&header[counts..end]).map(|c| c.map_err(|c| ReadError::WrongCount{line, count: String::from(c)})).collect::<Result<_, _>>()?;
+      (counts, format!("{} {}", &header[1..start], &header[end..]))
+    },
+    None => (Vec::new(), String::from(&header[1..]))
+  };
+  let (links, rest) = link_fields(&rest);
+  let links = links.into_iter().map(|l| l.map_err(|l| ReadError::WrongLink{line, link: String::from(l)})).collect::<Result<_, _>>()?;
+  // The first field is the id
+  let tags = rest.split_whitespace().skip(1).map(String::from).collect();
+  Ok(Record{id: 0, line, sequence, counts, links, tags})
+}
+
+impl<R: BufRead> RecordSource for BcalmReader<R> {
+  fn bytes(&self) -> u64 {
+    self.bytes
+  }
+}
+
+impl<R: BufRead> Iterator for BcalmReader<R> {
+  type Item = Result<Record, ReadError>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    let record = self.raw()?.and_then(parse);
+    Some(self.number(record))
+  }
+}
+
+/// Number of records read before being parsed together
+const CHUNK: usize = 4096;
+
+/// Reads the records of a BCALM FASTA file as [`BcalmReader`] does, parsing their headers on rayon's worker threads:
+/// the calling thread only splits the input in records, reading a chunk of them while the previous one is parsed.
+/// It reads a chunk of records ahead of the ones taken.
+///
+/// # Example
+/// ```
+/// use closed_unitigs::{BcalmReader, ParallelBcalmReader};
+///
+/// let input = ">0 LN:i:6 ab:Z:5 5 L:+:1:+\nAACCGT\n>1 LN:i:6 ab:Z:5 4 L:-:0:-\nCCGTTA\n";
+/// let parallel: Vec<_> = ParallelBcalmReader::new(input.as_bytes()).map(Result::unwrap).collect();
+/// assert_eq!(parallel, BcalmReader::new(input.as_bytes()).map(Result::unwrap).collect::<Vec<_>>());
+/// ```
+pub struct ParallelBcalmReader<R: BufRead> {
+  reader: BcalmReader<R>,
+  /// Chunk read and not parsed yet
+  raw: Vec<Result<Raw, ReadError>>,
+  /// Records parsed and not taken yet
+  parsed: std::vec::IntoIter<Result<Record, ReadError>>
+}
+
+impl<R: BufRead> ParallelBcalmReader<R> {
+  /// Creates a reader of the given input
+  pub fn new(input: R) -> ParallelBcalmReader<R> {
+    ParallelBcalmReader{reader: BcalmReader::new(input), raw: Vec::new(), parsed: Vec::new().into_iter()}
+  }
+}
+
+/// Reads the lines of the next records
+fn chunk<R: BufRead>(reader: &mut BcalmReader<R>) -> Vec<Result<Raw, ReadError>> {
+  std::iter::from_fn(|| reader.raw()).take(CHUNK).collect()
+}
+
+impl<R: BufRead> RecordSource for ParallelBcalmReader<R> {
+  fn bytes(&self) -> u64 {
+    self.reader.bytes
+  }
+}
+
+impl<R: BufRead> Iterator for ParallelBcalmReader<R> {
+  type Item = Result<Record, ReadError>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    if let Some(record) = self.parsed.next() {
+      return Some(record);
+    }
+    if self.raw.is_empty() {
+      self.raw = chunk(&mut self.reader);
+    }
+    if self.raw.is_empty() {
+      return None;
+    }
+    let raw = mem::take(&mut self.raw);
+    let mut parsed = Vec::new();
+    let target = &mut parsed;
+    let reader = &mut self.reader;
+    self.raw = rayon::in_place_scope(|s| {
+      s.spawn(move |_| *target = raw.into_par_iter().map(|raw| raw.and_then(parse)).collect());
+      chunk(reader)
+    });
+    // Numbered in file order, as the serial reader does
+    let numbered: Vec<_> = parsed.into_iter().map(|record| self.reader.number(record)).collect();
+    self.parsed = numbered.into_iter();
+    self.parsed.next()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn records_are_split_in_fields() {
+    let input = ">0 LN:i:6 KC:i:10 ab:Z:5 5 L:+:1:+ L:-:1:-\nAACCGT\n>1\n>2 ab:Z:99999999999\nACGTA\n";
+    let mut reader = BcalmReader::new(input.as_bytes());
+    let record = reader.next().unwrap().unwrap();
+    assert_eq!(record, Record{
+      id: 0,
+      line: 1,
+      sequence: String::from("AACCGT"),
+      counts: vec![5, 5],
+      links: vec![Link{from_dir: Orientation::Forward, to: 1, to_dir: Orientation::Forward}, Link{from_dir: Orientation::Reverse, to: 1, to_dir: Orientation::Reverse}],
+      tags: vec![String::from("LN:i:6"), String::from("KC:i:10")]
+    });
+    assert_eq!(reader.next().unwrap().unwrap().sequence, "");
+    assert!(matches!(reader.next(), Some(Err(ReadError::WrongCount{line: 4, ..}))));
+    assert!(reader.next().is_none());
+    assert_eq!(reader.bytes(), input.len() as u64);
+  }
+
+  #[test]
+  fn wrapped_sequences_are_joined() {
+    let input = ">0 ab:Z:1 2 3 4 L:+:1:+\nAACC\nGT\r\n\nT\n>1 ab:Z:5\nCCG\nTT\n";
+    let records: Vec<_> = BcalmReader::new(input.as_bytes()).map(|r| r.unwrap()).collect();
+    assert_eq!(records.iter().map(|r| (r.line, r.sequence.as_str())).collect::<Vec<_>>(), [(1, "AACCGTT"), (6, "CCGTT")]);
+    assert!(matches!(BcalmReader::new("ACGT\n>0\nACGT\n".as_bytes()).next(), Some(Err(ReadError::Syntax{line: 1, ..}))));
+  }
+
+  #[test]
+  fn breaks_split_records_keeping_offsets() {
+    let input = ">0 ab:Z:1 2 3 4 5 6 7 8 9 10 11 12 13\nAACCNNGTTACNGTAA\n";
+    let record = BcalmReader::new(input.as_bytes()).next().unwrap().unwrap();
+    let segments: Vec<_> = record.segments(4, Some('N')).into_iter().map(|s| (s.offset, s.sequence, s.counts.to_vec())).collect();
+    // "GTTAC" holds two 4-mers, "AACC" one, "GTAA" one
+    assert_eq!(segments, vec![(0, "AACC", vec![1]), (6, "GTTAC", vec![7, 8]), (12, "GTAA", vec![13])]);
+    assert_eq!(record.segments(4, None), vec![Segment{offset: 0, sequence: &record.sequence, counts: &record.counts}]);
+    let record = BcalmReader::new(">1 ab:Z:1 1 1\nACNTG\n".as_bytes()).next().unwrap().unwrap();
+    assert_eq!(record.segments(3, Some('N')), vec![]);
+  }
+
+  #[test]
+  fn counts_are_scanned_in_place() {
+    let scan = |field| Counts::new(field).collect::<Vec<_>>();
+    assert_eq!(scan("1 22 333"), vec![Ok(1), Ok(22), Ok(333)]);
+    assert_eq!(scan("4294967295 4294967296"), vec![Ok(u32::MAX), Err("4294967296")]);
+    assert_eq!(scan(""), vec![]);
+    // The field ends where the digits do, and only the first field with counts is taken
+    let header = ">0 ab:Z: ab:Z:1 2  3 ab:Z:4";
+    assert_eq!(count_field(header).map(|(start, counts, end)| (&header[start..counts], &header[counts..end])), Some(("ab:Z:", "1 2")));
+    assert_eq!(count_field(">0 ab:Z:1 2 L:+:1:+").map(|(_, c, e)| e - c), Some(3));
+    assert_eq!(count_field(">0 LN:i:4"), None);
+  }
+
+  #[test]
+  fn long_count_lists_are_read() {
+    let counts: Vec<u32> = (0..100_000).map(|i| i * 7919 % 100_003).collect();
+    let list: Vec<_> = counts.iter().map(ToString::to_string).collect();
+    let input = format!(">0 LN:i:100002 ab:Z:{} L:+:0:-\n{}\n", list.join(" "), "A".repeat(100_002));
+    let record = BcalmReader::new(input.as_bytes()).next().unwrap().unwrap();
+    assert_eq!(record.counts, counts);
+    assert_eq!((record.links.len(), record.tags), (1, vec![String::from("LN:i:100002")]));
+  }
+
+  #[test]
+  fn links_are_found_anywhere_in_the_header() {
+    let input = ">0 XL:+:3:+y L:+:L:-:2:- L:+:1:+:-\nACGT\n";
+    let record = BcalmReader::new(input.as_bytes()).next().unwrap().unwrap();
+    let link = |from_dir, to, to_dir| Link{from_dir, to, to_dir};
+    assert_eq!(record.links, [link(Orientation::Forward, 3, Orientation::Forward), link(Orientation::Reverse, 2, Orientation::Reverse), link(Orientation::Forward, 1, Orientation::Forward)]);
+    assert_eq!(record.tags, ["Xy", "L:+:", ":-"]);
+    let input = format!(">0 L:+:{}0:+\nACGT\n", usize::MAX);
+    assert!(matches!(BcalmReader::new(input.as_bytes()).next(), Some(Err(ReadError::WrongLink{line: 1, ..}))));
+  }
+
+  #[test]
+  fn adversarial_headers_are_read_quickly() {
+    let digits = "1".repeat(1_000_000);
+    let spaced = "1 ".repeat(500_000);
+    let headers = [
+      format!(">0 ab:Z:{} x", spaced),
+      format!(">0 ab:Z:{}  ab:Z:{}", spaced, spaced),
+      format!(">0 ab:Z:{}", digits),
+      format!(">0 L:+:{}", digits),
+      format!(">0 {}", "L:+:1".repeat(200_000)),
+      format!(">0 {}", "L:+:L:-:".repeat(200_000)),
+      format!(">0 {} {}", "ab:Z:".repeat(200_000), spaced)
+    ];
+    for header in headers {
+      let input = format!("{}\nACGT\n", header);
+      let start = std::time::Instant::now();
+      let _ = BcalmReader::new(input.as_bytes()).next().unwrap();
+      // Generous for unoptimized builds, which take well under a second
+      assert!(start.elapsed().as_secs() < 10, "{}...", &header[..20]);
+    }
+  }
+
+  #[test]
+  fn fixtures_are_read_as_before() {
+    for name in ["small", "duplicated", "stranded"] {
+      let input = std::fs::read_to_string(format!("tests/data/{}.fa", name)).unwrap();
+      let mut fields = String::new();
+      for record in BcalmReader::new(input.as_bytes()) {
+        let Record{id, counts, links, tags, ..} = record.unwrap();
+        let counts: Vec<_> = counts.iter().map(ToString::to_string).collect();
+        let links: Vec<_> = links.iter().map(|l| format!("L:{}:{}:{}", l.from_dir.sign(), l.to, l.to_dir.sign())).collect();
+        fields += &format!("{}\t{}\t{}\t{}\n", id, counts.join(" "), links.join(" "), tags.join(" "));
+      }
+      let golden = format!("tests/data/golden/{}.records.tsv", name);
+      if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        std::fs::write(&golden, &fields).unwrap();
+      }
+      assert_eq!(fields, std::fs::read_to_string(&golden).unwrap(), "{}", name);
+    }
+  }
+
+  #[test]
+  fn parallel_reading_matches_serial_reading() {
+    let mut input = String::new();
+    for i in 0..10_000 {
+      input += &format!(">{} LN:i:6 ab:Z:{} 5 L:+:{}:-\nAACC\nGT\n", i, i, (i + 1) % 10_000);
+    }
+    let serial: Vec<_> = BcalmReader::new(input.as_bytes()).map(Result::unwrap).collect();
+    let mut reader = ParallelBcalmReader::new(input.as_bytes());
+    assert_eq!(reader.by_ref().map(Result::unwrap).collect::<Vec<_>>(), serial);
+    assert_eq!(reader.bytes(), input.len() as u64);
+    // Records are numbered and errors reported in file order
+    input.insert_str(0, ">x ab:Z:99999999999\nACGTA\n");
+    let parallel: Vec<_> = ParallelBcalmReader::new(input.as_bytes()).collect();
+    assert!(matches!(parallel[0], Err(ReadError::WrongCount{line: 1, ..})));
+    assert_eq!(parallel[1..].iter().map(|r| r.as_ref().unwrap().id).collect::<Vec<_>>(), (0..10_000).collect::<Vec<_>>());
+  }
+
+  #[test]
+  fn sequences_need_a_header() {
+    let mut reader = BcalmReader::new("ACGT\n".as_bytes());
+    assert!(matches!(reader.next(), Some(Err(ReadError::Syntax{line: 1, ..}))));
+  }
+}
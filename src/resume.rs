@@ -0,0 +1,150 @@
+//! State of a closing saved at checkpoints, from which an interrupted closing is resumed
+
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use super::{Pending, Reason, Unitig};
+use crate::io::with_suffix;
+
+/// Saving of the state of a closing
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Checkpointing {
+  /// File of the state, replaced at each checkpoint and removed once closing completes.
+  /// The closed unitigs found are appended, as they are, to the journal next to it, with extension `unitigs`.
+  pub path: PathBuf,
+  /// Time between two checkpoints; if None, the state is only saved when closing is aborted for lack of memory
+  pub every: Option<Duration>
+}
+
+impl Checkpointing {
+  /// File of the closed unitigs found
+  pub fn journal(&self) -> PathBuf {
+    self.path.with_extension("unitigs")
+  }
+}
+
+/// Closed unitigs found, one JSON line each, appended as they are found so that a checkpoint only records their length
+pub(super) struct Journal {
+  out: BufWriter<File>,
+  len: u64
+}
+
+impl Journal {
+  /// Starts an empty journal
+  pub(super) fn create(path: &Path) -> io::Result<Journal> {
+    Ok(Journal{out: BufWriter::new(File::create(path)?), len: 0})
+  }
+
+  /// Reads back the first `len` bytes of a journal, passing each closed unitig to `f`,
+  /// then drops what follows, found after the state was saved, and goes on appending
+  pub(super) fn resume(path: &Path, len: u64, mut f: impl FnMut(Unitig, Pending) -> io::Result<()>) -> io::Result<Journal> {
+    let mut file = OpenOptions::new().read(true).write(true).open(path)?;
+    for line in BufReader::new((&file).take(len)).lines() {
+      let (u, pending): (String, Pending) = serde_json::from_str(&line?)?;
+      let u = Unitig::try_from(u.as_str()).map_err(|_| io::Error::new(io::ErrorKind::InvalidData, format!("wrong saved unitig {}", u)))?;
+      f(u, pending)?;
+    }
+    file.set_len(len)?;
+    file.seek(SeekFrom::Start(len))?;
+    Ok(Journal{out: BufWriter::new(file), len})
+  }
+
+  /// Appends a closed unitig
+  pub(super) fn push(&mut self, u: &Unitig, pending: &Pending) -> io::Result<()> {
+    let mut line = serde_json::to_vec(&(u.to_string(), pending))?;
+    line.push(b'\n');
+    self.out.write_all(&line)?;
+    self.len += line.len() as u64;
+    Ok(())
+  }
+
+  /// Writes the closed unitigs appended to the disk, returning the length of the journal
+  pub(super) fn sync(&mut self) -> io::Result<u64> {
+    self.out.flush()?;
+    self.out.get_ref().sync_data()?;
+    Ok(self.len)
+  }
+}
+
+/// Packs flags in 64 bit words, the first flag in the lowest bit
+pub(super) fn pack(flags: impl Iterator<Item = bool>) -> Vec<u64> {
+  let mut words = Vec::new();
+  for (i, flag) in flags.enumerate() {
+    if i % 64 == 0 {
+      words.push(0);
+    }
+    *words.last_mut().unwrap() |= (flag as u64) << (i % 64);
+  }
+  words
+}
+
+/// Flag at the given position of packed flags, false past their end
+pub(super) fn flag(words: &[u64], i: usize) -> bool {
+  words.get(i / 64).is_some_and(|word| word >> (i % 64) & 1 == 1)
+}
+
+/// State of a closing between two seeds, from which [`Graph::resume`](super::Graph::resume) continues it.
+/// The support cache is not saved: closing never changes it, so it is built again from the graph.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CloseState {
+  /// Fingerprint of the graph and of the closing options, which must be the same to resume
+  pub(super) fingerprint: u64,
+  /// Number of seeds processed, in seed order
+  pub(super) seeds_done: usize,
+  /// Nodes whose k-mers were closed, which are not used as seeds again, packed
+  pub(super) closed: Vec<u64>,
+  /// Nodes represented in the closed unitigs found, packed
+  pub(super) covered: Vec<u64>,
+  /// Nodes which may not be represented, with the reason
+  pub(super) reasons: Vec<(usize, Reason)>,
+  pub(super) skipped_seeds: usize,
+  pub(super) truncated: usize,
+  pub(super) timed_out: usize,
+  pub(super) capped: usize,
+  pub(super) floored: usize,
+  /// Length of the journal of the closed unitigs found, in the order they were written when streaming;
+  /// the ones after it were found after the state was saved
+  pub(super) journal_len: u64
+}
+
+impl CloseState {
+  /// Reads a state saved at a checkpoint
+  pub fn read(path: &Path) -> io::Result<CloseState> {
+    Ok(serde_json::from_reader(BufReader::new(File::open(path)?))?)
+  }
+
+  /// Writes the state under a temporary name, then renames it, so that an interruption leaves the previous state intact
+  pub(super) fn write(&self, path: &Path) -> io::Result<()> {
+    let written = with_suffix(path, ".tmp");
+    let mut out = BufWriter::new(File::create(&written)?);
+    serde_json::to_writer(&mut out, self)?;
+    out.into_inner().map_err(io::IntoInnerError::into_error)?.sync_all()?;
+    fs::rename(written, path)
+  }
+
+  /// Fingerprint of the graph and of the options the state was saved with, as given by [`Graph::fingerprint`](super::Graph::fingerprint)
+  pub fn fingerprint(&self) -> u64 {
+    self.fingerprint
+  }
+
+  /// Number of seeds processed before the state was saved
+  pub fn seeds_done(&self) -> usize {
+    self.seeds_done
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn flags_are_packed() {
+    let flags: Vec<_> = (0..130).map(|i| i % 3 == 0 || i == 129).collect();
+    let words = pack(flags.iter().copied());
+    assert_eq!(words.len(), 3);
+    assert!((0..140).all(|i| flag(&words, i) == flags.get(i).copied().unwrap_or(false)));
+  }
+}
@@ -0,0 +1,191 @@
+//! Binary format of a parsed graph, saved to be loaded again without parsing its input
+
+use snafu::Snafu;
+use rustc_hash::FxHashMap;
+use std::convert::TryInto;
+use std::io::{self, Write};
+use xxhash_rust::xxh3::xxh3_64;
+use super::{Edge, Graph, KmerIndex, Node, Orientation, Origin, Unitig};
+
+/// First bytes of a saved graph
+const MAGIC: &[u8; 8] = b"CUGRAPH\0";
+/// Version of the format, increased at each incompatible change
+const VERSION: u32 = 1;
+/// Bytes of the header: magic, version, k, number of nodes, number of records and checksum of the body
+const HEADER: usize = 8 + 4 + 4 + 8 + 8 + 8;
+
+#[derive(Debug, Snafu)]
+/// Describes why a saved graph cannot be loaded
+pub enum LoadError {
+  #[snafu(display("Not a saved graph"))]
+  NotSaved,
+  #[snafu(display("Saved graph version {} is not supported (expected {})", version, VERSION))]
+  Version{version: u32},
+  #[snafu(display("Corrupted saved graph"))]
+  Corrupted
+}
+
+/// Encodes a pair of orientations in the two lowest bits
+fn dirs(dir: Orientation, to_dir: Orientation) -> u8 {
+  (dir == Orientation::Reverse) as u8 | ((to_dir == Orientation::Reverse) as u8) << 1
+}
+
+/// Decodes an orientation from a bit
+fn dir(bit: u8) -> Orientation {
+  match bit & 1 {
+    0 => Orientation::Forward,
+    _ => Orientation::Reverse
+  }
+}
+
+/// Appends the edges of a node: their number, then the node reached, the orientations and the weight of each one
+fn put_edges(body: &mut Vec<u8>, edges: &[Edge]) {
+  body.extend_from_slice(&(edges.len() as u32).to_le_bytes());
+  for e in edges {
+    body.extend_from_slice(&(e.to as u64).to_le_bytes());
+    body.push(dirs(e.dir, e.to_dir));
+    body.extend_from_slice(&e.weight.to_le_bytes());
+  }
+}
+
+/// Reads the body of a saved graph, failing on a truncated one
+struct Body<'a> {
+  data: &'a [u8]
+}
+
+impl Body<'_> {
+  fn bytes(&mut self, n: usize) -> Result<&[u8], LoadError> {
+    if self.data.len() < n {
+      return Err(LoadError::Corrupted);
+    }
+    let (bytes, rest) = self.data.split_at(n);
+    self.data = rest;
+    Ok(bytes)
+  }
+
+  fn u8(&mut self) -> Result<u8, LoadError> {
+    Ok(self.bytes(1)?[0])
+  }
+
+  fn u32(&mut self) -> Result<u32, LoadError> {
+    Ok(u32::from_le_bytes(self.bytes(4)?.try_into().unwrap()))
+  }
+
+  /// Reads a number, which must be below the given bound
+  fn below(&mut self, bound: usize) -> Result<usize, LoadError> {
+    let n = u64::from_le_bytes(self.bytes(8)?.try_into().unwrap());
+    (n < bound as u64).then_some(n as usize).ok_or(LoadError::Corrupted)
+  }
+
+  fn edges(&mut self, nodes: usize) -> Result<Vec<Edge>, LoadError> {
+    (0..self.u32()?).map(|_| {
+      let to = self.below(nodes)?;
+      let dirs = self.u8()?;
+      Ok(Edge{to, dir: dir(dirs), to_dir: dir(dirs >> 1), weight: self.u32()?})
+    }).collect()
+  }
+}
+
+impl Graph {
+  /// Writes the graph in the binary format, with its k-mers packed in 2 bits per base
+  pub fn save<W: Write>(&self, mut out: W) -> io::Result<()> {
+    let mut body = Vec::new();
+    for node in &self.nodes {
+      let codes: Vec<_> = node.kmer.codes().collect();
+      body.extend(codes.chunks(4).map(|c| c.iter().enumerate().fold(0, |byte, (i, &code)| byte | code << (2 * i))));
+      body.extend_from_slice(&node.count.to_le_bytes());
+      body.extend_from_slice(&(node.origin.record as u64).to_le_bytes());
+      body.extend_from_slice(&(node.origin.offset as u64).to_le_bytes());
+      put_edges(&mut body, &node.out);
+      put_edges(&mut body, &node.into);
+    }
+    for record in &self.records {
+      body.extend_from_slice(&(record.len() as u64).to_le_bytes());
+      for &(n, d) in record {
+        body.extend_from_slice(&(n as u64).to_le_bytes());
+        body.push(dirs(d, Orientation::Forward));
+      }
+    }
+    out.write_all(MAGIC)?;
+    out.write_all(&VERSION.to_le_bytes())?;
+    out.write_all(&(self.k as u32).to_le_bytes())?;
+    for field in [self.nodes.len() as u64, self.records.len() as u64, xxh3_64(&body)] {
+      out.write_all(&field.to_le_bytes())?;
+    }
+    out.write_all(&body)?;
+    out.flush()
+  }
+
+  /// Loads a graph written by [`Graph::save`], checking that it is intact.
+  /// Its k-mers are looked up in a hash map, rebuilt from the nodes.
+  pub fn load(data: &[u8]) -> Result<Graph, LoadError> {
+    if data.len() < HEADER || &data[..8] != MAGIC {
+      return Err(LoadError::NotSaved);
+    }
+    let u32_at = |i: usize| u32::from_le_bytes(data[i..i+4].try_into().unwrap());
+    let u64_at = |i: usize| u64::from_le_bytes(data[i..i+8].try_into().unwrap());
+    let version = u32_at(8);
+    if version != VERSION {
+      return Err(LoadError::Version{version});
+    }
+    let (k, nodes, records, checksum) = (u32_at(12) as usize, u64_at(16), u64_at(24), u64_at(32));
+    let mut body = Body{data: &data[HEADER..]};
+    if xxh3_64(body.data) != checksum || nodes > body.data.len() as u64 || records > body.data.len() as u64 {
+      return Err(LoadError::Corrupted);
+    }
+    let nodes = nodes as usize;
+
+    let mut graph = Graph::new(k);
+    graph.nodes.reserve(nodes);
+    for _ in 0..nodes {
+      let packed = body.bytes(k.div_ceil(4))?;
+      let kmer = Unitig::from_codes((0..k).map(|i| packed[i / 4] >> (2 * (i % 4)) & 3));
+      let count = body.u32()?;
+      let origin = Origin{record: body.below(usize::MAX)?, offset: body.below(usize::MAX)?};
+      let mut node = Node::new(kmer, count, origin);
+      node.out = body.edges(nodes)?;
+      node.into = body.edges(nodes)?;
+      graph.nodes.push(node);
+    }
+    for _ in 0..records {
+      let len = body.below(nodes + 1)?;
+      let record = (0..len).map(|_| Ok((body.below(nodes)?, dir(body.u8()?)))).collect::<Result<_, _>>()?;
+      graph.records.push(record);
+    }
+    if !body.data.is_empty() {
+      return Err(LoadError::Corrupted);
+    }
+    let index: FxHashMap<_, _> = graph.nodes.iter().enumerate().map(|(i, n)| (n.kmer.clone(), i)).collect();
+    if index.len() != nodes {
+      return Err(LoadError::Corrupted);
+    }
+    graph.index = KmerIndex::Map(index);
+    Ok(graph)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn saved_graphs_are_loaded_unchanged() {
+    let graph = Graph::from(include_str!("../tests/data/small.fa").as_bytes());
+    let mut data = Vec::new();
+    graph.save(&mut data).unwrap();
+    let loaded = Graph::load(&data).unwrap();
+    assert_eq!((loaded.k, &loaded.nodes, &loaded.records), (graph.k, &graph.nodes, &graph.records));
+    for node in &graph.nodes {
+      assert_eq!(loaded.node(&node.kmer.rev_compl()), graph.node(&node.kmer.rev_compl()));
+    }
+
+    let mut corrupted = data.clone();
+    *corrupted.last_mut().unwrap() ^= 1;
+    assert!(matches!(Graph::load(&corrupted), Err(LoadError::Corrupted)));
+    assert!(matches!(Graph::load(&data[..data.len() - 1]), Err(LoadError::Corrupted)));
+    corrupted = data.clone();
+    corrupted[8] = 9;
+    assert!(matches!(Graph::load(&corrupted), Err(LoadError::Version{version: 9})));
+    assert!(matches!(Graph::load(b">0 ab:Z:1\nACGTA\n"), Err(LoadError::NotSaved)));
+  }
+}
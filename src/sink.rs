@@ -0,0 +1,612 @@
+//! Destinations of the closed unitigs
+
+use crate::format;
+use crate::graph::{Components, Graph, Orientation, Spectrum, SupportStats, Trim, Unitig, Unrepresented};
+use crate::io::{with_suffix, Compress, Encoder, FastaOptions, FastaRecord, FastaWriter};
+use serde::Serialize;
+use std::collections::{btree_map::Entry, BTreeMap, HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// What is known about a closed unitig besides its sequence and support
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Metadata<'a> {
+  /// Number of k-mers its closure closed
+  pub closed: usize,
+  /// Positions of its gap k-mers, if it jumped over some
+  pub gaps: &'a [usize],
+  /// How shrinking changed it, if asked
+  pub trim: Option<&'a Trim>,
+  /// Whether its closure was stopped by the timeout
+  pub timed_out: bool,
+  /// Whether its closure was stopped by the maximum length or number of steps
+  pub capped: bool,
+  /// Counts of its k-mers, if asked
+  pub counts: Option<&'a [u32]>
+}
+
+/// Receives the closed unitigs, each one with a globally unique id
+pub trait Sink {
+  /// Writes a closed unitig with its support and metadata
+  fn emit(&mut self, id: usize, u: &Unitig, support: u32, meta: &Metadata) -> io::Result<()>;
+
+  /// Completes the output once all closed unitigs are emitted
+  fn finish(&mut self) -> io::Result<()> {
+    Ok(())
+  }
+}
+
+/// Writes closed unitigs to a FASTA file, named `clo_ID` after their id, with their length and support as `len=`
+/// and `support=` header tags, the number of k-mers they closed as `nk=` header tag,
+/// the positions of their gap k-mers, if any, as `gaps=` header tag and, if given, their length before shrinking,
+/// the bases trimmed on the left and on the right and their support before shrinking as `cl=`, `tl=`, `tr=`
+/// and `cs=` header tags, `timeout=1` or `capped=1` if their closure was stopped by the timeout or by the maximum
+/// length or steps, and their name, length and support to another file, one per line, separated by tabs,
+/// so that the two files can be joined on the name.
+/// If the counts of their k-mers are given, their count and those are also written BCALM-style as `km:f:` and `ab:Z:` header tags.
+pub struct FastaSink<T: Write, U: Write> {
+  fasta: FastaWriter<T>,
+  counts: Encoder<U>
+}
+
+impl<T: Write, U: Write> FastaSink<T, U> {
+  /// Creates a sink writing to the given FASTA and counts writers
+  pub fn new(fasta: T, counts: U) -> FastaSink<T, U> {
+    FastaSink::with_writer(FastaWriter::new(fasta, FastaOptions::default()).unwrap(), Encoder::Plain(counts))
+  }
+
+  /// Creates a sink writing with the given FASTA and counts writers
+  pub fn with_writer(fasta: FastaWriter<T>, counts: Encoder<U>) -> FastaSink<T, U> {
+    FastaSink{fasta, counts}
+  }
+}
+
+impl<T: Write> FastaSink<T, io::Sink> {
+  /// Creates a sink writing only the FASTA file, for closed unitigs emitted with the counts of their k-mers
+  pub fn without_counts(fasta: FastaWriter<T>) -> FastaSink<T, io::Sink> {
+    FastaSink::with_writer(fasta, Encoder::Plain(io::sink()))
+  }
+}
+
+impl<T: Write, U: Write> Sink for FastaSink<T, U> {
+  fn emit(&mut self, id: usize, u: &Unitig, support: u32, meta: &Metadata) -> io::Result<()> {
+    self.fasta.write(&FastaRecord{name: Some(format!("clo_{}", id)), tags: tags(u.len(), support, meta), sequence: &u.to_string()})?;
+    writeln!(self.counts, "clo_{}\t{}\t{}", id, u.len(), support)
+  }
+
+  fn finish(&mut self) -> io::Result<()> {
+    self.fasta.finish()?;
+    self.counts.finish()
+  }
+}
+
+/// Header tags of a closed unitig: its length and support, the number of k-mers it closed, the positions of its gap k-mers,
+/// if there are any, its length before shrinking, the bases trimmed on each side and its support before shrinking, if given, whether it timed out
+/// or was capped, and its count with the counts of its k-mers, if given
+fn tags(len: usize, support: u32, &Metadata{closed, gaps, trim, timed_out, capped, counts}: &Metadata) -> Vec<String> {
+  let mut tags = vec![format!("len={}", len), format!("support={}", support), format!("nk={}", closed)];
+  if !gaps.is_empty() {
+    tags.push(format!("gaps={}", gaps.iter().map(ToString::to_string).collect::<Vec<_>>().join(",")));
+  }
+  if let Some(Trim{closure_len, left, right, support}) = trim {
+    tags.extend([format!("cl={}", closure_len), format!("tl={}", left), format!("tr={}", right), format!("cs={}", support)]);
+  }
+  if timed_out {
+    tags.push(String::from("timeout=1"));
+  }
+  if capped {
+    tags.push(String::from("capped=1"));
+  }
+  if let Some(counts) = counts {
+    tags.push(format!("km:f:{:.1}", support as f64));
+    tags.push(format!("ab:Z:{}", counts.iter().map(ToString::to_string).collect::<Vec<_>>().join(" ")));
+  }
+  tags
+}
+
+/// A FASTA and counts file pair of a sharded output
+struct Shard {
+  fasta: PathBuf,
+  counts: PathBuf,
+  sink: FastaSink<BufWriter<File>, BufWriter<File>>,
+  records: usize
+}
+
+/// Creates a text file starting with the given comment lines
+pub fn create_with_comments(path: &Path, comments: &str) -> io::Result<BufWriter<File>> {
+  let mut out = BufWriter::new(File::create(path)?);
+  out.write_all(comments.as_bytes())?;
+  Ok(out)
+}
+
+/// Buffers and compresses as asked a counts writer, starting with the given comment lines
+pub fn counts_with_comments<W: Write>(out: W, compress: Option<Compress>, comments: &str) -> io::Result<Encoder<BufWriter<W>>> {
+  let mut out = Encoder::new(BufWriter::new(out), compress)?;
+  out.write_all(comments.as_bytes())?;
+  Ok(out)
+}
+
+/// Writes the statistics of the closed unitigs by support as TSV, after the given comment lines
+pub fn write_support_summary(path: &Path, per_support: &BTreeMap<u32, SupportStats>, comments: &str) -> io::Result<()> {
+  let mut out = create_with_comments(path, comments)?;
+  writeln!(out, "support\tn_unitigs\ttotal_bp\tmean_len\tmax_len")?;
+  for (support, stats) in per_support {
+    let mean = format::ratio(stats.mean_len()).unwrap(); // there is at least one closed unitig
+    writeln!(out, "{}\t{}\t{}\t{}\t{}", support, stats.unitigs, stats.bases, mean, stats.max_len)?;
+  }
+  out.flush()
+}
+
+/// Writes the number of k-mers with each count as TSV, after the given comment lines
+pub fn write_spectrum(path: &Path, spectrum: &Spectrum, comments: &str) -> io::Result<()> {
+  let mut out = create_with_comments(path, comments)?;
+  writeln!(out, "count\tkmers")?;
+  for (count, kmers) in &spectrum.kmers {
+    writeln!(out, "{}\t{}", count, kmers)?;
+  }
+  out.flush()
+}
+
+/// Writes the number of k-mers and of input bases of each connected component as TSV
+pub fn write_components(path: &Path, components: &Components) -> io::Result<()> {
+  let mut out = BufWriter::new(File::create(path)?);
+  writeln!(out, "component\tkmers\tinput_bp")?;
+  for (c, (kmers, bases)) in components.kmers.iter().zip(&components.bases).enumerate() {
+    writeln!(out, "{}\t{}\t{}", c, kmers, bases)?;
+  }
+  out.flush()
+}
+
+/// Writes the k-mers not represented in the output, with their count and the reason, as TSV after the given comment lines
+pub fn write_unrepresented(path: &Path, unrepresented: &[Unrepresented], comments: &str) -> io::Result<()> {
+  let mut out = create_with_comments(path, comments)?;
+  writeln!(out, "kmer\tcount\treason")?;
+  for Unrepresented{kmer, count, reason} in unrepresented {
+    writeln!(out, "{}\t{}\t{}", kmer, count, reason)?;
+  }
+  out.flush()
+}
+
+impl Shard {
+  /// Creates the files of a shard, named by a prefix, with the given comment lines at the top of the counts file
+  fn create(prefix: &Path, options: FastaOptions, comments: &str) -> io::Result<Shard> {
+    let (fasta, counts) = (with_suffix(prefix, &options.extension()), with_suffix(prefix, &options.counts_extension()));
+    let sink = FastaSink::with_writer(FastaWriter::create(&fasta, options)?, counts_with_comments(File::create(&counts)?, options.compress, comments)?);
+    Ok(Shard{fasta, counts, sink, records: 0})
+  }
+
+  /// Writes a closed unitig, named after its global id
+  fn emit(&mut self, id: usize, u: &Unitig, support: u32, meta: &Metadata) -> io::Result<()> {
+    self.records += 1;
+    self.sink.emit(id, u, support, meta)
+  }
+
+  /// Flushes the shard files and lists them in a manifest line after the given leading columns
+  fn finish(&mut self, manifest: &mut impl Write, columns: &str) -> io::Result<()> {
+    self.sink.finish()?;
+    writeln!(manifest, "{}{}\t{}\t{}", columns, self.fasta.display(), self.counts.display(), self.records)
+  }
+}
+
+/// Splits closed unitigs round-robin among `PREFIX.i.fa` FASTA files with matching `PREFIX.i.counts` files,
+/// and lists them with their number of records in a `PREFIX.shards.tsv` manifest.
+/// Headers contain the global id of the closed unitig, so records can be traced across shards, and its `nk=` tag.
+pub struct ShardedSink {
+  shards: Vec<Shard>,
+  manifest: PathBuf,
+  comments: String
+}
+
+impl ShardedSink {
+  /// Creates the files of `n` shards, written as given, with the given comment lines at the top of the text files
+  pub fn create(prefix: &Path, n: usize, fasta: FastaOptions, comments: &str) -> io::Result<ShardedSink> {
+    assert!(n > 0, "At least one shard is needed");
+    let shards = (0..n)
+      .map(|i| Shard::create(&with_suffix(prefix, &format!(".{}", i)), fasta, comments))
+      .collect::<io::Result<_>>()?;
+    Ok(ShardedSink{shards, manifest: with_suffix(prefix, ".shards.tsv"), comments: String::from(comments)})
+  }
+}
+
+impl Sink for ShardedSink {
+  fn emit(&mut self, id: usize, u: &Unitig, support: u32, meta: &Metadata) -> io::Result<()> {
+    let n = self.shards.len();
+    self.shards[id % n].emit(id, u, support, meta)
+  }
+
+  fn finish(&mut self) -> io::Result<()> {
+    let mut manifest = create_with_comments(&self.manifest, &self.comments)?;
+    writeln!(manifest, "fasta\tcounts\trecords")?;
+    for shard in &mut self.shards {
+      shard.finish(&mut manifest, "")?;
+    }
+    manifest.flush()
+  }
+}
+
+/// Group of supports written to the same file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum SupportBin {
+  Exactly(u32),
+  AtLeast(u32)
+}
+
+/// Splits closed unitigs by support, writing each support value `s` to `DIR/support_s.fa` and `DIR/support_s.counts`,
+/// and the supports from the cap on (if any) together to `DIR/support_ge_CAP.fa` and `DIR/support_ge_CAP.counts`.
+/// The files are listed with their support and number of records in a `DIR/supports.tsv` manifest.
+pub struct SupportSink {
+  dir: PathBuf,
+  cap: Option<u32>,
+  shards: BTreeMap<SupportBin, Shard>,
+  fasta: FastaOptions,
+  comments: String
+}
+
+impl SupportSink {
+  /// Creates the output directory; the FASTA files will be written as given and the text files will start
+  /// with the given comment lines
+  pub fn create(dir: &Path, cap: Option<u32>, fasta: FastaOptions, comments: &str) -> io::Result<SupportSink> {
+    fs::create_dir_all(dir)?;
+    Ok(SupportSink{dir: dir.to_path_buf(), cap, shards: BTreeMap::new(), fasta, comments: String::from(comments)})
+  }
+}
+
+impl Sink for SupportSink {
+  fn emit(&mut self, id: usize, u: &Unitig, support: u32, meta: &Metadata) -> io::Result<()> {
+    let bin = match self.cap {
+      Some(cap) if support >= cap => SupportBin::AtLeast(cap),
+      _ => SupportBin::Exactly(support)
+    };
+    let shard = match self.shards.entry(bin) {
+      Entry::Occupied(e) => e.into_mut(),
+      Entry::Vacant(e) => {
+        let name = match bin {
+          SupportBin::Exactly(s) => format!("support_{}", s),
+          SupportBin::AtLeast(s) => format!("support_ge_{}", s)
+        };
+        e.insert(Shard::create(&self.dir.join(name), self.fasta, &self.comments)?)
+      }
+    };
+    shard.emit(id, u, support, meta)
+  }
+
+  fn finish(&mut self) -> io::Result<()> {
+    let mut manifest = create_with_comments(&self.dir.join("supports.tsv"), &self.comments)?;
+    writeln!(manifest, "support\tfasta\tcounts\trecords")?;
+    for (bin, shard) in &mut self.shards {
+      let support = match bin {
+        SupportBin::Exactly(s) => s.to_string(),
+        SupportBin::AtLeast(s) => format!(">={}", s)
+      };
+      shard.finish(&mut manifest, &(support + "\t"))?;
+    }
+    manifest.flush()
+  }
+}
+
+/// Splits closed unitigs by connected component of the graph, writing each component `c` to `DIR/component_c.fa`
+/// and `DIR/component_c.counts`, and the components with fewer k-mers than the minimum (if any) together
+/// to `DIR/rest.fa` and `DIR/rest.counts`. The components are listed in a `DIR/components.tsv` manifest with their
+/// number of k-mers, the bases of their input records, the number and total length of their closed unitigs and their files.
+pub struct ComponentSink<'a> {
+  dir: PathBuf,
+  graph: &'a Graph,
+  components: Components,
+  min_kmers: Option<usize>,
+  /// Files of each component, None for the pooled ones
+  shards: BTreeMap<Option<usize>, Shard>,
+  /// Number and total length of the closed unitigs of each component
+  written: Vec<(usize, u64)>,
+  fasta: FastaOptions,
+  comments: String
+}
+
+impl<'a> ComponentSink<'a> {
+  /// Labels the components of a graph and creates the output directory; the FASTA files will be written as given
+  /// and the text files will start with the given comment lines
+  pub fn create(dir: &Path, graph: &'a Graph, min_kmers: Option<usize>, fasta: FastaOptions, comments: &str) -> io::Result<ComponentSink<'a>> {
+    fs::create_dir_all(dir)?;
+    let components = graph.components();
+    let written = vec![(0, 0); components.len()];
+    let shards = BTreeMap::new();
+    Ok(ComponentSink{dir: dir.to_path_buf(), graph, components, min_kmers, shards, written, fasta, comments: String::from(comments)})
+  }
+
+  /// Files of a component, None if it is pooled with the other small ones
+  fn shard_of(&self, component: usize) -> Option<usize> {
+    Some(component).filter(|&c| self.min_kmers.map_or(true, |min| self.components.kmers[c] >= min))
+  }
+
+  /// Base name of the files of a component
+  fn name(shard: Option<usize>) -> String {
+    match shard {
+      Some(c) => format!("component_{}", c),
+      None => String::from("rest")
+    }
+  }
+}
+
+impl Sink for ComponentSink<'_> {
+  fn emit(&mut self, id: usize, u: &Unitig, support: u32, meta: &Metadata) -> io::Result<()> {
+    let kmer = u.slice(0..self.graph.k());
+    let component = self.components.of_node[self.graph.node(&kmer).unwrap()];
+    let written = &mut self.written[component];
+    written.0 += 1;
+    written.1 += u.len() as u64;
+    let shard = self.shard_of(component);
+    let shard = match self.shards.entry(shard) {
+      Entry::Occupied(e) => e.into_mut(),
+      Entry::Vacant(e) => {
+        let name = ComponentSink::name(shard);
+        e.insert(Shard::create(&self.dir.join(name), self.fasta, &self.comments)?)
+      }
+    };
+    shard.emit(id, u, support, meta)
+  }
+
+  fn finish(&mut self) -> io::Result<()> {
+    for shard in self.shards.values_mut() {
+      shard.sink.finish()?;
+    }
+    let mut manifest = create_with_comments(&self.dir.join("components.tsv"), &self.comments)?;
+    writeln!(manifest, "component\tkmers\tinput_bp\tunitigs\tunitig_bp\tfasta\tcounts")?;
+    for (c, &(unitigs, bases)) in self.written.iter().enumerate() {
+      // Components without closed unitigs have no files
+      let (fasta, counts) = match self.shards.get(&self.shard_of(c)) {
+        Some(shard) if unitigs > 0 => (shard.fasta.display().to_string(), shard.counts.display().to_string()),
+        _ => (String::from("-"), String::from("-"))
+      };
+      writeln!(manifest, "{}\t{}\t{}\t{}\t{}\t{}\t{}", c, self.components.kmers[c], self.components.bases[c], unitigs, bases, fasta, counts)?;
+    }
+    manifest.flush()
+  }
+}
+
+/// Forwards closed unitigs to another sink, writing the count of each k-mer window of the selected ones
+/// to a TSV file with `id`, `position` and `count` columns
+pub struct ProfileSink<'a> {
+  inner: Box<dyn Sink + 'a>,
+  graph: &'a Graph,
+  /// Ids to profile, or None for all of them
+  ids: Option<HashSet<usize>>,
+  out: BufWriter<File>
+}
+
+impl<'a> ProfileSink<'a> {
+  /// Creates a sink profiling the given ids (all if None) of the closed unitigs of a graph,
+  /// writing the given comment lines at the top of the TSV file
+  pub fn create(inner: Box<dyn Sink + 'a>, graph: &'a Graph, ids: Option<HashSet<usize>>, path: &Path, comments: &str) -> io::Result<ProfileSink<'a>> {
+    let mut out = create_with_comments(path, comments)?;
+    writeln!(out, "id\tposition\tcount")?;
+    Ok(ProfileSink{inner, graph, ids, out})
+  }
+}
+
+impl Sink for ProfileSink<'_> {
+  fn emit(&mut self, id: usize, u: &Unitig, support: u32, meta: &Metadata) -> io::Result<()> {
+    if self.ids.as_mut().map_or(true, |ids| ids.remove(&id)) {
+      for (position, count) in self.graph.support_profile(u).unwrap().into_iter().enumerate() {
+        writeln!(self.out, "{}\t{}\t{}", id, position, count)?;
+      }
+    }
+    self.inner.emit(id, u, support, meta)
+  }
+
+  fn finish(&mut self) -> io::Result<()> {
+    if let Some(ids) = &self.ids {
+      let mut unknown: Vec<_> = ids.iter().collect();
+      unknown.sort_unstable();
+      for id in unknown {
+        log::warn!("no closed unitig with id {} to profile", id);
+      }
+    }
+    self.out.flush()?;
+    self.inner.finish()
+  }
+}
+
+/// Forwards closed unitigs to another sink, writing for each one the input records its k-mers come from to a TSV file,
+/// with `id` and `path` columns. The path lists the runs of consecutive k-mers of a record, in the order of the closed unitig,
+/// as `record+:first-last` if read on the strand of the record and `record-:first-last` otherwise, first and last being
+/// the 0-based positions of the k-mers of the run in the record, separated by commas.
+pub struct ProvenanceSink<'a> {
+  inner: Box<dyn Sink + 'a>,
+  graph: &'a Graph,
+  out: BufWriter<File>
+}
+
+impl<'a> ProvenanceSink<'a> {
+  /// Creates a sink writing the provenance of the closed unitigs of a graph, with the given comment lines at the top of the TSV file
+  pub fn create(inner: Box<dyn Sink + 'a>, graph: &'a Graph, path: &Path, comments: &str) -> io::Result<ProvenanceSink<'a>> {
+    let mut out = create_with_comments(path, comments)?;
+    writeln!(out, "id\tpath")?;
+    Ok(ProvenanceSink{inner, graph, out})
+  }
+}
+
+impl Sink for ProvenanceSink<'_> {
+  fn emit(&mut self, id: usize, u: &Unitig, support: u32, meta: &Metadata) -> io::Result<()> {
+    // Runs of k-mers as record, strand, first and last position
+    let mut runs: Vec<(usize, Orientation, usize, usize)> = Vec::new();
+    for (origin, dir) in self.graph.provenance(u).unwrap() {
+      match runs.last_mut() {
+        Some((record, run_dir, _, last)) if *record == origin.record && *run_dir == dir
+          && Some(origin.offset) == if dir == Orientation::Forward {last.checked_add(1)} else {last.checked_sub(1)} => *last = origin.offset,
+        _ => runs.push((origin.record, dir, origin.offset, origin.offset))
+      }
+    }
+    let path: Vec<_> = runs.iter().map(|(record, dir, first, last)| format!("{}{}:{}-{}", record, dir.sign(), first, last)).collect();
+    writeln!(self.out, "{}\t{}", id, path.join(","))?;
+    self.inner.emit(id, u, support, meta)
+  }
+
+  fn finish(&mut self) -> io::Result<()> {
+    self.out.flush()?;
+    self.inner.finish()
+  }
+}
+
+/// Forwards closed unitigs to another sink and, once all are written, writes every k-mer of the graph as a canonical k-mer
+/// to a TSV file, with its count, the id of the first closed unitig containing it (`-` if none does) and the number of those
+/// containing it, as columns `kmer`, `count`, `id` and `unitigs`
+pub struct MembershipSink<'a> {
+  inner: Box<dyn Sink + 'a>,
+  graph: &'a Graph,
+  out: BufWriter<File>,
+  /// First closed unitig containing each node, and the number of them
+  members: Vec<(Option<usize>, usize)>
+}
+
+impl<'a> MembershipSink<'a> {
+  /// Creates a sink writing the membership of the k-mers of a graph, with the given comment lines at the top of the TSV file
+  pub fn create(inner: Box<dyn Sink + 'a>, graph: &'a Graph, path: &Path, comments: &str) -> io::Result<MembershipSink<'a>> {
+    let mut out = create_with_comments(path, comments)?;
+    writeln!(out, "kmer\tcount\tid\tunitigs")?;
+    let members = vec![(None, 0); graph.kmers().len()];
+    Ok(MembershipSink{inner, graph, out, members})
+  }
+}
+
+impl Sink for MembershipSink<'_> {
+  fn emit(&mut self, id: usize, u: &Unitig, support: u32, meta: &Metadata) -> io::Result<()> {
+    let k = self.graph.k();
+    let mut nodes: Vec<_> = (0..(u.len() + 1).saturating_sub(k)).map(|i| self.graph.node(&u.slice(i..i+k)).unwrap()).collect();
+    // A k-mer found twice in a closed unitig is counted once
+    nodes.sort_unstable();
+    nodes.dedup();
+    for n in nodes {
+      let (first, unitigs) = &mut self.members[n];
+      first.get_or_insert(id);
+      *unitigs += 1;
+    }
+    self.inner.emit(id, u, support, meta)
+  }
+
+  fn finish(&mut self) -> io::Result<()> {
+    for ((kmer, count), (first, unitigs)) in self.graph.kmers().zip(&self.members) {
+      let id = first.map_or_else(|| String::from("-"), |id| id.to_string());
+      writeln!(self.out, "{}\t{}\t{}\t{}", kmer.norm(), count, id, unitigs)?;
+    }
+    self.out.flush()?;
+    self.inner.finish()
+  }
+}
+
+/// Version of the GFA format written
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub enum GfaVersion {
+  /// GFA1, with links (`L`) overlapping by a number of bases
+  #[default]
+  #[serde(rename = "1")]
+  V1,
+  /// GFA2, with edges (`E`) giving the coordinates of the overlap on both segments
+  #[serde(rename = "2")]
+  V2
+}
+
+impl FromStr for GfaVersion {
+  type Err = String;
+
+  /// Parses a version from its command line name: `1` or `2`
+  fn from_str(s: &str) -> Result<GfaVersion, Self::Err> {
+    match s {
+      "1" => Ok(GfaVersion::V1),
+      "2" => Ok(GfaVersion::V2),
+      _ => Err(format!("Unknown GFA version \"{}\"", s))
+    }
+  }
+}
+
+/// Coordinates of an overlap of `overlap` bases at the start or at the end of the forward strand of a segment
+/// of length `len`, as written in GFA2 edges, the end of the segment being marked by `$`
+fn gfa2_overlap(len: usize, overlap: usize, at_end: bool) -> String {
+  let position = |pos: usize| if pos == len {format!("{}$", pos)} else {pos.to_string()};
+  let begin = if at_end {len - overlap} else {0};
+  format!("{}\t{}", position(begin), position(begin + overlap))
+}
+
+/// Forwards closed unitigs to another sink, writing them also as the segments of a GFA file, with their total
+/// k-mer count (`KC:i:`) and their mean k-mer count (`dp:f:`), as Bandage reads them. Once all are written,
+/// two closed unitigs are linked if the last k-mer of one is followed in the graph by the first k-mer of the other,
+/// on either strand; each link is written once, overlapping by k-1 bases, as a GFA1 link or a GFA2 edge.
+pub struct GfaSink<'a> {
+  inner: Box<dyn Sink + 'a>,
+  graph: &'a Graph,
+  out: BufWriter<File>,
+  version: GfaVersion,
+  /// Id, length, first and last k-mer of each closed unitig
+  ends: Vec<(usize, usize, Unitig, Unitig)>
+}
+
+impl<'a> GfaSink<'a> {
+  /// Creates a sink writing the closed unitigs of a graph to the given GFA file
+  pub fn create(inner: Box<dyn Sink + 'a>, graph: &'a Graph, path: &Path, version: GfaVersion) -> io::Result<GfaSink<'a>> {
+    let mut out = BufWriter::new(File::create(path)?);
+    match version {
+      GfaVersion::V1 => writeln!(out, "H\tVN:Z:1.0")?,
+      GfaVersion::V2 => writeln!(out, "H\tVN:Z:2.0")?
+    }
+    Ok(GfaSink{inner, graph, out, version, ends: Vec::new()})
+  }
+
+  /// First and last k-mer of a closed unitig read on the given strand
+  fn oriented((first, last): (&Unitig, &Unitig), dir: Orientation) -> (Unitig, Unitig) {
+    match dir {
+      Orientation::Forward => (first.clone(), last.clone()),
+      Orientation::Reverse => (last.rev_compl(), first.rev_compl())
+    }
+  }
+}
+
+impl Sink for GfaSink<'_> {
+  fn emit(&mut self, id: usize, u: &Unitig, support: u32, meta: &Metadata) -> io::Result<()> {
+    let counts = self.graph.support_profile(u).unwrap_or_default();
+    let total: u64 = counts.iter().map(|&c| c as u64).sum();
+    let mean = format::ratio(total as f64 / counts.len().max(1) as f64).unwrap();
+    match self.version {
+      GfaVersion::V1 => writeln!(self.out, "S\t{}\t{}\tLN:i:{}\tKC:i:{}\tdp:f:{}", id, u, u.len(), total, mean)?,
+      GfaVersion::V2 => writeln!(self.out, "S\t{}\t{}\t{}\tKC:i:{}\tdp:f:{}", id, u.len(), u, total, mean)?
+    }
+    let k = self.graph.k();
+    if u.len() >= k {
+      let kmer = |i: usize| u.slice(i..i+k);
+      self.ends.push((id, u.len(), kmer(0), kmer(u.len()-k)));
+    }
+    self.inner.emit(id, u, support, meta)
+  }
+
+  fn finish(&mut self) -> io::Result<()> {
+    // The closed unitigs starting with each k-mer, as read on each strand
+    let mut starts: HashMap<String, Vec<(usize, Orientation, usize)>> = HashMap::new();
+    for (id, len, first, last) in &self.ends {
+      for dir in [Orientation::Forward, Orientation::Reverse] {
+        let (first, _) = GfaSink::oriented((first, last), dir);
+        starts.entry(String::from(first)).or_default().push((*id, dir, *len));
+      }
+    }
+    let overlap = self.graph.k() - 1;
+    for (id, len, first, last) in &self.ends {
+      for dir in [Orientation::Forward, Orientation::Reverse] {
+        let (_, last) = GfaSink::oriented((first, last), dir);
+        for next in self.graph.neighbors(&last, true).unwrap_or_default() {
+          for &(to, to_dir, to_len) in starts.get(&next.kmer.to_string()).into_iter().flatten() {
+            // The same link read on the other strand
+            if (*id, dir) > (to, !to_dir) {
+              continue;
+            }
+            match self.version {
+              GfaVersion::V1 => writeln!(self.out, "L\t{}\t{}\t{}\t{}\t{}M", id, dir.sign(), to, to_dir.sign(), overlap)?,
+              // The overlap is at the end of the first segment as read, and at the start of the second one
+              GfaVersion::V2 => writeln!(self.out, "E\t*\t{}{}\t{}{}\t{}\t{}\t{}M", id, dir.sign(), to, to_dir.sign(),
+                gfa2_overlap(*len, overlap, dir == Orientation::Forward), gfa2_overlap(to_len, overlap, to_dir == Orientation::Reverse), overlap)?
+            }
+          }
+        }
+      }
+    }
+    self.out.flush()?;
+    self.inner.finish()
+  }
+}
@@ -0,0 +1,146 @@
+//! K-mer count spectrum of a graph, and the choice of a minimum count from it
+
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+/// Default largest fraction of the total count mass which a suggested minimum count may remove
+pub const MAX_REMOVED_MASS: f64 = 0.1;
+
+/// Minimum count of the k-mers kept
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum MinCount {
+  Fixed(u32),
+  /// The one suggested by the spectrum
+  Auto
+}
+
+impl FromStr for MinCount {
+  type Err = String;
+
+  /// Parses a minimum count from its command line value: a number or `auto`
+  fn from_str(s: &str) -> Result<MinCount, Self::Err> {
+    match s {
+      "auto" => Ok(MinCount::Auto),
+      _ => s.parse().map(MinCount::Fixed).map_err(|_| format!("Invalid minimum count \"{}\"", s))
+    }
+  }
+}
+
+/// Number of k-mers with each count
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Spectrum {
+  pub kmers: BTreeMap<u32, usize>
+}
+
+impl Spectrum {
+  /// Builds the spectrum of the given k-mer counts
+  pub fn new(counts: impl Iterator<Item = u32>) -> Spectrum {
+    let mut kmers = BTreeMap::new();
+    for count in counts {
+      *kmers.entry(count).or_insert(0) += 1;
+    }
+    Spectrum{kmers}
+  }
+
+  /// Number of k-mers with a count, zero if there are none
+  fn get(&self, count: u32) -> usize {
+    self.kmers.get(&count).copied().unwrap_or(0)
+  }
+
+  /// Count below which lies the given fraction (between 0 and 1) of the k-mers, by the nearest rank; None without k-mers
+  pub fn quantile(&self, fraction: f64) -> Option<u32> {
+    let total: usize = self.kmers.values().sum();
+    let rank = ((fraction * total as f64).ceil() as usize).max(1);
+    let mut below = 0;
+    self.kmers.iter().find(|(_, &n)| {
+      below += n;
+      below >= rank
+    }).map(|(&count, _)| count)
+  }
+
+  /// Suggests a minimum count: the first local minimum after the peak of the erroneous k-mers, at the lowest counts,
+  /// lowered so that the k-mers below it carry at most the given fraction of the total count mass.
+  /// Without a valley (a spectrum which never rises again, or rises from the lowest count on) the suggestion is 1,
+  /// which keeps every k-mer.
+  pub fn suggest_min_count(&self, max_removed: f64) -> u32 {
+    let (Some(&first), Some(&last)) = (self.kmers.keys().next(), self.kmers.keys().next_back()) else {return 1};
+    // Go down from the error peak to the first count followed by a higher one
+    let mut valley = first;
+    while valley < last && self.get(valley + 1) <= self.get(valley) {
+      valley += 1;
+    }
+    if valley == first || valley == last {
+      return 1;
+    }
+
+    let total: u64 = self.kmers.iter().map(|(&c, &n)| c as u64 * n as u64).sum();
+    let mut removed = 0;
+    for (&count, &n) in self.kmers.range(..valley) {
+      removed += count as u64 * n as u64;
+      if removed as f64 > max_removed * total as f64 {
+        return count.max(1); // keeps this count, removing only the lower ones
+      }
+    }
+    valley
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// Spectrum of erroneous k-mers, decaying from count 1, and of genomic ones, around the coverage
+  fn bimodal(errors: usize, coverage: u32) -> Spectrum {
+    let kmers = (1..=3 * coverage).map(|c| {
+      let error = errors as f64 * 0.3f64.powi(c as i32 - 1);
+      let genome = 1000. * (-((c as f64 - coverage as f64).powi(2)) / (2. * coverage as f64)).exp();
+      (c, (error + genome).round() as usize)
+    });
+    Spectrum{kmers: kmers.filter(|&(_, n)| n > 0).collect()}
+  }
+
+  #[test]
+  fn bimodal_spectra_are_split_at_the_valley() {
+    let spectrum = Spectrum{kmers: [(1, 1000), (2, 300), (3, 80), (4, 20), (5, 30), (6, 60), (7, 90), (8, 60), (9, 20)].into()};
+    assert_eq!(spectrum.suggest_min_count(1.), 4);
+    for coverage in [10, 20, 30, 50] {
+      let spectrum = bimodal(20000, coverage);
+      // The valley is one of the least frequent counts between the peaks
+      let valley = spectrum.suggest_min_count(1.);
+      assert!(valley > 1 && valley < coverage, "coverage {}", coverage);
+      assert_eq!(spectrum.get(valley), (1..coverage).map(|c| spectrum.get(c)).min().unwrap(), "coverage {}", coverage);
+    }
+  }
+
+  #[test]
+  fn suggestions_remove_at_most_the_given_mass() {
+    let spectrum = Spectrum{kmers: [(1, 10000), (2, 10), (3, 1000)].into()};
+    assert_eq!(spectrum.suggest_min_count(1.), 2);
+    assert_eq!(spectrum.suggest_min_count(0.1), 1);
+    let spectrum = bimodal(200000, 20);
+    let suggested = spectrum.suggest_min_count(0.05);
+    let mass = |range: std::ops::Range<u32>| spectrum.kmers.range(range).map(|(&c, &n)| c as u64 * n as u64).sum::<u64>();
+    assert!(suggested < spectrum.suggest_min_count(1.));
+    assert!(mass(0..suggested) as f64 <= 0.05 * mass(0..u32::MAX) as f64);
+  }
+
+  #[test]
+  fn quantiles_are_counts_of_the_spectrum() {
+    let spectrum = Spectrum::new(vec![5, 1, 3, 3, 9, 3, 1, 2].into_iter());
+    let quantiles: Vec<_> = [0., 0.25, 0.5, 0.75, 1.].iter().map(|&q| spectrum.quantile(q).unwrap()).collect();
+    assert_eq!(quantiles, [1, 1, 3, 3, 9]);
+    assert_eq!(Spectrum::default().quantile(0.5), None);
+  }
+
+  #[test]
+  fn spectra_without_valley_keep_every_kmer() {
+    assert_eq!(Spectrum::default().suggest_min_count(MAX_REMOVED_MASS), 1);
+    assert_eq!(Spectrum::new(vec![3, 3, 3].into_iter()).suggest_min_count(MAX_REMOVED_MASS), 1);
+    // Only erroneous k-mers
+    assert_eq!(Spectrum{kmers: [(1, 1000), (2, 300), (3, 300), (4, 20)].into()}.suggest_min_count(1.), 1);
+    // No erroneous k-mers
+    assert_eq!(Spectrum{kmers: [(5, 10), (6, 30), (7, 10)].into()}.suggest_min_count(1.), 1);
+  }
+}
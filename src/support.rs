@@ -0,0 +1,167 @@
+//! Measures of the k-mer counts of an unitig giving its support
+
+use serde::Serialize;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::str::FromStr;
+
+/// Measure of the k-mer counts of an unitig, computed incrementally while a closure joins k-mers.
+/// A closure is extended only by k-mers whose count is at least its support, which never decreases it:
+/// any measure implementing this trait must keep that property.
+pub trait SupportMeasure {
+  /// Adds the count of a k-mer joined to the unitig
+  fn push(&mut self, count: u32);
+
+  /// Support of the counts added so far, 0 if there is none
+  fn support(&self) -> u32;
+}
+
+/// Minimum count, the support of a closed unitig proper
+#[derive(Debug, Clone, Default)]
+pub struct MinSupport(Option<u32>);
+
+impl SupportMeasure for MinSupport {
+  fn push(&mut self, count: u32) {
+    self.0 = Some(self.0.map_or(count, |min| min.min(count)));
+  }
+
+  fn support(&self) -> u32 {
+    self.0.unwrap_or(0)
+  }
+}
+
+/// Mean count, rounded down
+#[derive(Debug, Clone, Default)]
+pub struct MeanSupport {
+  sum: u64,
+  n: u64
+}
+
+impl SupportMeasure for MeanSupport {
+  fn push(&mut self, count: u32) {
+    self.sum += count as u64;
+    self.n += 1;
+  }
+
+  fn support(&self) -> u32 {
+    self.sum.checked_div(self.n).unwrap_or(0) as u32
+  }
+}
+
+/// Count at a fraction of the sorted counts (nearest rank), as [`Spectrum::quantile`](super::Spectrum::quantile):
+/// the lower counts, up to it, are kept in a max-heap and the others in a min-heap, so that adding one takes O(log n)
+#[derive(Debug, Clone)]
+pub struct QuantileSupport {
+  fraction: f64,
+  lower: BinaryHeap<u32>,
+  upper: BinaryHeap<Reverse<u32>>
+}
+
+impl QuantileSupport {
+  /// Measures the count at the given fraction, between 0 and 1
+  pub fn new(fraction: f64) -> QuantileSupport {
+    QuantileSupport{fraction, lower: BinaryHeap::new(), upper: BinaryHeap::new()}
+  }
+}
+
+impl SupportMeasure for QuantileSupport {
+  fn push(&mut self, count: u32) {
+    match self.lower.peek() {
+      Some(&max) if count > max => self.upper.push(Reverse(count)),
+      _ => self.lower.push(count)
+    }
+    // Rank of the quantile among the counts, from 1
+    let rank = ((self.fraction * (self.lower.len() + self.upper.len()) as f64).ceil() as usize).max(1);
+    while self.lower.len() > rank {
+      self.upper.push(Reverse(self.lower.pop().unwrap()));
+    }
+    while self.lower.len() < rank {
+      self.lower.push(self.upper.pop().unwrap().0);
+    }
+  }
+
+  fn support(&self) -> u32 {
+    self.lower.peek().copied().unwrap_or(0)
+  }
+}
+
+/// Definition of the support of an unitig from the counts of its k-mers
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Support {
+  /// Minimum count: every k-mer of a closed unitig has at least its support
+  #[default]
+  Min,
+  /// Mean count, smoothing the abundance along the unitig
+  Mean,
+  /// Median count
+  Median,
+  /// Count at the given percentile, from 1 to 99
+  Percentile(u8)
+}
+
+impl Support {
+  /// Incremental measure of this support, with no count yet
+  pub fn measure(&self) -> Box<dyn SupportMeasure> {
+    match *self {
+      Support::Min => Box::new(MinSupport::default()),
+      Support::Mean => Box::new(MeanSupport::default()),
+      Support::Median => Box::new(QuantileSupport::new(0.5)),
+      Support::Percentile(p) => Box::new(QuantileSupport::new(p as f64 / 100.))
+    }
+  }
+
+  /// Support of the given counts
+  pub fn of(&self, counts: impl IntoIterator<Item = u32>) -> u32 {
+    let mut measure = self.measure();
+    for count in counts {
+      measure.push(count);
+    }
+    measure.support()
+  }
+}
+
+impl FromStr for Support {
+  type Err = String;
+
+  /// Parses a support from its command line name: `min`, `mean`, `median` or `pN`, N being a percentile from 1 to 99
+  fn from_str(s: &str) -> Result<Support, Self::Err> {
+    match s {
+      "min" => Ok(Support::Min),
+      "mean" => Ok(Support::Mean),
+      "median" => Ok(Support::Median),
+      _ => s.strip_prefix('p').and_then(|p| p.parse().ok()).filter(|p| (1..100).contains(p)).map(Support::Percentile)
+        .ok_or_else(|| format!("Unknown support \"{}\"", s))
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn supports_are_measured_incrementally() {
+    let counts = [7, 3, 9, 4, 4, 12, 8];
+    for (support, expected) in [("min", 3), ("mean", 6), ("median", 7), ("p25", 4), ("p99", 12)] {
+      let support: Support = support.parse().unwrap();
+      assert_eq!(support.of(counts.iter().copied()), expected, "{:?}", support);
+      // Every prefix agrees with sorting it
+      let mut measure = support.measure();
+      for n in 1..=counts.len() {
+        measure.push(counts[n - 1]);
+        let mut sorted = counts[..n].to_vec();
+        sorted.sort_unstable();
+        let expected = match support {
+          Support::Min => sorted[0],
+          Support::Mean => sorted.iter().sum::<u32>() / n as u32,
+          Support::Median => sorted[((0.5 * n as f64).ceil() as usize).max(1) - 1],
+          Support::Percentile(p) => sorted[((p as f64 / 100. * n as f64).ceil() as usize).max(1) - 1]
+        };
+        assert_eq!(measure.support(), expected, "{:?} of {:?}", support, sorted);
+      }
+    }
+    assert_eq!(Support::Mean.of(Vec::new()), 0);
+    assert!("p0".parse::<Support>().is_err() && "p100".parse::<Support>().is_err() && "max".parse::<Support>().is_err());
+  }
+}
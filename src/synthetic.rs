@@ -0,0 +1,207 @@
+//! Synthetic de Bruijn graphs for tests and benchmarks.
+//! The deterministic generators also give the closed unitigs expected with the default options.
+
+use crate::graph::{Graph, GraphBuilder, Orientation, Unitig};
+use rand::{Rng, SeedableRng, seq::SliceRandom};
+use rand_pcg::Pcg64;
+use std::collections::{BTreeMap, HashSet};
+use std::convert::TryFrom;
+
+/// Seed of the deterministic generators
+const SEED: u64 = 0;
+/// Length of the reads sampled from random genomes
+const READ_LEN: usize = 100;
+
+/// A graph with the closed unitigs it gives with the default close options
+#[derive(Debug)]
+pub struct Synthetic {
+  pub graph: Graph,
+  /// Closed unitigs with their support, in lexicographic order
+  pub expected: Vec<(String, u32)>
+}
+
+/// Draws sequences whose k-mers are all distinct, on either strand, and not palindromes
+struct Kmers {
+  k: usize,
+  used: HashSet<Unitig>,
+  rng: Pcg64
+}
+
+impl Kmers {
+  fn new(k: usize) -> Kmers {
+    Kmers{k, used: HashSet::new(), rng: Pcg64::seed_from_u64(SEED)}
+  }
+
+  /// Marks a k-mer as used, if it can be
+  fn take(&mut self, kmer: &str) -> bool {
+    let kmer = Unitig::try_from(kmer).unwrap();
+    !kmer.is_identical(&kmer.rev_compl()) && self.used.insert(kmer)
+  }
+
+  /// Starts a sequence with an unused k-mer
+  fn start(&mut self) -> String {
+    loop {
+      let seq: String = (0..self.k).map(|_| *b"ACGT".choose(&mut self.rng).unwrap() as char).collect();
+      if self.take(&seq) {
+        return seq;
+      }
+    }
+  }
+
+  /// Appends to a sequence a base making an unused k-mer
+  ///
+  /// # Panics
+  /// If every extension is already used.
+  fn extend(&mut self, seq: &mut String) {
+    let mut bases = *b"ACGT";
+    bases.shuffle(&mut self.rng);
+    for b in bases {
+      let kmer = format!("{}{}", &seq[seq.len()+1-self.k..], b as char);
+      if self.take(&kmer) {
+        seq.push(b as char);
+        return;
+      }
+    }
+    panic!("No unused k-mer extends {}", seq);
+  }
+}
+
+/// A single unitig of `n_kmers` k-mers, the i-th of which has count `count_profile(i)`.
+///
+/// # Panics
+/// If there are not enough distinct k-mers.
+pub fn generate_linear<F: Fn(usize) -> u32>(k: usize, n_kmers: usize, count_profile: F) -> Synthetic {
+  let mut kmers = Kmers::new(k);
+  let mut seq = kmers.start();
+  for _ in 1..n_kmers {
+    kmers.extend(&mut seq);
+  }
+  let counts: Vec<u32> = (0..n_kmers).map(count_profile).collect();
+  let mut builder = GraphBuilder::new();
+  builder.add_unitig(&seq, &counts);
+
+  // Each seed not closed yet extends over its neighbours with at least its count,
+  // closing those with the same count, then the ends with higher counts are trimmed
+  let mut closed = vec![false; n_kmers];
+  let mut expected = Vec::new();
+  for (i, &c) in counts.iter().enumerate() {
+    if closed[i] {continue}
+    let a = counts[..i].iter().rposition(|&x| x < c).map_or(0, |a| a + 1);
+    let b = counts[i..].iter().position(|&x| x < c).map_or(n_kmers, |b| i + b);
+    let same: Vec<_> = (a..b).filter(|&j| counts[j] == c).collect();
+    for &j in &same {
+      closed[j] = true;
+    }
+    let (a, b) = (same[0], same[same.len()-1] + 1);
+    expected.push((String::from(&seq[a..b+k-1]), c));
+  }
+  expected.sort();
+  expected.dedup();
+
+  Synthetic{graph: builder.build(k).unwrap(), expected}
+}
+
+/// A tree of unitigs of k k-mers each: the root, and `fanout` children for each unitig up to `depth` levels below it.
+/// Every k-mer has count 1, so closing gives a closed unitig for each path from the root to a leaf,
+/// whatever the seed order.
+///
+/// # Panics
+/// If `fanout` is more than 4, or there are not enough distinct k-mers.
+pub fn generate_branching(k: usize, depth: usize, fanout: usize) -> Synthetic {
+  assert!(fanout <= 4, "A k-mer has at most 4 successors");
+  let mut kmers = Kmers::new(k);
+  let mut builder = GraphBuilder::new();
+  let mut expected = Vec::new();
+
+  let mut root = kmers.start();
+  for _ in 1..k {
+    kmers.extend(&mut root);
+  }
+  // Unitigs still to be expanded, depth first, with their record, level and path from the root
+  let mut stack = vec![(builder.add_unitig(&root, &vec![1; k]), 0, root)];
+  while let Some((id, level, path)) = stack.pop() {
+    if level == depth {
+      expected.push((path, 1));
+      continue;
+    }
+    let mut children = Vec::new();
+    for _ in 0..fanout {
+      let mut seq = String::from(&path[path.len()+1-k..]);
+      for _ in 0..k {
+        kmers.extend(&mut seq);
+      }
+      let child = builder.add_unitig(&seq, &vec![1; k]);
+      // Links are given in both directions, as BCALM does
+      builder.add_link(id, Orientation::Forward, child, Orientation::Forward);
+      builder.add_link(child, Orientation::Reverse, id, Orientation::Reverse);
+      children.push((child, level + 1, path.clone() + &seq[k-1..]));
+    }
+    stack.extend(children.into_iter().rev());
+  }
+  expected.sort();
+
+  Synthetic{graph: builder.build(k).unwrap(), expected}
+}
+
+/// `n` unitigs of `n_kmers` k-mers each, with random counts from 1 to 5, and no links between them:
+/// each one is a connected component.
+///
+/// # Panics
+/// If there are not enough distinct k-mers.
+pub fn generate_components(k: usize, n: usize, n_kmers: usize) -> Graph {
+  let mut kmers = Kmers::new(k);
+  let mut rng = Pcg64::seed_from_u64(SEED);
+  let mut builder = GraphBuilder::new();
+  for _ in 0..n {
+    let mut seq = kmers.start();
+    for _ in 1..n_kmers {
+      kmers.extend(&mut seq);
+    }
+    let counts: Vec<u32> = (0..n_kmers).map(|_| rng.gen_range(1..=5)).collect();
+    builder.add_unitig(&seq, &counts);
+  }
+  builder.build(k).unwrap()
+}
+
+/// The graph of the k-mers of reads sampled from a random genome of length `genome_len`, on both strands,
+/// at the given coverage, with substitutions at the given rate. Each k-mer is a record of its own.
+/// `k` should be odd, so that no k-mer is its own reverse complement.
+pub fn generate_random_genome(k: usize, genome_len: usize, coverage: f64, error_rate: f64, rng_seed: u64) -> Graph {
+  let mut rng = Pcg64::seed_from_u64(rng_seed);
+  let genome: Vec<u8> = (0..genome_len).map(|_| *b"ACGT".choose(&mut rng).unwrap()).collect();
+  let read_len = READ_LEN.min(genome_len);
+
+  // Count canonical k-mers
+  let mut counts = BTreeMap::<String, u32>::new();
+  for _ in 0..(coverage * genome_len as f64 / read_len as f64).round() as usize {
+    let start = rng.gen_range(0..=genome_len - read_len);
+    let read: String = genome[start..start+read_len].iter()
+      .map(|&b| if rng.gen_bool(error_rate) {*b"ACGT".choose(&mut rng).unwrap() as char} else {b as char})
+      .collect();
+    let read = if rng.gen() {read} else {String::from(Unitig::try_from(read).unwrap().rev_compl())};
+    for i in 0..(read_len + 1).saturating_sub(k) {
+      *counts.entry(String::from(Unitig::try_from(&read[i..i+k]).unwrap().norm())).or_insert(0) += 1;
+    }
+  }
+
+  let mut builder = GraphBuilder::new();
+  let ids: BTreeMap<_, _> = counts.iter().map(|(kmer, &c)| (kmer, builder.add_unitig(kmer, &[c]))).collect();
+  // Link each k-mer to its successors on both strands
+  for (kmer, &id) in &ids {
+    for dir in [Orientation::Forward, Orientation::Reverse] {
+      let kmer = match dir {
+        Orientation::Forward => String::from(kmer.as_str()),
+        Orientation::Reverse => String::from(Unitig::try_from(kmer.as_str()).unwrap().rev_compl())
+      };
+      for b in ['A', 'C', 'G', 'T'] {
+        let next = format!("{}{}", &kmer[1..], b);
+        let norm = String::from(Unitig::try_from(next.as_str()).unwrap().norm());
+        if let Some(&to) = ids.get(&norm) {
+          builder.add_link(id, dir, to, if next == norm {Orientation::Forward} else {Orientation::Reverse});
+        }
+      }
+    }
+  }
+
+  builder.build(k).unwrap()
+}
@@ -0,0 +1,250 @@
+//! Progress and diagnostic messages, written to stderr.
+//! Diagnostics go through the `log` crate, and are written by the logger installed with [`init_log`].
+//! Progress bars are drawn only on a terminal, unless `NO_COLOR` is set or `TERM` is `dumb`:
+//! elsewhere (log files, pipes) progress updates are omitted and messages are plain lines.
+//! Progress can instead be reported as newline-delimited JSON events, for workflow managers.
+
+use indicatif::{ProgressBar, ProgressStyle};
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt::Display;
+use std::io::{self, IsTerminal, Write};
+use std::sync::{Mutex, OnceLock};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Minimum interval between two JSON progress events
+const THROTTLE: Duration = Duration::from_secs(1);
+/// Minimum interval between two updates of the progress bar
+const BAR_THROTTLE: Duration = Duration::from_millis(100);
+/// Progress is reported every this many calls, so that reporting it for each node does not slow down the run
+const EVERY: usize = 1024;
+
+/// Phases of a run
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Phase {
+  /// Reading the input
+  Parse,
+  /// Closing the graph
+  Close
+}
+
+/// Progress of a phase
+#[derive(Debug, Clone, Copy)]
+pub enum Progress {
+  /// Records read, and the bytes they take
+  Parse{records: usize, bytes: u64},
+  /// Seeds processed out of all of them, and k-mers closed out of all of them
+  Close{seeds_done: usize, seeds_total: usize, closed: usize, kmers: usize}
+}
+
+impl Progress {
+  /// Phase whose progress this is
+  fn phase(&self) -> Phase {
+    match self {
+      Progress::Parse{..} => Phase::Parse,
+      Progress::Close{..} => Phase::Close
+    }
+  }
+
+  /// Progress bar of the phase: the bytes read while parsing, whose total is unknown, and the seeds processed while closing
+  fn bar(&self) -> ProgressBar {
+    let (bar, template) = match *self {
+      Progress::Parse{..} => (ProgressBar::new_spinner(), "{spinner} Reading the graph: {msg} [{elapsed_precise}] {binary_bytes} ({binary_bytes_per_sec})"),
+      Progress::Close{seeds_total, ..} =>
+        (ProgressBar::new(seeds_total as u64), "Closing [{elapsed_precise}] {wide_bar} {human_pos}/{human_len} seeds ({per_sec}, ETA {eta}) {msg}")
+    };
+    bar.with_style(ProgressStyle::with_template(template).unwrap())
+  }
+
+  /// Updates the progress bar of the phase
+  fn update(&self, bar: &ProgressBar) {
+    match *self {
+      Progress::Parse{records, bytes} => {
+        bar.set_position(bytes);
+        bar.set_message(format!("record {}", records));
+      },
+      Progress::Close{seeds_done, closed, kmers, ..} => {
+        bar.set_position(seeds_done as u64);
+        bar.set_message(format!("{:.2}% of k-mers closed", closed as f64 / kmers as f64 * 100.));
+      }
+    }
+  }
+}
+
+/// A JSON progress event
+#[derive(Serialize)]
+#[serde(untagged)]
+enum Event {
+  /// Start or end of a phase
+  Phase{phase: Phase, event: &'static str, time_ms: u64},
+  Parse{phase: Phase, records: usize, bytes: u64},
+  Close{phase: Phase, seeds_done: usize, seeds_total: usize}
+}
+
+/// Destination of JSON progress events, with the time of the last progress event
+struct JsonProgress {
+  out: Box<dyn Write + Send>,
+  last: Option<Instant>
+}
+
+impl JsonProgress {
+  /// Writes an event line; progress is best effort, so write errors are ignored
+  fn write(&mut self, event: Event) {
+    let _ = writeln!(self.out, "{}", serde_json::to_string(&event).unwrap()).and_then(|_| self.out.flush());
+  }
+}
+
+/// JSON progress destination, if progress is reported as JSON
+static JSON: OnceLock<Mutex<JsonProgress>> = OnceLock::new();
+
+/// Reports progress as newline-delimited JSON events written to `out`, instead of a progress line on the terminal.
+/// Only the first destination given is used: returns false, dropping `out`, if one already was.
+pub fn json_progress(out: Box<dyn Write + Send>) -> bool {
+  JSON.set(Mutex::new(JsonProgress{out, last: None})).is_ok()
+}
+
+/// Milliseconds since the Unix epoch
+fn now_ms() -> u64 {
+  SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis() as u64
+}
+
+/// Whether stderr is an interactive terminal accepting escape sequences
+fn fancy() -> bool {
+  static FANCY: OnceLock<bool> = OnceLock::new();
+  *FANCY.get_or_init(|| {
+    io::stderr().is_terminal() && env::var_os("NO_COLOR").is_none() && env::var("TERM").map_or(true, |t| t != "dumb")
+  })
+}
+
+/// Whether progress is shown as a bar on the terminal, unless messages are quieted
+fn bar() -> bool {
+  JSON.get().is_none() && fancy() && log::max_level() >= LevelFilter::Info
+}
+
+/// Progress bar shown on the terminal, with its phase and the time of its last update
+static BAR: Mutex<Option<(Phase, ProgressBar, Instant)>> = Mutex::new(None);
+
+/// Whether progress is reported, unless disabled with [`disable_progress`]
+static ENABLED: AtomicBool = AtomicBool::new(true);
+/// Calls to [`progress`], of which only one every [`EVERY`] is considered
+static CALLS: AtomicUsize = AtomicUsize::new(0);
+
+/// Stops reporting progress, either as a progress bar or as JSON events
+pub fn disable_progress() {
+  ENABLED.store(false, Ordering::Relaxed);
+}
+
+/// Reports the progress of the current phase: updates its progress bar, only on a terminal and at most
+/// every 100 ms, or writes a JSON event, at most one per second. Only one call every 1024 is considered.
+pub fn progress(progress: Progress) {
+  if !ENABLED.load(Ordering::Relaxed) || CALLS.fetch_add(1, Ordering::Relaxed) % EVERY != 0 {
+    return;
+  }
+  if let Some(json) = JSON.get() {
+    let mut json = json.lock().unwrap();
+    if json.last.is_some_and(|last| last.elapsed() < THROTTLE) {
+      return;
+    }
+    json.last = Some(Instant::now());
+    json.write(match progress {
+      Progress::Parse{records, bytes} => Event::Parse{phase: Phase::Parse, records, bytes},
+      Progress::Close{seeds_done, seeds_total, ..} => Event::Close{phase: Phase::Close, seeds_done, seeds_total}
+    });
+  } else if bar() {
+    let mut bar = BAR.lock().unwrap();
+    match &mut *bar {
+      Some((phase, _, last)) if phase == &progress.phase() => {
+        if last.elapsed() < BAR_THROTTLE {
+          return;
+        }
+        *last = Instant::now();
+      },
+      _ => {
+        if let Some((_, old, _)) = bar.take() {
+          old.finish_and_clear();
+        }
+        *bar = Some((progress.phase(), progress.bar(), Instant::now()));
+      }
+    }
+    progress.update(&bar.as_ref().unwrap().1);
+  }
+}
+
+/// Removes the progress bar, only on a terminal
+pub fn clear_progress() {
+  if let Some((_, bar, _)) = BAR.lock().unwrap().take() {
+    bar.finish_and_clear();
+  }
+}
+
+/// Start of the current phase
+static PHASE_START: Mutex<Option<Instant>> = Mutex::new(None);
+/// Time taken by each phase ended, summed over its runs
+static PHASE_TIMES: Mutex<BTreeMap<Phase, Duration>> = Mutex::new(BTreeMap::new());
+
+/// Time taken by each phase ended so far
+pub fn phase_times() -> BTreeMap<Phase, Duration> {
+  PHASE_TIMES.lock().unwrap().clone()
+}
+
+/// Reports the start of a phase, only as a JSON event
+pub fn phase_start(phase: Phase) {
+  *PHASE_START.lock().unwrap() = Some(Instant::now());
+  if let Some(json) = JSON.get() {
+    json.lock().unwrap().write(Event::Phase{phase, event: "start", time_ms: now_ms()});
+  }
+}
+
+/// Reports the end of a phase, removing the progress bar on a terminal
+pub fn phase_end(phase: Phase) {
+  if let Some(json) = JSON.get() {
+    json.lock().unwrap().write(Event::Phase{phase, event: "end", time_ms: now_ms()});
+  }
+  clear_progress();
+  if let Some(start) = PHASE_START.lock().unwrap().take() {
+    *PHASE_TIMES.lock().unwrap().entry(phase).or_default() += start.elapsed();
+    log::debug!("{} phase took {:.2?}", serde_json::to_value(phase).unwrap().as_str().unwrap(), start.elapsed());
+  }
+}
+
+/// Writes a message line, above the progress bar on a terminal
+fn message(msg: impl Display) {
+  match &*BAR.lock().unwrap() {
+    Some((_, bar, _)) => bar.suspend(|| eprintln!("{}", msg)),
+    None => eprintln!("{}", msg)
+  }
+}
+
+/// Logger writing a line to stderr for each message: warnings and errors are prefixed with their level
+struct Logger;
+
+impl Log for Logger {
+  fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+    metadata.level() <= log::max_level()
+  }
+
+  fn log(&self, record: &Record<'_>) {
+    if !self.enabled(record.metadata()) {
+      return;
+    }
+    match record.level() {
+      Level::Error => message(format_args!("Error: {}", record.args())),
+      Level::Warn => message(format_args!("Warning: {}", record.args())),
+      _ => message(record.args())
+    }
+  }
+
+  fn flush(&self) {}
+}
+
+/// Installs the logger writing to stderr the messages up to `level`
+pub fn init_log(level: LevelFilter) {
+  if log::set_logger(&Logger).is_err() {
+    panic!("logger already installed");
+  }
+  log::set_max_level(level);
+}
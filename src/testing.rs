@@ -0,0 +1,71 @@
+//! Helpers checking the readers and writers of graphs
+
+use crate::convert::{self, Format};
+use crate::graph::Graph;
+
+/// Writes a graph in each supported format and reads it back with the matching reader,
+/// asserting that the graph read is equivalent to the original one.
+///
+/// # Panics
+/// If a format does not round-trip.
+pub fn assert_roundtrip(graph: &Graph) {
+  for format in Format::ALL {
+    assert_roundtrip_as(graph, format);
+  }
+}
+
+/// Writes a graph in the given format and reads it back, asserting that the graph read is equivalent to the original one.
+///
+/// # Panics
+/// If the format does not round-trip, or cannot be written.
+pub fn assert_roundtrip_as(graph: &Graph, format: Format) {
+  let mut buf = Vec::new();
+  convert::write_to(graph, format, &mut buf).unwrap();
+  let read = convert::read_from(format, buf.as_slice()).unwrap();
+  assert!(graph.equivalent(&read), "{:?} does not round-trip:\n{}", format, String::from_utf8_lossy(&buf));
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::graph::{GraphBuilder, Orientation};
+  use crate::synthetic;
+
+  #[test]
+  fn small_graph_roundtrips() {
+    assert_roundtrip(&Graph::from(include_str!("../tests/data/small.fa").as_bytes()));
+  }
+
+  #[test]
+  fn synthetic_graphs_roundtrip() {
+    assert_roundtrip(&synthetic::generate_linear(5, 20, |i| i as u32 % 7 + 1).graph);
+    assert_roundtrip(&synthetic::generate_branching(7, 2, 3).graph);
+    assert_roundtrip(&synthetic::generate_random_genome(11, 300, 5., 0.01, 1));
+    // A long unitig, with a huge count list
+    assert_roundtrip(&synthetic::generate_linear(15, 20_000, |i| (i * 7919 % 1000) as u32 + 1).graph);
+  }
+
+  #[test]
+  fn differences_are_detected() {
+    let build = |counts: &[u32], link: bool| {
+      let mut builder = GraphBuilder::new();
+      let a = builder.add_unitig("AACCGT", &counts[..2]);
+      let b = builder.add_unitig("CCGTTA", &counts[2..]);
+      if link {
+        builder.add_link(a, Orientation::Forward, b, Orientation::Forward);
+      }
+      builder.build(5).unwrap()
+    };
+    let graph = build(&[5, 5, 5, 4], true);
+    assert!(graph.equivalent(&build(&[5, 5, 5, 4], true)));
+    assert!(!graph.equivalent(&build(&[5, 5, 5, 3], true)));
+    assert!(!graph.equivalent(&build(&[5, 5, 5, 4], false)));
+
+    // Same graph, with the records in the other order and the link given on the other strand
+    let mut builder = GraphBuilder::new();
+    let b = builder.add_unitig("TAACGG", &[4, 5]);
+    let a = builder.add_unitig("AACCGT", &[5, 5]);
+    builder.add_link(b, Orientation::Forward, a, Orientation::Reverse);
+    assert!(graph.equivalent(&builder.build(5).unwrap()));
+  }
+}
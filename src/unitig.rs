@@ -3,11 +3,13 @@
 mod utils;
 
 use std::{
-  ops::{Deref, Add},
-  fmt::{self, Display},
+  ops::{Add, Range},
+  fmt::{self, Display, Write},
   hash::{Hash, Hasher},
   cmp::Ordering,
-  convert::{From, TryFrom, TryInto}
+  convert::{From, TryFrom, TryInto},
+  mem::size_of,
+  sync::OnceLock
 };
 use snafu::Snafu;
 
@@ -18,25 +20,174 @@ pub enum UnitigError {
   WrongNucleotide{nucleo: char},
 }
 
-#[derive(Clone, Default, Debug)]
-#[repr(transparent)]
-/// Represents an unitig
-pub struct Unitig(String);
+/// Number of nucleotides packed in a word
+const BASES: usize = 64 / 2;
+
+#[derive(Clone, Default)]
+/// Represents an unitig, packing its nucleotides in 2 bits each, the first one in the highest bits of the first word.
+/// The bits after the last nucleotide are zero, so that the words compare as the sequences.
+/// Its normal form, on which it is hashed and compared, is found on first use and kept.
+pub struct Unitig {
+  len: usize,
+  words: Vec<u64>,
+  /// Words of the reverse complement if it is the normal form, None if this unitig is
+  rc_norm: OnceLock<Option<Vec<u64>>>
+}
 
 impl Unitig {
+  /// Builds an unitig from its packed words
+  fn new(len: usize, words: Vec<u64>) -> Unitig {
+    Unitig{len, words, rc_norm: OnceLock::new()}
+  }
+
+  /// Returns the number of nucleotides
+  pub fn len(&self) -> usize {
+    self.len
+  }
+
+  /// Checks if this unitig has no nucleotides
+  pub fn is_empty(&self) -> bool {
+    self.len == 0
+  }
+
+  /// Returns the heap bytes an unitig of the given length may take, with the words of its normal form:
+  /// they are kept once found if they are the reverse complement, which about half the unitigs have,
+  /// and are counted for all of them so that memory is never underestimated
+  pub fn heap_size(len: usize) -> usize {
+    2 * len.div_ceil(BASES) * size_of::<u64>()
+  }
+
+  /// Returns the 2-bit codes of the nucleotides (A, C, G and T are 0 to 3), in order
+  pub fn codes(&self) -> impl Iterator<Item = u8> + '_ {
+    (0..self.len).map(move |i| self.code(i))
+  }
+
+  /// Builds an unitig from the 2-bit codes of its nucleotides
+  pub fn from_codes(codes: impl Iterator<Item = u8>) -> Unitig {
+    let mut u = Unitig::default();
+    for code in codes {
+      u.push_code(code & 3);
+    }
+    u
+  }
+
+  /// Returns a mask of the highest bits of a word, packing `n` nucleotides
+  fn high_codes(n: usize) -> u64 {
+    !(!0u64).checked_shr(2 * n as u32).unwrap_or(0)
+  }
+
+  /// Returns the 2-bit code of the nucleotide at position `i`
+  fn code(&self, i: usize) -> u8 {
+    (self.words[i / BASES] >> (2 * (BASES - 1 - i % BASES)) & 3) as u8
+  }
+
+  /// Appends a nucleotide given by its 2-bit code
+  fn push_code(&mut self, code: u8) {
+    self.rc_norm.take();
+    if self.len % BASES == 0 {
+      self.words.push(0);
+    }
+    self.words[self.len / BASES] |= (code as u64) << (2 * (BASES - 1 - self.len % BASES));
+    self.len += 1;
+  }
+
+  /// Returns the word packing the nucleotides from position `i` on, followed by zeros past the end
+  fn word_at(&self, i: usize) -> u64 {
+    let (word, offset) = (i / BASES, 2 * (i % BASES));
+    let high = self.words.get(word).map_or(0, |w| w << offset);
+    let low = match offset {
+      0 => 0,
+      _ => self.words.get(word + 1).map_or(0, |w| w >> (64 - offset))
+    };
+    high | low
+  }
+
+  /// Returns the nucleotides in the given range
+  pub fn slice(&self, range: Range<usize>) -> Unitig {
+    assert!(range.start <= range.end && range.end <= self.len, "Range {:?} out of an unitig of length {}", range, self.len);
+    let len = range.end - range.start;
+    let mut words: Vec<_> = (0..len.div_ceil(BASES)).map(|j| self.word_at(range.start + j * BASES)).collect();
+    if let Some(last) = words.last_mut() {
+      *last &= Unitig::high_codes(len - (len - 1) / BASES * BASES);
+    }
+    Unitig::new(len, words)
+  }
+
+  /// Checks if the nucleotides from position `i` on are the given unitig
+  fn matches_at(&self, i: usize, x: &Unitig) -> bool {
+    i + x.len <= self.len && x.words.iter().enumerate().all(|(j, &w)| {
+      self.word_at(i + j * BASES) & Unitig::high_codes(x.len - j * BASES) == w
+    })
+  }
+
+  /// Returns the normal form of an unitig of at most 32 nucleotides as the 2-bit codes packed in the lowest bits of an integer,
+  /// so that equal integers are equal unitigs, or None if it is longer
+  pub fn norm_code(&self) -> Option<u64> {
+    (1..=BASES).contains(&self.len).then(|| {
+      let shift = 2 * (BASES - self.len) as u32;
+      let word = self.words[0];
+      // The complemented padding is reversed into the highest bits
+      (word >> shift).min(utils::reverse_codes(!word) & !0 >> shift)
+    })
+  }
+
+  /// Checks if two unitigs have the same sequence on the same strand (equality is up to reverse complement)
+  pub fn is_identical(&self, other: &Unitig) -> bool {
+    self.len == other.len && self.words == other.words
+  }
+
   /// Returns the reverse complement of this unitig
   pub fn rev_compl(&self) -> Unitig {
-    Unitig(utils::rev_compl(&self.0).unwrap())
+    // Complementing and reversing the words gives the padding first
+    let words = self.words.iter().rev().map(|&w| utils::reverse_codes(!w)).collect();
+    let padded = Unitig::new(self.words.len() * BASES, words);
+    padded.slice(padded.len - self.len..padded.len)
   }
 
   /// Returns the normalized unitig (the lexicographically lower among itself and its reverse complement)
   pub fn norm(&self) -> Unitig {
-    Unitig(utils::norm(&self.0).unwrap())
+    Unitig{len: self.len, words: self.norm_words().to_vec(), rc_norm: OnceLock::from(None)}
+  }
+
+  /// Finds the normal form now, so that hashing the unitig later does not have to
+  pub(crate) fn find_norm(&self) {
+    self.norm_words();
+  }
+
+  /// Returns the words of the normal form, finding it on first use
+  fn norm_words(&self) -> &[u64] {
+    self.rc_norm.get_or_init(|| {
+      let rc = self.rev_compl();
+      (rc.cmp_seq(self) == Ordering::Less).then_some(rc.words)
+    }).as_deref().unwrap_or(&self.words)
+  }
+
+  /// Lexicographically compare the sequences of two unitigs, as they are read
+  fn cmp_seq(&self, other: &Unitig) -> Ordering {
+    self.words.cmp(&other.words).then(self.len.cmp(&other.len))
   }
 
   /// Check if this unitig contains as substring the given unitig
   pub fn contains(&self, x: &Unitig) -> bool {
-    self.0.contains(&x.0)
+    x.len <= self.len && (0..=self.len - x.len).any(|i| self.matches_at(i, x))
+  }
+
+  /// Appends in place an unitig whose first `overlap` nucleotides are the last ones of this unitig.
+  /// Only the new nucleotides are copied, so appending is amortized O(other.len()).
+  pub fn push_overlap(&mut self, other: &Unitig, overlap: usize) {
+    assert!(overlap <= self.len && self.matches_at(self.len - overlap, &other.slice(0..overlap)), "The two Unitigs {:?} and {:?} are not joinable", self, other);
+    for i in overlap..other.len {
+      self.push_code(other.code(i));
+    }
+  }
+
+  /// Prepends in place an unitig whose last `overlap` nucleotides are the first ones of this unitig.
+  pub fn prepend_overlap(&mut self, other: &Unitig, overlap: usize) {
+    let new = other.len-overlap;
+    assert!(other.matches_at(new, &self.slice(0..overlap)), "The two Unitigs {:?} and {:?} are not joinable", other, self);
+    let mut joined = other.slice(0..new);
+    joined.push_overlap(self, 0);
+    *self = joined;
   }
 }
 
@@ -45,34 +196,33 @@ impl Add for &Unitig {
 
   /// Concatenates two unitigs sharing a common tail-head
   fn add(self, other: Self) -> Self::Output {
-      let common = self.0.len().min(other.0.len())-1;
-      assert!(self.0[self.0.len()-common..] == other.0[..common], "The two Unitigs {:?} and {:?} are not joinable", self, other);
-      Unitig(String::from(&self.0) + &other.0[common..])
+      let common = self.len.min(other.len).saturating_sub(1);
+      let mut joined = self.clone();
+      joined.push_overlap(other, common);
+      joined
   }
 }
 
 impl Display for Unitig {
   /// Displays an unitig
   fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-    write!(f, "{}", self.0)
+    self.codes().try_for_each(|c| f.write_char(utils::decode(c)))
   }
 }
 
-// Casting
-
-impl Deref for Unitig {
-  type Target = str;
-
-  /// Deref an unitig to String
-  fn deref(&self) -> &Self::Target {
-      &self.0
+impl fmt::Debug for Unitig {
+  /// Displays an unitig as its sequence
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "Unitig(\"{}\")", self)
   }
 }
 
+// Casting
+
 impl From<Unitig> for String {
   /// Generates a String from a unitig
   fn from(u: Unitig) -> String {
-    u.0
+    u.to_string()
   }
 }
 
@@ -81,7 +231,12 @@ impl TryFrom<&str> for Unitig {
 
   /// Generates an Unitig from a string slice
   fn try_from(u: &str) -> Result<Unitig, Self::Error> {
-    String::from(u).try_into()
+    let mut unitig = Unitig::new(0, Vec::with_capacity(u.len().div_ceil(BASES)));
+    for c in u.chars() {
+      let nucleo = c.to_ascii_uppercase();
+      unitig.push_code(utils::encode(nucleo).ok_or(UnitigError::WrongNucleotide{nucleo})?);
+    }
+    Ok(unitig)
   }
 }
 
@@ -90,14 +245,7 @@ impl TryFrom<String> for Unitig {
 
   /// Generates an Unitig from a String
   fn try_from(u: String) -> Result<Unitig, Self::Error> {
-    let u = u.to_uppercase();
-    for c in u.chars(){
-      match c {
-        'A' | 'C' | 'G' | 'T' => (),
-        nucleo => return Err(UnitigError::WrongNucleotide{nucleo})
-      }
-    }
-    Ok(Unitig(u))
+    u.as_str().try_into()
   }
 }
 
@@ -106,21 +254,21 @@ impl TryFrom<String> for Unitig {
 impl Ord for Unitig {
   /// Lexicographically compare two unitigs by normal form
   fn cmp(&self, other: &Self) -> Ordering {
-    self.norm().0.cmp(&other.norm().0)
+    self.norm_words().cmp(other.norm_words()).then(self.len.cmp(&other.len))
   }
 }
 
 impl PartialOrd for Unitig {
   /// Lexicographically compare two unitigs by normal form
   fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-    Some(self.cmp(&other))
+    Some(self.cmp(other))
   }
 }
 
 impl PartialEq for Unitig {
   /// Lexicographically compare two unitigs by normal form
   fn eq(&self, other: &Self) -> bool {
-    self.norm().0 == other.norm().0
+    self.cmp(other) == Ordering::Equal
   }
 }
 
@@ -129,7 +277,66 @@ impl Eq for Unitig {}
 impl Hash for Unitig {
   /// Hash the normal form of this unitig
   fn hash<H: Hasher>(&self, state: &mut H) {
-    self.norm().0.hash(state);
+    self.len.hash(state);
+    self.norm_words().hash(state);
   }
 }
 
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn overlap_joins_match_add() {
+    let (a, b) = (Unitig::try_from("AACCG").unwrap(), Unitig::try_from("ACCGT").unwrap());
+    let (mut right, mut left) = (a.clone(), b.clone());
+    right.push_overlap(&b, 4);
+    left.prepend_overlap(&a, 4);
+    assert_eq!(String::from(right), String::from(&a + &b));
+    assert_eq!(String::from(left), String::from(&a + &b));
+  }
+
+  #[test]
+  #[should_panic]
+  fn overlap_joins_check_overlap() {
+    Unitig::try_from("AACCG").unwrap().push_overlap(&Unitig::try_from("ACGTT").unwrap(), 3);
+  }
+
+  #[test]
+  fn normal_form_is_kept_until_changed() {
+    let u = |s| Unitig::try_from(s).unwrap();
+    let mut a = u("TTG");
+    assert_eq!(a, u("CAA"));
+    // Extending the unitig forgets its normal form
+    a.push_overlap(&u("TGC"), 2);
+    assert_eq!((a.norm().to_string(), a == u("GCAA")), (String::from("GCAA"), true));
+    assert!(a != u("CAA"));
+  }
+
+  #[test]
+  fn packed_unitigs_behave_as_strings() {
+    let seq = "ACGTTGCAAGGCTTACCGATCGATTACGGCATGCATTGACCAGTAcgtacgtTTGA";
+    let rc: String = seq.to_uppercase().chars().rev().map(|c| match c {'A' => 'T', 'C' => 'G', 'G' => 'C', _ => 'A'}).collect();
+    let u = Unitig::try_from(seq).unwrap();
+    assert_eq!((u.len(), u.to_string()), (seq.len(), seq.to_uppercase()));
+    assert_eq!(u.rev_compl().to_string(), rc);
+    assert_eq!(Unitig::try_from("GCAT").unwrap().norm().to_string(), "ATGC");
+    for (start, end) in [(0, 0), (3, 40), (32, 56), (30, 55), (0, 56)] {
+      let slice = u.slice(start..end);
+      assert_eq!(slice.to_string(), seq[start..end].to_uppercase());
+      assert!(u.contains(&slice) && u.rev_compl().contains(&slice.rev_compl()));
+    }
+    assert!(!u.contains(&Unitig::try_from("GGGG").unwrap()));
+    // Packed words compare as the sequences, the shorter first when one is a prefix of the other
+    let mut sorted: Vec<_> = ["A", "AA", "AC", "C", &"A".repeat(33), &"A".repeat(32)].iter().map(|s| Unitig::try_from(*s).unwrap()).collect();
+    sorted.sort_by(Unitig::cmp_seq);
+    assert_eq!(sorted.iter().map(|u| u.len()).collect::<Vec<_>>(), [1, 2, 32, 33, 2, 1]);
+    assert_eq!(Unitig::try_from("ACGU").unwrap_err().to_string(), "Unknown 'U' nucleotide");
+    // Codes of the normal form are the same on both strands
+    let kmer = u.slice(5..37);
+    assert_eq!(kmer.norm_code(), kmer.rev_compl().norm_code());
+    assert_eq!(kmer.norm().norm_code(), Some(kmer.norm().codes().fold(0, |code, c| code << 2 | c as u64)));
+    assert_eq!(Unitig::try_from("GCAT").unwrap().norm_code(), Some(0b00_11_10_01));
+    assert_eq!(u.slice(0..33).norm_code(), None);
+  }
+}
@@ -1,26 +1,26 @@
-
-/// Gets the complement of a nucleotide.
+/// Gets the 2-bit code of a nucleotide: A, C, G and T are 0 to 3, in alphabetical order,
+/// so that the complement of a code is 3 minus it.
 /// Returns None if the input char is not a valid nucleotide.
-pub fn complement(nucleo: char) -> Option<char>{
+pub fn encode(nucleo: char) -> Option<u8>{
   match nucleo{
-    'A' => Some('T'),
-    'C' => Some('G'),
-    'G' => Some('C'),
-    'T' => Some('A'),
+    'A' => Some(0),
+    'C' => Some(1),
+    'G' => Some(2),
+    'T' => Some(3),
     _ => None
   }
 }
 
-/// Gets the reverse complement of a unitig.
-/// Returns None if the input contains not a valid nucleotide.
-pub fn rev_compl(seq: &str) -> Option<String>{
-  seq.chars().map(complement).rev().collect()
+/// Gets the nucleotide of a 2-bit code.
+pub fn decode(code: u8) -> char{
+  ['A', 'C', 'G', 'T'][code as usize]
 }
 
-/// Gets the normalized version of a unitig.
-/// Returns None if the input contains not a valid nucleotide.
-pub fn norm(seq: &str) -> Option<String>{
-  Some(String::from(seq)).min(rev_compl(seq))
+/// Reverses the order of the 2-bit codes packed in a word.
+pub fn reverse_codes(word: u64) -> u64{
+  let word = (word >> 2 & 0x3333_3333_3333_3333) | (word & 0x3333_3333_3333_3333) << 2;
+  let word = (word >> 4 & 0x0F0F_0F0F_0F0F_0F0F) | (word & 0x0F0F_0F0F_0F0F_0F0F) << 4;
+  word.swap_bytes()
 }
 
 #[cfg(test)]
@@ -29,6 +29,7 @@ mod tests {
 
   #[test]
   fn test() {
-    assert_eq!("ATGC", norm("GCAT").unwrap());
+    assert_eq!("ATGC", "GCAT".chars().rev().map(|c| decode(3 - encode(c).unwrap())).collect::<String>());
+    assert_eq!(reverse_codes(0x1B), 0xE4 << 56);
   }
-}
\ No newline at end of file
+}
@@ -0,0 +1,189 @@
+//! Validation of BCALM FASTA files without building the graph
+
+use crate::graph::{BcalmReader, ReadError, Record, Unitig};
+use rayon::prelude::*;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{hash_map::Entry, BTreeSet, HashMap};
+use std::convert::TryFrom;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::io::BufRead;
+
+/// Number of records checked together
+const CHUNK: usize = 4096;
+
+/// A problem found in the input
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Issue {
+  /// Line of the header of the record
+  pub line: usize,
+  pub message: String
+}
+
+impl fmt::Display for Issue {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "line {}: {}", self.line, self.message)
+  }
+}
+
+/// Result of the checks of a single record, to be merged with the other ones
+struct Checked {
+  id: usize,
+  line: usize,
+  issues: Vec<String>,
+  /// k implied by the sequence length and the number of counts
+  k: Option<usize>,
+  /// 128 bit hashes of the canonical k-mers
+  kmers: Vec<u128>,
+  /// Ids of the linked records
+  links: Vec<usize>
+}
+
+/// Hashes the normal form of a k-mer with two salted 64 bit hashes.
+/// Two different k-mers sharing both hashes (probability about n²/2¹²⁹) would be reported as duplicates.
+fn hash(kmer: &str) -> u128 {
+  let hash = |salt: u8| {
+    let mut hasher = DefaultHasher::new();
+    salt.hash(&mut hasher);
+    kmer.hash(&mut hasher);
+    hasher.finish() as u128
+  };
+  hash(0) << 64 | hash(1)
+}
+
+/// Runs the checks which involve a single record, whose k-mers are the ones of its segments between break characters
+fn check(record: Result<Record, ReadError>, break_char: Option<char>) -> Result<Checked, Issue> {
+  let record = match record {
+    Ok(record) => record,
+    Err(e @ ReadError::Syntax{line, ..}) | Err(e @ ReadError::WrongCount{line, ..}) => return Err(Issue{line, message: e.to_string()}),
+    Err(e) => panic!("{}", e)
+  };
+  let Record{id, line, sequence, counts, links, ..} = &record;
+  let mut checked = Checked{id: *id, line: *line, issues: Vec::new(), k: None, kmers: Vec::new(), links: links.iter().map(|l| l.to).collect()};
+  if sequence.is_empty() {
+    checked.issues.push(String::from("record without sequence"));
+    return Ok(checked);
+  }
+  if counts.is_empty() {
+    checked.issues.push(String::from("record without counts"));
+    return Ok(checked);
+  }
+  if counts.len() > sequence.len() {
+    checked.issues.push(format!("{} counts for a sequence of length {}", counts.len(), sequence.len()));
+    return Ok(checked);
+  }
+  let k = sequence.len() + 1 - counts.len();
+  checked.k = Some(k);
+  for segment in record.segments(k, break_char) {
+    match Unitig::try_from(segment.sequence) {
+      Ok(u) => {
+        // The reverse complement of each k-mer is a window of the reverse complement of the sequence
+        let (len, rc) = (u.len(), u.rev_compl());
+        checked.kmers.extend((0..segment.counts.len()).map(|i| hash(&u.slice(i..i+k).to_string().min(rc.slice(len-i-k..len-i).to_string()))));
+      },
+      Err(e) => checked.issues.push(e.to_string())
+    }
+  }
+  Ok(checked)
+}
+
+/// Checks a BCALM FASTA file, returning the issues found ordered by line.
+/// Records are checked on their own in parallel chunks (serially unless `parallel`),
+/// then k consistency, duplicate k-mers and link targets are checked across records.
+/// K-mers containing the break character, if given, are skipped.
+pub fn validate<T: BufRead>(input: T, parallel: bool, break_char: Option<char>) -> (usize, Vec<Issue>) {
+  let mut reader = BcalmReader::new(input);
+  let mut issues = Vec::new();
+  let mut first_k = None; // k and line of the first record
+  let mut kmers = HashMap::new(); // record of each k-mer
+  let mut links = Vec::new();
+  let mut records = 0;
+
+  loop {
+    let chunk: Vec<_> = reader.by_ref().take(CHUNK).collect();
+    if chunk.is_empty() {
+      break;
+    }
+    let checked: Vec<_> = if parallel {
+      chunk.into_par_iter().map(|record| check(record, break_char)).collect()
+    } else {
+      chunk.into_iter().map(|record| check(record, break_char)).collect()
+    };
+
+    for checked in checked {
+      let Checked{id, line, issues: record_issues, k, kmers: record_kmers, links: record_links} = match checked {
+        Ok(checked) => checked,
+        Err(issue) => {
+          issues.push(issue);
+          continue;
+        }
+      };
+      records += 1;
+      issues.extend(record_issues.into_iter().map(|message| Issue{line, message}));
+      if let Some(k) = k {
+        match first_k {
+          None => first_k = Some((k, line)),
+          Some((first, first_line)) if first != k => {
+            issues.push(Issue{line, message: format!("k = {} but k = {} at line {}", k, first, first_line)});
+          },
+          _ => ()
+        }
+      }
+      let mut shared = BTreeSet::new(); // records sharing k-mers with this one
+      for kmer in record_kmers {
+        match kmers.entry(kmer) {
+          Entry::Occupied(e) => { shared.insert(*e.get()); },
+          Entry::Vacant(e) => { e.insert(id); }
+        }
+      }
+      for other in shared {
+        let message = if other == id {String::from("repeated k-mers")} else {format!("k-mers shared with record {}", other)};
+        issues.push(Issue{line, message});
+      }
+      links.extend(record_links.into_iter().map(|to| (line, to)));
+    }
+  }
+
+  for (line, to) in links {
+    if to >= records {
+      issues.push(Issue{line, message: format!("link to unknown record {}", to)});
+    }
+  }
+  issues.sort_by_key(|issue| issue.line);
+  (records, issues)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const SMALL: &str = include_str!("../tests/data/small.fa");
+
+  #[test]
+  fn small_input_is_valid() {
+    assert_eq!(validate(SMALL.as_bytes(), true, None), (7, Vec::new()));
+  }
+
+  #[test]
+  fn parallel_issues_match_serial_ones() {
+    let input = String::from(SMALL) + include_str!("../tests/data/duplicated.fa")
+      + ">8 ab:Z:1 1 L:+:99:+\nAACCGTA\n>9 ab:Z:1\nAAXCG\n>10 ab:Z:1 1\n";
+    let (records, issues) = validate(input.as_bytes(), true, None);
+    assert_eq!((records, &issues), (validate(input.as_bytes(), false, None).0, &validate(input.as_bytes(), false, None).1));
+    let messages: Vec<_> = issues.iter().map(ToString::to_string).collect();
+    for expected in ["line 15: k-mers shared with record 0", "line 31: k = 6 but k = 7 at line 1", "line 31: link to unknown record 99",
+                     "line 33: Unknown 'X' nucleotide", "line 35: record without sequence"] {
+      assert!(messages.iter().any(|m| m == expected), "{} not in {:?}", expected, messages);
+    }
+    assert!(issues.windows(2).all(|w| w[0].line <= w[1].line));
+  }
+
+  #[test]
+  fn break_characters_are_skipped() {
+    let input = ">0 ab:Z:1 1 1 1 1 1 1 1 1 1\nAACCGTNNGATTCA\n>1 ab:Z:1\nNNGAT\n";
+    let (_, issues) = validate(input.as_bytes(), false, Some('N'));
+    assert_eq!(issues, vec![]);
+    let messages: Vec<_> = validate(input.as_bytes(), false, None).1.iter().map(ToString::to_string).collect();
+    assert_eq!(messages, ["line 1: Unknown 'N' nucleotide", "line 3: Unknown 'N' nucleotide"]);
+  }
+}
@@ -0,0 +1,62 @@
+//! Verification of the closed unitigs written by a previous run
+
+use crate::error::Error;
+use crate::graph::{Graph, Unitig};
+use crate::io::{open, read_counts, FastaReader};
+use std::convert::TryFrom;
+use std::io::{self, BufRead};
+use std::path::Path;
+
+/// A record read back: its sequence, the gap positions declared by its `gaps=` header tag and its count
+/// given by its `km:f:` header tag, if any
+type Record = (String, Vec<usize>, Option<u32>);
+
+/// Reads the records of a FASTA file
+fn read_fasta<T: BufRead>(buf: T) -> io::Result<Vec<Record>> {
+  let invalid = |what: &str, value: &str| io::Error::new(io::ErrorKind::InvalidData, format!("wrong {} \"{}\"", what, value));
+  FastaReader::new(buf).map(|record| {
+    let (header, seq) = record?;
+    let gaps = match header.split_whitespace().find_map(|tag| tag.strip_prefix("gaps=")) {
+      Some(gaps) => gaps.split(',').map(|i| i.parse().map_err(|_| invalid("gaps", gaps))).collect::<io::Result<_>>()?,
+      None => Vec::new()
+    };
+    let count = match header.split_whitespace().find_map(|tag| tag.strip_prefix("km:f:")) {
+      Some(count) => Some(count.parse::<f64>().ok().filter(|c| c.fract() == 0. && *c >= 0. && *c <= u32::MAX as f64).ok_or_else(|| invalid("count", count))? as u32),
+      None => None
+    };
+    Ok((seq, gaps, count))
+  }).collect()
+}
+
+/// Checks that the given closed unitigs and counts are valid for the graph, reporting any violation.
+/// Without a counts file, the counts are read from the `km:f:` header tags.
+/// Returns the number of invalid records.
+pub fn verify(graph: &Graph, fasta: &Path, counts: Option<&Path>) -> Result<usize, Error> {
+  let records = open(fasta).and_then(read_fasta).map_err(Error::io(fasta))?;
+  let counts: Vec<_> = match counts {
+    Some(counts) => open(counts).and_then(|f| read_counts(f).collect()).map_err(Error::io(counts))?,
+    None => records.iter().map_while(|(_, _, count)| *count).collect()
+  };
+  let seqs: Vec<_> = records.into_iter().map(|(seq, gaps, _)| (seq, gaps)).collect();
+  if seqs.len() != counts.len() {
+    println!("{} sequences but {} counts", seqs.len(), counts.len());
+    return Ok(seqs.len().max(counts.len()));
+  }
+
+  let mut invalid = 0;
+  for (i, ((seq, gaps), &count)) in seqs.iter().zip(&counts).enumerate() {
+    let violations = match Unitig::try_from(seq.as_str()) {
+      Ok(u) => graph.check_closed(&u, count, gaps).iter().map(ToString::to_string).collect(),
+      Err(e) => vec![e.to_string()]
+    };
+    if !violations.is_empty() {
+      invalid += 1;
+      println!("Record {} ({}, count {}):", i, seq, count);
+      for v in violations {
+        println!("  {}", v);
+      }
+    }
+  }
+  println!("{} records checked, {} invalid", seqs.len(), invalid);
+  Ok(invalid)
+}
@@ -0,0 +1,82 @@
+use std::fs;
+use std::process::Command;
+
+const BIN: &str = env!("CARGO_BIN_EXE_closed-unitigs");
+
+#[test]
+fn converted_graphs_are_stable() {
+  // Converting regenerates the links, so only a converted file is expected to convert to itself
+  let dir = tempfile::tempdir().unwrap();
+  let (once, twice) = (dir.path().join("once.fa"), dir.path().join("twice.fa"));
+  for (input, output) in [("tests/data/small.fa".as_ref(), &once), (once.as_path(), &twice)] {
+    assert!(Command::new(BIN).arg("convert").arg(input).arg(output).status().unwrap().success());
+  }
+  assert_eq!(fs::read_to_string(&once).unwrap(), fs::read_to_string(&twice).unwrap());
+}
+
+#[test]
+fn gfa_inputs_give_the_same_closed_unitigs() {
+  let dir = tempfile::tempdir().unwrap();
+  let (bcalm, gfa) = (dir.path().join("small.fa"), dir.path().join("graph.gfa"));
+  fs::copy("tests/data/small.fa", &bcalm).unwrap();
+  assert!(Command::new(BIN).arg("convert").arg(&bcalm).arg(&gfa).status().unwrap().success());
+  assert!(fs::read_to_string(&gfa).unwrap().starts_with("H\tVN:Z:1.0\nS\t0\t"));
+  for input in [&bcalm, &gfa] {
+    assert!(Command::new(BIN).arg("--no-manifest").arg(input).output().unwrap().status.success());
+  }
+  let read = |name| fs::read_to_string(dir.path().join(name)).unwrap();
+  assert_eq!((read("graph.clo.fa"), read("graph.clo.counts")), (read("small.clo.fa"), read("small.clo.counts")));
+
+  // The format can be given when the extension does not tell it
+  let renamed = dir.path().join("graph.txt");
+  fs::rename(&gfa, &renamed).unwrap();
+  assert!(!Command::new(BIN).arg("--no-manifest").arg(&renamed).output().unwrap().status.success());
+  assert!(Command::new(BIN).args(["--no-manifest", "--format", "gfa"]).arg(&renamed).output().unwrap().status.success());
+  assert_eq!(read("graph.clo.fa"), read("small.clo.fa"));
+}
+
+#[test]
+fn parse_options_are_refused_on_saved_graphs() {
+  let dir = tempfile::tempdir().unwrap();
+  let saved = dir.path().join("small.cug");
+  assert!(Command::new(BIN).arg("convert").arg("tests/data/small.fa").arg(&saved).status().unwrap().success());
+  assert!(Command::new(BIN).arg("--no-manifest").arg(&saved).output().unwrap().status.success());
+  for flags in [&["--head", "3"][..], &["--merge-duplicates"], &["--collapse-counts", "sum"], &["--break-char", "N"], &["--lenient"], &["--k", "7"]] {
+    let output = Command::new(BIN).arg("--no-manifest").args(flags).arg(&saved).output().unwrap();
+    assert_eq!(output.status.code(), Some(1), "{:?}", flags);
+  }
+}
+
+#[test]
+fn graphs_are_exported_as_dot() {
+  let dir = tempfile::tempdir().unwrap();
+  let dot = dir.path().join("small.dot");
+  assert!(Command::new(BIN).args(["export", "dot", "tests/data/small.fa"]).arg(&dot).status().unwrap().success());
+  let all = fs::read_to_string(&dot).unwrap();
+  assert!(all.starts_with("digraph {\n") && all.ends_with("}\n"));
+  // A node line for each k-mer and an edge line for each edge, counted once with its twin
+  assert_eq!(all.lines().filter(|l| l.contains(" [label=") && !l.contains(" -> ")).count(), 80);
+  assert_eq!(all.matches(" -> ").count(), 80);
+
+  let output = Command::new(BIN).args(["export", "dot", "--around", "GAAACTA", "--radius", "1", "tests/data/small.fa"]).output().unwrap();
+  assert!(output.status.success());
+  let near = String::from_utf8(output.stdout).unwrap();
+  assert!(near.contains("[label=\"GAAACTA\\n2\"]") && near.lines().count() < all.lines().count());
+  let output = Command::new(BIN).args(["export", "dot", "--around", "TTTTTTT", "tests/data/small.fa"]).output().unwrap();
+  assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+fn graphs_are_exported_as_graphml() {
+  let output = Command::new(BIN).args(["export", "graphml", "tests/data/small.fa"]).output().unwrap();
+  assert!(output.status.success());
+  let graphml = String::from_utf8(output.stdout).unwrap();
+  assert!(graphml.starts_with("<?xml ") && graphml.contains("<graph id=\"G\" edgedefault=\"directed\">"));
+  // The same nodes and edges as the DOT export
+  assert_eq!((graphml.matches("<node ").count(), graphml.matches("<edge ").count()), (80, 80));
+  assert!(graphml.contains("<data key=\"sequence\">GAAACTA</data><data key=\"count\">2</data><data key=\"length\">7</data>"));
+
+  let output = Command::new(BIN).args(["export", "graphml", "--around", "GAAACTA", "--radius", "1", "tests/data/small.fa"]).output().unwrap();
+  assert!(output.status.success());
+  assert_eq!(String::from_utf8(output.stdout).unwrap().matches("<node ").count(), 3);
+}
@@ -0,0 +1,33 @@
+use std::fs;
+use std::process::Command;
+
+const BIN: &str = env!("CARGO_BIN_EXE_closed-unitigs");
+
+#[test]
+fn runs_are_compared() {
+  let dir = tempfile::tempdir().unwrap();
+  let input = dir.path().join("small.fa");
+  fs::copy("tests/data/small.fa", &input).unwrap();
+  let (a, b) = (dir.path().join("a.fa"), dir.path().join("b.fa"));
+  for (options, fasta) in [(&["--sort", "none"][..], &a), (&["--min-count", "4"][..], &b)] {
+    assert!(Command::new(BIN).args(options).arg(&input).output().unwrap().status.success());
+    fs::rename(dir.path().join("small.clo.fa"), fasta).unwrap();
+    fs::rename(dir.path().join("small.clo.counts"), fasta.with_extension("counts")).unwrap();
+  }
+
+  let output = Command::new(BIN).arg("diff").arg(&a).arg(&a).output().unwrap();
+  assert!(output.status.success());
+  let records = fs::read_to_string(&a).unwrap().lines().filter(|l| l.starts_with('>')).count();
+  assert!(String::from_utf8(output.stdout).unwrap().contains(&format!("In both: {} closed unitigs, 0 with a different support", records)));
+
+  let tsv = dir.path().join("diff.tsv");
+  let output = Command::new(BIN).arg("diff").arg("--tsv").arg(&tsv).arg(&a).arg(&b).output().unwrap();
+  // Apart from the usage errors
+  assert_eq!(output.status.code(), Some(5));
+  assert_eq!(Command::new(BIN).arg("diff").arg(&a).output().unwrap().status.code(), Some(1));
+  let stdout = String::from_utf8(output.stdout).unwrap();
+  let rows: Vec<_> = fs::read_to_string(&tsv).unwrap().lines().skip(1).map(|l| l.split('\t').map(String::from).collect::<Vec<_>>()).collect();
+  let only_a = rows.iter().filter(|r| r[3] == "only_a").count();
+  assert!(only_a > 0 && stdout.contains(&format!("Only in A: {} closed unitigs", only_a)), "{}", stdout);
+  assert!(rows.iter().all(|r| r[0].bytes().all(|b| b"ACGT".contains(&b))));
+}
@@ -0,0 +1,33 @@
+use std::fs;
+use std::process::Command;
+
+const BIN: &str = env!("CARGO_BIN_EXE_closed-unitigs");
+
+#[test]
+fn empty_input_gives_empty_output() {
+  let dir = tempfile::tempdir().unwrap();
+  for (name, content) in [("empty.fa", ""), ("headers.fa", ">0 LN:i:0\n\n>1 LN:i:0\n")] {
+    let input = dir.path().join(name);
+    fs::write(&input, content).unwrap();
+    let output = Command::new(BIN).arg("--no-manifest").arg(&input).output().unwrap();
+    assert!(output.status.success());
+    assert!(String::from_utf8(output.stderr).unwrap().contains("Warning: no k-mers"));
+    let stem = name.trim_end_matches(".fa");
+    assert_eq!(fs::read_to_string(dir.path().join(format!("{}.clo.fa", stem))).unwrap(), "");
+    assert_eq!(fs::read_to_string(dir.path().join(format!("{}.clo.counts", stem))).unwrap(), "");
+
+    let output = Command::new(BIN).arg("--strict-empty").arg(&input).output().unwrap();
+    assert_eq!(output.status.code(), Some(2));
+  }
+}
+
+#[test]
+fn links_cross_records_without_sequence() {
+  let dir = tempfile::tempdir().unwrap();
+  let input = dir.path().join("gap.fa");
+  fs::write(&input, ">0 ab:Z:3 3 L:+:2:+\nAACCGT\n>1 LN:i:0\n>2 ab:Z:3 3 L:-:0:-\nCCGTTA\n").unwrap();
+  assert!(Command::new(BIN).arg(&input).output().unwrap().status.success());
+  // Record 2 is still the one after the record without sequence
+  let fasta = fs::read_to_string(dir.path().join("gap.clo.fa")).unwrap();
+  assert!(fasta.lines().any(|l| l == "AACCGTTA"), "{}", fasta);
+}
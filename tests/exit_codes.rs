@@ -0,0 +1,85 @@
+use std::ffi::{OsStr, OsString};
+use std::fs;
+use std::process::Command;
+
+const BIN: &str = env!("CARGO_BIN_EXE_closed-unitigs");
+
+/// Runs the program returning its exit code and the last line of its stderr
+fn run<S: AsRef<OsStr>>(args: &[S]) -> (Option<i32>, String) {
+  let output = Command::new(BIN).arg("--no-manifest").args(args).output().unwrap();
+  let stderr = String::from_utf8(output.stderr).unwrap();
+  (output.status.code(), String::from(stderr.lines().last().unwrap_or_default()))
+}
+
+#[test]
+fn failures_have_their_exit_code_and_prefix() {
+  let dir = tempfile::tempdir().unwrap();
+  let input = dir.path().join("small.fa");
+  fs::copy("tests/data/small.fa", &input).unwrap();
+  let (code, line) = run(&[&input]);
+  assert_eq!((code, line.starts_with("error")), (Some(0), false), "{}", line);
+
+  let args = |args: &[&str]| args.iter().map(OsString::from).chain([input.clone().into()]).collect::<Vec<_>>();
+  for (args, code, prefix) in [
+    (args(&["--sort", "random"]), 1, "error[usage]: invalid value 'random' for '--sort <SORT>'"),
+    (args(&["--shards", "2", "--split-by-support", "out"]), 1, "error[usage]: only one of"),
+    (args(&["--head", "ten"]), 1, "error[usage]: invalid value 'ten' for '--head <N>'"),
+    (vec![OsString::from("--head")], 1, "error[usage]: a value is required for '--head <N>'"),
+    (vec![OsString::from("--lenient")], 1, "error[usage]: the following required arguments were not provided: <INPUT>"),
+    (args(&["--max-memory", "0.000000001"]), 3, "error[limit]: memory ceiling reached"),
+    (vec![OsString::from("missing.fa")], 4, "error[io]: missing.fa: "),
+    (args(&["--profiles", "missing.txt"]), 4, "error[io]: missing.txt: ")
+  ] {
+    let (status, line) = run(&args);
+    assert_eq!(status, Some(code), "{:?}: {}", args, line);
+    assert!(line.starts_with(prefix), "{:?}: {}", args, line);
+  }
+
+  // Malformed inputs
+  for (content, message) in [
+    (">0 ab:Z:99999999999\nACGTA\n", "Wrong count \"99999999999\" at line 1"),
+    (">0 ab:Z:3 3 L:+:7:+\nAACCGT\n", "Link to unknown record 7"),
+    ("AACCGT\n", "Syntax error at line 1: \"AACCGT\""),
+    (">0 ab:Z:5 5\nAACCGT\n>1 ab:Z:5 4 3\nCCGTTA\n", "record 1 at line 3: Unitig of length 6 has 3 counts, but 2 are expected with k = 5"),
+    (">0 ab:Z:5 5\nAACCGT\n>1 ab:Z:5 5\nAACCGT\n", "record 1 at line 3: k-mer AACCG appears in records 0 and 1 (--merge-duplicates merges repeated k-mers)")
+  ] {
+    let malformed = dir.path().join("malformed.fa");
+    fs::write(&malformed, content).unwrap();
+    let (status, line) = run(&[&malformed]);
+    assert_eq!(status, Some(2), "{}", line);
+    assert!(line.starts_with("error[parse]: ") && line.ends_with(message), "{}", line);
+  }
+  let ids = dir.path().join("ids.txt");
+  fs::write(&ids, "1\nfirst\n").unwrap();
+  let (status, line) = run(&[OsStr::new("--profiles"), ids.as_os_str(), input.as_os_str()]);
+  assert_eq!((status, line.starts_with("error[parse]: ")), (Some(2), true), "{}", line);
+}
+
+#[test]
+fn subcommands_report_failed_checks_as_input_errors() {
+  let dir = tempfile::tempdir().unwrap();
+  let input = dir.path().join("small.fa");
+  fs::copy("tests/data/small.fa", &input).unwrap();
+  let output = Command::new(BIN).args(["query"]).arg(&input).arg("AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA").output().unwrap();
+  assert_eq!(output.status.code(), Some(2));
+  assert!(String::from_utf8(output.stderr).unwrap().lines().last().unwrap().starts_with("error[parse]: "));
+  let output = Command::new(BIN).args(["verify"]).arg(&input).output().unwrap();
+  assert_eq!(output.status.code(), Some(1));
+  assert!(String::from_utf8(output.stderr).unwrap().starts_with("error[usage]: the following required arguments were not provided: <FASTA>"));
+}
+
+#[test]
+fn help_and_version_are_shown_on_request() {
+  for arg in ["--help", "--version"] {
+    let output = Command::new(BIN).arg(arg).output().unwrap();
+    assert_eq!(output.status.code(), Some(0));
+    assert!(!output.stdout.is_empty() && output.stderr.is_empty(), "{}", arg);
+  }
+  let output = Command::new(BIN).args(["close", "--help"]).output().unwrap();
+  assert!(String::from_utf8(output.stdout).unwrap().contains("--shrink-delta <D>"));
+  // Without arguments the help is shown, as a usage error
+  let output = Command::new(BIN).output().unwrap();
+  assert_eq!(output.status.code(), Some(1));
+  let stderr = String::from_utf8(output.stderr).unwrap();
+  assert!(stderr.contains("Commands:") && stderr.ends_with("error[usage]: missing arguments\n"), "{}", stderr);
+}
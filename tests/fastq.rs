@@ -0,0 +1,57 @@
+use std::fs;
+use std::process::Command;
+
+const BIN: &str = env!("CARGO_BIN_EXE_closed-unitigs");
+
+#[test]
+fn reads_are_closed_as_their_counted_graph() {
+  let dir = tempfile::tempdir().unwrap();
+  // Reads tiling a sequence, covering its middle more than its ends
+  let genome = "ACGTTGCATGTCGCATGATGCATGAGAGCTAGCTTACGGATCCAGT";
+  let reads: String = (0..genome.len() - 12).step_by(3).map(|i| format!("@r{}\n{}\n+\n{}\n", i, &genome[i..i+12], "I".repeat(12))).collect();
+  let input = dir.path().join("reads.fq");
+  fs::write(&input, reads).unwrap();
+  let graph = dir.path().join("reads.fa");
+  let run = |args: &[&str], input: &std::path::Path| Command::new(BIN).args(args).arg(input).output().unwrap();
+
+  let output = Command::new(BIN).args(["convert", "--k", "7"]).arg(&input).arg(&graph).output().unwrap();
+  assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+  assert!(run(&["--no-manifest", "--k", "7", "-o", dir.path().join("from_reads").to_str().unwrap()], &input).status.success());
+  assert!(run(&["--no-manifest", "-o", dir.path().join("from_graph").to_str().unwrap()], &graph).status.success());
+  let read = |name: &str| fs::read_to_string(dir.path().join(name)).unwrap();
+  assert!(!read("from_reads.clo.counts").is_empty());
+  assert_eq!((read("from_reads.clo.fa"), read("from_reads.clo.counts")), (read("from_graph.clo.fa"), read("from_graph.clo.counts")));
+
+  // The same k-mers counted by another tool, in the order of the reads, given on either strand
+  let mut table: Vec<(&str, u32)> = Vec::new();
+  for i in (0..genome.len() - 12).step_by(3) {
+    for kmer in (i..=i + 5).map(|j| &genome[j..j+7]) {
+      match table.iter_mut().find(|(other, _)| *other == kmer) {
+        Some((_, count)) => *count += 1,
+        None => table.push((kmer, 1))
+      }
+    }
+  }
+  let reverse = |kmer: &str| kmer.chars().rev().map(|c| match c {'A' => 'T', 'C' => 'G', 'G' => 'C', _ => 'A'}).collect::<String>();
+  let table: String = table.iter().enumerate().map(|(i, (kmer, count))| match i % 2 {
+    0 => format!("{}\t{}\n", kmer, count),
+    _ => format!("{}\t{}\n", reverse(kmer), count)
+  }).collect();
+  let kmers = dir.path().join("reads.kmers");
+  fs::write(&kmers, table).unwrap();
+  let output = run(&["--no-manifest", "-o", dir.path().join("from_kmers").to_str().unwrap()], &kmers);
+  assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+  // The unitigs are the same, read on the strand of their first k-mer
+  let unitigs = |name: &str| {
+    let (fasta, counts) = (read(&format!("{}.clo.fa", name)), read(&format!("{}.clo.counts", name)));
+    let mut unitigs: Vec<_> = fasta.lines().skip(1).step_by(2).map(|s| s.min(&reverse(s)).to_string()).zip(counts.lines().map(|c| String::from(c.rsplit('\t').next().unwrap()))).collect();
+    unitigs.sort();
+    unitigs
+  };
+  assert_eq!(unitigs("from_kmers"), unitigs("from_graph"));
+
+  // k cannot be told from reads
+  let output = run(&[], &input);
+  assert_eq!(output.status.code(), Some(2));
+  assert!(String::from_utf8(output.stderr).unwrap().contains("Counting the k-mers of reads needs k, given with --k"));
+}
@@ -0,0 +1,91 @@
+use std::collections::HashMap;
+use std::fs;
+use std::process::Command;
+
+const BIN: &str = env!("CARGO_BIN_EXE_closed-unitigs");
+
+/// Reverse complement of a sequence
+fn rev_compl(seq: &str) -> String {
+  seq.chars().rev().map(|c| match c {'A' => 'T', 'C' => 'G', 'G' => 'C', _ => 'A'}).collect()
+}
+
+#[test]
+fn closed_unitigs_are_written_as_gfa() {
+  let dir = tempfile::tempdir().unwrap();
+  let input = dir.path().join("small.fa");
+  fs::copy("tests/data/small.fa", &input).unwrap();
+  let gfa = dir.path().join("small.gfa");
+  let output = Command::new(BIN).args(["--profiles", "all", "--gfa-out"]).arg(&gfa).arg(&input).output().unwrap();
+  assert!(output.status.success());
+
+  let fasta = fs::read_to_string(dir.path().join("small.clo.fa")).unwrap();
+  let seqs: Vec<_> = fasta.lines().skip(1).step_by(2).collect();
+  let profiles = fs::read_to_string(dir.path().join("small.clo.profiles.tsv")).unwrap();
+  let mut totals = HashMap::new();
+  for row in profiles.lines().filter(|l| !l.starts_with(';')).skip(1) {
+    let row: Vec<u64> = row.split('\t').map(|x| x.parse().unwrap()).collect();
+    *totals.entry(row[0] as usize).or_insert(0) += row[2];
+  }
+
+  let gfa = fs::read_to_string(&gfa).unwrap();
+  let mut lines = gfa.lines();
+  assert_eq!(lines.next(), Some("H\tVN:Z:1.0"));
+  let (segments, links): (Vec<_>, Vec<_>) = lines.map(|l| l.split('\t').collect::<Vec<_>>()).partition(|l| l[0] == "S");
+  // Segments are the closed unitigs, in output order, with their total and mean k-mer count
+  assert_eq!(segments.len(), seqs.len());
+  for (id, segment) in segments.iter().enumerate() {
+    let total = totals[&id];
+    let mean = total as f64 / (seqs[id].len() - 7 + 1) as f64;
+    assert_eq!(segment[1..5], [id.to_string(), seqs[id].to_string(), format!("LN:i:{}", seqs[id].len()), format!("KC:i:{}", total)]);
+    assert!((segment[5].strip_prefix("dp:f:").unwrap().parse::<f64>().unwrap() - mean).abs() < 1e-6);
+  }
+  // Linked closed unitigs overlap by k-1 bases
+  assert!(!links.is_empty());
+  for link in links {
+    let oriented = |id: &str, dir: &str| {
+      let seq = seqs[id.parse::<usize>().unwrap()];
+      if dir == "+" {seq.to_string()} else {rev_compl(seq)}
+    };
+    let (from, to) = (oriented(link[1], link[2]), oriented(link[3], link[4]));
+    assert_eq!(link[5], "6M");
+    assert_eq!(from[from.len()-6..], to[..6]);
+  }
+}
+
+#[test]
+fn closed_unitigs_are_written_as_gfa2() {
+  let dir = tempfile::tempdir().unwrap();
+  let input = dir.path().join("small.fa");
+  fs::copy("tests/data/small.fa", &input).unwrap();
+  let gfa = dir.path().join("small.gfa");
+  let output = Command::new(BIN).args(["--gfa-version", "2", "--gfa-out"]).arg(&gfa).arg(&input).output().unwrap();
+  assert!(output.status.success());
+
+  let gfa = fs::read_to_string(&gfa).unwrap();
+  let mut lines = gfa.lines();
+  assert_eq!(lines.next(), Some("H\tVN:Z:2.0"));
+  let (segments, edges): (Vec<_>, Vec<_>) = lines.map(|l| l.split('\t').collect::<Vec<_>>()).partition(|l| l[0] == "S");
+  let mut seqs = HashMap::new();
+  for segment in segments {
+    assert_eq!(segment[2], segment[3].len().to_string());
+    seqs.insert(segment[1], segment[3]);
+  }
+  // Each edge gives the k-1 bases shared by its segments, on their forward strand, $ marking the end of a segment
+  assert!(!edges.is_empty());
+  for edge in edges {
+    assert_eq!((edge[1], edge[8]), ("*", "6M"));
+    // The overlap is at the end of the first segment as read and at the start of the second one
+    let overlap = |reference: &str, begin: &str, end: &str, last: bool| {
+      let (id, dir) = reference.split_at(reference.len() - 1);
+      let seq = seqs[id];
+      if last == (dir == "+") {
+        assert_eq!(end, format!("{}$", seq.len()));
+      } else {
+        assert_eq!(begin, "0");
+      }
+      let bases = &seq[begin.trim_end_matches('$').parse().unwrap()..end.trim_end_matches('$').parse().unwrap()];
+      if dir == "+" {bases.to_string()} else {rev_compl(bases)}
+    };
+    assert_eq!(overlap(edge[2], edge[4], edge[5], true), overlap(edge[3], edge[6], edge[7], false));
+  }
+}
@@ -0,0 +1,104 @@
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+const BIN: &str = env!("CARGO_BIN_EXE_closed-unitigs");
+const GOLDEN: &str = "tests/data/golden";
+
+/// Parses every number of a machine-readable output back, checking that integers are written in plain decimal
+/// and ratios with 6 decimals
+fn check_numbers(name: &str, content: &str) {
+  let digits = |n: &str| !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit());
+  let plain = |n: &str| match n.split_once('.') {
+    None => digits(n) && n.parse::<u64>().is_ok(),
+    Some((int, dec)) => digits(int) && digits(dec) && dec.len() == 6 && n.parse::<f64>().is_ok()
+  };
+  if name.ends_with(".json") {
+    let mut values = vec![serde_json::from_str::<serde_json::Value>(content).unwrap()];
+    while let Some(value) = values.pop() {
+      match value {
+        // Their formatting is lost by parsing, but checked by the comparison with the golden files
+        serde_json::Value::Number(n) => assert!(n.is_u64() || n.as_f64().is_some_and(f64::is_finite), "{}: {}", name, n),
+        serde_json::Value::Array(a) => values.extend(a),
+        serde_json::Value::Object(o) => for (key, value) in o {
+          if key.bytes().all(|b| b.is_ascii_digit()) {
+            assert!(plain(&key), "{}: {}", name, key);
+          }
+          values.push(value);
+        },
+        _ => ()
+      }
+    }
+  } else {
+    for line in content.lines() {
+      let fields: Vec<_> = match line.strip_prefix(">clo_") {
+        // The id, then the values of the tags
+        Some(header) => header.split(' ').map(|f| f.split_once('=').map_or(f, |(_, value)| value)).collect(),
+        None if line.starts_with('>') => panic!("{}: unexpected header {}", name, line),
+        None if line.bytes().all(|b| b"ACGT".contains(&b)) => continue,
+        // Closed unitigs are named after their id
+        None => line.split('\t').map(|f| f.strip_prefix("clo_").unwrap_or(f)).collect()
+      };
+      if fields != ["id", "position", "count"] && fields[0] != "support" {
+        assert!(fields.iter().all(|f| plain(f)), "{}: {}", name, line);
+      }
+    }
+  }
+}
+
+#[test]
+fn outputs_match_golden_files() {
+  let dir = tempfile::tempdir().unwrap();
+  let input = dir.path().join("small.fa");
+  fs::copy("tests/data/small.fa", &input).unwrap();
+  let output = Command::new(BIN).args(["--sort", "none", "--no-manifest", "--profiles", "all"])
+    .arg("--json-report").arg(dir.path().join("small.report.json"))
+    .arg("--support-summary").arg(dir.path().join("small.supports.tsv")).arg(&input).output().unwrap();
+  assert!(output.status.success());
+
+  for name in ["small.clo.fa", "small.clo.counts", "small.clo.profiles.tsv", "small.report.json", "small.supports.tsv"] {
+    let content = fs::read_to_string(dir.path().join(name)).unwrap();
+    check_numbers(name, &content);
+    check_golden(name, &content);
+  }
+}
+
+/// Compares an output with its golden file, or updates the golden file with it if `UPDATE_GOLDEN` is set
+fn check_golden(name: &str, content: &str) {
+  let golden = Path::new(GOLDEN).join(name);
+  if std::env::var_os("UPDATE_GOLDEN").is_some() {
+    fs::write(&golden, content).unwrap();
+  }
+  assert_eq!(content, fs::read_to_string(&golden).unwrap(), "{} differs from {}, run with UPDATE_GOLDEN=1 to update it", name, golden.display());
+}
+
+#[test]
+fn other_fasta_outputs_match_golden_files() {
+  let dir = tempfile::tempdir().unwrap();
+  let input = dir.path().join("small.fa");
+  fs::copy("tests/data/small.fa", &input).unwrap();
+  let run = |args: &[&str]| {
+    let output = Command::new(BIN).args(["--sort", "none", "--no-manifest"]).args(args).arg(&input).output().unwrap();
+    assert!(output.status.success());
+  };
+  run(&["--shards", "2"]);
+  run(&["--split-by-support", dir.path().join("supports").to_str().unwrap()]);
+  let output = Command::new(BIN).arg("convert").arg(&input).arg(dir.path().join("small.convert.fa")).output().unwrap();
+  assert!(output.status.success());
+
+  let mut outputs = vec![
+    (String::from("small.clo.0.fa"), dir.path().join("small.clo.0.fa")),
+    (String::from("small.clo.1.fa"), dir.path().join("small.clo.1.fa")),
+    (String::from("small.convert.fa"), dir.path().join("small.convert.fa"))
+  ];
+  for entry in fs::read_dir(dir.path().join("supports")).unwrap() {
+    let path = entry.unwrap().path();
+    if path.extension().is_some_and(|e| e == "fa") {
+      outputs.push((format!("small.{}", path.file_name().unwrap().to_str().unwrap()), path));
+    }
+  }
+  assert!(outputs.len() > 3);
+  for (name, path) in outputs {
+    check_golden(&name, &fs::read_to_string(path).unwrap());
+  }
+}
@@ -0,0 +1,48 @@
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+const BIN: &str = env!("CARGO_BIN_EXE_closed-unitigs");
+
+/// Closes an input, returning the closed unitigs and the messages
+fn close(input: &Path, args: &[&str]) -> (String, String) {
+  let output = Command::new(BIN).args(["--sort", "none", "--no-manifest"]).args(args).arg(input).output().unwrap();
+  assert!(output.status.success());
+  (fs::read_to_string(input.with_extension("clo.fa")).unwrap(), String::from_utf8(output.stderr).unwrap())
+}
+
+#[test]
+fn indexes_are_used_only_when_valid() {
+  let dir = tempfile::tempdir().unwrap();
+  let input = dir.path().join("small.fa");
+  fs::copy("tests/data/small.fa", &input).unwrap();
+  let index = dir.path().join("small.fa.cuidx");
+  let (expected, _) = close(&input, &[]);
+  assert!(!index.exists());
+
+  let (fasta, stderr) = close(&input, &["--write-index"]);
+  assert_eq!(fasta, expected);
+  assert!(stderr.contains("K-mer index written"), "{}", stderr);
+  // Found next to the input and used
+  let (fasta, stderr) = close(&input, &[]);
+  assert_eq!(fasta, expected);
+  assert!(!stderr.contains("index"), "{}", stderr);
+
+  // A corrupted index is rebuilt
+  let mut data = fs::read(&index).unwrap();
+  let last = data.len() - 1;
+  data[last] ^= 1;
+  fs::write(&index, &data).unwrap();
+  let (fasta, stderr) = close(&input, &[]);
+  assert_eq!(fasta, expected);
+  assert!(stderr.contains("Corrupted index, rebuilding it") && stderr.contains("K-mer index written"), "{}", stderr);
+  assert!(!close(&input, &[]).1.contains("index"));
+
+  // So is the index of other options, or of a changed input
+  assert!(close(&input, &["--head", "2"]).1.contains("Index of another input or of other options, rebuilding it"));
+  let changed = fs::read_to_string(&input).unwrap().replacen("ab:Z:", "ab:Z:9", 1);
+  fs::write(&input, changed).unwrap();
+  let (fasta, stderr) = close(&input, &[]);
+  assert_ne!(fasta, expected);
+  assert!(stderr.contains("rebuilding it"), "{}", stderr);
+}
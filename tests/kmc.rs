@@ -0,0 +1,71 @@
+use closed_unitigs::{convert, Graph};
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+const BIN: &str = env!("CARGO_BIN_EXE_closed-unitigs");
+
+/// Sorted canonical closed unitigs of a run, with their support
+fn unitigs(dir: &Path, name: &str) -> Vec<(String, String)> {
+  let read = |ext: &str| fs::read_to_string(dir.join(format!("{}.clo.{}", name, ext))).unwrap();
+  let reverse = |s: &str| s.chars().rev().map(|c| match c {'A' => 'T', 'C' => 'G', 'G' => 'C', _ => 'A'}).collect::<String>();
+  let (fasta, counts) = (read("fa"), read("counts"));
+  let mut unitigs: Vec<_> = fasta.lines().skip(1).step_by(2).map(|s| s.min(&reverse(s)).to_string())
+    .zip(counts.lines().map(|c| String::from(c.rsplit('\t').next().unwrap()))).collect();
+  unitigs.sort();
+  unitigs
+}
+
+#[test]
+fn databases_are_closed_as_their_counted_graph() {
+  let dir = tempfile::tempdir().unwrap();
+  // The canonical 7-mers of small.fa with their counts, in two bins
+  let database = Path::new("tests/data/small.kmc_pre");
+  let graph = convert::read_kmc(database, &Default::default()).unwrap();
+  assert!(graph.equivalent(&Graph::try_from_reader(&fs::read(Path::new("tests/data/small.fa")).unwrap()[..], &Default::default()).unwrap()));
+
+  let run = |args: &[&str], input: &Path| Command::new(BIN).args(args).arg(input).output().unwrap();
+  for (name, input) in [("from_graph", "tests/data/small.fa"), ("from_pre", "tests/data/small.kmc_pre"), ("from_suf", "tests/data/small.kmc_suf")] {
+    let output = run(&["--no-manifest", "-o", dir.path().join(name).to_str().unwrap()], Path::new(input));
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+  }
+  assert!(!unitigs(dir.path(), "from_graph").is_empty());
+  assert_eq!(unitigs(dir.path(), "from_pre"), unitigs(dir.path(), "from_graph"));
+  assert_eq!(unitigs(dir.path(), "from_suf"), unitigs(dir.path(), "from_graph"));
+  let output = Command::new(BIN).args(["verify", "tests/data/small.kmc_pre"]).arg(dir.path().join("from_pre.clo.fa")).arg(dir.path().join("from_pre.clo.counts")).output().unwrap();
+  assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+
+  // Given by the name the two files share
+  let output = run(&["--no-manifest", "--format", "kmc", "-o", dir.path().join("from_name").to_str().unwrap()], Path::new("tests/data/small"));
+  assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+  assert_eq!(unitigs(dir.path(), "from_name"), unitigs(dir.path(), "from_graph"));
+
+  // Converted to BCALM, but not written back
+  let converted = dir.path().join("small.fa");
+  let output = Command::new(BIN).args(["convert", "tests/data/small.kmc_pre"]).arg(&converted).output().unwrap();
+  assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+  assert!(graph.equivalent(&convert::read_from(convert::Format::Bcalm, &fs::read(&converted).unwrap()[..]).unwrap()));
+  let output = Command::new(BIN).args(["convert", "tests/data/small.fa"]).arg(dir.path().join("small.kmc_pre")).output().unwrap();
+  assert_eq!(output.status.code(), Some(1));
+}
+
+#[test]
+fn broken_databases_are_input_errors() {
+  let dir = tempfile::tempdir().unwrap();
+  let (pre, suf) = (dir.path().join("db.kmc_pre"), dir.path().join("db.kmc_suf"));
+  fs::copy("tests/data/small.kmc_pre", &pre).unwrap();
+  let data = fs::read("tests/data/small.kmc_suf").unwrap();
+  fs::write(&suf, &data[..data.len() - 10]).unwrap();
+  let output = Command::new(BIN).arg(&pre).output().unwrap();
+  assert_eq!(output.status.code(), Some(2));
+  assert!(String::from_utf8(output.stderr).unwrap().contains("Malformed KMC database: the suffix file has fewer k-mers than the prefix file"));
+
+  // The suffix file is needed
+  fs::remove_file(&suf).unwrap();
+  let output = Command::new(BIN).arg(&pre).output().unwrap();
+  assert_eq!(output.status.code(), Some(4));
+  assert!(String::from_utf8(output.stderr).unwrap().contains("db.kmc_suf"));
+
+  let output = Command::new(BIN).args(["--format", "kmc", "-"]).output().unwrap();
+  assert_eq!(output.status.code(), Some(1));
+}
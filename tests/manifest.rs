@@ -0,0 +1,56 @@
+use std::fs;
+use std::process::Command;
+
+const BIN: &str = env!("CARGO_BIN_EXE_closed-unitigs");
+
+#[test]
+fn reports_record_the_run() {
+  let dir = tempfile::tempdir().unwrap();
+  let input = dir.path().join("small.fa");
+  fs::copy("tests/data/small.fa", &input).unwrap();
+  let report = dir.path().join("report.json");
+  assert!(Command::new(BIN).arg("--json-report").arg(&report).args(["--sort", "none"]).arg(&input).output().unwrap().status.success());
+
+  let json: serde_json::Value = serde_json::from_str(&fs::read_to_string(&report).unwrap()).unwrap();
+  assert_eq!(json["manifest"]["input"]["size"], fs::metadata(&input).unwrap().len());
+  assert_eq!(json["manifest"]["options"]["close"]["sort"], "none");
+  assert!(json["manifest"]["finished"].as_u64() >= json["manifest"]["started"].as_u64());
+  assert_eq!(json["manifest"]["graph"], serde_json::json!({"k": 7, "kmers": 80, "edges": 80, "records": 7}));
+  for phase in ["parse", "close"] {
+    assert!(json["manifest"]["phases"][phase].as_f64().unwrap() >= 0.);
+  }
+  assert!(cfg!(not(target_os = "linux")) || json["manifest"]["peak_memory"].as_u64() > Some(0));
+  let counts = fs::read_to_string(dir.path().join("small.clo.counts")).unwrap();
+  assert!(counts.starts_with("; closed-unitigs "));
+  assert_eq!(json["summary"]["outputs"], counts.lines().filter(|l| !l.starts_with(';')).count());
+
+  // Without manifest outputs only depend on the input and the options
+  assert!(Command::new(BIN).arg("--no-manifest").arg("--json-report").arg(&report).arg(&input).output().unwrap().status.success());
+  let json: serde_json::Value = serde_json::from_str(&fs::read_to_string(&report).unwrap()).unwrap();
+  assert!(json.get("manifest").is_none());
+  assert!(!fs::read_to_string(dir.path().join("small.clo.counts")).unwrap().contains(';'));
+}
+
+#[test]
+fn sampled_runs_are_marked() {
+  let dir = tempfile::tempdir().unwrap();
+  let input = dir.path().join("small.fa");
+  fs::copy("tests/data/small.fa", &input).unwrap();
+  let report = dir.path().join("report.json");
+  let run = |args: &[&str]| {
+    let output = Command::new(BIN).args(["--no-manifest", "--rng-seed", "7"]).args(args).arg("--json-report").arg(&report).arg(&input).output().unwrap();
+    assert!(output.status.success());
+    let records = |ext| fs::read_to_string(dir.path().join(ext)).unwrap().lines().filter(|l| !l.starts_with(';')).map(String::from).collect::<Vec<_>>();
+    let json: serde_json::Value = serde_json::from_str(&fs::read_to_string(&report).unwrap()).unwrap();
+    (records("small.clo.fa"), records("small.clo.counts"), String::from_utf8(output.stderr).unwrap(), json)
+  };
+
+  let (fasta, counts, _, json) = run(&[]);
+  assert!(json["summary"].get("sample").is_none());
+  // Keeping all the components gives the full run
+  let (sampled_fasta, sampled_counts, stderr, json) = run(&["--sample-components", "1"]);
+  assert_eq!((sampled_fasta, sampled_counts), (fasta, counts));
+  assert!(stderr.contains("Sampled 1 of 1 connected components (fraction 1, RNG seed 7)"), "{}", stderr);
+  assert_eq!(json["summary"]["sample"], serde_json::json!({"fraction": 1.0, "seed": 7, "components": 1, "kept": 1}));
+  assert!(fs::read_to_string(dir.path().join("small.clo.counts")).unwrap().starts_with("; sampled: 1 of 1 connected components"));
+}
@@ -0,0 +1,53 @@
+use std::fs;
+use std::process::Command;
+
+const BIN: &str = env!("CARGO_BIN_EXE_closed-unitigs");
+
+#[test]
+fn tiny_ceilings_degrade_then_abort() {
+  let dir = tempfile::tempdir().unwrap();
+  let input = dir.path().join("small.fa");
+  fs::copy("tests/data/small.fa", &input).unwrap();
+  let unrepresented = dir.path().join("unrepresented.tsv");
+  let output = Command::new(BIN).args(["--max-memory", "0.000000001", "--unrepresented"]).arg(&unrepresented).arg(&input).output().unwrap();
+  assert_eq!(output.status.code(), Some(3));
+  let stderr = String::from_utf8(output.stderr).unwrap();
+  for step in ["dropped the support cache", "writing new closed unitigs unsorted", "wrote the closed unitigs waiting to be sorted", "aborting"] {
+    assert!(stderr.contains(step), "{}", stderr);
+  }
+
+  let state: serde_json::Value = serde_json::from_str(&fs::read_to_string(dir.path().join("small.clo.resume.json")).unwrap()).unwrap();
+  assert!(state["seeds_done"].as_u64().unwrap() > 0);
+  // What was written before aborting is still valid
+  let written = fs::read_to_string(dir.path().join("small.clo.fa")).unwrap().lines().count() / 2;
+  assert_eq!(written, 3);
+  let verify = Command::new(BIN).arg("verify").arg(&input).arg(dir.path().join("small.clo.fa")).arg(dir.path().join("small.clo.counts")).output().unwrap();
+  assert!(verify.status.success());
+  // The seeds left are reported
+  assert!(fs::read_to_string(&unrepresented).unwrap().lines().any(|l| l.ends_with("\tnever-seeded")));
+
+  // Resumed with a higher ceiling, closing writes the same closed unitigs as without one
+  let closed = |args: &[&str]| {
+    let output = Command::new(BIN).args(["--no-manifest", "--unrepresented"]).arg(&unrepresented).args(args).arg(&input).output().unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let mut fasta: Vec<_> = fs::read_to_string(dir.path().join("small.clo.fa")).unwrap().lines().filter(|l| !l.starts_with('>')).map(String::from).collect();
+    fasta.sort();
+    fasta
+  };
+  let resumed = closed(&["--resume", "--max-memory", "1"]);
+  assert!(!dir.path().join("small.clo.resume.json").exists());
+  assert_eq!(resumed, closed(&[]));
+}
+
+#[test]
+fn large_ceilings_change_nothing() {
+  let dir = tempfile::tempdir().unwrap();
+  let input = dir.path().join("small.fa");
+  fs::copy("tests/data/small.fa", &input).unwrap();
+  let run = |args: &[&str]| {
+    let output = Command::new(BIN).args(["--sort", "none", "--no-manifest"]).args(args).arg(&input).output().unwrap();
+    assert!(output.status.success());
+    fs::read_to_string(dir.path().join("small.clo.fa")).unwrap()
+  };
+  assert_eq!(run(&[]), run(&["--max-memory", "1"]));
+}
@@ -0,0 +1,76 @@
+use std::ffi::OsString;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+const BIN: &str = env!("CARGO_BIN_EXE_closed-unitigs");
+
+/// Closes a copy of the small test graph stored with the given file name,
+/// checking the outputs are named after its stem
+fn close_as(dir: &Path, name: OsString, stem: OsString) {
+  let input = dir.join(name);
+  fs::copy("tests/data/small.fa", &input).unwrap();
+  assert!(Command::new(BIN).arg(&input).output().unwrap().status.success());
+  for ext in &[".clo.fa", ".clo.counts"] {
+    let mut output = stem.clone();
+    output.push(ext);
+    assert!(dir.join(output).is_file());
+  }
+}
+
+#[test]
+fn spaces_and_unicode() {
+  let dir = tempfile::tempdir().unwrap();
+  let dir = dir.path().join("dir with spaces");
+  fs::create_dir(&dir).unwrap();
+  close_as(&dir, "grafo ünïcødé 图.fa".into(), "grafo ünïcødé 图".into());
+}
+
+#[test]
+fn no_extension() {
+  let dir = tempfile::tempdir().unwrap();
+  close_as(dir.path(), "graph".into(), "graph".into());
+}
+
+#[test]
+fn outputs_can_be_placed_elsewhere() {
+  let dir = tempfile::tempdir().unwrap();
+  let input = dir.path().join("graph.fa");
+  fs::copy("tests/data/small.fa", &input).unwrap();
+  let scratch = dir.path().join("scratch");
+  fs::create_dir(&scratch).unwrap();
+  let run = |args: &[&str]| assert!(Command::new(BIN).arg("--no-manifest").args(args).arg(&input).output().unwrap().status.success());
+
+  run(&["-o", scratch.join("run").to_str().unwrap(), "--shards", "2"]);
+  for name in &["run.clo.0.fa", "run.clo.1.fa", "run.clo.0.counts", "run.clo.1.counts"] {
+    assert!(scratch.join(name).is_file(), "{}", name);
+  }
+  // The files named explicitly take precedence over the prefix
+  run(&["--output-prefix", scratch.join("other").to_str().unwrap(), "--fasta-out", scratch.join("unitigs.fa").to_str().unwrap()]);
+  assert!(scratch.join("unitigs.fa").is_file() && scratch.join("other.clo.counts").is_file());
+  assert!(!scratch.join("other.clo.fa").exists() && !dir.path().join("graph.clo.fa").exists());
+}
+
+#[test]
+fn compressed_inputs_are_named_without_the_compression() {
+  use flate2::{write::GzEncoder, Compression};
+  use std::io::Write;
+  let dir = tempfile::tempdir().unwrap();
+  let input = dir.path().join("graph.fa.gz");
+  let mut gzip = GzEncoder::new(fs::File::create(&input).unwrap(), Compression::default());
+  gzip.write_all(&fs::read("tests/data/small.fa").unwrap()).unwrap();
+  gzip.finish().unwrap();
+  assert!(Command::new(BIN).arg("--no-manifest").arg(&input).output().unwrap().status.success());
+  let plain = dir.path().join("plain.fa");
+  fs::copy("tests/data/small.fa", &plain).unwrap();
+  assert!(Command::new(BIN).arg("--no-manifest").arg(&plain).output().unwrap().status.success());
+  assert_eq!(fs::read_to_string(dir.path().join("graph.clo.fa")).unwrap(), fs::read_to_string(dir.path().join("plain.clo.fa")).unwrap());
+}
+
+#[cfg(unix)]
+#[test]
+fn non_utf8() {
+  use std::os::unix::ffi::OsStringExt;
+  let dir = tempfile::tempdir().unwrap();
+  close_as(dir.path(), OsString::from_vec(b"gr\xffph.fa".to_vec()), OsString::from_vec(b"gr\xffph".to_vec()));
+}
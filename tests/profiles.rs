@@ -0,0 +1,94 @@
+use std::fs;
+use std::process::Command;
+
+const BIN: &str = env!("CARGO_BIN_EXE_closed-unitigs");
+
+#[test]
+fn selected_ids_are_profiled() {
+  let dir = tempfile::tempdir().unwrap();
+  let input = dir.path().join("small.fa");
+  fs::copy("tests/data/small.fa", &input).unwrap();
+  let ids = dir.path().join("ids.txt");
+  fs::write(&ids, "0\n2\n999\n").unwrap();
+  let output = Command::new(BIN).arg("--profiles").arg(&ids).arg(&input).output().unwrap();
+  assert!(output.status.success());
+  assert!(String::from_utf8(output.stderr).unwrap().contains("no closed unitig with id 999"));
+
+  // Ids are assigned in output order
+  let fasta = fs::read_to_string(dir.path().join("small.clo.fa")).unwrap();
+  let seqs: Vec<_> = fasta.lines().skip(1).step_by(2).collect();
+  let counts: Vec<u32> = fs::read_to_string(dir.path().join("small.clo.counts")).unwrap().lines().filter(|l| !l.starts_with(';'))
+    .map(|c| c.rsplit('\t').next().unwrap().parse().unwrap()).collect();
+  let profiles = fs::read_to_string(dir.path().join("small.clo.profiles.tsv")).unwrap();
+  for id in [0, 2] {
+    let rows: Vec<Vec<u32>> = profiles.lines().filter(|l| !l.starts_with(';')).skip(1).map(|l| l.split('\t').map(|x| x.parse().unwrap()).collect()).filter(|r: &Vec<u32>| r[0] == id as u32).collect();
+    assert_eq!(rows.len(), seqs[id].len() - 7 + 1);
+    assert_eq!(rows.iter().map(|r| r[2]).min(), Some(counts[id]));
+  }
+}
+
+#[test]
+fn provenance_spells_the_closed_unitigs() {
+  let dir = tempfile::tempdir().unwrap();
+  let input = dir.path().join("small.fa");
+  fs::copy("tests/data/small.fa", &input).unwrap();
+  let provenance = dir.path().join("provenance.tsv");
+  assert!(Command::new(BIN).arg("--provenance").arg(&provenance).arg(&input).output().unwrap().status.success());
+
+  let k = 7;
+  let records: Vec<_> = fs::read_to_string(&input).unwrap().lines().skip(1).step_by(2).map(String::from).collect();
+  let rev_compl = |s: &str| s.chars().rev().map(|c| match c {'A' => 'T', 'C' => 'G', 'G' => 'C', _ => 'A'}).collect::<String>();
+  let fasta = fs::read_to_string(dir.path().join("small.clo.fa")).unwrap();
+  let seqs: Vec<_> = fasta.lines().skip(1).step_by(2).collect();
+  let rows: Vec<_> = fs::read_to_string(&provenance).unwrap().lines().filter(|l| !l.starts_with(';')).skip(1).map(String::from).collect();
+  assert_eq!(rows.len(), seqs.len());
+  for (id, row) in rows.iter().enumerate() {
+    let (row_id, path) = row.split_once('\t').unwrap();
+    assert_eq!(row_id.parse::<usize>().unwrap(), id);
+    // Each run spells its k-mers, overlapping the previous ones by k-1 bases
+    let mut spelled = String::new();
+    for run in path.split(',') {
+      let (record, range) = run.split_once(':').unwrap();
+      let (record, forward) = (record[..record.len() - 1].parse::<usize>().unwrap(), record.ends_with('+'));
+      let (first, last) = range.split_once('-').map(|(a, b)| (a.parse::<usize>().unwrap(), b.parse::<usize>().unwrap())).unwrap();
+      let bases = match forward {
+        true => records[record][first..last + k].to_string(),
+        false => rev_compl(&records[record][last..first + k])
+      };
+      spelled += if spelled.is_empty() {&bases} else {&bases[k - 1..]};
+    }
+    assert_eq!(spelled, seqs[id]);
+  }
+}
+
+#[test]
+fn membership_lists_the_closed_unitigs_of_each_kmer() {
+  let dir = tempfile::tempdir().unwrap();
+  let input = dir.path().join("small.fa");
+  fs::copy("tests/data/small.fa", &input).unwrap();
+  let membership = dir.path().join("membership.tsv");
+  let output = Command::new(BIN).arg("--membership-out").arg(&membership).args(["--support-floor", "2", "--drop-below-floor"]).arg(&input).output().unwrap();
+  assert!(output.status.success());
+
+  let rev_compl = |s: &str| s.chars().rev().map(|c| match c {'A' => 'T', 'C' => 'G', 'G' => 'C', _ => 'A'}).collect::<String>();
+  let fasta = fs::read_to_string(dir.path().join("small.clo.fa")).unwrap();
+  let seqs: Vec<_> = fasta.lines().skip(1).step_by(2).collect();
+  let rows: Vec<_> = fs::read_to_string(&membership).unwrap().lines().filter(|l| !l.starts_with(';')).skip(1).map(String::from).collect();
+  assert_eq!(rows.len(), 80);
+  let mut uncovered = 0;
+  for row in rows {
+    let fields: Vec<_> = row.split('\t').collect();
+    let kmer = fields[0];
+    let containing: Vec<_> = (0..seqs.len()).filter(|&id| seqs[id].contains(kmer) || seqs[id].contains(&rev_compl(kmer))).collect();
+    assert_eq!(fields[3].parse::<usize>().unwrap(), containing.len(), "{}", row);
+    match containing.first() {
+      Some(id) => assert_eq!(fields[2], id.to_string()),
+      None => {
+        assert_eq!(fields[2], "-");
+        uncovered += 1;
+      }
+    }
+  }
+  // The k-mers of the closures dropped at the support floor
+  assert!(uncovered > 0);
+}
@@ -0,0 +1,52 @@
+use serde_json::Value;
+use std::fs;
+use std::process::Command;
+
+const BIN: &str = env!("CARGO_BIN_EXE_closed-unitigs");
+
+#[test]
+fn progress_events_are_json() {
+  let dir = tempfile::tempdir().unwrap();
+  let input = dir.path().join("small.fa");
+  fs::copy("tests/data/small.fa", &input).unwrap();
+  let events = dir.path().join("progress.jsonl");
+  assert!(Command::new(BIN).arg("--progress-file").arg(&events).arg(&input).status().unwrap().success());
+
+  let events: Vec<Value> = fs::read_to_string(&events).unwrap().lines().map(|l| serde_json::from_str(l).unwrap()).collect();
+  // Phases start and end in order
+  let phases: Vec<_> = events.iter().filter_map(|e| Some(format!("{} {}", e["phase"].as_str()?, e["event"].as_str()?))).collect();
+  assert_eq!(phases, ["parse start", "parse end", "close start", "close end"]);
+  let times: Vec<_> = events.iter().filter_map(|e| e["time_ms"].as_u64()).collect();
+  assert!(times.windows(2).all(|w| w[0] <= w[1]));
+
+  // Counters only grow within each phase
+  for (phase, counters) in [("parse", ["records", "bytes"]), ("close", ["seeds_done", "seeds_total"])] {
+    let progress: Vec<_> = events.iter().filter(|e| e["phase"] == phase && e.get("event").is_none()).collect();
+    for counter in counters {
+      let values: Vec<_> = progress.iter().map(|e| e[counter].as_u64().unwrap()).collect();
+      assert!(values.windows(2).all(|w| w[0] <= w[1]), "{} {:?}", counter, values);
+    }
+  }
+  assert!(events.iter().any(|e| e["phase"] == "parse" && e["records"].as_u64() == Some(1)));
+}
+
+#[test]
+fn progress_can_be_disabled() {
+  let dir = tempfile::tempdir().unwrap();
+  let input = dir.path().join("small.fa");
+  fs::copy("tests/data/small.fa", &input).unwrap();
+  assert!(Command::new(BIN).args(["--no-manifest", "--no-progress"]).arg(&input).status().unwrap().success());
+  let output = Command::new(BIN).args(["--no-progress", "--progress-json"]).arg(&input).output().unwrap();
+  assert_eq!(output.status.code(), Some(1));
+}
+
+#[test]
+fn progress_goes_to_a_single_destination() {
+  let dir = tempfile::tempdir().unwrap();
+  let input = dir.path().join("small.fa");
+  fs::copy("tests/data/small.fa", &input).unwrap();
+  let events = dir.path().join("progress.jsonl");
+  let output = Command::new(BIN).arg("--progress-json").arg("--progress-file").arg(&events).arg(&input).output().unwrap();
+  assert_eq!(output.status.code(), Some(1));
+  assert!(String::from_utf8(output.stderr).unwrap().contains("--progress-file"));
+}
@@ -0,0 +1,45 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+const BIN: &str = env!("CARGO_BIN_EXE_closed-unitigs");
+
+/// Copies the small test graph into the given directory, returning its path
+fn input(dir: &Path) -> PathBuf {
+  let input = dir.join("small.fa");
+  fs::copy("tests/data/small.fa", &input).unwrap();
+  input
+}
+
+/// Runs the program on the input with the given options, returning the exit code and the outputs
+fn run(input: &Path, args: &[&str]) -> (Option<i32>, String, String) {
+  let status = Command::new(BIN).args(["--no-manifest", "--sort", "count"]).args(args).arg(input).output().unwrap().status;
+  let read = |extension| fs::read_to_string(input.with_extension(extension)).unwrap_or_default();
+  (status.code(), read("clo.fa"), read("clo.counts"))
+}
+
+#[test]
+fn checkpointing_does_not_change_the_output() {
+  let dir = tempfile::tempdir().unwrap();
+  let input = input(dir.path());
+  let plain = run(&input, &[]);
+  assert_eq!(plain.0, Some(0));
+  assert_eq!(run(&input, &["--checkpoint-every", "0"]), plain);
+  // The state is removed once closing completes
+  assert!(!dir.path().join("small.clo.resume.json").exists());
+}
+
+#[test]
+fn wrong_states_are_refused() {
+  let dir = tempfile::tempdir().unwrap();
+  let input = input(dir.path());
+  assert_eq!(run(&input, &["--resume"]).0, Some(4));
+  fs::write(dir.path().join("small.clo.resume.json"), concat!(
+    r#"{"fingerprint":0,"seeds_done":0,"closed":[],"covered":[],"reasons":[],"skipped_seeds":0,"#,
+    r#""truncated":0,"timed_out":0,"capped":0,"floored":0,"journal_len":0}"#
+  )).unwrap();
+  assert_eq!(run(&input, &["--resume"]).0, Some(1));
+  // The memory ceiling is not part of the state
+  let plain = run(&input, &[]);
+  assert_eq!(run(&input, &["--checkpoint-every", "1", "--max-memory", "1"]), plain);
+}
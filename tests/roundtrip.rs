@@ -0,0 +1,57 @@
+use closed_unitigs::Graph;
+use closed_unitigs::convert::{self, Format};
+use closed_unitigs::synthetic;
+use closed_unitigs::testing::assert_roundtrip_as;
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::process::Command;
+
+const BIN: &str = env!("CARGO_BIN_EXE_closed-unitigs");
+
+/// The small test graph and synthetic graphs
+fn graphs() -> Vec<(String, Graph)> {
+  let small = convert::read_from(Format::Bcalm, BufReader::new(File::open("tests/data/small.fa").unwrap())).unwrap();
+  let mut graphs = vec![(String::from("small"), small)];
+  graphs.push((String::from("linear"), synthetic::generate_linear(41, 200, |i| (i * 7919 % 13) as u32 + 1).graph));
+  graphs.push((String::from("branching"), synthetic::generate_branching(7, 3, 3).graph));
+  graphs.push((String::from("genome"), synthetic::generate_random_genome(11, 500, 5., 0.01, 1)));
+  graphs
+}
+
+/// File extension of a format
+fn extension(format: Format) -> &'static str {
+  match format {
+    Format::Bcalm => "fa",
+    Format::Gfa => "gfa",
+    Format::Binary => "cug",
+    _ => unreachable!("{:?} is only read", format)
+  }
+}
+
+#[test]
+fn every_format_roundtrips() {
+  for (_, graph) in graphs() {
+    for format in Format::ALL {
+      assert_roundtrip_as(&graph, format);
+    }
+  }
+}
+
+#[test]
+fn every_conversion_keeps_the_graph() {
+  let dir = tempfile::tempdir().unwrap();
+  for (name, graph) in graphs() {
+    for from in Format::ALL {
+      let input = dir.path().join(format!("{}.{}", name, extension(from)));
+      convert::write(&graph, from, &input).unwrap();
+      for to in Format::ALL {
+        let output = dir.path().join(format!("{}.converted.{}", name, extension(to)));
+        let status = Command::new(BIN).arg("convert").arg(&input).arg(&output).status().unwrap();
+        assert!(status.success(), "{} from {:?} to {:?}", name, from, to);
+        let converted = convert::read_from(to, BufReader::new(File::open(&output).unwrap())).unwrap();
+        assert!(graph.equivalent(&converted), "{} from {:?} to {:?}", name, from, to);
+        fs::remove_file(output).unwrap();
+      }
+    }
+  }
+}
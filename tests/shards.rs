@@ -0,0 +1,91 @@
+use closed_unitigs::convert::{self, Format};
+use closed_unitigs::synthetic;
+use std::fs;
+use std::process::Command;
+
+const BIN: &str = env!("CARGO_BIN_EXE_closed-unitigs");
+
+#[test]
+fn shards_split_the_output() {
+  let dir = tempfile::tempdir().unwrap();
+  // Enough closed unitigs to fill every shard
+  let input = dir.path().join("genome.fa");
+  convert::write(&synthetic::generate_random_genome(21, 2000, 8., 0.01, 3), Format::Bcalm, &input).unwrap();
+  assert!(Command::new(BIN).arg(&input).output().unwrap().status.success());
+  assert!(Command::new(BIN).args(["--shards", "3"]).arg(&input).output().unwrap().status.success());
+
+  let mut whole: Vec<_> = fs::read_to_string(dir.path().join("genome.clo.fa")).unwrap().lines().filter(|l| !l.starts_with('>')).map(String::from).collect();
+  let manifest = fs::read_to_string(dir.path().join("genome.clo.shards.tsv")).unwrap();
+  let (mut sharded, mut ids) = (Vec::new(), Vec::new());
+  for row in manifest.lines().filter(|l| !l.starts_with(';')).skip(1) {
+    let row: Vec<_> = row.split('\t').collect();
+    let fasta = fs::read_to_string(row[0]).unwrap();
+    assert!(!fasta.is_empty());
+    let counts = fs::read_to_string(row[1]).unwrap();
+    assert_eq!(fasta.lines().count(), 2 * row[2].parse::<usize>().unwrap());
+    assert_eq!(counts.lines().filter(|l| !l.starts_with(';')).count(), row[2].parse::<usize>().unwrap());
+    ids.extend(fasta.lines().step_by(2).map(|h| h[">clo_".len()..].split(' ').next().unwrap().parse::<usize>().unwrap()));
+    sharded.extend(fasta.lines().skip(1).step_by(2).map(String::from));
+  }
+  whole.sort();
+  sharded.sort();
+  ids.sort_unstable();
+  assert_eq!(whole, sharded);
+  assert_eq!(ids, (0..whole.len()).collect::<Vec<_>>());
+}
+
+#[test]
+fn supports_split_the_output() {
+  let dir = tempfile::tempdir().unwrap();
+  let input = dir.path().join("small.fa");
+  fs::copy("tests/data/small.fa", &input).unwrap();
+  let out = dir.path().join("supports");
+  assert!(Command::new(BIN).arg(&input).output().unwrap().status.success());
+  assert!(Command::new(BIN).arg("--split-by-support").arg(&out).args(["--support-cap", "4"]).arg(&input).output().unwrap().status.success());
+
+  let whole = fs::read_to_string(dir.path().join("small.clo.counts")).unwrap().lines().filter(|l| !l.starts_with(';')).count();
+  let manifest = fs::read_to_string(out.join("supports.tsv")).unwrap();
+  let mut records = 0;
+  for row in manifest.lines().filter(|l| !l.starts_with(';')).skip(1) {
+    let row: Vec<_> = row.split('\t').collect();
+    let counts: Vec<u32> = fs::read_to_string(row[2]).unwrap().lines().filter(|l| !l.starts_with(';'))
+      .map(|c| c.rsplit('\t').next().unwrap().parse().unwrap()).collect();
+    assert_eq!(counts.len(), row[3].parse::<usize>().unwrap());
+    match row[0].strip_prefix(">=") {
+      Some(cap) => assert!(row[1].ends_with("support_ge_4.fa") && cap == "4" && counts.iter().all(|&c| c >= 4)),
+      None => assert!(row[1].ends_with(&format!("support_{}.fa", row[0])) && counts.iter().all(|c| c.to_string() == row[0]))
+    }
+    records += counts.len();
+  }
+  assert_eq!(records, whole);
+}
+
+#[test]
+fn components_split_the_output() {
+  let dir = tempfile::tempdir().unwrap();
+  let input = dir.path().join("parts.fa");
+  // A component of two records, one of a record and a single k-mer
+  fs::write(&input, ">0 ab:Z:5 5 L:+:1:+\nAACCGT\n>1 ab:Z:5 4 L:-:0:-\nCCGTTA\n>2 ab:Z:1 1 3\nGGGATCA\n>3 ab:Z:2\nTTCAG\n").unwrap();
+  let out = dir.path().join("components");
+  let run = |args: &[&str]| assert!(Command::new(BIN).arg("--split-by-component").arg(&out).args(args).arg(&input).output().unwrap().status.success());
+  let manifest = || fs::read_to_string(out.join("components.tsv")).unwrap().lines().filter(|l| !l.starts_with(';'))
+    .map(|row| row.split('\t').map(|c| c.rsplit('/').next().unwrap().to_string()).collect::<Vec<_>>()).collect::<Vec<_>>();
+
+  run(&[]);
+  assert_eq!(manifest(), vec![
+    vec!["component", "kmers", "input_bp", "unitigs", "unitig_bp", "fasta", "counts"],
+    vec!["0", "4", "12", "2", "12", "component_0.fa", "component_0.counts"],
+    vec!["1", "3", "7", "2", "11", "component_1.fa", "component_1.counts"],
+    vec!["2", "1", "5", "1", "5", "component_2.fa", "component_2.counts"]
+  ]);
+  assert_eq!(fs::read_to_string(out.join("component_2.fa")).unwrap().lines().nth(1), Some("TTCAG"));
+
+  // Components with fewer k-mers than the minimum are pooled
+  fs::remove_dir_all(&out).unwrap();
+  run(&["--min-component", "4"]);
+  let rows = manifest();
+  assert_eq!(rows.iter().skip(1).map(|r| r[5].as_str()).collect::<Vec<_>>(), vec!["component_0.fa", "rest.fa", "rest.fa"]);
+  assert!(!out.join("component_1.fa").exists());
+  let rest = fs::read_to_string(out.join("rest.fa")).unwrap();
+  assert_eq!(rest.lines().skip(1).step_by(2).collect::<Vec<_>>().len(), 3);
+}
@@ -0,0 +1,101 @@
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+const BIN: &str = env!("CARGO_BIN_EXE_closed-unitigs");
+
+/// Closes a single record input, checking that the output verifies, and returns the closed unitigs with their counts
+fn close_single(dir: &Path, name: &str, content: &str) -> Vec<(String, u32)> {
+  let input = dir.join(format!("{}.fa", name));
+  fs::write(&input, content).unwrap();
+  let output = Command::new(BIN).arg("--no-manifest").arg(&input).output().unwrap();
+  assert!(output.status.success());
+  assert!(String::from_utf8(output.stderr).unwrap().contains("k = 5"));
+
+  let (fasta, counts) = (dir.join(format!("{}.clo.fa", name)), dir.join(format!("{}.clo.counts", name)));
+  assert!(Command::new(BIN).arg("verify").args([&input, &fasta, &counts]).output().unwrap().status.success());
+  let fasta = fs::read_to_string(fasta).unwrap();
+  let counts = fs::read_to_string(counts).unwrap();
+  // Each closed unitig is joined to its row of the counts file by name
+  fasta.lines().step_by(2).zip(fasta.lines().skip(1).step_by(2)).zip(counts.lines()).map(|((header, u), row)| {
+    let row: Vec<_> = row.split('\t').collect();
+    assert_eq!((row[0], row[1].parse().unwrap()), (header[1..].split(' ').next().unwrap(), u.len()));
+    (String::from(u), row[2].parse().unwrap())
+  }).collect()
+}
+
+#[test]
+fn single_records_are_closed() {
+  let dir = tempfile::tempdir().unwrap();
+  let dir = dir.path();
+  assert_eq!(close_single(dir, "kmer", ">0 ab:Z:4\nAACCG\n"), vec![(String::from("AACCG"), 4)]);
+  assert_eq!(close_single(dir, "linear", ">0 ab:Z:3 3 3\nAACCGTT\n"), vec![(String::from("AACCGTT"), 3)]);
+  // Closures extend through the internal edges, stopping where the counts drop
+  assert_eq!(close_single(dir, "dip", ">0 ab:Z:3 3 1 3\nAACCGTTA\n"),
+    vec![(String::from("CCGTT"), 1), (String::from("AACCGT"), 3), (String::from("CGTTA"), 3)]);
+  // A record whose end is joined to its own reverse complement
+  assert_eq!(close_single(dir, "hairpin", ">0 ab:Z:2 2 2 2 L:+:0:-\nTTAACGCG\n"), vec![(String::from("TTAACGCG"), 2)]);
+}
+
+#[test]
+fn circular_records_are_closed_around() {
+  let dir = tempfile::tempdir().unwrap();
+  let dir = dir.path();
+  // The last k-mer is linked to the first one, on both strands
+  let circular = |counts: &str| format!(">0 LN:i:12 ab:Z:{} L:+:0:+ L:-:0:-\nCCGTAATGCCGT\n", counts);
+  assert_eq!(close_single(dir, "flat", &circular("3 3 3 3 3 3 3 3")), vec![(String::from("CCGTAATGCCGT"), 3)]);
+  // The first seed closes across the junction of the ends, the next one around the whole circle, then is shrunk
+  assert_eq!(close_single(dir, "around", &circular("3 3 2 2 2 2 3 3")),
+    vec![(String::from("GTAATGCC"), 2), (String::from("TGCCGTAA"), 3)]);
+}
+
+#[test]
+fn shrink_metadata_is_written_when_asked() {
+  let dir = tempfile::tempdir().unwrap();
+  let input = dir.path().join("dip.fa");
+  fs::write(&input, ">0 ab:Z:3 3 1 3\nAACCGTTA\n").unwrap();
+  let report = dir.path().join("dip.json");
+  let output = Command::new(BIN).args(["--no-manifest", "--shrink-metadata", "--json-report"]).arg(&report).arg(&input).output().unwrap();
+  assert!(output.status.success());
+  // The closure of the dip spans the whole record before its higher count ends are trimmed
+  let fasta = fs::read_to_string(dir.path().join("dip.clo.fa")).unwrap();
+  assert!(fasta.starts_with(">clo_0 len=5 support=1 nk=1 cl=8 tl=2 tr=1 cs=1\nCCGTT\n"), "{}", fasta);
+  let json: serde_json::Value = serde_json::from_str(&fs::read_to_string(&report).unwrap()).unwrap();
+  assert_eq!(json["summary"]["trims"], serde_json::json!({"trimmed": 1, "left_bases": 2, "right_bases": 1, "support_raised": 0}));
+
+  // Without the option the headers are unchanged
+  assert!(Command::new(BIN).arg("--no-manifest").arg(&input).output().unwrap().status.success());
+  assert!(fs::read_to_string(dir.path().join("dip.clo.fa")).unwrap().starts_with(">clo_0 len=5 support=1 nk=1\nCCGTT\n"));
+}
+
+#[test]
+fn sequences_are_wrapped_under_named_headers() {
+  let dir = tempfile::tempdir().unwrap();
+  let input = dir.path().join("small.fa");
+  fs::copy("tests/data/small.fa", &input).unwrap();
+  let records = |wrap: &str| {
+    assert!(Command::new(BIN).args(["--no-manifest", "--wrap", wrap]).arg(&input).output().unwrap().status.success());
+    let fasta = fs::read_to_string(dir.path().join("small.clo.fa")).unwrap();
+    let mut records: Vec<(String, String)> = Vec::new();
+    for line in fasta.lines() {
+      match line.strip_prefix('>') {
+        Some(header) => records.push((String::from(header), String::new())),
+        None => {
+          assert!(wrap == "0" || line.len() <= 10, "{}", line);
+          records.last_mut().unwrap().1 += line;
+        }
+      }
+    }
+    records
+  };
+
+  let single = records("0");
+  assert_eq!(records("10"), single);
+  // Headers give a unique name, the length and the support of each closed unitig
+  let counts = fs::read_to_string(dir.path().join("small.clo.counts")).unwrap();
+  for (id, ((header, sequence), row)) in single.iter().zip(counts.lines()).enumerate() {
+    let support = row.rsplit('\t').next().unwrap();
+    assert!(header.starts_with(&format!("clo_{} len={} support={} nk=", id, sequence.len(), support)), "{}", header);
+  }
+  assert!(single.iter().any(|(_, sequence)| sequence.len() > 10));
+}
@@ -0,0 +1,64 @@
+use std::fs;
+use std::process::Command;
+
+const BIN: &str = env!("CARGO_BIN_EXE_closed-unitigs");
+
+#[test]
+fn low_counts_are_dropped() {
+  let dir = tempfile::tempdir().unwrap();
+  let input = dir.path().join("small.fa");
+  fs::copy("tests/data/small.fa", &input).unwrap();
+  let spectrum = dir.path().join("small.spectrum.tsv");
+  let output = Command::new(BIN).arg("--spectrum").arg(&spectrum).args(["--min-count", "4"]).arg(&input).output().unwrap();
+  assert!(output.status.success());
+
+  let rows: Vec<(u32, usize)> = fs::read_to_string(&spectrum).unwrap().lines().filter(|l| !l.starts_with(';')).skip(1)
+    .map(|l| l.split_once('\t').map(|(c, n)| (c.parse().unwrap(), n.parse().unwrap())).unwrap())
+    .collect();
+  assert_eq!(rows, [(2, 9), (3, 11), (4, 25), (5, 23), (6, 9), (7, 3)]);
+  // The spectrum of this input rises from its lowest count on: there is no valley
+  let stderr = String::from_utf8(output.stderr).unwrap();
+  assert!(stderr.contains("suggested --min-count: 1"), "{}", stderr);
+
+  let counts = fs::read_to_string(dir.path().join("small.clo.counts")).unwrap();
+  assert!(counts.contains("; min count: 4 (20 of 80 k-mers dropped)\n"));
+  assert!(counts.lines().filter(|l| !l.starts_with(';')).all(|c| c.rsplit('\t').next().unwrap().parse::<u32>().unwrap() >= 4));
+
+  // Nothing is dropped with the suggestion
+  assert!(Command::new(BIN).args(["--min-count", "auto"]).arg(&input).output().unwrap().status.success());
+  let counts = fs::read_to_string(dir.path().join("small.clo.counts")).unwrap();
+  assert!(!counts.contains("; min count"));
+  assert!(counts.lines().any(|c| c.ends_with("\t2")));
+}
+
+#[test]
+fn weak_tips_are_clipped() {
+  let dir = tempfile::tempdir().unwrap();
+  let input = dir.path().join("tip.fa");
+  fs::write(&input, ">0 ab:Z:10 10 10 L:+:1:+\nGATTACA\n>1 ab:Z:10 10 10 L:-:0:- L:-:2:-\nTACAGGT\n>2 ab:Z:1 1 L:+:1:+\nCCTACA\n").unwrap();
+  let closed = |options: &[&str]| {
+    assert!(Command::new(BIN).args(options).arg(&input).output().unwrap().status.success());
+    let fasta = fs::read_to_string(dir.path().join("tip.clo.fa")).unwrap();
+    (fasta.lines().filter(|l| !l.starts_with('>')).map(String::from).collect::<Vec<_>>(), fs::read_to_string(dir.path().join("tip.clo.counts")).unwrap())
+  };
+  let (unitigs, _) = closed(&[]);
+  assert!(unitigs.iter().any(|u| u.contains("CCTAC") || u.contains("GTAGG")));
+
+  let (unitigs, counts) = closed(&["--clip-tips", "3"]);
+  assert!(unitigs == ["GATTACAGGT"] || unitigs == ["ACCTGTAATC"], "{:?}", unitigs);
+  assert!(counts.contains("; tips clipped: 1 with count below 3 (2 k-mers)\n"));
+}
+
+#[test]
+fn bubbles_are_popped() {
+  let dir = tempfile::tempdir().unwrap();
+  let input = dir.path().join("snp.fa");
+  fs::write(&input, ">0 ab:Z:10 10 L:+:2:+ L:+:3:+\nGATTAC\n>1 ab:Z:10 10 L:-:2:- L:-:3:-\nGGTCCA\n\
+    >2 ab:Z:2 2 2 2 2 L:-:0:- L:+:1:+\nTTACAGGTC\n>3 ab:Z:10 10 10 10 10 L:-:0:- L:+:1:+\nTTACTGGTC\n").unwrap();
+  assert!(Command::new(BIN).arg("--pop-bubbles").arg(&input).output().unwrap().status.success());
+  let fasta = fs::read_to_string(dir.path().join("snp.clo.fa")).unwrap();
+  let unitigs: Vec<_> = fasta.lines().filter(|l| !l.starts_with('>')).collect();
+  assert!(unitigs == ["GATTACTGGTCCA"] || unitigs == ["TGGACCAGTAATC"], "{:?}", unitigs);
+  let counts = fs::read_to_string(dir.path().join("snp.clo.counts")).unwrap();
+  assert!(counts.contains("; bubbles popped: 1 (5 k-mers)\n"));
+}
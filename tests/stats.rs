@@ -0,0 +1,54 @@
+use std::collections::HashMap;
+use std::process::Command;
+
+const BIN: &str = env!("CARGO_BIN_EXE_closed-unitigs");
+
+#[test]
+fn graph_statistics_are_printed() {
+  let output = Command::new(BIN).args(["stats", "--max-degree", "1", "tests/data/small.fa"]).output().unwrap();
+  assert!(output.status.success());
+  let stdout = String::from_utf8(output.stdout).unwrap();
+  let stats: HashMap<_, _> = stdout.lines().take_while(|l| !l.starts_with("degree\t")).map(|l| l.split_once('\t').unwrap()).collect();
+  let value = |name: &str| stats[name].parse::<u64>().unwrap();
+  assert_eq!(value("k"), 7);
+  assert!(value("bases") >= value("records") * value("k"));
+  assert!(value("edges") > 0 && value("above_max_degree") <= value("k-mers"));
+  assert!(value("count_min") <= value("count_median") && value("count_median") <= value("count_max"));
+  // The degree histogram covers every k-mer
+  let histogram: u64 = stdout.lines().skip_while(|l| !l.starts_with("degree\t")).skip(1).map(|l| l.split_once('\t').unwrap().1.parse::<u64>().unwrap()).sum();
+  assert_eq!(histogram, value("k-mers"));
+}
+
+#[test]
+fn components_are_reported_and_selected() {
+  let dir = tempfile::tempdir().unwrap();
+  let input = dir.path().join("three.fa");
+  std::fs::write(&input, ">0 ab:Z:5 5\nAACCGT\n>1 ab:Z:3 3 3\nGGGATCA\n>2 ab:Z:4\nCCCCT\n").unwrap();
+  let tsv = dir.path().join("components.tsv");
+  let output = Command::new(BIN).args(["components", "--tsv"]).arg(&tsv).arg(&input).output().unwrap();
+  assert!(output.status.success());
+  let stdout = String::from_utf8(output.stdout).unwrap();
+  assert_eq!(stdout, "components\t3\nk-mers\t6\nlargest\t3\nmedian\t2\nsingletons\t1\n");
+  assert_eq!(std::fs::read_to_string(&tsv).unwrap(), "component\tkmers\tinput_bp\n0\t2\t6\n1\t3\t7\n2\t1\t5\n");
+
+  let output = Command::new(BIN).args(["--components", "0,2"]).arg(&input).output().unwrap();
+  assert!(output.status.success());
+  let fasta = std::fs::read_to_string(dir.path().join("three.clo.fa")).unwrap();
+  let unitigs: Vec<_> = fasta.lines().filter(|l| !l.starts_with('>')).collect();
+  assert_eq!(unitigs, ["CCCCT", "AACCGT"]);
+  let counts = std::fs::read_to_string(dir.path().join("three.clo.counts")).unwrap();
+  assert!(counts.contains("; components: 0,2 of 3 connected components kept\n"));
+
+  let output = Command::new(BIN).args(["--components", "3"]).arg(&input).output().unwrap();
+  assert_eq!(output.status.code(), Some(1));
+}
+
+#[test]
+fn cycles_are_reported() {
+  let dir = tempfile::tempdir().unwrap();
+  let input = dir.path().join("circular.fa");
+  std::fs::write(&input, ">0 ab:Z:4 4\nCCCCTT\n>1 ab:Z:5 5 5 5 5 5 5 5 5 L:+:1:+ L:-:1:-\nGATTACAGCGATT\n").unwrap();
+  let output = Command::new(BIN).arg("cycles").arg(&input).output().unwrap();
+  assert!(output.status.success());
+  assert_eq!(String::from_utf8(output.stdout).unwrap(), "cycles\t1\nk-mers\t9\ncycle\tkmers\trecords\n0\t9\t1\n");
+}
@@ -0,0 +1,37 @@
+use std::fs;
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+const BIN: &str = env!("CARGO_BIN_EXE_closed-unitigs");
+
+#[test]
+fn closing_can_sit_in_a_pipe() {
+  let dir = tempfile::tempdir().unwrap();
+  let input = dir.path().join("small.fa");
+  fs::copy("tests/data/small.fa", &input).unwrap();
+  let report = dir.path().join("report.json");
+  assert!(Command::new(BIN).arg("--json-report").arg(&report).arg(&input).output().unwrap().status.success());
+  let expected = fs::read_to_string(dir.path().join("small.clo.fa")).unwrap();
+  let file: serde_json::Value = serde_json::from_str(&fs::read_to_string(&report).unwrap()).unwrap();
+
+  let mut child = Command::new(BIN).current_dir(dir.path()).args(["--json-report", "stdin.json", "-"])
+    .stdin(Stdio::piped()).stdout(Stdio::piped()).stderr(Stdio::piped()).spawn().unwrap();
+  child.stdin.take().unwrap().write_all(&fs::read("tests/data/small.fa").unwrap()).unwrap();
+  let output = child.wait_with_output().unwrap();
+  assert!(output.status.success());
+  // Only the closed unitigs go to the standard output, the summary to stderr
+  assert_eq!(String::from_utf8(output.stdout).unwrap(), expected);
+  assert!(String::from_utf8(output.stderr).unwrap().contains("Seed order: "));
+  let counts = |name| fs::read_to_string(dir.path().join(name)).unwrap().lines().filter(|l| !l.starts_with(';')).map(|l| format!("{}\n", l)).collect::<String>();
+  assert_eq!(counts("stdin.clo.counts"), counts("small.clo.counts"));
+  // The input is identified as if read from a file
+  let stdin: serde_json::Value = serde_json::from_str(&fs::read_to_string(dir.path().join("stdin.json")).unwrap()).unwrap();
+  assert_eq!(stdin["manifest"]["input"]["path"], "-");
+  assert_eq!((&stdin["manifest"]["input"]["size"], &stdin["manifest"]["input"]["hash"]), (&file["manifest"]["input"]["size"], &file["manifest"]["input"]["hash"]));
+
+  // The counts can go to the standard output instead
+  let output = Command::new(BIN).args(["--no-manifest", "--counts-out", "-"]).arg(&input).output().unwrap();
+  assert_eq!(String::from_utf8(output.stdout).unwrap(), counts("small.clo.counts"));
+  let output = Command::new(BIN).args(["--fasta-out", "-", "--counts-out", "-"]).arg(&input).output().unwrap();
+  assert_eq!(output.status.code(), Some(1));
+}
@@ -0,0 +1,41 @@
+use closed_unitigs::convert::{self, Format};
+use closed_unitigs::synthetic::{self, Synthetic};
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+const BIN: &str = env!("CARGO_BIN_EXE_closed-unitigs");
+
+/// Closes a synthetic graph written as BCALM, returning the closed unitigs written with their support, in lexicographic order
+fn close(dir: &Path, synthetic: &Synthetic, args: &[&str]) -> Vec<(String, u32)> {
+  let input = dir.join("synthetic.fa");
+  convert::write(&synthetic.graph, Format::Bcalm, &input).unwrap();
+  assert!(Command::new(BIN).arg("--no-manifest").args(args).arg(&input).output().unwrap().status.success());
+  let verify = Command::new(BIN).arg("verify").arg(&input).arg(dir.join("synthetic.clo.fa")).arg(dir.join("synthetic.clo.counts")).output().unwrap();
+  assert!(verify.status.success());
+  let fasta = fs::read_to_string(dir.join("synthetic.clo.fa")).unwrap();
+  let counts = fs::read_to_string(dir.join("synthetic.clo.counts")).unwrap();
+  let supports = counts.lines().filter(|l| !l.starts_with(';')).map(|l| l.rsplit('\t').next().unwrap().parse().unwrap());
+  let mut closed: Vec<_> = fasta.lines().skip(1).step_by(2).map(String::from).zip(supports).collect();
+  closed.sort();
+  closed
+}
+
+#[test]
+fn linear_graphs_match_the_oracle() {
+  let dir = tempfile::tempdir().unwrap();
+  let profiles: [&dyn Fn(usize) -> u32; 3] = [&|_| 5, &|i| (i as u32).abs_diff(20) + 1, &|i| (i * 7919 % 13) as u32 + 1];
+  for (k, profile) in [9, 41].iter().flat_map(|&k| profiles.map(|profile| (k, profile))) {
+    let linear = synthetic::generate_linear(k, 40, profile);
+    assert_eq!(close(dir.path(), &linear, &[]), linear.expected, "k = {}", k);
+  }
+}
+
+#[test]
+fn branching_graphs_match_the_oracle() {
+  let dir = tempfile::tempdir().unwrap();
+  let branching = synthetic::generate_branching(7, 3, 3);
+  for args in [&[][..], &["--seed-order", "shuffle"], &["--sort", "none"], &["--threads", "1"]] {
+    assert_eq!(close(dir.path(), &branching, args), branching.expected, "{:?}", args);
+  }
+}
@@ -0,0 +1,46 @@
+use std::fs;
+use std::process::Command;
+
+const BIN: &str = env!("CARGO_BIN_EXE_closed-unitigs");
+
+#[test]
+fn captured_output_has_no_control_sequences() {
+  let dir = tempfile::tempdir().unwrap();
+  let input = dir.path().join("small.fa");
+  fs::copy("tests/data/small.fa", &input).unwrap();
+  let output = Command::new(BIN).args(["--head", "3"]).arg(&input).output().unwrap();
+  assert!(output.status.success());
+  // Messages go to stderr only
+  assert!(output.stdout.is_empty());
+  let text = String::from_utf8(output.stderr).unwrap();
+  assert!(!text.is_empty());
+  assert!(text.chars().all(|c| c == '\n' || (c.is_ascii() && !c.is_ascii_control())), "{:?}", text);
+}
+
+#[test]
+fn verbosity_selects_the_messages() {
+  let dir = tempfile::tempdir().unwrap();
+  let input = dir.path().join("small.fa");
+  fs::copy("tests/data/small.fa", &input).unwrap();
+  let stderr = |args: &[&str]| {
+    let output = Command::new(BIN).arg("--no-manifest").args(args).arg(&input).output().unwrap();
+    assert!(output.status.success());
+    String::from_utf8(output.stderr).unwrap()
+  };
+  let (quiet, normal, verbose) = (stderr(&["-q"]), stderr(&[]), stderr(&["-v"]));
+  assert!(quiet.is_empty(), "{}", quiet);
+  assert!(normal.contains("k = ") && !normal.contains("phase took"), "{}", normal);
+  assert!(verbose.contains("k = ") && verbose.contains("parse phase took"), "{}", verbose);
+  // Warnings are kept by -q, but not by -qq
+  assert!(stderr(&["-q", "--head", "1"]).contains("Warning: dropped"));
+  assert!(stderr(&["-qq", "--head", "1"]).is_empty());
+}
+
+#[test]
+fn verbosity_flags_may_come_before_the_subcommand() {
+  for flags in [&["-v"][..], &["-qq"], &["--verbose", "-v"]] {
+    let output = Command::new(BIN).args(flags).args(["stats", "tests/data/small.fa"]).output().unwrap();
+    assert!(output.status.success(), "{:?}: {}", flags, String::from_utf8_lossy(&output.stderr));
+    assert!(!output.stdout.is_empty());
+  }
+}
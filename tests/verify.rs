@@ -0,0 +1,94 @@
+use closed_unitigs::convert::{self, Format};
+use closed_unitigs::synthetic;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+const BIN: &str = env!("CARGO_BIN_EXE_closed-unitigs");
+
+/// Closes the small test graph into the given directory, returning the input path
+fn close(dir: &Path) -> PathBuf {
+  let input = dir.join("small.fa");
+  fs::copy("tests/data/small.fa", &input).unwrap();
+  assert!(Command::new(BIN).arg(&input).output().unwrap().status.success());
+  input
+}
+
+/// Runs the verify subcommand on the output of a run
+fn verify(input: &Path) -> bool {
+  Command::new(BIN).arg("verify").arg(input).arg(input.with_extension("clo.fa")).arg(input.with_extension("clo.counts"))
+    .output().unwrap().status.success()
+}
+
+#[test]
+fn output_is_closed() {
+  let dir = tempfile::tempdir().unwrap();
+  assert!(verify(&close(dir.path())));
+  // Graphs from reads with errors, with bubbles and tips, and many components
+  for (name, graph) in [
+    ("genome.fa", synthetic::generate_random_genome(21, 2000, 8., 0.01, 3)),
+    ("components.fa", synthetic::generate_components(9, 30, 20))
+  ] {
+    let input = dir.path().join(name);
+    convert::write(&graph, Format::Bcalm, &input).unwrap();
+    assert!(Command::new(BIN).arg(&input).output().unwrap().status.success());
+    assert!(verify(&input), "{}", name);
+  }
+}
+
+#[test]
+fn wrong_counts_are_detected() {
+  let dir = tempfile::tempdir().unwrap();
+  let input = close(dir.path());
+  let counts = input.with_extension("clo.counts");
+  let wrong: String = fs::read_to_string(&counts).unwrap().lines().filter(|l| !l.starts_with(';')).map(|c| {
+    let (row, support) = c.rsplit_once('\t').unwrap();
+    format!("{}\t{}\n", row, support.parse::<u32>().unwrap() + 1)
+  }).collect();
+  fs::write(&counts, wrong).unwrap();
+  assert!(!verify(&input));
+}
+
+#[test]
+fn compressed_outputs_are_verified() {
+  let dir = tempfile::tempdir().unwrap();
+  let input = close(dir.path());
+  for (args, fasta, counts) in [
+    (vec!["--compress", "gz"], "small.clo.fa.gz", "small.clo.counts.gz"),
+    (vec!["--compress", "zst", "--shards", "1"], "small.clo.0.fa.zst", "small.clo.0.counts.zst"),
+    (vec!["--fasta-out", "out.fa.zst", "--counts-out", "out.counts"], "out.fa.zst", "out.counts")
+  ] {
+    assert!(Command::new(BIN).current_dir(dir.path()).args(&args).arg(&input).output().unwrap().status.success());
+    let (fasta, counts) = (dir.path().join(fasta), dir.path().join(counts));
+    assert!(fs::read(&fasta).unwrap() != fs::read(input.with_extension("clo.fa")).unwrap(), "{:?}", args);
+    let output = Command::new(BIN).arg("verify").args([&input, &fasta, &counts]).output().unwrap();
+    assert!(output.status.success(), "{:?}", args);
+  }
+  assert!(fs::read_to_string(dir.path().join("out.counts")).unwrap().starts_with("; closed-unitigs"));
+  // The counts are found next to compressed FASTA files too
+  let output = Command::new(BIN).arg("diff").arg(input.with_extension("clo.fa")).arg(dir.path().join("small.clo.fa.gz")).output().unwrap();
+  assert!(output.status.success());
+}
+
+#[test]
+fn abundance_tags_replace_the_counts_file() {
+  let dir = tempfile::tempdir().unwrap();
+  let input = close(dir.path());
+  let fasta = dir.path().join("tagged.fa");
+  assert!(Command::new(BIN).arg("--abundance-tags").arg("--fasta-out").arg(&fasta).arg("-o").arg(dir.path().join("tagged")).arg(&input)
+    .output().unwrap().status.success());
+  assert!(!dir.path().join("tagged.clo.counts").exists());
+  // The counts are those of the counts file, and each record can be read back as a BCALM record
+  let counts: Vec<_> = fs::read_to_string(input.with_extension("clo.counts")).unwrap().lines().filter(|l| !l.starts_with(';'))
+    .map(|c| String::from(c.rsplit('\t').next().unwrap())).collect();
+  let tagged = fs::read_to_string(&fasta).unwrap();
+  let headers: Vec<_> = tagged.lines().filter(|l| l.starts_with('>')).collect();
+  assert_eq!(headers.len(), counts.len());
+  for (header, count) in headers.iter().zip(&counts) {
+    assert!(header.contains(&format!(" km:f:{}.0 ab:Z:", count)), "{}", header);
+  }
+  assert!(Command::new(BIN).arg("verify").arg(&input).arg(&fasta).output().unwrap().status.success());
+  // Without the tags, there is nothing to check the records against
+  fs::write(&fasta, tagged.replace("km:f:", "xx:f:")).unwrap();
+  assert!(!Command::new(BIN).arg("verify").arg(&input).arg(&fasta).output().unwrap().status.success());
+}